@@ -37,12 +37,14 @@ public abstract class MinecraftClientRenderMixin {
         
         //Warning, zero-initialized!
         Window window = (Window) Wgpu.getUnsafe().allocateInstance(Window.class);
-        window.width = 1280;
-        window.height = 720;
+        window.width = settings.width;
+        window.height = settings.height;
+        window.fullscreen = settings.fullscreen;
+        Wgpu.setWindowSettings(settings);
 
         // fixes the message saying that it recovers from an invalid resolution
-        window.setFramebufferWidth(1280);
-        window.setFramebufferHeight(720);
+        window.setFramebufferWidth(settings.width);
+        window.setFramebufferHeight(settings.height);
         return window;
     }
 
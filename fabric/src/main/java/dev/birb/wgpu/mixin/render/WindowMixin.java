@@ -19,6 +19,8 @@ public class WindowMixin {
 
     @Shadow private double scaleFactor;
 
+    @Shadow private boolean fullscreen;
+
     /**
      * @author wgpu-mc
      * @reason replaced with wgpu equivalent
@@ -138,6 +140,16 @@ public class WindowMixin {
         return (int) (this.getHeight() / this.scaleFactor);
     }
 
+    /**
+     * @author wgpu-mc
+     * @reason replaced with wgpu equivalent
+     */
+    @Overwrite
+    public void toggleFullscreen() {
+        this.fullscreen = !this.fullscreen;
+        WgpuNative.setFullscreen(this.fullscreen);
+    }
+
     /**
      * @author wgpu-mc
      * @reason replaced with wgpu equivalent
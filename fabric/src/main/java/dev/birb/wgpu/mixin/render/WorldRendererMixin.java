@@ -210,6 +210,8 @@ public abstract class WorldRendererMixin {
 
         if(player != null) {
             WgpuNative.setSectionPos((int)Math.floor(translate.x/16.0),(int)Math.floor(translate.z/16.0));
+            WgpuNative.setCameraPos(translate.x, translate.y, translate.z);
+            WgpuNative.setCameraPitch(camera.getPitch());
             MatrixStack stack = new MatrixStack();
             stack.push();
             stack.translate(-(translate.x%16+16)%16, -translate.y, -(translate.z%16+16)%16);
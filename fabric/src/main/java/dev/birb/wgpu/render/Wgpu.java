@@ -7,6 +7,7 @@ import dev.birb.wgpu.rust.WgpuTextureManager;
 import lombok.Getter;
 import lombok.Setter;
 import net.minecraft.client.MinecraftClient;
+import net.minecraft.client.WindowSettings;
 import net.minecraft.util.crash.CrashReport;
 import net.minecraft.util.math.BlockPos;
 import sun.misc.Unsafe;
@@ -14,6 +15,7 @@ import sun.misc.Unsafe;
 import java.lang.reflect.Field;
 import java.util.ArrayList;
 import java.util.HashMap;
+import java.util.OptionalInt;
 
 import static dev.birb.wgpu.WgpuMcMod.LOGGER;
 
@@ -46,6 +48,12 @@ public class Wgpu {
     @Getter
     private static Unsafe unsafe;
 
+    /**
+     * The size and fullscreen state the game asked for, used to create the window
+     */
+    @Setter
+    private static WindowSettings windowSettings;
+
     @Getter
     private static int windowWidth;
     @Getter
@@ -74,7 +82,11 @@ public class Wgpu {
     public static void startRendering() {
         if (!initialized) {
             //linkRenderDoc();
-            WgpuNative.startRendering("Minecraft");
+            WindowSettings settings = windowSettings != null ? windowSettings : new WindowSettings(1280, 720, OptionalInt.empty(), OptionalInt.empty(), false);
+            windowWidth = settings.width;
+            windowHeight = settings.height;
+
+            WgpuNative.startRendering("Minecraft", settings.width, settings.height, settings.fullscreen);
         } else {
             throw new IllegalStateException("wgpu-mc has already been initialized");
         }
@@ -70,7 +70,9 @@ public class WgpuNative {
 
     public static native int getTextureId(String identifier);
 
-    public static native void startRendering(String title);
+    public static native void startRendering(String title, int width, int height, boolean fullscreen);
+
+    public static native void setFullscreen(boolean fullscreen);
 
     public static native void setPanicHook();
 
@@ -82,6 +84,8 @@ public class WgpuNative {
 
     public static native String getBackend();
 
+    public static native String getGpuName();
+
     public static native void setWorldRenderState(boolean render);
 
     public static native void texImage2D(int textureId, int target, int level, int internalFormat, int width, int height, int border, int format, int type, long pixelsPtr);
@@ -110,6 +114,18 @@ public class WgpuNative {
 
     public static native void attachTextureBindGroup(int slot, int texture);
 
+    /**
+     * Loads a texture on its own instead of into an atlas, so it can tile. Shader packs built afterwards bind it as
+     * "@texture_standalone_" and "@sampler_standalone_" followed by its path.
+     *
+     * @param path        The full path of the image, e.g. "minecraft:textures/gui/options_background.png"
+     * @param addressMode 0 clamps to the edge, 1 repeats and 2 mirrors
+     * @param glId        The GL texture id to bind it as in the GL path, or -1 to only use it in shader packs
+     * @return false if the image couldn't be loaded
+     * @throws IllegalArgumentException if the address mode is unknown
+     */
+    public static native boolean registerStandaloneTexture(String path, int addressMode, int glId);
+
     public static native double getMouseX();
 
     public static native double getMouseY();
@@ -142,6 +158,51 @@ public class WgpuNative {
 
     public static native void cacheBlockStates();
 
+    /**
+     * Buckets a block state's faces into a render layer: 0 is solid, 1 cutout and 2 translucent.
+     *
+     * @throws IllegalArgumentException if the layer is unknown
+     */
+    public static native void setBlockRenderLayer(int packedKey, int layer);
+
+    /**
+     * Scrolls the faces of a block state which are textured with a block atlas sprite. Sections have to be rebuilt
+     * for this to apply.
+     *
+     * @param sprite    The sprite, e.g. "minecraft:block/stone"
+     * @param velocityU How many sprite widths the texture moves per second
+     * @param velocityV How many sprite heights the texture moves per second
+     * @return The scroll channel, or -1 if the sprite isn't in the atlas or too many scrolls are registered
+     */
+    public static native int addUvScroll(int packedKey, String sprite, float velocityU, float velocityV);
+
+    /**
+     * Makes a block state sway in the wind, e.g. grass with 0 and 1 so it stays fixed to the ground. Sections have to
+     * be rebuilt for this to apply.
+     *
+     * @param bottom How strongly the bottom of the model sways, from 0 to 1
+     * @param top    How strongly the top of the model sways, from 0 to 1. Pass 0 for both to stop swaying
+     */
+    public static native void setBlockSway(int packedKey, float bottom, float top);
+
+    /**
+     * Sets the block light level a block state emits, like its luminance, so it renders bright even in the dark.
+     * Sections have to be rebuilt for this to apply.
+     *
+     * @param level From 0 to 15, e.g. 14 for torches and 15 for glowstone
+     */
+    public static native void setLightEmission(int packedKey, int level);
+
+    /**
+     * Sets how sharp block textures look, rebuilding the block sampler.
+     *
+     * @param anisotropy From 1 (off) to 16, only used by shader packs whose block sampler filters linearly
+     * @param mipBias    Added to the mip level, negative values sharpen distant textures and positive ones blur them
+     * @param maxMip     The smallest mip level which is sampled
+     * @return false if the adapter doesn't support these settings, in which case nothing changes
+     */
+    public static native boolean setTextureQuality(int anisotropy, float mipBias, float maxMip);
+
     public static native void setCamera(double x, double y, double z, float renderYaw, float renderPitch);
 
     public static native void bakeSection(int x, int y, int z, long[] paletteIndices, long[] storageIndices, byte[][] blockIndices, byte[][] skyIndices);
@@ -162,6 +223,13 @@ public class WgpuNative {
 
     public static native void scheduleStop();
 
+    /**
+     * Blocks the calling thread until all submitted GPU work has finished, so the renderer can be torn down safely
+     */
+    public static native void waitIdle();
+
+    public static native void setRenderScale(float scale, boolean linear);
+
     public static native long createAndDeserializeLightData(byte[] array, int index);
 
     public static native void bindLightData(long lightData, int x, int z);
@@ -184,6 +252,12 @@ public class WgpuNative {
 
     public static native void setSectionPos(int x,int z);
 
+    public static native void setCameraPos(double x, double y, double z);
+
+    public static native void setCameraPitch(float pitch);
+
+    public static native void setUndergroundCulling(boolean enabled, double surfaceY, double depth, float maxPitch);
+
     public static native void render(float tickDelta, long startTime, boolean tick);
 
     public static native void setShaderColor(float r, float g, float b, float a);
@@ -0,0 +1,28 @@
+@group(0) @binding(0) var t_texture: texture_2d<f32>;
+@group(0) @binding(1) var t_sampler: sampler;
+
+struct VertexResult {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>
+};
+
+//One triangle covering the whole screen
+@vertex
+fn vert(
+    @builtin(vertex_index) vi: u32
+) -> VertexResult {
+    var uv = vec2(f32((vi << 1u) & 2u), f32(vi & 2u));
+
+    var vr: VertexResult;
+    vr.pos = vec4(uv * vec2(2.0, -2.0) + vec2(-1.0, 1.0), 0.0, 1.0);
+    vr.uv = uv;
+
+    return vr;
+}
+
+@fragment
+fn frag(
+    in: VertexResult
+) -> @location(0) vec4<f32> {
+    return textureSample(t_texture, t_sampler, in.uv);
+}
@@ -1,32 +1,27 @@
-struct Uniforms {
-    view_proj: mat4x4<f32>,
-};
+//For LineGeometry, drawn with `topology: line_list`
 
-@group(0) @binding(0)
-var<uniform> uniform_data: Uniforms;
+@group(0) @binding(0) var<uniform> view: mat4x4<f32>;
+@group(0) @binding(1) var<uniform> projection: mat4x4<f32>;
 
 struct VertexResult {
     @builtin(position) pos: vec4<f32>,
-    @location(0) color: vec3<f32>
+    @location(0) color: vec4<f32>
 };
 
 @vertex
-fn vs_main(
+fn vert(
     @location(0) pos_in: vec3<f32>,
-    @location(1) color: vec3<f32>
+    @location(1) color: vec4<f32>
 ) -> VertexResult {
     var vr: VertexResult;
 
-    vr.pos = uniform_data.view_proj * vec4<f32>(pos_in, 1.0);
-    vr.pos.z = vr.pos.z + 0.5;
-
+    vr.pos = projection * view * vec4<f32>(pos_in, 1.0);
     vr.color = color;
 
     return vr;
 }
 
 @fragment
-fn fs_main(in: VertexResult) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.color, 1.0);
-   // return textureSample(t_texture, t_sampler, in.tex_coords);
-}
\ No newline at end of file
+fn frag(in: VertexResult) -> @location(0) vec4<f32> {
+    return in.color;
+}
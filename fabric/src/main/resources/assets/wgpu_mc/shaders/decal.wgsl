@@ -0,0 +1,49 @@
+@group(0) @binding(0) var<uniform> mat4_model: mat4x4<f32>;
+@group(0) @binding(1) var<uniform> mat4_view: mat4x4<f32>;
+@group(0) @binding(2) var<uniform> mat4_persp: mat4x4<f32>;
+
+@group(0) @binding(3) var t_texture: texture_2d<f32>;
+@group(0) @binding(4) var t_sampler: sampler;
+
+struct PushConstants {
+    transform: mat4x4<f32>,
+    //In blocks, towards the camera
+    depth_bias: f32
+}
+
+var<push_constant> pc: PushConstants;
+
+struct VertexResult {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>
+};
+
+@vertex
+fn vert(
+    @location(0) pos_in: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>
+) -> VertexResult {
+    var vr: VertexResult;
+
+    var view_pos = mat4_view * mat4_model * pc.transform * vec4(pos_in, 1.0);
+    //Moving along the view ray only changes depth, so the decal stays where it is on screen
+    view_pos = vec4(view_pos.xyz - normalize(view_pos.xyz) * pc.depth_bias, view_pos.w);
+
+    vr.pos = mat4_persp * view_pos;
+    vr.tex_coords = tex_coords;
+
+    return vr;
+}
+
+@fragment
+fn frag(
+    in: VertexResult
+) -> @location(0) vec4<f32> {
+    let col = textureSample(t_texture, t_sampler, in.tex_coords);
+
+    if(col.a == 0.0f){
+        discard;
+    }
+
+    return col;
+}
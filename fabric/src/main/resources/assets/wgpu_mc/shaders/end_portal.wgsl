@@ -0,0 +1,68 @@
+@group(0) @binding(0) var<uniform> mat4_model: mat4x4<f32>;
+@group(0) @binding(1) var<uniform> mat4_view: mat4x4<f32>;
+@group(0) @binding(2) var<uniform> mat4_persp: mat4x4<f32>;
+
+struct PushConstants {
+    animation_time: f32
+}
+
+var<push_constant> pc: PushConstants;
+
+const LAYERS: i32 = 8;
+
+struct VertexResult {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) screen_pos: vec4<f32>,
+    @location(1) time: f32
+};
+
+@vertex
+fn vert(
+    @location(0) pos_in: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) instance_pos: vec3<f32>,
+    @location(3) phase: f32
+) -> VertexResult {
+    var vr: VertexResult;
+
+    vr.pos = mat4_persp * mat4_view * mat4_model * vec4(pos_in + instance_pos, 1.0);
+    vr.screen_pos = vr.pos;
+    vr.time = pc.animation_time + phase;
+
+    return vr;
+}
+
+fn hash(p: vec2<f32>) -> f32 {
+    return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+fn rotate(uv: vec2<f32>, angle: f32) -> vec2<f32> {
+    let c = cos(angle);
+    let s = sin(angle);
+    return vec2(uv.x * c - uv.y * s, uv.x * s + uv.y * c);
+}
+
+@fragment
+fn frag(
+    in: VertexResult
+) -> @location(0) vec4<f32> {
+    //Sampled in screen space so the layers stay put as the camera moves, which gives the parallax effect
+    let screen_uv = in.screen_pos.xy / in.screen_pos.w;
+
+    var col = vec3(0.035, 0.07, 0.09);
+
+    for (var i = 0; i < LAYERS; i++) {
+        let layer = f32(i);
+        //Deeper layers are smaller, darker and scroll slower
+        let scale = 4.0 + layer * 2.0;
+        let uv = rotate(screen_uv * scale, layer * 0.7) + vec2(0.0, in.time * 0.02 * (layer + 1.0));
+        let cell = floor(uv * 8.0);
+
+        if (hash(cell + layer) > 0.97) {
+            let tint = vec3(0.3 + 0.5 * hash(cell.yx), 0.6 + 0.3 * hash(cell + 3.0), 0.7);
+            col += tint * (1.0 - layer / f32(LAYERS)) * 0.4;
+        }
+    }
+
+    return vec4(col, 1.0);
+}
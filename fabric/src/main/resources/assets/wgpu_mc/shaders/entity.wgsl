@@ -15,7 +15,8 @@ struct VertexResult {
     @builtin(position) pos: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
     @location(1) normal: vec3<f32>,
-    @location(2) overlay: vec4<f32>
+    @location(2) overlay: vec4<f32>,
+    @location(3) tint: vec4<f32>
 };
 
 @vertex
@@ -25,8 +26,10 @@ fn vert(
     @location(2) normal: vec3<f32>,
     @location(3) part_id: u32,
     //Instance vertex start
-    @location(4) entity_texture_offset: vec2<f32>,
+    @location(4) entity_texture_offset: vec2<u32>,
     @location(5) overlay: u32,
+    @location(6) scale: f32,
+    @location(7) tint: u32,
     @builtin(instance_index) entity_index: u32
 ) -> VertexResult {
     var vr: VertexResult;
@@ -44,16 +47,25 @@ fn vert(
         f32(overlay >> 24u) / 255.0,
     );
 
-    vr.pos = persp_proj * view_proj * ((part_transform * vec4<f32>(pos_in, 1.0)));
+    //Scale about the translation of the root part, which is where the entity is
+    var origin: vec3<f32> = transforms[entity_index * push_constants.parts_per_entity][3].xyz;
+    var world_pos: vec3<f32> = (part_transform * vec4<f32>(pos_in, 1.0)).xyz;
+    world_pos = origin + (world_pos - origin) * scale;
 
-    vr.tex_coords = tex_coords + entity_texture_offset;
+    vr.pos = persp_proj * view_proj * vec4<f32>(world_pos, 1.0);
+
+    vr.tex_coords = tex_coords + vec2<f32>(entity_texture_offset) * 0.015625;
     vr.normal = vec3(1.0, 0.0, 0.0);
     vr.overlay = overlay_color;
+    vr.tint = unpack4x8unorm(tint);
 
     return vr;
 }
 
 @fragment
 fn frag(in: VertexResult) -> @location(0) vec4<f32> {
-   return vec4<f32>(textureSample(e_texture, e_sampler, in.tex_coords).rgb, 1.0);
+    var color = textureSample(e_texture, e_sampler, in.tex_coords).rgb * in.tint.rgb;
+
+    //The overlay's alpha is how much of the overlay color replaces the texture, for the hurt and frozen effects
+    return vec4<f32>(mix(color, in.overlay.rgb, in.overlay.a), in.tint.a);
 }
\ No newline at end of file
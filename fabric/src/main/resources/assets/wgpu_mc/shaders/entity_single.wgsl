@@ -0,0 +1,64 @@
+@group(0) @binding(0) var<uniform> view_proj: mat4x4<f32>;
+@group(0) @binding(1) var<uniform> persp_proj: mat4x4<f32>;
+@group(0) @binding(2) var e_sampler: sampler;
+
+//The part transforms of a single entity, see MAX_SINGLE_ENTITY_PARTS
+@group(1) @binding(0) var<uniform> transforms: array<mat4x4<f32>, 64>;
+@group(1) @binding(1) var e_texture: texture_2d<f32>;
+
+struct VertexResult {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) overlay: vec4<f32>,
+    @location(3) tint: vec4<f32>
+};
+
+@vertex
+fn vert(
+    @location(0) pos_in: vec3<f32>,
+    @location(1) tex_coords_u32: u32,
+    @location(2) normal: vec3<f32>,
+    @location(3) part_id: u32,
+    //Instance vertex start
+    @location(4) entity_texture_offset: vec2<u32>,
+    @location(5) overlay: u32,
+    @location(6) scale: f32,
+    @location(7) tint: u32
+) -> VertexResult {
+    var vr: VertexResult;
+
+//    var tex_coords: vec2<f32> = vec2<f32>(f32(tex_coords_u32 & 0xffffu), f32(tex_coords_u32 >> 16u)) * vec2<f32>(0.00048828125, 0.00048828125);
+    var tex_coords: vec2<f32> = vec2<f32>(f32(tex_coords_u32 & 0xffffu), f32(tex_coords_u32 >> 16u)) * vec2<f32>(0.015625, 0.015625);
+
+    var part_transform: mat4x4<f32> = transforms[part_id];
+
+    var overlay_color: vec4<f32> = vec4<f32>(
+        f32(overlay & 0xffu) / 255.0,
+        f32((overlay >> 8u) & 0xffu) / 255.0,
+        f32((overlay >> 16u) & 0xffu) / 255.0,
+        f32(overlay >> 24u) / 255.0,
+    );
+
+    //Scale about the translation of the root part, which is where the entity is
+    var origin: vec3<f32> = transforms[0][3].xyz;
+    var world_pos: vec3<f32> = (part_transform * vec4<f32>(pos_in, 1.0)).xyz;
+    world_pos = origin + (world_pos - origin) * scale;
+
+    vr.pos = persp_proj * view_proj * vec4<f32>(world_pos, 1.0);
+
+    vr.tex_coords = tex_coords + vec2<f32>(entity_texture_offset) * 0.015625;
+    vr.normal = vec3(1.0, 0.0, 0.0);
+    vr.overlay = overlay_color;
+    vr.tint = unpack4x8unorm(tint);
+
+    return vr;
+}
+
+@fragment
+fn frag(in: VertexResult) -> @location(0) vec4<f32> {
+    var color = textureSample(e_texture, e_sampler, in.tex_coords).rgb * in.tint.rgb;
+
+    //The overlay's alpha is how much of the overlay color replaces the texture, for the hurt and frozen effects
+    return vec4<f32>(mix(color, in.overlay.rgb, in.overlay.a), in.tint.a);
+}
\ No newline at end of file
@@ -0,0 +1,33 @@
+//For ScreenOverlayGeometry, positions are already in normalized device coordinates
+
+@group(0) @binding(0)
+var t_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var t_sampler: sampler;
+
+struct VertexResult {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) tint: vec4<f32>
+};
+
+@vertex
+fn vert(
+    @location(0) pos_in: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) tint: vec4<f32>
+) -> VertexResult {
+    var vr: VertexResult;
+
+    vr.pos = vec4<f32>(pos_in, 0.0, 1.0);
+    vr.uv = uv;
+    vr.tint = tint;
+
+    return vr;
+}
+
+@fragment
+fn frag(in: VertexResult) -> @location(0) vec4<f32> {
+    return in.tint * textureSample(t_texture, t_sampler, in.uv);
+}
@@ -14,10 +14,19 @@ var<uniform> view: mat4x4<f32>;
 @group(2) @binding(0)
 var<uniform> model: mat4x4<f32>;
 
-struct PushConstants {
+//Written every frame from Scene::sky_state, see SkyUniform
+struct Sky {
+    color: vec3<f32>,
     angle: f32,
     brightness: f32,
     star_shimmer: f32,
+    moon_phase: i32,
+}
+
+@group(3) @binding(0)
+var<uniform> sky: Sky;
+
+struct PushConstants {
     fog_start: f32,
     fog_end: f32,
     fog_shape: f32,
@@ -78,7 +87,7 @@ fn vert(
 
     var f1 = 0.0;
 
-    if sin(data.angle) < 0.0 {
+    if sin(sky.angle) < 0.0 {
         f1 = 180.0;
     }
 
@@ -12,16 +12,33 @@ var<uniform> view: mat4x4<f32>;
 @group(2) @binding(0)
 var<uniform> model: mat4x4<f32>;
 
-struct PushConstants {
+//Written every frame from Scene::sky_state, see SkyUniform
+struct Sky {
+    color: vec3<f32>,
     angle: f32,
     brightness: f32,
     star_shimmer: f32,
+    moon_phase: i32,
+}
+
+@group(3) @binding(0)
+var<uniform> sky: Sky;
+
+struct PushConstants {
     fog_start: f32,
     fog_end: f32,
     fog_shape: f32,
-    fog_color: vec4,
-    color_modulator: vec3,
-    dimension_fog_color: vec4,
+    fog_color_r: f32,
+    fog_color_g: f32,
+    fog_color_b: f32,
+    fog_color_a: f32,
+    color_modulator_r: f32,
+    color_modulator_g: f32,
+    color_modulator_b: f32,
+    dimension_fog_color_r: f32,
+    dimension_fog_color_g: f32,
+    dimension_fog_color_b: f32,
+    dimension_fog_color_a: f32,
 }
 
 var<push_constant> data: PushConstants;
@@ -50,12 +67,12 @@ fn fog_distance(pos: vec3<f32>) -> f32 {
 @fragment
 fn frag(in: VO) -> @location(0) vec4<f32> {
     // Stars
-    if data.star_shimmer > 0.0 {
+    if sky.star_shimmer > 0.0 {
         return linear_fog(vec4<f32>(
-            data.star_shimmer,
-            data.star_shimmer,
-            data.star_shimmer,
-            data.star_shimmer,
+            sky.star_shimmer,
+            sky.star_shimmer,
+            sky.star_shimmer,
+            sky.star_shimmer,
         ), in.vertex_distance);
     } else {
         discard;
@@ -21,10 +21,19 @@ var<uniform> model: mat4x4<f32>;
 @group(4) @binding(0) var moon_texture: texture_2d<f32>;
 @group(4) @binding(1) var moon_sampler: sampler;
 
-struct PushConstants {
+//Written every frame from Scene::sky_state, see SkyUniform
+struct Sky {
+    color: vec3<f32>,
     angle: f32,
     brightness: f32,
     star_shimmer: f32,
+    moon_phase: i32,
+}
+
+@group(5) @binding(0)
+var<uniform> sky: Sky;
+
+struct PushConstants {
     fog_start: f32,
     fog_end: f32,
     fog_shape: f32,
@@ -88,7 +97,7 @@ fn vert(
     var vo: VO;
     vo.og_pos = pos;
 
-    var transformation_matrix = model * rotateY(-90.0) * rotateX(data.angle * 360.0);
+    var transformation_matrix = model * rotateY(-90.0) * rotateX(sky.angle * 360.0);
     var dir = transformation_matrix * vec4<f32>(pos, 1.0);
 
     vo.pos = projection * view * vec4<f32>(dir.xyz, 1.0);
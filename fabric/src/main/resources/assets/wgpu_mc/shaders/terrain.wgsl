@@ -22,7 +22,35 @@ struct ChunkOffset {
 @group(0) @binding(3) var t_texture: texture_2d<f32>;
 @group(0) @binding(4) var t_sampler: sampler;
 
+struct UvScroll {
+    //Minimum UV and size of the scrolling sprite
+    rect: vec4<f32>,
+    offset: vec4<f32>
+};
+
+//Entry 0 is used by faces which don't scroll and leaves their UVs as they are
+@group(0) @binding(5) var<uniform> uv_scrolls: array<UvScroll, 256>;
+
+struct TextureQuality {
+    //Added to the sampled mip level, negative sharpens
+    mip_bias: f32
+};
+
+@group(0) @binding(6) var<uniform> texture_quality: TextureQuality;
+
+struct ColorSpaces {
+    //0 if linear, 1 if sRGB. Vertex colors are sRGB when they're stored encoded, the block atlas when it's decoded as
+    //it's sampled
+    vertex_colors: u32,
+    block_atlas: u32
+};
+
+@group(0) @binding(7) var<uniform> color_spaces: ColorSpaces;
+
 @group(1) @binding(0) var<storage> chunk_data: array<u32>;
+@group(2) @binding(0) var<storage> section_tints: array<u32>;
+
+const SECTION_TINT_SLOTS: u32 = 4u;
 
 struct VertexResult {
     @builtin(position) pos: vec4<f32>,
@@ -44,25 +72,45 @@ struct VertexResult {
     @interpolate(flat) @location(15) ao4: f32,
     @location(16) light_uv: vec2<f32>,
     @interpolate(flat) @location(17) int: u32,
-    @location(18) color: vec4<f32>
+    @location(18) color: vec4<f32>,
+    @interpolate(flat) @location(19) fade: f32,
+    @interpolate(flat) @location(20) uv_scroll: u32,
+    @location(21) view_pos: vec3<f32>
 };
 
-var<push_constant> section_pos: vec3i;
+struct PushConstants {
+    section_pos: vec3i,
+    tint_slot: u32,
+    //0 to 1 as newly loaded sections fade in
+    fade: f32,
+    //Seconds, then the wind direction scaled by its strength, see Wind::push_constant
+    wind_time: f32,
+    wind_x: f32,
+    wind_z: f32,
+    //Fragment only, start, end and shape, see RenderEffectsData::fog_params
+    fog: vec4<f32>,
+    fog_color: vec4<f32>
+}
 
-@vertex
-fn vert(
-    @builtin(vertex_index) vi: u32,
-    @builtin(instance_index) base_vertex: u32
-) -> VertexResult {
+var<push_constant> pc: PushConstants;
+
+//Gusts travel along the wind, with some ripple across it so neighbouring plants don't move in lockstep
+fn wind_offset(pos: vec3<f32>, wave: f32) -> vec3<f32> {
+    let wind = vec2(pc.wind_x, pc.wind_z);
+    let phase = pc.wind_time * 2.0 - dot(pos.xz, wind) * 0.5;
+    let gust = 0.6 + 0.4 * sin(phase) + 0.2 * sin(phase * 2.7 + pos.x * 0.9 + pos.z * 1.3);
+
+    return vec3(wind.x, 0.0, wind.y) * gust * wave;
+}
+
+//`data` is the vertex itself, the lighting of the rest of its quad is always read from chunk_data
+fn terrain_vertex(vi: u32, base_vertex: u32, data: vec4<u32>) -> VertexResult {
 //    var vert1_i = (vi >> 2) << 4;
 //    var vert1_i = (vi << 2) & 0xfffffffc;
 //    var vert1_i = ((vi >> 2u) << 2u)+base_vertex;
 
-    var offset = vi & 3;
     var vert1_i = vi & ~3u;
 
-    var id = ((vert1_i + offset) << 2u) + base_vertex;
-
     var vert1_base = ((vert1_i) << 2u) + base_vertex;
 
     var vert1_v4 = chunk_data[vert1_base + 3u];
@@ -74,10 +122,10 @@ fn vert(
     var v2_lc = 0.066666666666667 * vec2(f32(vert2_v4 & 15u), f32((vert2_v4 >> 4u) & 15u));
     var v3_lc = 0.066666666666667 * vec2(f32(vert3_v4 & 15u), f32((vert3_v4 >> 4u) & 15u));
     var v4_lc = 0.066666666666667 * vec2(f32(vert4_v4 & 15u), f32((vert4_v4 >> 4u) & 15u));
-    var v1_ao = f32((vert1_v4 >> 8u) & 0xff) * 0.333333;
-    var v2_ao = f32((vert2_v4 >> 8u) & 0xff) * 0.333333;
-    var v3_ao = f32((vert3_v4 >> 8u) & 0xff) * 0.333333;
-    var v4_ao = f32((vert4_v4 >> 8u) & 0xff) * 0.333333;
+    var v1_ao = f32((vert1_v4 >> 8u) & 3u) * 0.333333;
+    var v2_ao = f32((vert2_v4 >> 8u) & 3u) * 0.333333;
+    var v3_ao = f32((vert3_v4 >> 8u) & 3u) * 0.333333;
+    var v4_ao = f32((vert4_v4 >> 8u) & 3u) * 0.333333;
 
     var uv = array<vec2<f32>,4>(
             vec2(1.0,1.0),
@@ -100,10 +148,10 @@ fn vert(
 
     vr.light_uv = light_uv;
 
-    var v1 = chunk_data[id];
-    var v2 = chunk_data[id + 1u];
-    var v3 = chunk_data[id + 2u];
-    var v4 = chunk_data[id + 3u];
+    var v1 = data.x;
+    var v2 = data.y;
+    var v3 = data.z;
+    var v4 = data.w;
 
     var x: f32 = f32(v1 & 0xffu) * 0.0625;
     var y: f32 = f32((v1 >> 8u) & 0xffu) * 0.0625;
@@ -113,9 +161,20 @@ fn vert(
     var g: u32 = (v2 & 0xff);
     var b: u32 = (v2 >> 8u) & 0xff;
 
+    //A non-zero tint index means the tint color comes from the section's tint colors instead of the vertex
+    var tint_index: u32 = (v4 >> 16u) & 0xffu;
+    if(tint_index != 0u) {
+        var tint = section_tints[pc.tint_slot * SECTION_TINT_SLOTS + tint_index - 1u];
+        r = tint & 0xffu;
+        g = (tint >> 8u) & 0xffu;
+        b = (tint >> 16u) & 0xffu;
+    }
+
     vr.color = vec4(f32(r) * 0.003921568627451, f32(g) * 0.003921568627451, f32(b) * 0.003921568627451, 1.0);
 
-    var ao: f32 = f32((v4 >> 8u) & 0xff) * 0.33333;
+    var ao: f32 = f32((v4 >> 8u) & 3u) * 0.33333;
+    //How strongly the vertex sways, 0 for blocks which don't and at the bottom of rooted plants
+    var wave: f32 = f32((v4 >> 10u) & 63u) * 0.015873;
 
     var u: f32 = f32((v2 >> 16u) & 0xffffu) * 0.00048828125;
     var v: f32 = f32(v3 & 0xffffu) * 0.00048828125;
@@ -133,9 +192,13 @@ fn vert(
     }
     var pos = vec3<f32>(x, y, z);
 
-    var world_pos = pos + vec3<f32>(f32(section_pos.x) * 16.0, f32(section_pos.y) * 16.0, f32(section_pos.z) * 16.0);
+    var world_pos = pos + vec3<f32>(f32(pc.section_pos.x) * 16.0, f32(pc.section_pos.y) * 16.0, f32(pc.section_pos.z) * 16.0);
+    world_pos += wind_offset(world_pos, wave);
 
-    vr.pos = mat4_persp * mat4_view * mat4_model * vec4(world_pos, 1.0);
+    let view_pos = mat4_view * mat4_model * vec4(world_pos, 1.0);
+
+    vr.pos = mat4_persp * view_pos;
+    vr.view_pos = view_pos.xyz;
     vr.tex_coords = vec2<f32>(u, v);
     vr.tex_coords2 = vec2(0.0, 0.0);
     vr.world_pos = world_pos;
@@ -145,10 +208,45 @@ fn vert(
     vr.light_coords = 0.066666666666666 * vec2(f32(light_coords.x), f32(light_coords.y));
 
     vr.blend = 0.0;
+    vr.fade = pc.fade;
+    vr.uv_scroll = (v4 >> 24u) & 0xffu;
 
     return vr;
 }
 
+@vertex
+fn vert(
+    @builtin(vertex_index) vi: u32,
+    @builtin(instance_index) base_vertex: u32
+) -> VertexResult {
+    var id = (vi << 2u) + base_vertex;
+
+    return terrain_vertex(vi, base_vertex, vec4(chunk_data[id], chunk_data[id + 1u], chunk_data[id + 2u], chunk_data[id + 3u]));
+}
+
+//Used instead of vert with `vertex_fetch: attributes`
+@vertex
+fn vert_attributes(
+    @builtin(vertex_index) vi: u32,
+    @builtin(instance_index) base_vertex: u32,
+    @location(0) data: vec4<u32>
+) -> VertexResult {
+    return terrain_vertex(vi, base_vertex, data);
+}
+
+//Same as vanilla's fog_distance, except cylinder fog follows the camera's rotation
+fn fog_distance(pos: vec3<f32>, shape: f32) -> f32 {
+    if(shape == 0.0) {
+        return length(pos);
+    }
+
+    return max(length(pos.xz), abs(pos.y));
+}
+
+fn srgb_encode(linear: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055, linear * 12.92, linear <= vec3(0.0031308));
+}
+
 fn minecraft_sample_lighting(uv: vec2<f32>) -> vec3<f32> {
     return mix(uv.x * vec3(0.32156, 0.32156, 0.5) * 0.5 + uv.y * 0.5, vec3(1.0, 1.0, 1.0), uv.y);
 }
@@ -165,12 +263,38 @@ fn frag(
 
     var light = max(lc.x, lc.y);
 
-    let col = in.color * vec4(light, light, light, 1.0) * vec4(ao, ao, ao, 1.0) * textureSample(t_texture, t_sampler, in.tex_coords);
+    //Scrolled per pixel so the texture wraps around within its sprite. The mip level is picked from the UVs before
+    //wrapping, otherwise pixels where they wrap would jump to the smallest mip. Scaling the gradients biases it
+    let lod_scale = exp2(texture_quality.mip_bias);
+    let scroll = uv_scrolls[in.uv_scroll];
+    let tex_coords = scroll.rect.xy + fract((in.tex_coords - scroll.rect.xy) / scroll.rect.zw + scroll.offset.xy) * scroll.rect.zw;
+
+    var texel = textureSampleGrad(t_texture, t_sampler, tex_coords, dpdx(in.tex_coords) * lod_scale, dpdy(in.tex_coords) * lod_scale);
+    var tint = in.color;
+
+    //Vanilla multiplies sRGB encoded colors, so linear ones are encoded first for tints and AO to darken the same way
+    if(color_spaces.block_atlas == 1u) {
+        texel = vec4(srgb_encode(texel.rgb), texel.a);
+    }
+
+    if(color_spaces.vertex_colors == 0u) {
+        tint = vec4(srgb_encode(tint.rgb), tint.a);
+    }
+
+    var col = tint * vec4(light, light, light, 1.0) * vec4(ao, ao, ao, 1.0) * texel;
 
 //    let light = textureSample(lightmap_texture, lightmap_sampler, vec2(max(in.light_coords.x, in.light_coords.y), 0.0));
 
     if(col.a == 0.0f){
         discard;
     }
-    return col;
+
+    //No fog unless the host set it up
+    if(pc.fog.y > pc.fog.x) {
+        let fog = smoothstep(pc.fog.x, pc.fog.y, fog_distance(in.view_pos, pc.fog.z)) * pc.fog_color.a;
+        col = vec4(mix(col.rgb, pc.fog_color.rgb * col.a, fog), col.a);
+    }
+
+    //The terrain is blended with premultiplied alpha, so the whole color fades
+    return col * in.fade;
 }
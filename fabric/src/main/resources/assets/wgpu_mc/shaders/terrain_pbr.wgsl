@@ -0,0 +1,338 @@
+struct UV {
+    uv1: vec2<f32>,
+    uv2: vec2<f32>,
+    blend: f32,
+    padding: f32
+};
+
+struct UVs {
+    uvs: array<UV>
+};
+
+struct ChunkOffset {
+    x: i32,
+    z: i32
+}
+
+
+@group(0) @binding(0) var<uniform> mat4_model: mat4x4<f32>;
+@group(0) @binding(1) var<uniform> mat4_view: mat4x4<f32>;
+@group(0) @binding(2) var<uniform> mat4_persp: mat4x4<f32>;
+
+@group(0) @binding(3) var t_texture: texture_2d<f32>;
+@group(0) @binding(4) var t_sampler: sampler;
+//Laid out exactly like t_texture, sprites without a normal map are flat
+@group(0) @binding(5) var t_normal: texture_2d<f32>;
+//LabPBR layout, red is perceptual smoothness and green is reflectance, or a metal above 229
+@group(0) @binding(6) var t_specular: texture_2d<f32>;
+
+struct TextureQuality {
+    //Added to the sampled mip level, negative sharpens
+    mip_bias: f32
+};
+
+@group(0) @binding(7) var<uniform> texture_quality: TextureQuality;
+
+struct ColorSpaces {
+    //0 if linear, 1 if sRGB. Vertex colors are sRGB when they're stored encoded, the block atlas when it's decoded as
+    //it's sampled
+    vertex_colors: u32,
+    block_atlas: u32
+};
+
+@group(0) @binding(8) var<uniform> color_spaces: ColorSpaces;
+
+@group(1) @binding(0) var<storage> chunk_data: array<u32>;
+@group(2) @binding(0) var<storage> section_tints: array<u32>;
+
+const SECTION_TINT_SLOTS: u32 = 4u;
+
+struct VertexResult {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) tex_coords2: vec2<f32>,
+    @location(2) blend: f32,
+    @location(3) normal: vec3<f32>,
+    @location(4) world_pos: vec3<f32>,
+    @location(5) light_coords: vec2<f32>,
+    @location(6) section: u32,
+    @location(7) ao: f32,
+    @interpolate(flat) @location(8) lc1: vec2<f32>,
+    @interpolate(flat) @location(9) lc2: vec2<f32>,
+    @interpolate(flat) @location(10) lc3: vec2<f32>,
+    @interpolate(flat) @location(11) lc4: vec2<f32>,
+    @interpolate(flat) @location(12) ao1: f32,
+    @interpolate(flat) @location(13) ao2: f32,
+    @interpolate(flat) @location(14) ao3: f32,
+    @interpolate(flat) @location(15) ao4: f32,
+    @location(16) light_uv: vec2<f32>,
+    @interpolate(flat) @location(17) int: u32,
+    @location(18) color: vec4<f32>,
+    @interpolate(flat) @location(19) fade: f32,
+    @interpolate(flat) @location(20) sun_direction: vec3<f32>,
+    @interpolate(flat) @location(21) camera_pos: vec3<f32>
+};
+
+struct PushConstants {
+    section_pos: vec3i,
+    tint_slot: u32,
+    //0 to 1 as newly loaded sections fade in
+    fade: f32,
+    //Seconds, then the wind direction scaled by its strength, see Wind::push_constant
+    wind_time: f32,
+    wind_x: f32,
+    wind_z: f32,
+    sun_direction: vec3<f32>
+}
+
+var<push_constant> pc: PushConstants;
+
+//Gusts travel along the wind, with some ripple across it so neighbouring plants don't move in lockstep
+fn wind_offset(pos: vec3<f32>, wave: f32) -> vec3<f32> {
+    let wind = vec2(pc.wind_x, pc.wind_z);
+    let phase = pc.wind_time * 2.0 - dot(pos.xz, wind) * 0.5;
+    let gust = 0.6 + 0.4 * sin(phase) + 0.2 * sin(phase * 2.7 + pos.x * 0.9 + pos.z * 1.3);
+
+    return vec3(wind.x, 0.0, wind.y) * gust * wave;
+}
+
+@vertex
+fn vert(
+    @builtin(vertex_index) vi: u32,
+    @builtin(instance_index) base_vertex: u32
+) -> VertexResult {
+//    var vert1_i = (vi >> 2) << 4;
+//    var vert1_i = (vi << 2) & 0xfffffffc;
+//    var vert1_i = ((vi >> 2u) << 2u)+base_vertex;
+
+    var offset = vi & 3;
+    var vert1_i = vi & ~3u;
+
+    var id = ((vert1_i + offset) << 2u) + base_vertex;
+
+    var vert1_base = ((vert1_i) << 2u) + base_vertex;
+
+    var vert1_v4 = chunk_data[vert1_base + 3u];
+    var vert2_v4 = chunk_data[vert1_base + 7u];
+    var vert3_v4 = chunk_data[vert1_base + 11u];
+    var vert4_v4 = chunk_data[vert1_base + 15u];
+
+    var v1_lc = 0.066666666666667 * vec2(f32(vert1_v4 & 15u), f32((vert1_v4 >> 4u) & 15u));
+    var v2_lc = 0.066666666666667 * vec2(f32(vert2_v4 & 15u), f32((vert2_v4 >> 4u) & 15u));
+    var v3_lc = 0.066666666666667 * vec2(f32(vert3_v4 & 15u), f32((vert3_v4 >> 4u) & 15u));
+    var v4_lc = 0.066666666666667 * vec2(f32(vert4_v4 & 15u), f32((vert4_v4 >> 4u) & 15u));
+    var v1_ao = f32((vert1_v4 >> 8u) & 3u) * 0.333333;
+    var v2_ao = f32((vert2_v4 >> 8u) & 3u) * 0.333333;
+    var v3_ao = f32((vert3_v4 >> 8u) & 3u) * 0.333333;
+    var v4_ao = f32((vert4_v4 >> 8u) & 3u) * 0.333333;
+
+    var uv = array<vec2<f32>,4>(
+            vec2(1.0,1.0),
+            vec2(0.0,1.0),
+            vec2(0.0,0.0),
+            vec2(1.0,0.0));
+
+    var light_uv = uv[vi & 3];
+
+    var vr: VertexResult;
+    vr.int = vi & 3;
+    vr.lc1 = v1_lc;
+    vr.lc2 = v2_lc;
+    vr.lc3 = v3_lc;
+    vr.lc4 = v4_lc;
+    vr.ao1 = v1_ao;
+    vr.ao2 = v2_ao;
+    vr.ao3 = v3_ao;
+    vr.ao4 = v4_ao;
+
+    vr.light_uv = light_uv;
+
+    var v1 = chunk_data[id];
+    var v2 = chunk_data[id + 1u];
+    var v3 = chunk_data[id + 2u];
+    var v4 = chunk_data[id + 3u];
+
+    var x: f32 = f32(v1 & 0xffu) * 0.0625;
+    var y: f32 = f32((v1 >> 8u) & 0xffu) * 0.0625;
+    var z: f32 = f32((v1 >> 16u) & 0xffu) * 0.0625;
+
+    var r: u32 = (v1 >> 24u) & 0xff;
+    var g: u32 = (v2 & 0xff);
+    var b: u32 = (v2 >> 8u) & 0xff;
+
+    //A non-zero tint index means the tint color comes from the section's tint colors instead of the vertex
+    var tint_index: u32 = (v4 >> 16u) & 0xffu;
+    if(tint_index != 0u) {
+        var tint = section_tints[pc.tint_slot * SECTION_TINT_SLOTS + tint_index - 1u];
+        r = tint & 0xffu;
+        g = (tint >> 8u) & 0xffu;
+        b = (tint >> 16u) & 0xffu;
+    }
+
+    vr.color = vec4(f32(r) * 0.003921568627451, f32(g) * 0.003921568627451, f32(b) * 0.003921568627451, 1.0);
+
+    var ao: f32 = f32((v4 >> 8u) & 3u) * 0.33333;
+    //How strongly the vertex sways, 0 for blocks which don't and at the bottom of rooted plants
+    var wave: f32 = f32((v4 >> 10u) & 63u) * 0.015873;
+
+    var u: f32 = f32((v2 >> 16u) & 0xffffu) * 0.00048828125;
+    var v: f32 = f32(v3 & 0xffffu) * 0.00048828125;
+
+    if(((v3 >> 29u) & 1u) == 1u) {
+        x = 16.0;
+    }
+
+    if(((v3 >> 30u) & 1u) == 1u) {
+        y = 16.0;
+    }
+
+    if((v3 >> 31u) == 1u) {
+        z = 16.0;
+    }
+    var pos = vec3<f32>(x, y, z);
+
+    var world_pos = pos + vec3<f32>(f32(pc.section_pos.x) * 16.0, f32(pc.section_pos.y) * 16.0, f32(pc.section_pos.z) * 16.0);
+    world_pos += wind_offset(world_pos, wave);
+
+    vr.pos = mat4_persp * mat4_view * mat4_model * vec4(world_pos, 1.0);
+    vr.tex_coords = vec2<f32>(u, v);
+    vr.tex_coords2 = vec2(0.0, 0.0);
+    vr.world_pos = world_pos;
+    vr.ao = ao;
+
+    var light_coords = vec2<u32>(v4 & 15u, (v4 >> 4u) & 15u);
+    vr.light_coords = 0.066666666666666 * vec2(f32(light_coords.x), f32(light_coords.y));
+
+    vr.blend = 0.0;
+    vr.fade = pc.fade;
+    vr.sun_direction = normalize(pc.sun_direction);
+
+    //The camera is wherever the view matrix moves the origin from
+    let view = mat4_view * mat4_model;
+    vr.camera_pos = -(transpose(mat3x3(view[0].xyz, view[1].xyz, view[2].xyz)) * view[3].xyz);
+
+    return vr;
+}
+
+//The same directional shading vanilla applies to block faces, so flat normals look like vanilla
+fn face_shade(n: vec3<f32>) -> f32 {
+    var up = 0.5;
+    if(n.y > 0.0) {
+        up = 1.0;
+    }
+
+    return min(n.x * n.x * 0.6 + n.y * n.y * up + n.z * n.z * 0.8, 1.0);
+}
+
+//Builds the tangent frame from screen space derivatives, as the terrain vertices don't have tangents
+fn perturb_normal(world_pos: vec3<f32>, uv: vec2<f32>, tangent_normal: vec3<f32>) -> vec3<f32> {
+    let dp1 = dpdx(world_pos);
+    let dp2 = dpdy(world_pos);
+    let duv1 = dpdx(uv);
+    let duv2 = dpdy(uv);
+
+    //Framebuffer y points down, so this is the order which faces the camera
+    let n = normalize(cross(dp2, dp1));
+
+    let dp2perp = cross(dp2, n);
+    let dp1perp = cross(n, dp1);
+    let t = dp2perp * duv1.x + dp1perp * duv2.x;
+    let b = dp2perp * duv1.y + dp1perp * duv2.y;
+
+    let inv_max = inverseSqrt(max(dot(t, t), dot(b, b)));
+
+    return normalize(mat3x3(t * inv_max, b * inv_max, n) * tangent_normal);
+}
+
+const PI: f32 = 3.14159265;
+const SUN_INTENSITY: f32 = 2.0;
+
+//Cook-Torrance specular with a GGX distribution, Schlick fresnel and Smith-Schlick visibility
+fn specular_brdf(n: vec3<f32>, v: vec3<f32>, l: vec3<f32>, roughness: f32, f0: vec3<f32>) -> vec3<f32> {
+    let h = normalize(v + l);
+    let n_dot_l = max(dot(n, l), 0.0);
+    let n_dot_v = max(dot(n, v), 0.0001);
+    let n_dot_h = max(dot(n, h), 0.0);
+    let v_dot_h = max(dot(v, h), 0.0);
+
+    let a = max(roughness * roughness, 0.002);
+    let a2 = a * a;
+    let d_denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    let d = a2 / (PI * d_denom * d_denom);
+
+    let k = a * 0.5;
+    let vis = 1.0 / ((n_dot_l * (1.0 - k) + k) * (n_dot_v * (1.0 - k) + k));
+
+    let f = f0 + (1.0 - f0) * pow(1.0 - v_dot_h, 5.0);
+
+    return d * vis * f * 0.25 * n_dot_l;
+}
+
+//There's no HDR target to tonemap yet, so bright highlights are scaled down instead of clipping to white.
+//This belongs in a tonemapping pass once the framebuffer is HDR
+fn compress_highlights(color: vec3<f32>) -> vec3<f32> {
+    return color / max(1.0, max(color.r, max(color.g, color.b)));
+}
+
+fn srgb_encode(linear: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055, linear * 12.92, linear <= vec3(0.0031308));
+}
+
+fn srgb_decode(encoded: vec3<f32>) -> vec3<f32> {
+    return select(pow((encoded + 0.055) / 1.055, vec3(2.4)), encoded / 12.92, encoded <= vec3(0.04045));
+}
+
+fn minecraft_sample_lighting(uv: vec2<f32>) -> vec3<f32> {
+    return mix(uv.x * vec3(0.32156, 0.32156, 0.5) * 0.5 + uv.y * 0.5, vec3(1.0, 1.0, 1.0), uv.y);
+}
+
+@fragment
+fn frag(
+    in: VertexResult
+) -> @location(0) vec4<f32> {
+//    var ao: f32 = (in.ao * 0.7) + 0.3;
+
+    var lc = mix(mix(in.lc3, in.lc4, in.light_uv.x), mix(in.lc2, in.lc1, in.light_uv.x), in.light_uv.y);
+    var ao = 0.6 + 0.4 * mix(mix(in.ao3, in.ao4, in.light_uv.x), mix(in.ao2, in.ao1, in.light_uv.x), in.light_uv.y);
+//    var ao = mix(mix(0.0, 0.0, in.light_uv.x), mix(0.0, 1.0, in.light_uv.x), in.light_uv.y);
+
+    let tangent_normal = textureSampleBias(t_normal, t_sampler, in.tex_coords, texture_quality.mip_bias).xyz * 2.0 - 1.0;
+    let normal = perturb_normal(in.world_pos, in.tex_coords, tangent_normal);
+
+    var light = max(lc.x, lc.y) * face_shade(normal);
+
+    //The tint is brought into the space the atlas is sampled in, which is linear for an sRGB atlas
+    var tint = in.color.rgb;
+    if(color_spaces.block_atlas == 1u && color_spaces.vertex_colors == 1u) {
+        tint = srgb_decode(tint);
+    } else if(color_spaces.block_atlas == 0u && color_spaces.vertex_colors == 0u) {
+        tint = srgb_encode(tint);
+    }
+
+    let albedo = vec4(tint, in.color.a) * textureSampleBias(t_texture, t_sampler, in.tex_coords, texture_quality.mip_bias);
+    let specular = textureSampleBias(t_specular, t_sampler, in.tex_coords, texture_quality.mip_bias);
+
+    let roughness = (1.0 - specular.r) * (1.0 - specular.r);
+    let metal = specular.g > 0.9;
+
+    var f0 = vec3(specular.g);
+    var diffuse = albedo.rgb;
+    if(metal) {
+        f0 = albedo.rgb;
+        diffuse = vec3(0.0);
+    }
+
+    //Only lit by the sun where the sky is visible
+    let view_dir = normalize(in.camera_pos - in.world_pos);
+    let sun = specular_brdf(normal, view_dir, in.sun_direction, roughness, f0) * SUN_INTENSITY * lc.y;
+
+    let col = vec4(compress_highlights(diffuse * light * ao + sun * ao), albedo.a);
+
+//    let light = textureSample(lightmap_texture, lightmap_sampler, vec2(max(in.light_coords.x, in.light_coords.y), 0.0));
+
+    if(col.a == 0.0f){
+        discard;
+    }
+    //The terrain is blended with premultiplied alpha, so the whole color fades
+    return col * in.fade;
+}
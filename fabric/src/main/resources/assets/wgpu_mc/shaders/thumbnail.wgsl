@@ -0,0 +1,37 @@
+@group(0) @binding(0) var t_texture: texture_2d<f32>;
+@group(0) @binding(1) var t_sampler: sampler;
+
+struct VertexResult {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) shade: f32
+};
+
+//Vertices are already transformed into clip space and shaded on the CPU
+@vertex
+fn vert(
+    @location(0) pos: vec4<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) shade: f32
+) -> VertexResult {
+    var vr: VertexResult;
+    vr.pos = pos;
+    vr.uv = uv;
+    vr.shade = shade;
+
+    return vr;
+}
+
+@fragment
+fn frag(
+    in: VertexResult
+) -> @location(0) vec4<f32> {
+    let color = textureSample(t_texture, t_sampler, in.uv);
+
+    //Cutout textures like leaves would otherwise write depth for their transparent pixels
+    if (color.a < 0.1) {
+        discard;
+    }
+
+    return vec4(color.rgb * in.shade, color.a);
+}
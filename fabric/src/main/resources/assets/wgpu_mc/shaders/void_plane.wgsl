@@ -0,0 +1,52 @@
+@group(0) @binding(0) var<uniform> mat4_model: mat4x4<f32>;
+@group(0) @binding(1) var<uniform> mat4_view: mat4x4<f32>;
+@group(0) @binding(2) var<uniform> mat4_persp: mat4x4<f32>;
+
+struct VertexResult {
+    @builtin(position) pos: vec4<f32>,
+    //-1 to 1 across the plane
+    @location(0) local: vec2<f32>,
+    @interpolate(flat) @location(1) color: vec3<f32>
+};
+
+@vertex
+fn vert(
+    @builtin(vertex_index) vi: u32,
+    @location(0) center: vec3<f32>,
+    @location(1) radius: f32,
+    @location(2) color: vec3<f32>
+) -> VertexResult {
+    //Counter clockwise when seen from above
+    var corners = array<vec2<f32>, 6>(
+        vec2(-1.0, -1.0),
+        vec2(-1.0, 1.0),
+        vec2(1.0, 1.0),
+        vec2(-1.0, -1.0),
+        vec2(1.0, 1.0),
+        vec2(1.0, -1.0)
+    );
+    var corner = corners[vi];
+
+    var vr: VertexResult;
+
+    var world_pos = center + vec3(corner.x * radius, 0.0, corner.y * radius);
+
+    vr.pos = mat4_persp * mat4_view * mat4_model * vec4(world_pos, 1.0);
+    vr.local = corner;
+    vr.color = color;
+
+    return vr;
+}
+
+@fragment
+fn frag(
+    in: VertexResult
+) -> @location(0) vec4<f32> {
+    let alpha = 1.0 - smoothstep(0.5, 1.0, length(in.local));
+
+    if(alpha <= 0.0) {
+        discard;
+    }
+
+    return vec4(in.color, alpha);
+}
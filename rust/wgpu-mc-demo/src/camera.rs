@@ -1,32 +1,33 @@
 use std::f32::consts::PI;
 
-use glam::{vec3, Mat4, Vec3};
+use glam::{dvec3, vec3, DVec3, Mat4, Vec3};
+use wgpu_mc::render::camera::CameraProjection;
+use wgpu_mc::Frustum;
 
 const DEG_TO_RAD: f32 = PI / 180.0;
 #[derive(Debug, Copy, Clone)]
 pub struct Camera {
-    pub position: Vec3,
+    pub position: DVec3,
     pub yaw: f32,
     pub pitch: f32,
     pub up: Vec3,
-    pub aspect: f32,
-    pub fovy: f32,
-    pub znear: f32,
-    pub zfar: f32,
+    pub projection: CameraProjection,
 }
 
 impl Camera {
     #[must_use]
     pub fn new(aspect: f32) -> Self {
         Self {
-            position: vec3(0.0, 0.0, 0.0),
+            position: dvec3(0.0, 0.0, 0.0),
             yaw: 0.0,
             pitch: 0.0,
             up: Vec3::Y,
-            aspect,
-            fovy: 90.0 * DEG_TO_RAD,
-            znear: 0.001,
-            zfar: 1000.0,
+            projection: CameraProjection::Perspective {
+                fovy: 90.0 * DEG_TO_RAD,
+                aspect,
+                znear: 0.001,
+                zfar: 1000.0,
+            },
         }
     }
 
@@ -38,16 +39,22 @@ impl Camera {
         )
     }
 
+    /// Builds the view matrix relative to the scene's render origin, the corner of the section column the camera is in
     pub fn build_view_matrix(&self) -> Mat4 {
-        let pos = vec3(
+        let pos = dvec3(
             self.position.x.rem_euclid(16.0),
             self.position.y,
             self.position.z.rem_euclid(16.0),
-        );
+        )
+        .as_vec3();
         Mat4::look_at_rh(pos, pos + self.get_direction(), self.up)
     }
 
     pub fn build_perspective_matrix(&self) -> Mat4 {
-        Mat4::perspective_rh(self.fovy, self.aspect, self.znear, self.zfar)
+        self.projection.matrix()
+    }
+
+    pub fn frustum(&self) -> Frustum<f32> {
+        self.projection.frustum(self.build_view_matrix())
     }
 }
@@ -61,6 +61,7 @@ pub fn make_chunks(wm: &WmRenderer, pos: IVec3, _scene: &Scene) {
             [],
             &*wm.mc.resource_provider,
             atlas,
+            &wm.mc.model_loaders.read(),
             0,
         )
         .unwrap();
@@ -69,8 +69,8 @@ pub fn describe_entity(wm: &WmRenderer) -> (Arc<Entity>, BundledEntityInstances)
         position: (0.0, 0.0, 0.0),
         looking_yaw: 0.0,
         uv_offset: [0, 0],
-        part_transforms: vec![PartTransform::identity(); entity.parts.len()],
-        overlays: vec![0; entity.parts.len()],
+        part_transforms: vec![PartTransform::identity(); entity.part_count],
+        overlays: vec![0; entity.part_count],
     };
 
     let mut instances = BundledEntityInstances::new(
@@ -18,12 +18,12 @@ use crate::camera::Camera;
 use crate::chunk::make_chunks;
 use wgpu_mc::mc::direction::Direction;
 use wgpu_mc::mc::resource::{ResourcePath, ResourceProvider};
-use wgpu_mc::mc::Scene;
+use wgpu_mc::mc::{MissingModelPolicy, Scene};
 use wgpu_mc::render::graph::{RenderGraph, ResourceBacking};
 use wgpu_mc::render::shaderpack::ShaderPackConfig;
 use wgpu_mc::wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu_mc::wgpu::{BufferBindingType, Extent3d, PresentMode};
-use wgpu_mc::{wgpu, Display, Frustum, WmRenderer};
+use wgpu_mc::{wgpu, Display, WmRenderer};
 
 mod camera;
 mod chunk;
@@ -110,23 +110,18 @@ impl ApplicationHandler for Application {
         const VSYNC: bool = true;
 
         let surface_caps = surface.get_capabilities(&adapter);
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: wgpu::TextureFormat::Bgra8Unorm,
-            width: window.inner_size().width,
-            height: window.inner_size().height,
-            present_mode: if VSYNC {
+        let surface_config = Display::surface_config(
+            &surface_caps,
+            window.inner_size().width,
+            window.inner_size().height,
+            if VSYNC {
                 PresentMode::AutoVsync
             } else if surface_caps.present_modes.contains(&PresentMode::Immediate) {
                 PresentMode::Immediate
             } else {
                 surface_caps.present_modes[0]
             },
-
-            desired_maximum_frame_latency: 2,
-            alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
-        };
+        );
 
         surface.configure(&device, &surface_config);
 
@@ -185,7 +180,13 @@ impl ApplicationHandler for Application {
 
         wm.init();
 
-        wm.mc.bake_blocks(&wm, blocks.iter().map(|(a, b)| (a, b)));
+        wm.mc
+            .bake_blocks(
+                &wm,
+                blocks.iter().map(|(a, b)| (a, b)),
+                MissingModelPolicy::Placeholder,
+            )
+            .unwrap();
 
         let pack = serde_yaml::from_str::<ShaderPackConfig>(
             &wm.mc
@@ -335,7 +336,8 @@ impl ApplicationHandler for Application {
                     let frame_time = Instant::now().duration_since(self.last_frame).as_secs_f32();
                     self.last_frame = Instant::now();
 
-                    camera.position += camera.get_direction() * self.forward * 50.0 * frame_time;
+                    camera.position +=
+                        (camera.get_direction() * self.forward * 50.0 * frame_time).as_dvec3();
 
                     let perspective: [[f32; 4]; 4] =
                         camera.build_perspective_matrix().to_cols_array_2d();
@@ -351,9 +353,10 @@ impl ApplicationHandler for Application {
                         );
                     }
                     *self.scene.as_mut().unwrap().camera_section_pos.write() = ivec2(
-                        camera.position.x.floor() as i32 >> 4,
-                        camera.position.z.floor() as i32 >> 4,
+                        (camera.position.x / 16.0).floor() as i32,
+                        (camera.position.z / 16.0).floor() as i32,
                     );
+                    *self.scene.as_mut().unwrap().camera_position.write() = camera.position;
 
                     if let ResourceBacking::Buffer(buffer, _) =
                         &self.render_graph.as_ref().unwrap().resources["@mat4_perspective"]
@@ -392,11 +395,14 @@ impl ApplicationHandler for Application {
                                 wm.display.surface.get_current_texture().unwrap()
                             });
 
+                    //The rest of the frame reads the config, e.g. for the view format
+                    drop(config_guard);
+
                     let view = surface_texture
                         .texture
                         .create_view(&wgpu::TextureViewDescriptor {
                             label: None,
-                            format: Some(wgpu::TextureFormat::Bgra8Unorm),
+                            format: Some(wm.display.view_format()),
                             dimension: Some(wgpu::TextureViewDimension::D2),
                             aspect: Default::default(),
                             base_mip_level: 0,
@@ -414,9 +420,6 @@ impl ApplicationHandler for Application {
 
                     let mut geometry = HashMap::new();
 
-                    let mvp = (camera.build_perspective_matrix() * camera.build_view_matrix())
-                        .to_cols_array_2d();
-
                     self.render_graph.as_ref().unwrap().render(
                         wm,
                         &mut command_encoder,
@@ -424,7 +427,7 @@ impl ApplicationHandler for Application {
                         &view,
                         [0; 3],
                         &mut geometry,
-                        &Frustum::from_modelview_projection(mvp),
+                        &camera.frustum(),
                     );
 
                     wm.display.queue.submit([command_encoder.finish()]);
@@ -1,4 +1,6 @@
+use std::sync::mpsc::{channel, RecvTimeoutError};
 use std::sync::Arc;
+use std::time::Duration;
 
 use futures::executor::block_on;
 use jni::{objects::JValue, JavaVM};
@@ -20,6 +22,7 @@ use winit::{
     event_loop::ActiveEventLoop,
     keyboard::{KeyCode, ModifiersState, PhysicalKey},
     platform::scancode::PhysicalKeyExtScancode,
+    window::Fullscreen,
 };
 
 use crate::{
@@ -29,6 +32,7 @@ use crate::{
 };
 use std::collections::HashMap;
 use wgpu_mc::render::{
+    camera::CameraUniform,
     graph::{RenderGraph, ResourceBacking},
     shaderpack::ShaderPackConfig,
 };
@@ -60,6 +64,18 @@ pub fn load_shaders(wm: &WmRenderer) {
         ResourceBacking::Buffer(mat4_model.clone(), BufferBindingType::Uniform),
     );
 
+    render_resources.insert(
+        "@buffer_camera".into(),
+        ResourceBacking::Buffer(
+            Arc::new(wm.display.device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("camera"),
+                contents: &[0; std::mem::size_of::<CameraUniform>()],
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+            })),
+            BufferBindingType::Uniform,
+        ),
+    );
+
     let mut custom_bind_groups = HashMap::new();
     custom_bind_groups.insert(
         "@texture_electrum_gui".into(),
@@ -98,13 +114,183 @@ pub fn load_shaders(wm: &WmRenderer) {
     }
 }
 
+/// Why wgpu could not be initialized, reported to the user instead of panicking
+#[derive(Debug)]
+pub enum InitError {
+    CreateSurface(wgpu::CreateSurfaceError),
+    NoAdapter,
+    /// A driver never answered the adapter or device request
+    Timeout {
+        request: &'static str,
+        timeout: Duration,
+    },
+    RequestDevice {
+        error: wgpu::RequestDeviceError,
+        adapter: wgpu::AdapterInfo,
+        features: wgpu::Features,
+        limits: wgpu::Limits,
+    },
+}
+
+impl std::fmt::Display for InitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InitError::CreateSurface(error) => {
+                write!(f, "Failed to create a surface for the window: {}", error)
+            }
+            InitError::NoAdapter => write!(
+                f,
+                "No compatible GPU adapter was found, a GPU with Vulkan support is required"
+            ),
+            InitError::Timeout { request, timeout } => write!(
+                f,
+                "The GPU driver didn't answer the {} request within {:?}",
+                request, timeout
+            ),
+            InitError::RequestDevice {
+                error,
+                adapter,
+                features,
+                limits,
+            } => write!(
+                f,
+                "The GPU \"{}\" ({:?}) does not support the required features or limits: {}\nRequired features: {:?}\nRequired limits: {:?}",
+                adapter.name, adapter.backend, error, features, limits
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InitError {}
+
+/// How long [init_wgpu] waits for the adapter and the device, each
+pub const INIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Run `request` on a worker thread and wait at most [INIT_TIMEOUT] for it. wgpu does the work of adapter and
+/// device requests when they're made rather than when they're awaited, so the timeout can only fire if a hung driver
+/// blocks another thread. A worker which times out is left running
+fn request_with_timeout<T: Send + 'static>(
+    request: &'static str,
+    run: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, InitError> {
+    let (sender, receiver) = channel();
+
+    std::thread::Builder::new()
+        .name(format!("wgpu {request} request"))
+        .spawn(move || {
+            let _ = sender.send(run());
+        })
+        .unwrap();
+
+    match receiver.recv_timeout(INIT_TIMEOUT) {
+        Ok(output) => Ok(output),
+        Err(RecvTimeoutError::Timeout) => Err(InitError::Timeout {
+            request,
+            timeout: INIT_TIMEOUT,
+        }),
+        Err(RecvTimeoutError::Disconnected) => panic!("The wgpu {request} request panicked"),
+    }
+}
+
+pub fn init_wgpu(window: Arc<winit::window::Window>) -> Result<Display, InitError> {
+    let size = window.inner_size();
+
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::VULKAN,
+        ..Default::default()
+    });
+
+    let surface = instance
+        .create_surface(window.clone())
+        .map_err(InitError::CreateSurface)?;
+    let (instance, surface, adapter) = request_with_timeout("adapter", move || {
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            compatible_surface: Some(&surface),
+        }));
+
+        (instance, surface, adapter)
+    })?;
+    let adapter = adapter.ok_or(InitError::NoAdapter)?;
+
+    const VSYNC: bool = false;
+
+    let surface_caps = surface.get_capabilities(&adapter);
+    let surface_config = Display::surface_config(
+        &surface_caps,
+        size.width,
+        size.height,
+        if VSYNC {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        },
+    );
+
+    let required_limits = wgpu::Limits {
+        max_push_constant_size: 128,
+        max_bind_groups: 8,
+        max_storage_buffers_per_shader_stage: 1000,
+        ..Default::default()
+    };
+
+    let required_features = wgpu::Features::default()
+        | wgpu::Features::DEPTH_CLIP_CONTROL
+        | wgpu::Features::PUSH_CONSTANTS
+        | wgpu::Features::BUFFER_BINDING_ARRAY
+        | wgpu::Features::STORAGE_RESOURCE_BINDING_ARRAY
+        | wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING
+        | wgpu::Features::PARTIALLY_BOUND_BINDING_ARRAY
+        | wgpu::Features::MULTI_DRAW_INDIRECT;
+
+    let descriptor = wgpu::DeviceDescriptor {
+        label: None,
+        required_features,
+        required_limits: required_limits.clone(),
+        memory_hints: wgpu::MemoryHints::Performance,
+    };
+    let (adapter, device) = request_with_timeout("device", move || {
+        let device = block_on(adapter.request_device(&descriptor, None)); // No trace path
+
+        (adapter, device)
+    })?;
+    let (device, queue) = device.map_err(|error| InitError::RequestDevice {
+        error,
+        adapter: adapter.get_info(),
+        features: required_features,
+        limits: required_limits,
+    })?;
+
+    surface.configure(&device, &surface_config);
+
+    Ok(Display {
+        window,
+        size: RwLock::new(size),
+        surface,
+        device,
+        queue,
+        config: RwLock::new(surface_config),
+        instance,
+        adapter,
+    })
+}
+
+/// Fullscreen is always borderless on the window's current monitor, which switches instantly and doesn't change the
+/// monitor's video mode
+pub fn fullscreen_mode(fullscreen: bool) -> Option<Fullscreen> {
+    fullscreen.then_some(Fullscreen::Borderless(None))
+}
+
 pub struct Application {
     title: String,
+    size: PhysicalSize<u32>,
+    fullscreen: bool,
     current_modifiers: ModifiersState,
     jvm: JavaVM,
 }
 impl Application {
-    pub fn new(jvm: JavaVM, title: String) -> Self {
+    pub fn new(jvm: JavaVM, title: String, size: PhysicalSize<u32>, fullscreen: bool) -> Self {
         let current_modifiers = ModifiersState::empty();
         // {
         //     let tex_id = LIGHTMAP_GLID.lock().unwrap();
@@ -117,6 +303,8 @@ impl Application {
         profiling::register_thread!("Winit Thread");
         Self {
             title,
+            size,
+            fullscreen,
             current_modifiers,
             jvm,
         }
@@ -131,81 +319,19 @@ impl ApplicationHandler for Application {
         // initialisation, should only occure once on desktop
         let window_attributes = winit::window::Window::default_attributes()
             .with_title(&self.title)
-            .with_inner_size(winit::dpi::Size::Physical(PhysicalSize {
-                width: 1280,
-                height: 720,
-            }));
+            .with_inner_size(winit::dpi::Size::Physical(self.size))
+            .with_fullscreen(fullscreen_mode(self.fullscreen));
         let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
-        let size = window.inner_size();
-
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::VULKAN,
-            ..Default::default()
-        });
-
-        let surface = instance.create_surface(window.clone()).unwrap();
-        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::HighPerformance,
-            force_fallback_adapter: false,
-            compatible_surface: Some(&surface),
-        }))
-        .unwrap();
-
-        const VSYNC: bool = false;
-
-        let surface_caps = surface.get_capabilities(&adapter);
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: wgpu::TextureFormat::Bgra8Unorm,
-            width: size.width,
-            height: size.height,
-            present_mode: if VSYNC {
-                PresentMode::AutoVsync
-            } else {
-                PresentMode::AutoNoVsync
-            },
-
-            desired_maximum_frame_latency: 2,
-            alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
-        };
-
-        let required_limits = wgpu::Limits {
-            max_push_constant_size: 128,
-            max_bind_groups: 8,
-            max_storage_buffers_per_shader_stage: 1000,
-            ..Default::default()
-        };
 
-        let (device, queue) = block_on(adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                label: None,
-                required_features: wgpu::Features::default()
-                    | wgpu::Features::DEPTH_CLIP_CONTROL
-                    | wgpu::Features::PUSH_CONSTANTS
-                    | wgpu::Features::BUFFER_BINDING_ARRAY
-                    | wgpu::Features::STORAGE_RESOURCE_BINDING_ARRAY
-                    | wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING
-                    | wgpu::Features::PARTIALLY_BOUND_BINDING_ARRAY
-                    | wgpu::Features::MULTI_DRAW_INDIRECT,
-                required_limits,
-                memory_hints: wgpu::MemoryHints::Performance,
-            },
-            None, // Trace path
-        ))
-        .unwrap();
-
-        surface.configure(&device, &surface_config);
-
-        let display = Display {
-            window,
-            size: RwLock::new(size),
-            surface,
-            device,
-            queue,
-            config: RwLock::new(surface_config),
-            instance,
-            adapter,
+        let display = match init_wgpu(window) {
+            Ok(display) => display,
+            Err(error) => {
+                log::error!("{}", error);
+                env.throw_new("java/lang/IllegalStateException", error.to_string())
+                    .unwrap();
+                event_loop.exit();
+                return;
+            }
         };
 
         let resource_provider = Arc::new(MinecraftResourceManagerAdapter {
@@ -230,6 +356,10 @@ impl ApplicationHandler for Application {
                         | wgpu::BufferUsages::INDEX,
                 })),
                 last_bytes: None,
+                cull_offscreen: true,
+                culled: 0,
+                draws_before_batching: 0,
+                draws_after_batching: 0,
             }) as Box<dyn Geometry>,
         );
 
@@ -277,7 +407,11 @@ impl ApplicationHandler for Application {
         window_id: winit::window::WindowId,
         event: winit::event::WindowEvent,
     ) {
-        let wm = RENDERER.get().unwrap();
+        //Events can arrive before resumed has finished initializing the renderer, or after it failed to
+        let Some(wm) = RENDERER.get() else {
+            return;
+        };
+
         if window_id == wm.display.window.id() {
             match event {
                 WindowEvent::CloseRequested => event_loop.exit(),
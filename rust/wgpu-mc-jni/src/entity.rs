@@ -8,7 +8,7 @@ use serde::Deserialize;
 
 use crate::RENDERER;
 use wgpu_mc::mc::entity::Entity;
-use wgpu_mc::mc::entity::{Cuboid, CuboidUV, EntityPart, PartTransform};
+use wgpu_mc::mc::entity::{Cuboid, CuboidUV, EntityPart, PartTransform, Winding};
 use wgpu_mc::render::pipeline::ENTITY_ATLAS;
 
 #[derive(Debug, Deserialize)]
@@ -200,7 +200,13 @@ pub fn registerEntities(mut env: JNIEnv, _class: JClass, string: JString) {
 
             (
                 name.clone(),
-                Arc::new(Entity::new(name.clone(), entity_part, &wm.display)),
+                Arc::new(Entity::new_with_options(
+                    name.clone(),
+                    entity_part,
+                    &wm.display,
+                    Winding::CounterClockwise,
+                    wm.extra_buffer_usages.read().entities,
+                )),
             )
         })
         .collect();
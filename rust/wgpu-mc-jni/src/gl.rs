@@ -6,7 +6,7 @@ use std::sync::Arc;
 use std::vec::Vec;
 
 use bytemuck::{Pod, Zeroable};
-use glam::Mat4;
+use glam::{Mat4, Vec4};
 use once_cell::sync::Lazy;
 use parking_lot::RwLock;
 
@@ -201,7 +201,7 @@ enum DrawCall {
     Indexed(IndexedDraw),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum PipelineState {
     PositionColorUint,
     PositionUv,
@@ -210,6 +210,58 @@ enum PipelineState {
     PositionColorUvLight,
 }
 
+impl PipelineState {
+    /// The size of one vertex in the vertex buffers Minecraft sends
+    fn stride(&self) -> usize {
+        match self {
+            PipelineState::PositionColorUint => 16,
+            PipelineState::PositionUv => 20,
+            PipelineState::PositionColorF32 => 24,
+            PipelineState::PositionUvColor => 24,
+            PipelineState::PositionColorUvLight => 28,
+        }
+    }
+}
+
+impl IndexedDraw {
+    fn can_merge(&self, other: &IndexedDraw) -> bool {
+        self.pipeline_state == other.pipeline_state
+            && self.texture == other.texture
+            && self.matrix == other.matrix
+            && self.color == other.color
+    }
+
+    fn merge(&mut self, other: IndexedDraw) {
+        let base_vertex = (self.vertex_buffer.len() / self.pipeline_state.stride()) as u32;
+
+        self.index_buffer.truncate(self.count as usize);
+        self.index_buffer.extend(
+            other.index_buffer[..other.count as usize]
+                .iter()
+                .map(|index| index + base_vertex),
+        );
+        self.vertex_buffer.extend(other.vertex_buffer);
+        self.count += other.count;
+    }
+}
+
+/// Merge consecutive indexed draws which share a pipeline, texture, matrix and color (e.g. the glyphs of a string)
+/// into one draw. Only neighbours are merged, so draws still happen in the same order and blend the same way
+fn batch_draws(calls: Vec<DrawCall>) -> Vec<DrawCall> {
+    let mut batched: Vec<DrawCall> = Vec::with_capacity(calls.len());
+
+    for call in calls {
+        match (batched.last_mut(), call) {
+            (Some(DrawCall::Indexed(last)), DrawCall::Indexed(draw)) if last.can_merge(&draw) => {
+                last.merge(draw);
+            }
+            (_, call) => batched.push(call),
+        }
+    }
+
+    batched
+}
+
 pub struct BufferPool {
     pub data: Vec<u8>,
 }
@@ -233,6 +285,50 @@ impl BufferPool {
 pub struct ElectrumGeometry {
     pub pool: Arc<Buffer>,
     pub last_bytes: Option<Vec<u8>>,
+    /// Skip indexed draws which are entirely off screen, see [is_offscreen]
+    pub cull_offscreen: bool,
+    /// How many draws were skipped by [ElectrumGeometry::cull_offscreen] last frame
+    pub culled: u32,
+    /// How many draws Minecraft issued last frame, and how many were left after [batch_draws]
+    pub draws_before_batching: u32,
+    pub draws_after_batching: u32,
+}
+
+/// True if every vertex ends up past the same edge of the screen once transformed by `matrix`, in which case the
+/// draw can't cover a single pixel. This is conservative, anything which might be visible (including vertices
+/// behind the camera) is kept. The GUI doesn't use scissoring yet, so only the viewport is tested
+fn is_offscreen(matrix: &Mat4, vertices: &[ElectrumVertex]) -> bool {
+    let mut outside = 0b1111;
+
+    for vertex in vertices {
+        let clip = *matrix * Vec4::new(vertex.pos[0], vertex.pos[1], vertex.pos[2], 1.0);
+
+        if clip.w <= 0.0 {
+            return false;
+        }
+
+        let mut code = 0;
+        if clip.x < -clip.w {
+            code |= 0b0001;
+        }
+        if clip.x > clip.w {
+            code |= 0b0010;
+        }
+        if clip.y < -clip.w {
+            code |= 0b0100;
+        }
+        if clip.y > clip.w {
+            code |= 0b1000;
+        }
+
+        outside &= code;
+
+        if outside == 0 {
+            return false;
+        }
+    }
+
+    !vertices.is_empty()
 }
 
 impl Geometry for ElectrumGeometry {
@@ -245,6 +341,7 @@ impl Geometry for ElectrumGeometry {
         arena: &WmArena<'arena>,
     ) {
         let mut buffer_pool = BufferPool { data: Vec::new() };
+        let mut culled = 0;
 
         let (_, commands) = {
             GL_COMMANDS.read().clone() //Free the lock as soon as possible
@@ -331,6 +428,10 @@ impl Geometry for ElectrumGeometry {
             }
         }
 
+        self.draws_before_batching = calls.len() as u32;
+        let calls = batch_draws(calls);
+        self.draws_after_batching = calls.len() as u32;
+
         for call in calls {
             match call {
                 DrawCall::Verts(draw) => {
@@ -382,7 +483,7 @@ impl Geometry for ElectrumGeometry {
                             wgpu::ShaderStages::FRAGMENT,
                         ),
                     );
-                    set_push_constants(&bound_pipeline.config, render_pass, Some(push_constants));
+                    set_push_constants(bound_pipeline, render_pass, Some(push_constants));
 
                     let buffer_slice = buffer_pool.allocate(&draw.vertex_buffer);
 
@@ -402,6 +503,33 @@ impl Geometry for ElectrumGeometry {
                         }
                     };
 
+                    let vertices = match draw.pipeline_state {
+                        PipelineState::PositionColorUint => ElectrumVertex::map_pos_color_uint(
+                            bytemuck::cast_slice(&draw.vertex_buffer),
+                        ),
+                        PipelineState::PositionUv => {
+                            ElectrumVertex::map_pos_uv(bytemuck::cast_slice(&draw.vertex_buffer))
+                        }
+                        PipelineState::PositionColorF32 => ElectrumVertex::map_pos_col_float3(
+                            bytemuck::cast_slice(&draw.vertex_buffer),
+                        ),
+                        PipelineState::PositionUvColor => ElectrumVertex::map_pos_uv_color(
+                            bytemuck::cast_slice(&draw.vertex_buffer),
+                        ),
+                        PipelineState::PositionColorUvLight => {
+                            ElectrumVertex::map_pos_color_uv_light(
+                                bytemuck::try_cast_slice(&draw.vertex_buffer).unwrap(),
+                            )
+                        }
+                    };
+
+                    if self.cull_offscreen
+                        && is_offscreen(&Mat4::from_cols_array_2d(&draw.matrix), &vertices)
+                    {
+                        culled += 1;
+                        continue;
+                    }
+
                     for (index, bind_group) in bound_pipeline.bind_groups.iter() {
                         match bind_group {
                             WmBindGroup::Resource(name) => match &name[..] {
@@ -439,42 +567,41 @@ impl Geometry for ElectrumGeometry {
                             wgpu::ShaderStages::FRAGMENT,
                         ),
                     );
-                    set_push_constants(&bound_pipeline.config, render_pass, Some(push_constants));
-
-                    let vertices = match draw.pipeline_state {
-                        PipelineState::PositionColorUint => ElectrumVertex::map_pos_color_uint(
-                            bytemuck::cast_slice(&draw.vertex_buffer),
-                        ),
-                        PipelineState::PositionUv => {
-                            ElectrumVertex::map_pos_uv(bytemuck::cast_slice(&draw.vertex_buffer))
-                        }
-                        PipelineState::PositionColorF32 => ElectrumVertex::map_pos_col_float3(
-                            bytemuck::cast_slice(&draw.vertex_buffer),
-                        ),
-                        PipelineState::PositionUvColor => ElectrumVertex::map_pos_uv_color(
-                            bytemuck::cast_slice(&draw.vertex_buffer),
-                        ),
-                        PipelineState::PositionColorUvLight => {
-                            ElectrumVertex::map_pos_color_uv_light(
-                                bytemuck::try_cast_slice(&draw.vertex_buffer).unwrap(),
-                            )
-                        }
-                    };
+                    set_push_constants(bound_pipeline, render_pass, Some(push_constants));
 
                     let vert_slice = buffer_pool.allocate(&vertices);
 
-                    let index_slice = buffer_pool.allocate(&draw.index_buffer);
+                    //Most GUI meshes are small enough for 16 bit indices
+                    let (index_slice, index_format) = if vertices.len() <= u16::MAX as usize + 1 {
+                        let indices = draw
+                            .index_buffer
+                            .iter()
+                            .map(|&index| index as u16)
+                            .collect::<Vec<_>>();
+
+                        (buffer_pool.allocate(&indices), IndexFormat::Uint16)
+                    } else {
+                        (
+                            buffer_pool.allocate(&draw.index_buffer),
+                            IndexFormat::Uint32,
+                        )
+                    };
 
                     let pool_alloc = arena.alloc(self.pool.clone());
 
                     render_pass.set_vertex_buffer(0, pool_alloc.slice(vert_slice));
-                    render_pass
-                        .set_index_buffer(pool_alloc.slice(index_slice), IndexFormat::Uint32);
+                    render_pass.set_index_buffer(pool_alloc.slice(index_slice), index_format);
                     render_pass.draw_indexed(0..draw.count, 0, 0..1);
                 }
             }
         }
 
+        self.culled = culled;
+
+        //Buffer writes have to be a multiple of 4 bytes, which 16 bit indices might not end on
+        let padded_len = buffer_pool.data.len().next_multiple_of(4);
+        buffer_pool.data.resize(padded_len, 0);
+
         match &self.last_bytes {
             None => {}
             Some(bytes) => {
@@ -1,16 +1,18 @@
 pub extern crate wgpu_mc;
 
-use application::Application;
+use application::{fullscreen_mode, Application};
 use arc_swap::ArcSwap;
 use byteorder::{LittleEndian, ReadBytesExt};
 use core::slice;
 use crossbeam_channel::{unbounded, Receiver, Sender};
-use glam::{ivec2, ivec3, IVec3, Mat4};
+use glam::{ivec2, ivec3, DVec3, IVec3, Mat4};
 use jni::objects::{
     AutoElements, GlobalRef, JByteArray, JClass, JFloatArray, JIntArray, JLongArray, JObject,
     JObjectArray, JPrimitiveArray, JString, JValue, JValueOwned, ReleaseMode, WeakRef,
 };
-use jni::sys::{jboolean, jbyte, jfloat, jint, jlong, jsize, jstring, JNI_FALSE, JNI_TRUE};
+use jni::sys::{
+    jboolean, jbyte, jdouble, jfloat, jint, jlong, jsize, jstring, JNI_FALSE, JNI_TRUE,
+};
 use jni::{JNIEnv, JavaVM};
 use jni_fn::jni_fn;
 use once_cell::sync::{Lazy, OnceCell};
@@ -28,20 +30,25 @@ use std::sync::Arc;
 use std::time::Instant;
 use std::{mem, thread};
 use wgpu::Extent3d;
+use wgpu_mc::render::atlas::TextureQuality;
+use wgpu_mc::render::camera::CameraUniform;
 use wgpu_mc::render::graph::{Geometry, RenderGraph, ResourceBacking};
-use winit::dpi::PhysicalPosition;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
 use winit::event::{ElementState, MouseButton};
 use winit::window::CursorGrabMode;
 
 use wgpu_mc::mc::block::{BlockstateKey, ChunkBlockState};
-use wgpu_mc::mc::chunk::{bake_section, BlockStateProvider, LightLevel};
+use wgpu_mc::mc::chunk::{bake_section, BlockStateProvider, LightLevel, RenderLayer};
 use wgpu_mc::mc::resource::{ResourcePath, ResourceProvider};
-use wgpu_mc::mc::Scene;
+use wgpu_mc::mc::{MissingModelPolicy, Scene};
 use wgpu_mc::minecraft_assets::schemas::blockstates::multipart::StateValue;
 use wgpu_mc::render::pipeline::BLOCK_ATLAS;
+use wgpu_mc::render::underground_culling::UndergroundCulling;
+use wgpu_mc::render::uv_scroll::UvScroll;
+use wgpu_mc::render::wind::Sway;
 use wgpu_mc::texture::{BindableTexture, TextureAndView};
+use wgpu_mc::wgpu;
 use wgpu_mc::wgpu::ImageDataLayout;
-use wgpu_mc::wgpu::{self, TextureFormat};
 use wgpu_mc::{Frustum, WmRenderer};
 
 use crate::gl::{GLCommand, GlTexture, GL_ALLOC, GL_COMMANDS};
@@ -106,6 +113,8 @@ static MC_STATE: Lazy<ArcSwap<MinecraftRenderState>> = Lazy::new(|| {
     }))
 });
 
+/// What UV scrolls are timed from
+static RENDER_START: Lazy<Instant> = Lazy::new(Instant::now);
 static CLEAR_COLOR: Lazy<ArcSwap<[f32; 3]>> = Lazy::new(|| ArcSwap::new(Arc::new([0.0; 3])));
 
 static THREAD_POOL: OnceCell<ThreadPool> = OnceCell::new();
@@ -127,14 +136,19 @@ static AIR: Lazy<BlockstateKey> = Lazy::new(|| BlockstateKey {
 static SCENE: Lazy<Scene> = Lazy::new(|| {
     let wm = RENDERER.get().unwrap();
 
-    Scene::new(
+    let scene = Scene::new(
         wm,
         wgpu::Extent3d {
             width: wm.display.window.inner_size().width,
             height: wm.display.window.inner_size().height,
             depth_or_array_layers: 1,
         },
-    )
+    );
+
+    //Newly loaded sections fade in rather than popping up
+    *scene.section_fade_duration.write() = 0.5;
+
+    scene
 });
 
 static BLOCKS: Mutex<Vec<String>> = Mutex::new(Vec::new());
@@ -379,6 +393,14 @@ pub fn getBackend(env: JNIEnv, _class: JClass) -> jstring {
     env.new_string(backend).unwrap().into_raw()
 }
 
+#[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
+pub fn getGpuName(env: JNIEnv, _class: JClass) -> jstring {
+    let renderer = RENDERER.get().unwrap();
+    let info = renderer.adapter_info();
+
+    env.new_string(info.name).unwrap().into_raw()
+}
+
 #[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
 pub fn registerBlockState(
     mut env: JNIEnv,
@@ -409,6 +431,33 @@ pub fn setSectionPos(_env: JNIEnv, _class: JClass, x: jint, z: jint) {
     *SCENE.camera_section_pos.write() = ivec2(x, z);
 }
 
+#[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
+pub fn setCameraPos(_env: JNIEnv, _class: JClass, x: jdouble, y: jdouble, z: jdouble) {
+    *SCENE.camera_position.write() = DVec3::new(x, y, z);
+}
+
+#[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
+pub fn setCameraPitch(_env: JNIEnv, _class: JClass, pitch: jfloat) {
+    *SCENE.camera_pitch.write() = pitch;
+}
+
+#[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
+pub fn setUndergroundCulling(
+    _env: JNIEnv,
+    _class: JClass,
+    enabled: jboolean,
+    surface_y: jdouble,
+    depth: jdouble,
+    max_pitch: jfloat,
+) {
+    *SCENE.underground_culling.lock() = (enabled == JNI_TRUE).then(|| UndergroundCulling {
+        surface_y,
+        depth,
+        max_pitch,
+        ..Default::default()
+    });
+}
+
 struct MinecraftBlockStateProviderWrapper<'a> {
     internal: MinecraftBlockstateProvider,
     env: RefCell<JNIEnv<'a>>,
@@ -544,8 +593,138 @@ pub fn registerBlock(mut env: JNIEnv, _class: JClass, name: JString) {
 }
 
 #[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
-pub fn startRendering(mut env: JNIEnv, _class: JClass, title: JString) {
+pub fn setBlockRenderLayer(mut env: JNIEnv, _class: JClass, packed_key: jint, layer: jint) {
+    let layer = match layer {
+        0 => RenderLayer::Solid,
+        1 => RenderLayer::Cutout,
+        2 => RenderLayer::Transparent,
+        _ => {
+            env.throw_new(
+                "java/lang/IllegalArgumentException",
+                format!("Unknown render layer {layer}"),
+            )
+            .unwrap();
+            return;
+        }
+    };
+
+    RENDERER
+        .get()
+        .unwrap()
+        .mc
+        .block_manager
+        .write()
+        .set_render_layer(BlockstateKey::from(packed_key as u32), layer);
+}
+
+#[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
+pub fn addUvScroll(
+    mut env: JNIEnv,
+    _class: JClass,
+    packed_key: jint,
+    sprite: JString,
+    velocity_u: jfloat,
+    velocity_v: jfloat,
+) -> jint {
+    let sprite: String = env.get_string(&sprite).unwrap().into();
+    let wm = RENDERER.get().unwrap();
+
+    let atlases = wm.mc.texture_manager.atlases.read();
+    let Some(&uv) = atlases[BLOCK_ATLAS]
+        .uv_map
+        .read()
+        .get(&ResourcePath::from(&sprite[..]))
+    else {
+        return -1;
+    };
+
+    wm.mc
+        .block_manager
+        .write()
+        .add_uv_scroll(
+            BlockstateKey::from(packed_key as u32),
+            UvScroll {
+                sprite: uv,
+                velocity: [velocity_u, velocity_v],
+            },
+        )
+        .map_or(-1, jint::from)
+}
+
+#[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
+pub fn setBlockSway(_env: JNIEnv, _class: JClass, packed_key: jint, bottom: jfloat, top: jfloat) {
+    let sway = (bottom > 0.0 || top > 0.0).then_some(Sway { bottom, top });
+
+    RENDERER
+        .get()
+        .unwrap()
+        .mc
+        .block_manager
+        .write()
+        .set_sway(BlockstateKey::from(packed_key as u32), sway);
+}
+
+#[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
+pub fn setLightEmission(_env: JNIEnv, _class: JClass, packed_key: jint, level: jint) {
+    RENDERER
+        .get()
+        .unwrap()
+        .mc
+        .block_manager
+        .write()
+        .set_light_emission(
+            BlockstateKey::from(packed_key as u32),
+            level.clamp(0, 15) as u8,
+        );
+}
+
+#[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
+pub fn setTextureQuality(
+    _env: JNIEnv,
+    _class: JClass,
+    anisotropy: jint,
+    mip_bias: jfloat,
+    max_mip: jfloat,
+) -> jboolean {
+    let wm = RENDERER.get().unwrap();
+
+    let quality = TextureQuality {
+        anisotropy: anisotropy.clamp(0, u16::MAX as jint) as u16,
+        mip_bias,
+        max_mip,
+    };
+
+    if let Err(error) = wm
+        .mc
+        .texture_manager
+        .set_texture_quality(&wm.display, quality)
+    {
+        log::warn!("Ignoring texture quality {quality:?}: {error:?}");
+        return JNI_FALSE;
+    }
+
+    if let Some(render_graph) = RENDER_GRAPH.get() {
+        render_graph.lock().rebuild_block_sampler(wm);
+    }
+
+    JNI_TRUE
+}
+
+#[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
+pub fn startRendering(
+    mut env: JNIEnv,
+    _class: JClass,
+    title: JString,
+    width: jint,
+    height: jint,
+    fullscreen: jboolean,
+) {
     let title: String = env.get_string(&title).unwrap().into();
+    let size = if width > 0 && height > 0 {
+        PhysicalSize::new(width as u32, height as u32)
+    } else {
+        PhysicalSize::new(1280, 720)
+    };
     let jvm = env.get_java_vm().unwrap();
 
     let class_loader = env
@@ -564,7 +743,7 @@ pub fn startRendering(mut env: JNIEnv, _class: JClass, title: JString) {
         panic!("Failed to set classloader")
     };
 
-    let mut application = Application::new(jvm, title);
+    let mut application = Application::new(jvm, title, size, fullscreen == JNI_TRUE);
     let mut event_loop = winit::event_loop::EventLoop::builder();
 
     #[cfg(target_os = "linux")]
@@ -585,14 +764,19 @@ pub fn startRendering(mut env: JNIEnv, _class: JClass, title: JString) {
 #[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
 pub fn render(_env: JNIEnv, _class: JClass, _tick_delta: jfloat, _start_time: jlong, _tick: jlong) {
     let wm = RENDERER.wait();
-    let render_graph = RENDER_GRAPH.get().unwrap().lock();
+    let mut render_graph = RENDER_GRAPH.get().unwrap().lock();
     let mut geometry = CUSTOM_GEOMETRY.get().unwrap().lock();
     wm.display.window.request_redraw();
+    render_graph.resize_render_targets(wm);
+    render_graph.swap_atlas_textures(wm);
     wm.submit_chunk_updates(&SCENE);
     let pos = *SCENE.camera_section_pos.read();
     SCENE.section_storage.write().trim(pos);
     *SCENE.entity_instances.lock() = ENTITY_INSTANCES.lock().clone();
 
+    wm.update_uv_scrolls(RENDER_START.elapsed().as_secs_f32());
+    SCENE.wind.write().time = RENDER_START.elapsed().as_secs_f32();
+
     let matrices = MATRICES.lock();
     if let ResourceBacking::Buffer(buffer, _) = &render_graph.resources["@mat4_perspective"] {
         wm.display
@@ -611,28 +795,56 @@ pub fn render(_env: JNIEnv, _class: JClass, _tick_delta: jfloat, _start_time: jl
             bytemuck::cast_slice(&matrices.terrain_transformation),
         );
     }
+    if let ResourceBacking::Buffer(buffer, _) = &render_graph.resources["@buffer_camera"] {
+        let camera = CameraUniform::new(
+            Mat4::from_cols_array_2d(&matrices.view),
+            Mat4::from_cols_array_2d(&matrices.projection),
+            SCENE.to_render_space(*SCENE.camera_position.read()),
+        );
+
+        wm.display
+            .queue
+            .write_buffer(buffer, 0, bytemuck::bytes_of(&camera));
+    }
+
+    //Resizing is done here rather than on the window resize event for synchronization reasons
+    let reconfigure_surface = || {
+        let mut surface_config = wm.display.config.write();
+        let size = wm.display.size.read();
+        surface_config.width = size.width;
+        surface_config.height = size.height;
+        SCENE.resize_depth_texture(wm, size.width, size.height);
+        wm.display
+            .surface
+            .configure(&wm.display.device, &surface_config);
+    };
+
+    //Not every platform reports the surface as outdated after a resize, e.g. when toggling fullscreen, so a size
+    //change is picked up even if acquiring the next texture would still succeed. Minimized windows have no size
+    {
+        let size = *wm.display.size.read();
+        let config = wm.display.config.read();
+        let resized = (size.width, size.height) != (config.width, config.height);
+        drop(config);
+
+        if resized && size.width > 0 && size.height > 0 {
+            reconfigure_surface();
+        }
+    }
 
     let texture = wm
         .display
         .surface
         .get_current_texture()
         .unwrap_or_else(|_| {
-            //The surface is outdated, so we force an update. This can't be done on the window resize event for synchronization reasons.
-
-            let mut surface_config = wm.display.config.write();
-            let size = wm.display.size.read();
-            surface_config.width = size.width;
-            surface_config.height = size.height;
-            SCENE.resize_depth_texture(wm, size.width, size.height);
-            wm.display
-                .surface
-                .configure(&wm.display.device, &surface_config);
+            //The surface is outdated, so we force an update
+            reconfigure_surface();
             wm.display.surface.get_current_texture().unwrap()
         });
 
     let view = texture.texture.create_view(&wgpu::TextureViewDescriptor {
         label: None,
-        format: Some(TextureFormat::Bgra8Unorm),
+        format: Some(wm.display.view_format()),
         dimension: Some(wgpu::TextureViewDimension::D2),
         aspect: Default::default(),
         base_mip_level: 0,
@@ -681,12 +893,16 @@ pub fn cacheBlockStates(mut env: JNIEnv, _class: JClass) {
             })
             .collect::<Vec<_>>();
 
-        wm.mc.bake_blocks(
-            wm,
-            blockstates
-                .iter()
-                .map(|(string, resource)| (string, resource)),
-        );
+        //Blocks which still don't resolve to a model fall back to bedrock below
+        wm.mc
+            .bake_blocks(
+                wm,
+                blockstates
+                    .iter()
+                    .map(|(string, resource)| (string, resource)),
+                MissingModelPolicy::Placeholder,
+            )
+            .unwrap();
     }
 
     let mut states = BLOCK_STATES.lock();
@@ -732,6 +948,7 @@ pub fn cacheBlockStates(mut env: JNIEnv, _class: JClass) {
                     .map(|(a, b)| (*a, b)),
                 &*wm.mc.resource_provider,
                 atlas,
+                &wm.mc.model_loaders.read(),
                 0,
             );
             let fallback_key = block_manager.blocks.get_full("minecraft:bedrock").unwrap();
@@ -912,6 +1129,18 @@ pub fn centerCursor(_env: JNIEnv, _class: JClass, _locked: jboolean) {
     }
 }
 
+/// Switch between borderless fullscreen and windowed mode. The window is resized as a result, and the surface is
+/// reconfigured for the new size on the next frame
+#[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
+pub fn setFullscreen(_env: JNIEnv, _class: JClass, fullscreen: jboolean) {
+    if let Some(wm) = RENDERER.get() {
+        wm.display
+            .window
+            .set_fullscreen(fullscreen_mode(fullscreen == JNI_TRUE));
+        wm.display.window.request_redraw();
+    }
+}
+
 #[allow(unused_must_use)]
 #[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
 pub fn setCursorLocked(_env: JNIEnv, _class: JClass, locked: jboolean) {
@@ -1138,23 +1367,32 @@ pub fn subImage2D(
             }
         }
 
-        wm.display.queue.write_texture(
-            gl_texture
-                .bindable_texture
-                .as_ref()
-                .unwrap()
-                .tv
-                .texture
-                .as_image_copy(),
+        //Only the updated region is uploaded, maps and other dynamic textures do this every frame
+        wm.mc.texture_manager.write_texture(
+            &wm.display,
+            wgpu::ImageCopyTexture {
+                origin: wgpu::Origin3d {
+                    x: offsetX as u32,
+                    y: offsetY as u32,
+                    z: 0,
+                },
+                ..gl_texture
+                    .bindable_texture
+                    .as_ref()
+                    .unwrap()
+                    .tv
+                    .texture
+                    .as_image_copy()
+            },
             &gl_texture.pixels,
             ImageDataLayout {
-                offset: 0,
+                offset: (offsetY as u64 * gl_texture.width as u64 + offsetX as u64) * 4,
                 bytes_per_row: Some(gl_texture.width as u32 * 4),
                 rows_per_image: Some(gl_texture.height as u32),
             },
             Extent3d {
-                width: gl_texture.width as u32,
-                height: gl_texture.height as u32,
+                width: width as u32,
+                height: height as u32,
                 depth_or_array_layers: 1,
             },
         );
@@ -1198,6 +1436,61 @@ pub fn attachTextureBindGroup(_env: JNIEnv, _class: JClass, slot: jint, id: jint
         .push(GLCommand::AttachTexture(slot as u32, id));
 }
 
+#[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
+pub fn registerStandaloneTexture(
+    mut env: JNIEnv,
+    _class: JClass,
+    path: JString,
+    address_mode: jint,
+    gl_id: jint,
+) -> jboolean {
+    let address_mode = match address_mode {
+        0 => wgpu::AddressMode::ClampToEdge,
+        1 => wgpu::AddressMode::Repeat,
+        2 => wgpu::AddressMode::MirrorRepeat,
+        _ => {
+            env.throw_new(
+                "java/lang/IllegalArgumentException",
+                format!("Unknown address mode {address_mode}"),
+            )
+            .unwrap();
+            return JNI_FALSE;
+        }
+    };
+
+    let path = ResourcePath::from(&String::from(env.get_string(&path).unwrap())[..]);
+    let wm = RENDERER.get().unwrap();
+
+    if !wm.mc.texture_manager.register_standalone(
+        &wm.display,
+        &*wm.mc.resource_provider,
+        path.clone(),
+        address_mode,
+    ) {
+        return JNI_FALSE;
+    }
+
+    if gl_id >= 0 {
+        let texture = wm.mc.texture_manager.standalone.read()[&path]
+            .texture
+            .clone();
+        let (width, height) = (texture.texture.width(), texture.texture.height());
+
+        //GL textures keep their pixels for glTexSubImage2D
+        GL_ALLOC.write().insert(
+            gl_id as u32,
+            GlTexture {
+                width: width as u16,
+                height: height as u16,
+                bindable_texture: Some(Arc::new(BindableTexture::from_tv(wm, texture, false))),
+                pixels: vec![0; (width * height) as usize * 4],
+            },
+        );
+    }
+
+    JNI_TRUE
+}
+
 #[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
 pub fn wmUsePipeline(_env: JNIEnv, _class: JClass, pipeline: jint) {
     GL_COMMANDS
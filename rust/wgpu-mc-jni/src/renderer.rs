@@ -4,19 +4,22 @@ use std::slice;
 use std::{sync::Arc, time::Instant};
 
 use byteorder::LittleEndian;
+use glam::Vec3;
 use jni::objects::{AutoElements, JClass, JFloatArray, ReleaseMode};
-use jni::sys::{jfloat, jint, jlong};
+use jni::sys::{jboolean, jfloat, jint, jlong};
 use jni::{objects::JString, JNIEnv};
 use jni_fn::jni_fn;
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
 use wgpu_mc::mc::entity::{BundledEntityInstances, InstanceVertex};
-use wgpu_mc::mc::RenderEffectsData;
+use wgpu_mc::mc::{RenderEffectsData, SkyState};
+use wgpu_mc::render::entity_lod::EntityLodPartition;
+use wgpu_mc::render::scale::UpscaleFilter;
 use wgpu_mc::texture::BindableTexture;
 
 use crate::application::{load_shaders, SHOULD_STOP};
 use crate::gl::{GlTexture, GL_ALLOC};
-use crate::RENDERER;
+use crate::{RENDERER, SCENE};
 
 pub static MATRICES: Lazy<Mutex<Matrices>> = Lazy::new(|| {
     Mutex::new(Matrices {
@@ -76,6 +79,27 @@ pub fn scheduleStop(_env: JNIEnv, _class: JClass) {
     let _ = SHOULD_STOP.set(());
 }
 
+/// See [wgpu_mc::WmRenderer::set_render_scale]
+#[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
+pub fn setRenderScale(_env: JNIEnv, _class: JClass, scale: jfloat, linear: jboolean) {
+    let wm = RENDERER.get().unwrap();
+
+    *wm.upscale_filter.write() = if linear != 0 {
+        UpscaleFilter::Linear
+    } else {
+        UpscaleFilter::Nearest
+    };
+    wm.set_render_scale(scale);
+}
+
+/// Blocks until the GPU is idle, see [wgpu_mc::WmRenderer::wait_idle]. Does nothing if the renderer was never created
+#[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
+pub fn waitIdle(_env: JNIEnv, _class: JClass) {
+    if let Some(wm) = RENDERER.get() {
+        wm.wait_idle();
+    }
+}
+
 #[derive(Copy, Clone, Hash, Eq, PartialEq)]
 pub enum MCTextureId {
     BlockAtlas,
@@ -91,6 +115,94 @@ pub static MC_TEXTURES: Lazy<Mutex<HashMap<MCTextureId, Arc<BindableTexture>>>>
 #[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
 pub fn clearEntities(_env: JNIEnv, _class: JClass) {
     ENTITY_INSTANCES.lock().clear();
+    SCENE.entity_lod_stats.lock().clear();
+}
+
+/// Where the instances drawn with an entity type's reduced model are bundled, so they don't replace the bundle of
+/// the reduced model's own entity type
+fn reduced_bundle_key(entity_name: &str) -> String {
+    format!("{entity_name}#reduced")
+}
+
+fn upload_entity_instances(
+    bundle_key: &str,
+    model_name: &str,
+    texture_id: jint,
+    transforms: &[f32],
+    verts: &[InstanceVertex],
+) {
+    let wm = RENDERER.get().unwrap();
+    let instance_count = verts.len() as u32;
+
+    let mut instances = ENTITY_INSTANCES.lock();
+
+    if instance_count == 0 {
+        instances.remove(bundle_key);
+        return;
+    }
+
+    let to_upload = match instances.get_mut(bundle_key) {
+        Some(bundled_entity_instances) if bundled_entity_instances.has_room_for(instance_count) => {
+            bundled_entity_instances.capacity = instance_count;
+
+            bundled_entity_instances
+        }
+        _ => {
+            let texture = {
+                let gl_alloc = GL_ALLOC.read();
+
+                match gl_alloc.get(&(texture_id as u32)) {
+                    None => return,
+                    Some(GlTexture {
+                        bindable_texture: None,
+                        ..
+                    }) => return,
+                    _ => {}
+                }
+
+                gl_alloc
+                    .get(&(texture_id as u32))
+                    .unwrap()
+                    .bindable_texture
+                    .as_ref()
+                    .unwrap()
+                    .clone()
+            };
+            let models = wm.mc.entity_models.read();
+            let entity = models.get(model_name).unwrap();
+            //Leave room to grow, so that a few more entities don't recreate the buffers every frame
+            let max_instances =
+                BundledEntityInstances::max_instances(&wm.display.device.limits(), entity);
+            let capacity = instance_count
+                .max(4096)
+                .next_power_of_two()
+                .min(max_instances);
+
+            if capacity < instance_count {
+                log::warn!(
+                    "Only {capacity} of {instance_count} instances of {model_name} fit within the storage buffer limit"
+                );
+                return;
+            }
+
+            let mut bundle =
+                BundledEntityInstances::new(wm, entity.clone(), &texture.tv.view, capacity);
+            bundle.capacity = instance_count;
+            instances.insert(bundle_key.to_string(), bundle);
+            instances.get(bundle_key).unwrap()
+        }
+    };
+
+    wm.display.queue.write_buffer(
+        &to_upload.uploaded.instance_vbo,
+        0,
+        bytemuck::cast_slice(verts),
+    );
+    wm.display.queue.write_buffer(
+        &to_upload.uploaded.transforms_buffer,
+        0,
+        bytemuck::cast_slice(transforms),
+    );
 }
 
 #[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
@@ -125,13 +237,15 @@ pub fn setEntityInstanceBuffer(
     let now = Instant::now();
     let instance_count = instance_count as u32;
 
-    let wm = RENDERER.get().unwrap();
-
     //TODO this is slow, let's use an integer id somewhere
     let entity_name: String = env.get_string(&entity_name).unwrap().into();
 
     if instance_count == 0 {
         ENTITY_INSTANCES.lock().remove(&entity_name);
+        ENTITY_INSTANCES
+            .lock()
+            .remove(&reduced_bundle_key(&entity_name));
+        SCENE.entity_lod_stats.lock().remove(&entity_name);
         return Instant::now().duration_since(now).as_nanos() as jlong;
     }
 
@@ -140,65 +254,54 @@ pub fn setEntityInstanceBuffer(
     let overlays =
         unsafe { slice::from_raw_parts(overlay_ptr as usize as *mut i32, overlay_len as usize) };
 
-    let transforms: Vec<f32> = Vec::from(mat4s);
+    let floats_per_instance = mat4s.len() / instance_count as usize;
 
-    let verts: Vec<InstanceVertex> = overlays
-        .iter()
-        .map(|overlay| InstanceVertex {
-            uv_offset: [0, 0],
-            overlay: *overlay as u32,
-        })
-        .collect();
+    let entity_lod = SCENE.entity_lod.read();
+    let reduced_model = entity_lod
+        .reduced_model(&entity_name)
+        .map(|reduced| reduced.model.clone());
 
-    let mut instances = ENTITY_INSTANCES.lock();
+    //The entity matrices are relative to the camera, so the translation of the root part is the distance to it
+    let partition = entity_lod.partition(
+        &entity_name,
+        Vec3::ZERO,
+        mat4s.chunks_exact(floats_per_instance).zip(overlays.iter()),
+        |(transforms, _)| Vec3::new(transforms[12], transforms[13], transforms[14]),
+    );
+    drop(entity_lod);
 
-    let to_upload = match instances.get_mut(&entity_name) {
-        Some(bundled_entity_instances) if bundled_entity_instances.capacity <= instance_count => {
-            bundled_entity_instances.capacity = instance_count;
+    SCENE
+        .entity_lod_stats
+        .lock()
+        .insert(entity_name.clone(), partition.stats());
 
-            bundled_entity_instances
-        }
-        _ => {
-            let texture = {
-                let gl_alloc = GL_ALLOC.read();
+    let EntityLodPartition { full, reduced, .. } = partition;
 
-                match gl_alloc.get(&(texture_id as u32)) {
-                    None => return 0,
-                    Some(GlTexture {
-                        bindable_texture: None,
-                        ..
-                    }) => return 0,
-                    _ => {}
-                }
+    let upload = |bundle_key: &str, model_name: &str, instances: Vec<(&[f32], &i32)>| {
+        let transforms: Vec<f32> = instances
+            .iter()
+            .flat_map(|(transforms, _)| transforms.iter().copied())
+            .collect();
 
-                gl_alloc
-                    .get(&(texture_id as u32))
-                    .unwrap()
-                    .bindable_texture
-                    .as_ref()
-                    .unwrap()
-                    .clone()
-            };
-            let models = wm.mc.entity_models.read();
-            let entity = models.get(&entity_name).unwrap();
-            instances.insert(
-                entity_name.clone(),
-                BundledEntityInstances::new(wm, entity.clone(), &texture.tv.view, 4096),
-            );
-            instances.get(&entity_name).unwrap()
-        }
+        let verts: Vec<InstanceVertex> = instances
+            .iter()
+            .map(|(_, overlay)| {
+                InstanceVertex::with_overlay(InstanceVertex::overlay_from_vanilla(**overlay as u32))
+            })
+            .collect();
+
+        upload_entity_instances(bundle_key, model_name, texture_id, &transforms, &verts);
     };
 
-    wm.display.queue.write_buffer(
-        &to_upload.uploaded.instance_vbo,
-        0,
-        bytemuck::cast_slice(&verts),
-    );
-    wm.display.queue.write_buffer(
-        &to_upload.uploaded.transforms_buffer,
-        0,
-        bytemuck::cast_slice(&transforms),
-    );
+    upload(&entity_name, &entity_name, full);
+
+    let reduced_key = reduced_bundle_key(&entity_name);
+    match reduced_model {
+        Some(model) => upload(&reduced_key, &model, reduced),
+        None => {
+            ENTITY_INSTANCES.lock().remove(&reduced_key);
+        }
+    }
 
     Instant::now().duration_since(now).as_nanos() as jlong
 }
@@ -207,24 +310,21 @@ pub fn setEntityInstanceBuffer(
 pub fn bindSkyData(
     _env: JNIEnv,
     _class: JClass,
-    _r: jfloat,
-    _g: jfloat,
-    _b: jfloat,
-    _angle: jfloat,
-    _brightness: jfloat,
-    _star_shimmer: jfloat,
-    _moon_phase: jint,
+    r: jfloat,
+    g: jfloat,
+    b: jfloat,
+    angle: jfloat,
+    brightness: jfloat,
+    star_shimmer: jfloat,
+    moon_phase: jint,
 ) {
-    // let mut sky_data = (**RENDERER.get().unwrap().mc.sky_data.load()).clone();
-    // sky_data.color_r = r;
-    // sky_data.color_g = g;
-    // sky_data.color_b = b;
-    // sky_data.angle = angle;
-    // sky_data.brightness = brightness;
-    // sky_data.star_shimmer = star_shimmer;
-    // sky_data.moon_phase = moon_phase;
-    //
-    // RENDERER.get().unwrap().mc.sky_data.swap(Arc::new(sky_data));
+    *SCENE.sky_state.write() = SkyState {
+        color: [r, g, b].map(|channel| (channel.clamp(0.0, 1.0) * 255.0) as u8),
+        angle,
+        brightness,
+        star_shimmer,
+        moon_phase,
+    };
 }
 
 #[jni_fn("dev.birb.wgpu.rust.WgpuNative")]
@@ -238,7 +338,7 @@ pub fn bindRenderEffectsData(
     color_modulator: JFloatArray,
     dimension_fog_color: JFloatArray,
 ) {
-    let _render_effects_data = RenderEffectsData {
+    let mut render_effects_data = RenderEffectsData {
         fog_start,
         fog_end,
         fog_shape: fog_shape as f32,
@@ -259,14 +359,11 @@ pub fn bindRenderEffectsData(
     env.get_float_array_region(&dimension_fog_color, 0, &mut dimension_fog_color_vec[..])
         .unwrap();
 
-    // render_effects_data.fog_color = fog_color_vec;
-    // render_effects_data.color_modulator = color_modulator_vec;
-    // render_effects_data.dimension_fog_color = dimension_fog_color_vec;
-    //
-    // RENDERER
-    //     .get()
-    //     .unwrap()
-    //     .mc
-    //     .render_effects
-    //     .swap(Arc::new(render_effects_data));
+    let rgba = |values: Vec<f32>| std::array::from_fn(|i| values.get(i).copied().unwrap_or(1.0));
+
+    render_effects_data.fog_color = rgba(fog_color_vec);
+    render_effects_data.color_modulator = rgba(color_modulator_vec);
+    render_effects_data.dimension_fog_color = rgba(dimension_fog_color_vec);
+
+    SCENE.set_render_effects(render_effects_data);
 }
@@ -43,8 +43,14 @@ use std::collections::HashMap;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::Arc;
 
-use glam::IVec3;
-use mc::chunk::BakedLayer;
+use glam::{IVec3, Mat4};
+use image::RgbaImage;
+use mc::block::ModelMesh;
+use mc::chunk::{
+    bake_section, coalesce_chunk_updates, BakedLayer, BlockStateProvider, ChunkUpdateStats,
+    SectionStorage,
+};
+use mc::voxels::SectionVoxelData;
 use mc::Scene;
 pub use minecraft_assets;
 use parking_lot::{Mutex, RwLock};
@@ -55,8 +61,16 @@ use winit::window::Window;
 
 use crate::mc::resource::ResourceProvider;
 use crate::mc::MinecraftState;
-use crate::render::atlas::Atlas;
+use crate::render::camera::StereoEye;
+use crate::render::graph::{Geometry, RenderGraph, ResourceBacking};
+use crate::render::panorama::PanoramaCamera;
 use crate::render::pipeline::{create_bind_group_layouts, BLOCK_ATLAS, ENTITY_ATLAS};
+use crate::render::scale::{
+    scaled_size, ScaledTarget, UpscaleFilter, MAX_RENDER_SCALE, MIN_RENDER_SCALE,
+};
+use crate::render::thumbnail::{ThumbnailCamera, ThumbnailRenderer};
+use crate::render::uv_scroll::uv_scroll_uniforms;
+use crate::texture::read_texture;
 
 pub mod mc;
 pub mod render;
@@ -78,18 +92,123 @@ pub struct Display {
     pub queue: wgpu::Queue,
     pub config: RwLock<wgpu::SurfaceConfiguration>,
 }
+
+impl Display {
+    /// The surface format to configure out of the ones the surface supports. `Bgra8Unorm` is preferred, then any
+    /// other format without an sRGB suffix, since the shaders output colors which are already sRGB encoded
+    pub fn preferred_surface_format(formats: &[wgpu::TextureFormat]) -> wgpu::TextureFormat {
+        if formats.contains(&wgpu::TextureFormat::Bgra8Unorm) {
+            return wgpu::TextureFormat::Bgra8Unorm;
+        }
+
+        formats
+            .iter()
+            .copied()
+            .find(|format| !format.is_srgb())
+            .or(formats.first().copied())
+            .expect("The surface doesn't support any formats")
+    }
+
+    /// A surface configuration using [Display::preferred_surface_format]. If that's an sRGB format, its non-sRGB
+    /// variant is added to `view_formats`, so the surface can be drawn to through a [Display::view_format] view
+    pub fn surface_config(
+        capabilities: &wgpu::SurfaceCapabilities,
+        width: u32,
+        height: u32,
+        present_mode: wgpu::PresentMode,
+    ) -> wgpu::SurfaceConfiguration {
+        let format = Self::preferred_surface_format(&capabilities.formats);
+
+        wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width,
+            height,
+            present_mode,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: capabilities.alpha_modes[0],
+            view_formats: if format.is_srgb() {
+                vec![format.remove_srgb_suffix()]
+            } else {
+                vec![]
+            },
+        }
+    }
+
+    /// The format surface textures are viewed as, and so the format of pipelines and textures drawn to alongside it.
+    /// This never has an sRGB suffix, so shader output is stored as it is instead of being encoded a second time
+    pub fn view_format(&self) -> wgpu::TextureFormat {
+        self.config.read().format.remove_srgb_suffix()
+    }
+}
 /// The main wgpu-mc renderer struct
 /// Resources pertaining to Minecraft go in `MinecraftState`.
 ///
 /// `RenderGraph` is used in tandem with `World` to render scenes.
 pub struct WmRenderer {
     pub display: Display,
+    /// Read when a [Scene] or [mc::entity::Entity] creates it's buffers, so it must be set before creating them
+    pub extra_buffer_usages: RwLock<ExtraBufferUsages>,
+    /// See [WmRenderer::set_render_scale]
+    pub render_scale: RwLock<f32>,
+    pub upscale_filter: RwLock<UpscaleFilter>,
+    pub scaled_target: RwLock<Option<ScaledTarget>>,
+    /// Created by the first call to [WmRenderer::render_model_thumbnail]
+    pub thumbnail_renderer: RwLock<Option<ThumbnailRenderer>>,
     pub bind_group_layouts: Arc<HashMap<String, BindGroupLayout>>,
     pub mc: MinecraftState,
     pub chunk_update_queue: (
         Sender<(IVec3, Vec<BakedLayer>)>,
         Mutex<Receiver<(IVec3, Vec<BakedLayer>)>>,
     ),
+    /// Filled while baking when [MinecraftState::bake_voxels] is set
+    pub voxel_update_queue: (
+        Sender<(IVec3, SectionVoxelData)>,
+        Mutex<Receiver<(IVec3, SectionVoxelData)>>,
+    ),
+}
+
+/// Usage flags added to buffers on top of what rendering needs, so that they can be copied out (`COPY_SRC`) or bound to
+/// compute passes (`STORAGE`), e.g. for raytracing or physics. These all default to empty.
+///
+/// Extra usages aren't free, some drivers can't place buffers with `COPY_SRC` or `STORAGE` usage in the fastest memory,
+/// and `MAP_READ`/`MAP_WRITE` can only be combined with other usages if the device has
+/// [wgpu::Features::MAPPABLE_PRIMARY_BUFFERS], which is slow on discrete GPUs. WebGL2 has no storage buffers at all
+#[derive(Copy, Clone, Debug)]
+pub struct ExtraBufferUsages {
+    /// Added to [Scene::chunk_buffer], which already has `STORAGE` usage
+    pub chunks: wgpu::BufferUsages,
+    /// Added to the vertex buffers of entity meshes
+    pub entities: wgpu::BufferUsages,
+}
+
+impl Default for ExtraBufferUsages {
+    fn default() -> Self {
+        Self {
+            chunks: wgpu::BufferUsages::empty(),
+            entities: wgpu::BufferUsages::empty(),
+        }
+    }
+}
+
+/// What [WmRenderer::render_once] clears its target, along with the scaled target the scene is drawn into with a render
+/// scale, and [Scene::depth_texture] to before any pipeline runs,
+/// regardless of which pipelines clear. The two are independent, None leaves that one to the graph: the color to
+/// [RenderGraph::auto_clear], the depth to the first depth tested pipeline, which clears to 1. Defaults to transparent
+/// black and a depth of 1
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FrameClear {
+    pub color: Option<wgpu::Color>,
+    pub depth: Option<f32>,
+}
+
+impl Default for FrameClear {
+    fn default() -> Self {
+        Self {
+            color: Some(wgpu::Color::TRANSPARENT),
+            depth: Some(1.0),
+        }
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -106,21 +225,27 @@ impl WmRenderer {
     pub fn new(display: Display, resource_provider: Arc<dyn ResourceProvider>) -> WmRenderer {
         let mc = MinecraftState::new(&display, resource_provider);
         let (sender, receiver) = channel();
+        let (voxel_sender, voxel_receiver) = channel();
         Self {
             bind_group_layouts: Arc::new(create_bind_group_layouts(&display.device)),
             display,
+            extra_buffer_usages: RwLock::new(ExtraBufferUsages::default()),
+            render_scale: RwLock::new(1.0),
+            upscale_filter: RwLock::new(UpscaleFilter::default()),
+            scaled_target: RwLock::new(None),
+            thumbnail_renderer: RwLock::new(None),
             mc,
             chunk_update_queue: (sender, Mutex::new(receiver)),
+            voxel_update_queue: (voxel_sender, Mutex::new(voxel_receiver)),
         }
     }
 
     pub fn init(&self) {
-        let atlases = [BLOCK_ATLAS, ENTITY_ATLAS]
-            .iter()
-            .map(|&name| (name.into(), Atlas::new(&self.display, false)))
-            .collect();
+        self.mc.texture_manager.atlases.write().clear();
 
-        *self.mc.texture_manager.atlases.write() = atlases;
+        for name in [BLOCK_ATLAS, ENTITY_ATLAS] {
+            self.mc.texture_manager.create_atlas(&self.display, name);
+        }
     }
 
     pub fn upload_animated_block_buffer(&self, data: Vec<f32>) {
@@ -162,31 +287,536 @@ impl WmRenderer {
         );
     }
 
-    pub fn submit_chunk_updates(&self, scene: &Scene) {
+    /// Uploads every section baked since the last call. Sections baked more than once in between, e.g. because
+    /// several of their blocks changed in the same tick, are only uploaded once
+    pub fn submit_chunk_updates(&self, scene: &Scene) -> ChunkUpdateStats {
+        let voxel_updates: HashMap<IVec3, SectionVoxelData> =
+            self.voxel_update_queue.1.lock().try_iter().collect();
+
+        if let Some(voxels) = &mut *scene.voxels.write() {
+            for (pos, data) in &voxel_updates {
+                voxels.upload(self, *pos, data);
+            }
+        }
+
         let receiver = self.chunk_update_queue.1.lock();
-        let updates = receiver.try_iter();
+        let (dirty, stats) = coalesce_chunk_updates(receiver.try_iter());
+
+        if dirty.is_empty() {
+            return stats;
+        }
+
+        let mut storage = scene.section_storage.write();
+
+        for (pos, layers) in dirty {
+            self.upload_section(scene, &mut storage, pos, &layers);
+        }
+
+        stats
+    }
+
+    /// Bakes every section marked dirty in `scene` since the last call, once however often it was marked, then uploads
+    /// them along with any other finished bakes, see [WmRenderer::submit_chunk_updates]. Call it once per frame instead
+    /// of [WmRenderer::submit_chunk_updates]. `provider` supplies the blocks around a section, or None if it isn't
+    /// loaded anymore, in which case it's dropped
+    pub fn flush_dirty<Provider: BlockStateProvider>(
+        &self,
+        scene: &Scene,
+        provider: impl Fn(IVec3) -> Option<Provider>,
+    ) -> ChunkUpdateStats {
+        let dirty = std::mem::take(&mut *scene.dirty_sections.lock());
+        let mut rebaked = 0;
+
+        for pos in dirty {
+            if let Some(bsp) = provider(pos) {
+                bake_section(pos, self, &bsp);
+                rebaked += 1;
+            }
+        }
+
+        ChunkUpdateStats {
+            rebaked,
+            ..self.submit_chunk_updates(scene)
+        }
+    }
+
+    /// Replaces the section at `pos` in `scene`'s chunk buffer with `layers`
+    pub fn upload_section(
+        &self,
+        scene: &Scene,
+        storage: &mut SectionStorage,
+        pos: IVec3,
+        layers: &[BakedLayer],
+    ) {
+        let section = storage.replace(pos, layers);
+        for (i, ranges) in section.layers.iter().enumerate() {
+            if let Some(ranges) = ranges {
+                self.display.queue.write_buffer(
+                    &scene.chunk_buffer.buffer,
+                    ranges.vertex_range.start as u64 * 4,
+                    &layers[i].vertices,
+                );
+                self.display.queue.write_buffer(
+                    &scene.chunk_buffer.buffer,
+                    ranges.index_range.start as u64 * 4,
+                    &layers[i].indices,
+                );
+            }
+        }
+    }
+
+    /// Records and submits a single frame to `target`, then blocks until the GPU has finished it.
+    ///
+    /// This is meant for tests and captures where no event loop is running. Output isn't guaranteed
+    /// to be bit-identical across backends or drivers (rasterization rules, float precision and
+    /// texture filtering all differ), and section fade-in is still time based, so leave
+    /// `Scene::section_fade_duration` at 0 when comparing images.
+    ///
+    /// `frame_clear` is applied before the graph runs, so the background and depth are known even if no pipeline
+    /// clears, and replaces [RenderGraph::auto_clear] and the graph's depth clear. `clear_color` is still used by
+    /// pipelines which set `clear`
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_once(
+        &self,
+        graph: &RenderGraph,
+        scene: &Scene,
+        target: &wgpu::TextureView,
+        clear_color: [u8; 3],
+        geometry: &mut HashMap<String, Box<dyn Geometry>>,
+        frustum: &Frustum<f32>,
+        frame_clear: Option<FrameClear>,
+    ) {
+        let frame_clear = frame_clear.unwrap_or_default();
+
+        self.submit_chunk_updates(scene);
+
+        let mut encoder =
+            self.display
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("render_once"),
+                });
+
+        graph.render_frame(
+            self,
+            &mut encoder,
+            scene,
+            target,
+            clear_color,
+            geometry,
+            frustum,
+            frame_clear.depth.unwrap_or(1.0),
+            frame_clear.color,
+            frame_clear.depth.is_some(),
+        );
+
+        let index = self.display.queue.submit([encoder.finish()]);
+        self.display
+            .device
+            .poll(wgpu::Maintain::WaitForSubmissionIndex(index));
+    }
+
+    /// Renders a frame with [WmRenderer::render_once] to a texture the size of the surface and reads it back, e.g.
+    /// for golden image tests. The same caveats apply, and `frame_clear` is what makes the background deterministic,
+    /// None clears to transparent black and a depth of 1
+    pub fn capture_frame(
+        &self,
+        graph: &RenderGraph,
+        scene: &Scene,
+        clear_color: [u8; 3],
+        geometry: &mut HashMap<String, Box<dyn Geometry>>,
+        frustum: &Frustum<f32>,
+        frame_clear: Option<FrameClear>,
+    ) -> RgbaImage {
+        let (width, height) = {
+            let config = self.display.config.read();
+            (config.width, config.height)
+        };
+
+        let target = self
+            .display
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("capture_frame"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: self.display.view_format(),
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+
+        self.render_once(
+            graph,
+            scene,
+            &target.create_view(&wgpu::TextureViewDescriptor::default()),
+            clear_color,
+            geometry,
+            frustum,
+            frame_clear,
+        );
+
+        read_texture(self, &target)
+    }
 
-        updates.for_each(|(pos, layers)| {
-            let mut storage = scene.section_storage.write();
-            let section = storage.replace(pos, &layers);
-            for (i, ranges) in section.layers.iter().enumerate() {
-                if let Some(ranges) = ranges {
+    /// Renders the scene once for each eye, for VR. Baked terrain and entities are shared between the eyes, only the
+    /// camera changes: before each eye, its view and projection matrices are written to the graph's `@mat4_view` and
+    /// `@mat4_perspective` buffers. Each eye is its own submission, so the second eye's writes can't overtake the
+    /// first eye's draws. `head_view` is the view matrix of the point between the eyes.
+    ///
+    /// `targets` are the left and right eye's textures, e.g. the swapchain images of a VR runtime. For a side by side
+    /// image, render into two textures and copy them into the halves of the output. Both targets must match the size
+    /// of [Scene::depth_texture]. Multiview isn't used, since every shader would have to index its matrices by
+    /// `@builtin(view_index)`. Like [WmRenderer::render_once], this blocks until the GPU is done
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_stereo(
+        &self,
+        graph: &RenderGraph,
+        scene: &Scene,
+        targets: [&wgpu::TextureView; 2],
+        head_view: Mat4,
+        eyes: [StereoEye; 2],
+        clear_color: [u8; 3],
+        geometry: &mut HashMap<String, Box<dyn Geometry>>,
+        frame_clear: Option<FrameClear>,
+    ) {
+        for (target, eye) in targets.into_iter().zip(eyes) {
+            for (resource, matrix) in [
+                ("@mat4_view", eye.view(head_view)),
+                ("@mat4_perspective", eye.projection.matrix()),
+            ] {
+                if let Some(ResourceBacking::Buffer(buffer, _)) = graph.resources.get(resource) {
                     self.display.queue.write_buffer(
-                        &scene.chunk_buffer.buffer,
-                        ranges.vertex_range.start as u64 * 4,
-                        &layers[i].vertices,
+                        buffer,
+                        0,
+                        bytemuck::cast_slice(&matrix.to_cols_array()),
                     );
+                }
+            }
+
+            self.render_once(
+                graph,
+                scene,
+                target,
+                clear_color,
+                geometry,
+                &eye.frustum(head_view),
+                frame_clear,
+            );
+        }
+    }
+
+    /// Renders the six directions around `camera` to `size` by `size` images, in the order of vanilla's panorama
+    /// screenshots, for the title screen or 360° screenshots. [PanoramaCamera::equirectangular] stitches them into a
+    /// single image. Like [WmRenderer::render_stereo], each face writes its view and projection to the graph's
+    /// `@mat4_view` and `@mat4_perspective` buffers, so the host has to write its own again afterwards.
+    ///
+    /// The scene depth texture is resized to the faces while capturing and the render scale is ignored, both are
+    /// restored afterwards. Render targets declared by the shader pack keep their size, so a graph which draws to
+    /// them has to be created at `size`. Nothing in the graph adapts to what's on screen, so every face gets the same
+    /// exposure, but section fade-in is time based and should be left off with `Scene::section_fade_duration`.
+    /// Like [WmRenderer::render_once], this blocks until every face has been read back
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_panorama(
+        &self,
+        graph: &RenderGraph,
+        scene: &Scene,
+        camera: &PanoramaCamera,
+        size: u32,
+        clear_color: [u8; 3],
+        geometry: &mut HashMap<String, Box<dyn Geometry>>,
+        frame_clear: Option<FrameClear>,
+    ) -> [RgbaImage; 6] {
+        let size = size.max(1);
+
+        let render_scale = std::mem::replace(&mut *self.render_scale.write(), 1.0);
+        self.prepare_scaled_target();
+
+        let depth_size = scene.depth_texture.read().size();
+        scene.resize_depth_texture(self, size, size);
+
+        let target = self
+            .display
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("panorama"),
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: self.display.view_format(),
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+        let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let projection = camera.projection();
+
+        let faces = camera.views().map(|view| {
+            for (resource, matrix) in [
+                ("@mat4_view", view),
+                ("@mat4_perspective", projection.matrix()),
+            ] {
+                if let Some(ResourceBacking::Buffer(buffer, _)) = graph.resources.get(resource) {
                     self.display.queue.write_buffer(
-                        &scene.chunk_buffer.buffer,
-                        ranges.index_range.start as u64 * 4,
-                        &layers[i].indices,
+                        buffer,
+                        0,
+                        bytemuck::cast_slice(&matrix.to_cols_array()),
                     );
                 }
             }
+
+            self.render_once(
+                graph,
+                scene,
+                &target_view,
+                clear_color,
+                geometry,
+                &projection.frustum(view),
+                frame_clear,
+            );
+
+            read_texture(self, &target)
         });
+
+        *self.render_scale.write() = render_scale;
+        scene.resize_depth_texture(self, depth_size.width, depth_size.height);
+
+        faces
+    }
+
+    /// Submits any queued buffer and texture writes, then blocks the calling thread until the GPU has finished all
+    /// submitted work. Call this before tearing the renderer down so nothing is freed while still in use
+    pub fn wait_idle(&self) {
+        self.display.queue.submit([]);
+
+        while !self
+            .display
+            .device
+            .poll(wgpu::Maintain::Wait)
+            .is_queue_empty()
+        {}
+    }
+
+    /// Request a new device with `features` and `limits` from the same adapter, e.g. to enable optional features
+    /// after startup, then rebuild the renderer's own GPU state on it. The surface is reconfigured, and atlases are
+    /// recreated empty by [WmRenderer::init].
+    ///
+    /// wgpu objects can't be moved between devices, so anything created against the old device is invalid afterwards
+    /// and has to be created again: blocks must be rebaked and textures reloaded, and every [RenderGraph], [Scene],
+    /// entity and custom [Geometry] recreated. Chunk and voxel updates which haven't been submitted yet are dropped.
+    /// Settings such as the [mc::chunk::TintMode], voxel baking, model loaders, texture quality and the block settings
+    /// in [mc::BlockManager] are kept.
+    /// If the device can't be created, the error is returned and the renderer is left untouched
+    pub async fn recreate_device(
+        &mut self,
+        features: wgpu::Features,
+        limits: wgpu::Limits,
+    ) -> Result<(), wgpu::RequestDeviceError> {
+        let (device, queue) = self
+            .display
+            .adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    required_features: features,
+                    required_limits: limits,
+                    memory_hints: wgpu::MemoryHints::Performance,
+                },
+                None,
+            )
+            .await?;
+
+        self.wait_idle();
+
+        self.display.device = device;
+        self.display.queue = queue;
+        self.display
+            .surface
+            .configure(&self.display.device, &self.display.config.read());
+
+        self.bind_group_layouts = Arc::new(create_bind_group_layouts(&self.display.device));
+        let old = std::mem::replace(
+            &mut self.mc,
+            MinecraftState::new(&self.display, self.mc.resource_provider.clone()),
+        );
+        self.mc.carry_settings_from(old, &self.display);
+        *self.scaled_target.get_mut() = None;
+        *self.thumbnail_renderer.get_mut() = None;
+        self.chunk_update_queue
+            .1
+            .get_mut()
+            .try_iter()
+            .for_each(drop);
+        self.voxel_update_queue
+            .1
+            .get_mut()
+            .try_iter()
+            .for_each(drop);
+
+        self.init();
+
+        Ok(())
+    }
+
+    /// Move every registered [render::uv_scroll::UvScroll] to where it is `seconds` after it started scrolling.
+    /// Call this once per frame, e.g. with the time since the world was loaded
+    pub fn update_uv_scrolls(&self, seconds: f32) {
+        let atlas_size = self
+            .mc
+            .texture_manager
+            .atlases
+            .read()
+            .get(BLOCK_ATLAS)
+            .map_or(1.0, |atlas| atlas.texture.texture.width() as f32);
+
+        let uniforms = uv_scroll_uniforms(
+            &self.mc.block_manager.read().uv_scrolls,
+            atlas_size,
+            seconds,
+        );
+
+        self.display.queue.write_buffer(
+            &self.mc.uv_scroll_buffer,
+            0,
+            bytemuck::cast_slice(&uniforms),
+        );
+    }
+
+    /// Stream pending sprites of every atlas to the GPU, see [render::atlas::Atlas::upload_pending]. `byte_budget` applies to each atlas.
+    /// Returns the number of sprites which are still waiting to be uploaded at full size. Atlases showing their
+    /// preview are swapped back by [render::graph::RenderGraph::swap_atlas_textures]
+    pub fn stream_atlases(&self, byte_budget: usize) -> usize {
+        self.mc
+            .texture_manager
+            .atlases
+            .read()
+            .values()
+            .map(|atlas| atlas.upload_pending(self, byte_budget).remaining)
+            .sum()
+    }
+
+    /// Load the sprites of every atlas again from the resource provider in place, see
+    /// [render::atlas::Atlas::reload_sprites]. They're streamed in by [WmRenderer::stream_atlases].
+    /// Returns the number of sprites which were found
+    pub fn reload_atlas_sprites(&self) -> usize {
+        self.mc
+            .texture_manager
+            .atlases
+            .read()
+            .values()
+            .map(|atlas| atlas.reload_sprites(&*self.mc.resource_provider))
+            .sum()
+    }
+
+    /// Render the scene at `scale` times the window resolution, clamped to between [MIN_RENDER_SCALE] and
+    /// [MAX_RENDER_SCALE], then stretch it over the window with [WmRenderer::upscale_filter] before pipelines with
+    /// `native_resolution` (the GUI) draw. At 1 the scene is drawn to the window directly.
+    ///
+    /// The scene depth texture follows the scale on the next frame, as do render targets declared by the shader pack
+    /// once [RenderGraph::resize_render_targets] is called
+    pub fn set_render_scale(&self, scale: f32) {
+        *self.render_scale.write() = scale.clamp(MIN_RENDER_SCALE, MAX_RENDER_SCALE);
+    }
+
+    /// The size the scene is drawn at, which is the surface size multiplied by the render scale
+    pub fn render_size(&self) -> (u32, u32) {
+        let config = self.display.config.read();
+        scaled_size(config.width, config.height, *self.render_scale.read())
+    }
+
+    /// Recreate [WmRenderer::scaled_target] if the surface size, render scale or filter changed since the last frame.
+    /// Returns the size the scene depth texture has to be resized to, if it changed
+    pub fn prepare_scaled_target(&self) -> Option<(u32, u32)> {
+        let scale = *self.render_scale.read();
+        let filter = *self.upscale_filter.read();
+        let (width, height) = {
+            let config = self.display.config.read();
+            (config.width, config.height)
+        };
+
+        let mut scaled_target = self.scaled_target.write();
+
+        if scale == 1.0 {
+            return scaled_target.take().map(|_| (width, height));
+        }
+
+        let size = scaled_size(width, height, scale);
+
+        match &*scaled_target {
+            Some(target) if target.size() == size && target.filter == filter => None,
+            _ => {
+                *scaled_target = Some(ScaledTarget::new(self, size.0, size.1, filter));
+                Some(size)
+            }
+        }
+    }
+
+    /// Render a block model on its own to a `size` by `size` image with a transparent background, lit like blocks in the
+    /// inventory. `camera` defaults to the vanilla inventory angle. This blocks until the image has been read back
+    /// from the GPU, so batch icons up front instead of rendering them every frame
+    pub fn render_model_thumbnail(
+        &self,
+        mesh: &ModelMesh,
+        size: u32,
+        camera: Option<ThumbnailCamera>,
+    ) -> RgbaImage {
+        if self.thumbnail_renderer.read().is_none() {
+            *self.thumbnail_renderer.write() = Some(ThumbnailRenderer::new(self));
+        }
+
+        self.thumbnail_renderer.read().as_ref().unwrap().render(
+            self,
+            mesh,
+            size,
+            &camera.unwrap_or_default(),
+        )
+    }
+
+    /// The GPU name, driver, device type and vendor of the adapter, for diagnostics and per driver workarounds
+    pub fn adapter_info(&self) -> wgpu::AdapterInfo {
+        self.display.adapter.get_info()
     }
 
     pub fn get_backend_description(&self) -> String {
         format!("wgpu 0.20 ({:?})", self.display.adapter.get_info().backend)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use wgpu::TextureFormat;
+
+    use crate::Display;
+
+    #[test]
+    fn prefers_non_srgb_surface_formats() {
+        assert_eq!(
+            Display::preferred_surface_format(&[
+                TextureFormat::Bgra8UnormSrgb,
+                TextureFormat::Bgra8Unorm
+            ]),
+            TextureFormat::Bgra8Unorm
+        );
+        assert_eq!(
+            Display::preferred_surface_format(&[
+                TextureFormat::Rgba8UnormSrgb,
+                TextureFormat::Rgba8Unorm
+            ]),
+            TextureFormat::Rgba8Unorm
+        );
+        assert_eq!(
+            Display::preferred_surface_format(&[TextureFormat::Rgba8UnormSrgb]),
+            TextureFormat::Rgba8UnormSrgb
+        );
+    }
+}
@@ -5,6 +5,10 @@ use minecraft_assets::api::ModelResolver;
 use minecraft_assets::schemas;
 use minecraft_assets::schemas::blockstates::ModelProperties;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 
 use crate::mc::direction::Direction;
 use crate::mc::resource::{ResourcePath, ResourceProvider};
@@ -51,6 +55,8 @@ pub enum ChunkBlockState {
 }
 
 impl ChunkBlockState {
+    /// Only true for [ChunkBlockState::Air], blocks like `minecraft:cave_air` are registered states.
+    /// Use [crate::mc::BlockManager::is_air_like] to include those
     pub fn is_air(&self) -> bool {
         matches!(self, Self::Air)
     }
@@ -58,12 +64,12 @@ impl ChunkBlockState {
 
 ///Represents a vertex in a block mesh, including an additional UV offset index for animated textures.
 #[repr(C)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct BlockMeshVertex {
     pub position: Vec3,
     pub tex_coords: [u16; 2],
 }
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct BlockModelFace {
     pub vertices: [BlockMeshVertex; 4],
     pub normal: Vec3,
@@ -71,6 +77,61 @@ pub struct BlockModelFace {
     pub animation_uv_offset: u32,
 }
 
+impl BlockModelFace {
+    /// The same face with its texture turned by `quarter_turns` * 90 degrees, by moving each vertex's UVs to the next
+    /// vertex around the quad
+    pub fn rotate_uvs(&self, quarter_turns: u8) -> Self {
+        let mut rotated = *self;
+
+        for (index, vertex) in rotated.vertices.iter_mut().enumerate() {
+            vertex.tex_coords = self.vertices[(index + quarter_turns as usize) % 4].tex_coords;
+        }
+
+        rotated
+    }
+}
+
+/// How a model is lit when drawn in the GUI, from the `gui_light` of the model or the closest parent which sets it
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Default, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GuiLight {
+    /// Lit from the front, for flat item models
+    Front,
+    /// The two directional lights blocks have in the inventory
+    #[default]
+    Side,
+}
+
+#[derive(Deserialize)]
+struct GuiLightProperties {
+    parent: Option<String>,
+    gui_light: Option<GuiLight>,
+}
+
+/// The `gui_light` of the model in `model_json`, or of the closest parent which sets it. Parents which can't be
+/// found, like `builtin/generated`, end the search
+fn resolve_gui_light(
+    model_json: &str,
+    resource_provider: &dyn ResourceProvider,
+) -> Option<GuiLight> {
+    let properties: GuiLightProperties = serde_json::from_str(model_json).ok()?;
+
+    match (properties.gui_light, properties.parent) {
+        (Some(gui_light), _) => Some(gui_light),
+        (None, Some(parent)) => {
+            let parent_path = ResourcePath::from(&parent)
+                .prepend("models/")
+                .append(".json");
+
+            resolve_gui_light(
+                &resource_provider.get_string(&parent_path)?,
+                resource_provider,
+            )
+        }
+        (None, None) => None,
+    }
+}
+
 fn recurse_model_parents(
     model: &schemas::Model,
     resource_provider: &dyn ResourceProvider,
@@ -81,17 +142,15 @@ fn recurse_model_parents(
             let parent_path: ResourcePath = ResourcePath::from(parent_path_string)
                 .prepend("models/")
                 .append(".json");
-            recurse_model_parents(
-                &serde_json::from_str(
-                    &resource_provider
-                        .get_string(&parent_path)
-                        .expect(&parent_path.0),
-                )
-                .unwrap(),
-                resource_provider,
-                models,
-            );
+            let parent = serde_json::from_str(
+                &resource_provider
+                    .get_string(&parent_path)
+                    .expect(&parent_path.0),
+            )
+            .unwrap();
+            //Ordered from the closest parent to the root, which is what ModelResolver expects
             models.push(parent_path);
+            recurse_model_parents(&parent, resource_provider, models);
         }
         None => {}
     }
@@ -101,10 +160,6 @@ fn resolve_model(
     model: schemas::Model,
     resource_provider: &dyn ResourceProvider,
 ) -> schemas::Model {
-    if model.parent.is_none() {
-        return model;
-    }
-
     let mut parent_paths = Vec::new();
     recurse_model_parents(&model, resource_provider, &mut parent_paths);
 
@@ -115,7 +170,11 @@ fn resolve_model(
         })
         .collect();
 
-    let mut schema = ModelResolver::resolve_model([&model].into_iter().chain(parents.iter()));
+    let hierarchy = || [&model].into_iter().chain(parents.iter());
+
+    let mut schema = ModelResolver::resolve_model(hierarchy());
+    //Elements are never merged, the closest model which defines any replaces the elements of all of its parents
+    schema.elements = hierarchy().find_map(|model| model.elements.clone());
 
     if let Some(textures) = &mut schema.textures {
         let copy = textures.clone();
@@ -156,6 +215,36 @@ pub struct RenderSettings {
     pub opaque: bool,
 }
 
+/// An axis aligned box in block space, where a full block goes from 0 to 1 on every axis
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ElementBounds {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl ElementBounds {
+    /// The smallest box containing every point
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        points.into_iter().fold(
+            Self {
+                min: Vec3::INFINITY,
+                max: Vec3::NEG_INFINITY,
+            },
+            |bounds, point| Self {
+                min: bounds.min.min(point),
+                max: bounds.max.max(point),
+            },
+        )
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+}
+
 /// TODO: Use actual error handling library
 #[derive(Debug)]
 pub enum MeshBakeError {
@@ -164,8 +253,88 @@ pub enum MeshBakeError {
     JsonError(serde_json::Error),
 }
 
-/// A block model which has been baked into a mesh and is ready for rendering
-#[derive(Debug)]
+/// Bakes models which vanilla's JSON format can't express, like OBJ or composite models from mods. Given the model's
+/// JSON, it returns the mesh unrotated, the variant's `x` and `y` rotation is applied afterwards
+pub type ModelLoader = dyn Fn(&serde_json::Value, &dyn ResourceProvider, &Atlas) -> Result<ModelMesh, MeshBakeError>
+    + Send
+    + Sync;
+
+/// Custom [ModelLoader]s keyed by the `loader` field of the models they bake, e.g. `neoforge:obj`. Models without a
+/// `loader`, or with one that isn't registered, are baked as vanilla JSON models
+pub type ModelLoaders = HashMap<String, Arc<ModelLoader>>;
+
+/// The magenta and black cube blocks are baked as when their own models are missing or malformed, see
+/// [crate::mc::MissingModelPolicy]. It isn't part of any resource pack, [MissingModelResources] provides it
+pub const MISSING_MODEL: &str = "wgpu_mc:block/missing";
+
+const MISSING_MODEL_JSON: &str = r#"{
+    "textures": { "particle": "wgpu_mc:block/missing" },
+    "elements": [{
+        "from": [0, 0, 0],
+        "to": [16, 16, 16],
+        "faces": {
+            "down": { "texture": "wgpu_mc:block/missing", "cullface": "down" },
+            "up": { "texture": "wgpu_mc:block/missing", "cullface": "up" },
+            "north": { "texture": "wgpu_mc:block/missing", "cullface": "north" },
+            "south": { "texture": "wgpu_mc:block/missing", "cullface": "south" },
+            "west": { "texture": "wgpu_mc:block/missing", "cullface": "west" },
+            "east": { "texture": "wgpu_mc:block/missing", "cullface": "east" }
+        }
+    }]
+}"#;
+
+/// Rotate a point of a model by its blockstate variant's `x` and then `y` rotation, around the center of the block
+fn rotate_xy(v: Vec3, model_properties: &ModelProperties) -> Vec3 {
+    let v = match model_properties.x {
+        0 => v,
+        90 => vec3(v.x, 1.0 - v.z, v.y),
+        180 => vec3(v.x, 1.0 - v.y, 1.0 - v.z),
+        270 => vec3(v.x, v.z, 1.0 - v.y),
+        _ => panic!("invalid rotation"),
+    };
+
+    match model_properties.y {
+        0 => v,
+        90 => vec3(1.0 - v.z, v.y, v.x),
+        180 => vec3(1.0 - v.x, v.y, 1.0 - v.z),
+        270 => vec3(v.z, v.y, 1.0 - v.x),
+        _ => panic!("invalid rotation"),
+    }
+}
+
+/// Vanilla's missing texture, 2x2 magenta and black squares
+fn missing_texture() -> Vec<u8> {
+    let image = image::RgbaImage::from_fn(16, 16, |x, y| {
+        if (x < 8) == (y < 8) {
+            image::Rgba([248, 0, 248, 255])
+        } else {
+            image::Rgba([0, 0, 0, 255])
+        }
+    });
+
+    let mut png = Vec::new();
+    image
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    png
+}
+
+/// Provides [MISSING_MODEL] and its texture, everything else comes from the wrapped provider
+pub struct MissingModelResources<'a>(pub &'a dyn ResourceProvider);
+
+impl ResourceProvider for MissingModelResources<'_> {
+    fn get_bytes(&self, id: &ResourcePath) -> Option<Vec<u8>> {
+        match &id.0[..] {
+            "wgpu_mc:models/block/missing.json" => Some(MISSING_MODEL_JSON.as_bytes().to_vec()),
+            "wgpu_mc:textures/block/missing.png" => Some(missing_texture()),
+            _ => self.0.get_bytes(id),
+        }
+    }
+}
+
+/// A block model which has been baked into a mesh and is ready for rendering. Baked meshes can be saved and loaded
+/// again with [crate::mc::mesh_cache::BlockMeshCache]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ModelMesh {
     pub north: Vec<BlockModelFace>,
     pub south: Vec<BlockModelFace>,
@@ -176,14 +345,136 @@ pub struct ModelMesh {
     pub any: Vec<BlockModelFace>,
     pub cull: u8,
     pub layer: RenderLayer,
+    /// False if any of the models this mesh was baked from (or their parents) set `"ambientocclusion": false`,
+    /// in which case terrain baking skips AO darkening for all of its faces
+    pub ambient_occlusion: bool,
+    /// From the first of the models this mesh was baked from, only used when drawing it in the GUI
+    pub gui_light: GuiLight,
+    /// The bounds of every model element after rotation, including all parts of multipart models. These match what's
+    /// drawn, so they're suited to selection outlines but not collision
+    pub bounds: Vec<ElementBounds>,
 }
 
 impl ModelMesh {
+    /// Hash of everything which affects how the mesh renders, including texture UVs, so that identical meshes can be
+    /// shared between block states. See [crate::mc::BlockManager::intern_mesh]
+    pub fn structural_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        for faces in [
+            &self.north,
+            &self.south,
+            &self.west,
+            &self.east,
+            &self.up,
+            &self.down,
+            &self.any,
+        ] {
+            faces.len().hash(&mut hasher);
+
+            for face in faces {
+                for vertex in &face.vertices {
+                    vertex
+                        .position
+                        .to_array()
+                        .map(f32::to_bits)
+                        .hash(&mut hasher);
+                    vertex.tex_coords.hash(&mut hasher);
+                }
+                face.normal.to_array().map(f32::to_bits).hash(&mut hasher);
+                face.tint_index.hash(&mut hasher);
+                face.animation_uv_offset.hash(&mut hasher);
+            }
+        }
+
+        self.cull.hash(&mut hasher);
+        self.layer.hash(&mut hasher);
+        self.ambient_occlusion.hash(&mut hasher);
+        self.gui_light.hash(&mut hasher);
+
+        for bounds in &self.bounds {
+            bounds.min.to_array().map(f32::to_bits).hash(&mut hasher);
+            bounds.max.to_array().map(f32::to_bits).hash(&mut hasher);
+        }
+
+        hasher.finish()
+    }
+
+    /// One box around all of [ModelMesh::bounds], or `None` if the model has no elements
+    pub fn union_bounds(&self) -> Option<ElementBounds> {
+        self.bounds
+            .iter()
+            .copied()
+            .reduce(|union, bounds| union.union(&bounds))
+    }
+
+    /// Whether every side is a full face, like stone or dirt
+    pub fn is_full_cube(&self) -> bool {
+        self.cull == 0b111111
+    }
+
+    /// How many quads the mesh has. Faces which end up culled in terrain are still counted
+    pub fn face_count(&self) -> usize {
+        [
+            &self.north,
+            &self.south,
+            &self.west,
+            &self.east,
+            &self.up,
+            &self.down,
+            &self.any,
+        ]
+        .iter()
+        .map(|faces| faces.len())
+        .sum()
+    }
+
+    /// Roughly how much memory the mesh takes, including its faces and bounds
+    pub fn size_in_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.face_count() * std::mem::size_of::<BlockModelFace>()
+            + self.bounds.len() * std::mem::size_of::<ElementBounds>()
+    }
+
+    /// True if the mesh has no faces at all, e.g. the `minecraft:block/air` model
+    pub fn is_empty(&self) -> bool {
+        [
+            &self.north,
+            &self.south,
+            &self.west,
+            &self.east,
+            &self.up,
+            &self.down,
+            &self.any,
+        ]
+        .iter()
+        .all(|faces| faces.is_empty())
+    }
+
+    /// Bake [MISSING_MODEL], adding its texture to `block_atlas` if it isn't there yet
+    pub fn bake_missing(resource_provider: &dyn ResourceProvider, block_atlas: &Atlas) -> Self {
+        let properties: ModelProperties =
+            serde_json::from_value(serde_json::json!({ "model": MISSING_MODEL })).unwrap();
+
+        Self::bake(
+            std::slice::from_ref(&properties),
+            &MissingModelResources(resource_provider),
+            block_atlas,
+            &ModelLoaders::new(),
+        )
+        .unwrap()
+    }
+
     pub fn bake<'a>(
         model_properties: impl IntoIterator<Item = &'a ModelProperties>,
         resource_provider: &dyn ResourceProvider,
         block_atlas: &Atlas,
+        model_loaders: &ModelLoaders,
     ) -> Result<Self, MeshBakeError> {
+        let mut ambient_occlusion = true;
+        let mut gui_light = None;
+        let mut bounds = Vec::new();
+
         let mesh = model_properties
             .into_iter()
             .map(|model_properties: &ModelProperties| {
@@ -191,20 +482,66 @@ impl ModelMesh {
                     .prepend("models/")
                     .append(".json");
 
+                //Get the model JSON
+                let model_json = resource_provider
+                    .get_string(&model_resource_path)
+                    .ok_or_else(|| MeshBakeError::UnresolvedResourcePath(model_resource_path))?;
+
+                if gui_light.is_none() {
+                    gui_light = resolve_gui_light(&model_json, resource_provider);
+                }
+
+                if !model_loaders.is_empty() {
+                    let model: serde_json::Value =
+                        serde_json::from_str(&model_json).map_err(MeshBakeError::JsonError)?;
+                    let loader = model
+                        .get("loader")
+                        .and_then(serde_json::Value::as_str)
+                        .and_then(|loader| model_loaders.get(loader));
+
+                    if let Some(loader) = loader {
+                        let custom = loader(&model, resource_provider, block_atlas)?;
+                        ambient_occlusion &= custom.ambient_occlusion;
+
+                        let rotate = |v: Vec3| rotate_xy(v, model_properties);
+
+                        bounds.extend(custom.bounds.iter().map(|element| {
+                            ElementBounds::from_points([rotate(element.min), rotate(element.max)])
+                        }));
+
+                        //Faces are sorted into directions again below, since rotation can change which side they're on
+                        return Ok([
+                            &custom.north,
+                            &custom.south,
+                            &custom.west,
+                            &custom.east,
+                            &custom.up,
+                            &custom.down,
+                            &custom.any,
+                        ]
+                        .into_iter()
+                        .flatten()
+                        .map(|face| BlockModelFace {
+                            vertices: face.vertices.map(|vertex| BlockMeshVertex {
+                                position: rotate(vertex.position),
+                                ..vertex
+                            }),
+                            normal: rotate(face.normal) - rotate(Vec3::ZERO),
+                            ..*face
+                        })
+                        .collect::<Vec<BlockModelFace>>());
+                    }
+                }
+
                 //Recursively resolve the model using it's parents if it has any
                 let model: schemas::Model = resolve_model(
                     //Parse the JSON into the model schema
-                    serde_json::from_str(
-                        //Get the model JSON
-                        &resource_provider
-                            .get_string(&model_resource_path)
-                            .ok_or_else(|| {
-                                MeshBakeError::UnresolvedResourcePath(model_resource_path)
-                            })?,
-                    )
-                    .map_err(MeshBakeError::JsonError)?,
+                    serde_json::from_str(&model_json).map_err(MeshBakeError::JsonError)?,
                     resource_provider,
                 );
+                //Parents have already been resolved, so this includes the flag if it was inherited
+                ambient_occlusion &= model.ambient_occlusion.unwrap_or(true);
+
                 if let Some(textures) = model.textures {
                     //Make sure the textures in the model are fully resolved with no references
                     if let Some(reference) = textures
@@ -256,6 +593,8 @@ impl ModelMesh {
                     }
                 };
 
+                //Elements stay in model order, terrain baking orders coplanar faces for the depth compare, see
+                //crate::mc::chunk::CoplanarOrder
                 Ok(model
                     .elements
                     .iter()
@@ -395,23 +734,9 @@ impl ModelMesh {
                         };
                         let vec_origin = Vec3::from_array(rot.origin) / 16.0;
 
+                        //The element's own rotation is in model space, before the variant rotates the whole model
                         let vertex_transform = |v: Vec3| {
-                            let v = match model_properties.x {
-                                0 => v,
-                                90 => vec3(v.x, 1.0 - v.z, v.y),
-                                180 => vec3(v.x, 1.0 - v.y, 1.0 - v.z),
-                                270 => vec3(v.x, v.z, 1.0 - v.y),
-                                _ => panic!("invalid rotation"),
-                            };
-                            let v = matrix * (v - vec_origin) + vec_origin;
-
-                            match model_properties.y {
-                                0 => v,
-                                90 => vec3(1.0 - v.z, v.y, v.x),
-                                180 => vec3(1.0 - v.x, v.y, 1.0 - v.z),
-                                270 => vec3(v.z, v.y, 1.0 - v.x),
-                                _ => panic!("invalid rotation"),
-                            }
+                            rotate_xy(matrix * (v - vec_origin) + vec_origin, model_properties)
                         };
 
                         let p000 = vertex_transform(vec3(
@@ -455,6 +780,10 @@ impl ModelMesh {
                             element.to[2] / 16.0,
                         ));
 
+                        bounds.push(ElementBounds::from_points([
+                            p000, p001, p010, p011, p100, p101, p110, p111,
+                        ]));
+
                         let mut faces = vec![];
                         faces.extend(south.map(|south_face| BlockModelFace {
                             vertices: [
@@ -611,6 +940,9 @@ impl ModelMesh {
             down: vec![],
             any: vec![],
             cull: 0,
+            ambient_occlusion,
+            gui_light: gui_light.unwrap_or_default(),
+            bounds,
         };
         mesh.iter().for_each(|face| {
             let full_face = (face.vertices[0].position.fract() == vec3(0.0, 0.0, 0.0)
@@ -661,3 +993,170 @@ impl ModelMesh {
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Vec3};
+
+    use super::{
+        resolve_gui_light, resolve_model, ElementBounds, GuiLight, MissingModelResources,
+        MISSING_MODEL,
+    };
+    use crate::mc::resource::tests::InMemoryResources;
+    use crate::mc::resource::{ResourcePath, ResourceProvider};
+
+    fn models() -> InMemoryResources {
+        InMemoryResources::new(&[
+            (
+                "test:models/block/base.json",
+                r##"{
+                    "textures": { "particle": "#all" },
+                    "elements": [
+                        { "from": [0, 0, 0], "to": [16, 16, 16], "faces": { "up": { "texture": "#all" } } },
+                        { "from": [0, 0, 0], "to": [16, 8, 16], "faces": { "up": { "texture": "#all" } } }
+                    ]
+                }"##,
+            ),
+            (
+                "test:models/block/inherits.json",
+                r##"{ "parent": "test:block/base", "textures": { "all": "test:block/stone" } }"##,
+            ),
+            (
+                "test:models/item/flat.json",
+                r##"{ "parent": "builtin/generated", "gui_light": "front" }"##,
+            ),
+        ])
+    }
+
+    fn parse(json: &str) -> minecraft_assets::schemas::Model {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn texture(model: &minecraft_assets::schemas::Model, key: &str) -> String {
+        model
+            .textures
+            .iter()
+            .flat_map(|textures| textures.iter())
+            .find(|(name, _)| *name == key)
+            .map(|(_, texture)| texture.0.clone())
+            .unwrap()
+    }
+
+    #[test]
+    fn parentless_model_keeps_its_elements() {
+        let model = resolve_model(
+            parse(
+                r##"{
+                    "textures": { "pane": "test:block/glass", "particle": "#pane" },
+                    "elements": [
+                        { "from": [7, 0, 0], "to": [9, 16, 16], "faces": { "north": { "texture": "#pane" } } }
+                    ]
+                }"##,
+            ),
+            &models(),
+        );
+
+        assert_eq!(model.elements.as_ref().unwrap().len(), 1);
+        assert_eq!(texture(&model, "particle"), "test:block/glass");
+    }
+
+    #[test]
+    fn child_elements_replace_parent_elements() {
+        let model = resolve_model(
+            parse(
+                r##"{
+                    "parent": "test:block/inherits",
+                    "elements": [
+                        { "from": [0, 0, 0], "to": [16, 2, 16], "faces": { "up": { "texture": "#all" } } }
+                    ]
+                }"##,
+            ),
+            &models(),
+        );
+
+        let elements = model.elements.as_ref().unwrap();
+        assert_eq!(elements.len(), 1);
+        assert_eq!(elements[0].to, [16.0, 2.0, 16.0]);
+        assert_eq!(texture(&model, "all"), "test:block/stone");
+    }
+
+    #[test]
+    fn child_without_elements_inherits_them() {
+        let model = resolve_model(parse(r##"{ "parent": "test:block/inherits" }"##), &models());
+
+        assert_eq!(model.elements.as_ref().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn gui_light_is_inherited() {
+        assert_eq!(
+            resolve_gui_light(r##"{ "parent": "test:item/flat" }"##, &models()),
+            Some(GuiLight::Front)
+        );
+        assert_eq!(
+            resolve_gui_light(
+                r##"{ "parent": "test:item/flat", "gui_light": "side" }"##,
+                &models()
+            ),
+            Some(GuiLight::Side)
+        );
+        assert_eq!(
+            resolve_gui_light(r##"{ "parent": "test:block/inherits" }"##, &models()),
+            None
+        );
+    }
+
+    #[test]
+    fn element_bounds() {
+        //A stair seen from the side, the bottom slab and the upper half step
+        let slab = ElementBounds::from_points([vec3(0.0, 0.0, 0.0), vec3(1.0, 0.5, 1.0)]);
+        let step = ElementBounds::from_points([
+            vec3(0.5, 0.5, 0.0),
+            vec3(1.0, 1.0, 1.0),
+            vec3(0.75, 0.75, 0.5),
+        ]);
+
+        assert_eq!(step.min, vec3(0.5, 0.5, 0.0));
+        assert_eq!(step.max, Vec3::ONE);
+        assert_eq!(
+            slab.union(&step),
+            ElementBounds {
+                min: Vec3::ZERO,
+                max: Vec3::ONE
+            }
+        );
+    }
+
+    #[test]
+    fn missing_model_is_a_magenta_cube() {
+        let models = models();
+        let resources = MissingModelResources(&models);
+
+        let path = ResourcePath::from(MISSING_MODEL)
+            .prepend("models/")
+            .append(".json");
+        let model = resolve_model(
+            serde_json::from_str(&resources.get_string(&path).unwrap()).unwrap(),
+            &resources,
+        );
+        let elements = model.elements.unwrap();
+        assert_eq!(elements.len(), 1);
+        assert_eq!(elements[0].faces.len(), 6);
+
+        let texture = image::load_from_memory(
+            &resources
+                .get_bytes(&ResourcePath::from("wgpu_mc:textures/block/missing.png"))
+                .unwrap(),
+        )
+        .unwrap()
+        .to_rgba8();
+        assert_eq!(texture.dimensions(), (16, 16));
+        assert_eq!(texture.get_pixel(0, 0).0, [248, 0, 248, 255]);
+        assert_eq!(texture.get_pixel(8, 0).0, [0, 0, 0, 255]);
+
+        //Everything else still comes from the wrapped provider
+        assert!(resources
+            .get_string(&ResourcePath::from("test:models/block/base.json"))
+            .is_some());
+    }
+}
@@ -0,0 +1,302 @@
+//! The particles a block breaks into, textured with pieces of its faces' sprites like vanilla's `TerrainParticle`.
+//! wgpu-mc doesn't draw particles itself, these are queued on [crate::mc::Scene::block_break_particles] for whatever
+//! does, see [crate::mc::Scene::spawn_block_break_particles]
+
+use glam::{vec3, DVec3, IVec3, Vec3};
+
+use crate::mc::block::{BlockModelFace, ElementBounds, ModelMesh};
+use crate::mc::chunk::position_seed;
+use crate::texture::UV;
+
+/// Particles along each axis of a full block, vanilla spawns 4x4x4
+pub const PARTICLES_PER_BLOCK_AXIS: f32 = 4.0;
+/// How many particles [crate::mc::Scene::block_break_particles] holds, vanilla's limit per particle sheet. If the host
+/// doesn't take them quickly enough the oldest are dropped, see [queue_particles]
+pub const MAX_QUEUED_PARTICLES: usize = 16384;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BlockBreakParticle {
+    /// In world space, kept in f64 so particles far from the world origin don't jitter. See
+    /// [crate::mc::Scene::to_render_space]
+    pub position: DVec3,
+    /// In blocks per tick, pointing away from the center of the block
+    pub velocity: Vec3,
+    /// A quarter of the width and height of one of the block's face sprites, in block atlas pixels like
+    /// [crate::mc::block::BlockMeshVertex::tex_coords]
+    pub uv: UV,
+    /// The tint index of the face the sprite came from, -1 if it isn't tinted. Tinted particles should be colored
+    /// the same way the face is, e.g. green for grass
+    pub tint_index: i32,
+    /// The [crate::render::atlas::Atlas::generation] of the block atlas `uv` is in
+    pub atlas_generation: u64,
+}
+
+/// A small xorshift generator, so the same block breaking at the same position looks the same everywhere
+struct ParticleRng(u64);
+
+impl ParticleRng {
+    fn new(pos: IVec3) -> Self {
+        //xorshift gets stuck at 0
+        Self(position_seed(pos) as u64 | 1)
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        (self.0 >> 32) as u32
+    }
+
+    /// From 0 up to but not including 1
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+/// The UV rectangle a face's vertices cover
+fn face_uv(face: &BlockModelFace) -> UV {
+    face.vertices.iter().fold(
+        ((u16::MAX, u16::MAX), (0, 0)),
+        |((min_u, min_v), (max_u, max_v)), vertex| {
+            let [u, v] = vertex.tex_coords;
+            ((min_u.min(u), min_v.min(v)), (max_u.max(u), max_v.max(v)))
+        },
+    )
+}
+
+/// The particles `mesh` breaks into at `pos`. Each element of the model is filled with particles spaced about a
+/// quarter block apart, each showing a random quarter of a random face's sprite. `atlas_generation` is the generation
+/// of the block atlas `mesh` was baked against. Meshes without faces don't spawn any
+pub fn block_break_particles(
+    mesh: &ModelMesh,
+    pos: IVec3,
+    atlas_generation: u64,
+) -> Vec<BlockBreakParticle> {
+    let faces = [
+        &mesh.north,
+        &mesh.south,
+        &mesh.west,
+        &mesh.east,
+        &mesh.up,
+        &mesh.down,
+        &mesh.any,
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+
+    if faces.is_empty() {
+        return Vec::new();
+    }
+
+    let full_block = [ElementBounds {
+        min: Vec3::ZERO,
+        max: Vec3::ONE,
+    }];
+    let bounds = if mesh.bounds.is_empty() {
+        &full_block[..]
+    } else {
+        &mesh.bounds[..]
+    };
+
+    let mut rng = ParticleRng::new(pos);
+    let origin = pos.as_dvec3();
+    let mut particles = Vec::new();
+
+    for element in bounds {
+        let size = (element.max - element.min).max(Vec3::ZERO);
+        let counts = (size * PARTICLES_PER_BLOCK_AXIS)
+            .ceil()
+            .max(Vec3::splat(2.0))
+            .as_uvec3();
+
+        for x in 0..counts.x {
+            for y in 0..counts.y {
+                for z in 0..counts.z {
+                    let fraction = (vec3(x as f32, y as f32, z as f32) + 0.5) / counts.as_vec3();
+                    let local = element.min + fraction * size;
+
+                    let face = faces[rng.next_u32() as usize % faces.len()];
+                    let ((min_u, min_v), (max_u, max_v)) = face_uv(face);
+                    let (width, height) = ((max_u - min_u) / 4, (max_v - min_v) / 4);
+                    let u = min_u + width * (rng.next_u32() % 4) as u16;
+                    let v = min_v + height * (rng.next_u32() % 4) as u16;
+
+                    particles.push(BlockBreakParticle {
+                        position: origin + local.as_dvec3(),
+                        velocity: (local - Vec3::splat(0.5)) * (0.1 + rng.next_f32() * 0.05),
+                        uv: ((u, v), (u + width, v + height)),
+                        tint_index: face.tint_index,
+                        atlas_generation,
+                    });
+                }
+            }
+        }
+    }
+
+    particles
+}
+
+/// Append `particles` to `queue`, dropping the oldest ones past [MAX_QUEUED_PARTICLES]. Queued particles from an
+/// older block atlas generation are dropped as well, since their UVs may point at other sprites after a repack
+pub fn queue_particles(queue: &mut Vec<BlockBreakParticle>, particles: Vec<BlockBreakParticle>) {
+    if let Some(generation) = particles.first().map(|particle| particle.atlas_generation) {
+        queue.retain(|particle| particle.atlas_generation == generation);
+    }
+
+    queue.extend(particles);
+
+    let overflow = queue.len().saturating_sub(MAX_QUEUED_PARTICLES);
+    queue.drain(..overflow);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use glam::{dvec3, ivec3, IVec3, Vec3};
+    use indexmap::IndexMap;
+
+    use crate::mc::block::{BlockModelFace, BlockstateKey, ModelMesh};
+    use crate::mc::chunk::tests::{block_manager, mesh, up_face};
+    use crate::mc::Block;
+
+    use super::{block_break_particles, queue_particles, MAX_QUEUED_PARTICLES};
+
+    /// [up_face] showing the 16x16 sprite at `min`
+    fn face(min: [u16; 2], tint_index: i32) -> BlockModelFace {
+        let mut face = up_face();
+
+        for (vertex, [u, v]) in face
+            .vertices
+            .iter_mut()
+            .zip([[0, 0], [0, 16], [16, 16], [16, 0]])
+        {
+            vertex.tex_coords = [min[0] + u, min[1] + v];
+        }
+
+        BlockModelFace { tint_index, ..face }
+    }
+
+    /// Like a grass block, a tinted top sprite and untinted side sprites, baked into a [block_manager]
+    fn grass() -> ModelMesh {
+        let side = face([32, 0], -1);
+
+        let grass = ModelMesh {
+            north: vec![side],
+            south: vec![side],
+            west: vec![side],
+            east: vec![side],
+            down: vec![face([16, 0], -1)],
+            ..mesh(vec![face([0, 0], 0)], true)
+        };
+
+        let block_manager = block_manager(IndexMap::from([(
+            "grass_block".to_string(),
+            Block::Variants(IndexMap::from([(vec![], vec![Arc::new(grass)])])),
+        )]));
+
+        (*block_manager
+            .get_model(BlockstateKey {
+                block: 0,
+                augment: 0,
+            })
+            .unwrap())
+        .clone()
+    }
+
+    #[test]
+    fn particles_use_pieces_of_the_face_sprites() {
+        let pos = ivec3(10, 64, -3);
+        let particles = block_break_particles(&grass(), pos, 0);
+
+        assert_eq!(particles.len(), 64);
+        assert!(particles.iter().any(|particle| particle.tint_index == 0));
+        assert!(particles.iter().any(|particle| particle.tint_index == -1));
+
+        for particle in &particles {
+            let ((min_u, min_v), (max_u, max_v)) = particle.uv;
+            assert_eq!((max_u - min_u, max_v - min_v), (4, 4));
+            assert!(max_u <= 48 && max_v <= 16);
+
+            let local = (particle.position - pos.as_dvec3()).as_vec3();
+            assert!(local.cmpge(Vec3::ZERO).all() && local.cmple(Vec3::ONE).all());
+        }
+
+        assert_eq!(particles, block_break_particles(&grass(), pos, 0));
+    }
+
+    #[test]
+    fn particles_keep_their_precision_far_from_the_origin() {
+        let near = block_break_particles(&grass(), ivec3(0, 64, 0), 0);
+        let far = block_break_particles(&grass(), ivec3(1_000_000, 64, 1_000_000), 0);
+
+        for (near, far) in near.iter().zip(&far) {
+            assert_eq!(
+                far.position - dvec3(1_000_000.0, 0.0, 1_000_000.0),
+                near.position
+            );
+        }
+    }
+
+    #[test]
+    fn empty_meshes_spawn_nothing() {
+        let mut mesh = grass();
+        for faces in [
+            &mut mesh.north,
+            &mut mesh.south,
+            &mut mesh.west,
+            &mut mesh.east,
+            &mut mesh.up,
+            &mut mesh.down,
+        ] {
+            faces.clear();
+        }
+
+        assert!(block_break_particles(&mesh, ivec3(0, 0, 0), 0).is_empty());
+    }
+
+    #[test]
+    fn the_queue_drops_the_oldest_particles() {
+        let mut queue = Vec::new();
+        let first = block_break_particles(&grass(), IVec3::ZERO, 0);
+
+        queue_particles(&mut queue, first.clone());
+
+        while queue.len() < MAX_QUEUED_PARTICLES {
+            queue_particles(
+                &mut queue,
+                block_break_particles(&grass(), ivec3(1, 0, 0), 0),
+            );
+        }
+
+        assert_eq!(queue.len(), MAX_QUEUED_PARTICLES);
+        assert_eq!(queue[0], first[0]);
+
+        queue_particles(
+            &mut queue,
+            block_break_particles(&grass(), ivec3(2, 0, 0), 0),
+        );
+
+        assert_eq!(queue.len(), MAX_QUEUED_PARTICLES);
+        assert!(!queue.contains(&first[0]));
+    }
+
+    #[test]
+    fn the_queue_drops_particles_from_an_older_atlas() {
+        let mut queue = Vec::new();
+
+        queue_particles(&mut queue, block_break_particles(&grass(), IVec3::ZERO, 1));
+        queue_particles(
+            &mut queue,
+            block_break_particles(&grass(), ivec3(1, 0, 0), 1),
+        );
+        assert_eq!(queue.len(), 128);
+
+        let repacked = block_break_particles(&grass(), IVec3::ZERO, 2);
+        queue_particles(&mut queue, repacked.clone());
+
+        assert_eq!(queue, repacked);
+    }
+}
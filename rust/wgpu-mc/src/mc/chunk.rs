@@ -8,23 +8,101 @@
 //! rendering purposes.
 use arrayvec::ArrayVec;
 use glam::{ivec3, vec3, IVec2, IVec3, Vec3Swizzles};
+use itertools::Either;
 use range_alloc::RangeAllocator;
-use std::collections::HashMap;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::Debug;
 use std::ops::{Not, Range};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::mc::block::{BlockModelFace, ChunkBlockState, ModelMesh};
 use crate::mc::direction::Direction;
+use crate::mc::voxels::SectionVoxelData;
 use crate::mc::BlockManager;
-use crate::render::pipeline::Vertex;
+use crate::render::pipeline::TerrainVertex;
 use crate::WmRenderer;
 
 pub const CHUNK_WIDTH: usize = 16;
 pub const CHUNK_AREA: usize = CHUNK_WIDTH * CHUNK_WIDTH;
+/// The height of the vanilla overworld, see [WorldBounds] for worlds with other heights
 pub const CHUNK_HEIGHT: usize = 384;
+/// Sections are this tall in every world, only how many of them there are changes. Baking, the terrain vertex
+/// format and the voxel layout all depend on it
 pub const CHUNK_SECTION_HEIGHT: usize = 16;
 pub const SECTION_VOLUME: usize = CHUNK_AREA * CHUNK_SECTION_HEIGHT;
+/// How many tint indices each section can store colors for in [TintMode::PerSection]
+pub const SECTION_TINT_SLOTS: usize = 4;
+/// How many sections can have tint colors uploaded at once
+pub const MAX_TINTED_SECTIONS: u32 = 65536;
+/// How many section bakes [BakeTimes] remembers
+pub const BAKE_TIME_HISTORY: usize = 256;
+
+/// Where biome tint colors for terrain come from.
+///
+/// [TintMode::Baked] writes the result of [BlockStateProvider::get_block_color] into every vertex, which is
+/// the cheapest to render and resolves tint per-block, but any change to biome colors requires the section to be rebaked.
+///
+/// [TintMode::PerSection] only stores the face's tint index in the vertex, and the terrain shader looks the color up
+/// from a per-section storage buffer (see [crate::mc::Scene::set_section_tints]). Biome colors can then be changed without
+/// remeshing (e.g. seasons mods), at the cost of an extra storage buffer read per vertex and tint being uniform
+/// across a whole section rather than per-block. Faces with a tint index of [SECTION_TINT_SLOTS] or more have no slot
+/// to look their color up in, so they fall back to the baked tint.
+#[derive(Debug, Copy, Clone, Default, Hash, Eq, PartialEq)]
+pub enum TintMode {
+    #[default]
+    Baked,
+    PerSection,
+}
+
+/// Which of two coplanar faces of a block stays visible, which depends on the terrain pipeline's depth compare. Layered
+/// models (e.g. grass block sides) put their overlays in elements after the base, coplanar with it, and the base should
+/// only show through where the overlay is transparent and discarded. Set by [crate::render::graph::RenderGraph::new]
+/// from the shader pack's `@geo_terrain` pipeline
+#[derive(Debug, Copy, Clone, Default, Hash, Eq, PartialEq)]
+pub enum CoplanarOrder {
+    /// Faces are emitted in reverse model order, since with a strict depth compare like `less` the first face drawn
+    /// wins
+    #[default]
+    OverlaysFirst,
+    /// Faces are emitted in model order, since with a depth compare like `less_equal` the last face drawn wins
+    ModelOrder,
+}
+
+impl CoplanarOrder {
+    pub fn for_depth_compare(depth_compare: wgpu::CompareFunction) -> Self {
+        match depth_compare {
+            wgpu::CompareFunction::Less
+            | wgpu::CompareFunction::Greater
+            | wgpu::CompareFunction::NotEqual
+            | wgpu::CompareFunction::Never => CoplanarOrder::OverlaysFirst,
+            wgpu::CompareFunction::LessEqual
+            | wgpu::CompareFunction::GreaterEqual
+            | wgpu::CompareFunction::Equal
+            | wgpu::CompareFunction::Always => CoplanarOrder::ModelOrder,
+        }
+    }
+}
+
+/// `faces` in the order [CoplanarOrder] emits them in
+fn in_coplanar_order(
+    faces: &[BlockModelFace],
+    order: CoplanarOrder,
+) -> impl Iterator<Item = &BlockModelFace> {
+    match order {
+        CoplanarOrder::OverlaysFirst => Either::Left(faces.iter().rev()),
+        CoplanarOrder::ModelOrder => Either::Right(faces.iter()),
+    }
+}
+
+/// The slot a face's color is looked up from with [TintMode::PerSection], the tint index offset by one since 0 means
+/// untinted. None for untinted faces and tint indices without a slot
+fn section_tint_slot(tint_index: i32) -> Option<u8> {
+    (0..SECTION_TINT_SLOTS as i32)
+        .contains(&tint_index)
+        .then(|| tint_index as u8 + 1)
+}
 
 #[derive(Clone, Copy, Debug)]
 pub struct LightLevel {
@@ -45,6 +123,11 @@ impl LightLevel {
     pub fn get_block_level(&self) -> u8 {
         self.byte & 0b1111
     }
+
+    /// The same light with the block level raised to `emission` if it's lower, for the faces of light emitting blocks
+    pub fn with_emission(self, emission: u8) -> Self {
+        Self::from_sky_and_block(self.get_sky_level(), self.get_block_level().max(emission))
+    }
 }
 
 /// Return a [ChunkBlockState] within the provided world coordinates.
@@ -58,17 +141,76 @@ pub trait BlockStateProvider {
     fn get_block_color(&self, pos: IVec3, tint_index: i32) -> u32;
 }
 
-#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub enum RenderLayer {
     Solid = 0,
     Cutout = 1,
     Transparent = 2,
 }
 
+impl RenderLayer {
+    /// Every layer, in the order they're drawn
+    pub const ALL: [RenderLayer; 3] = [
+        RenderLayer::Solid,
+        RenderLayer::Cutout,
+        RenderLayer::Transparent,
+    ];
+}
+
 #[derive(Clone)]
 pub struct SectionRanges {
     pub vertex_range: Range<u32>,
+    /// Where the indices are in the chunk buffer, in 4 byte words like `vertex_range`
     pub index_range: Range<u32>,
+    pub index_format: wgpu::IndexFormat,
+}
+
+impl SectionRanges {
+    /// The indices to draw, counted in indices of [SectionRanges::index_format] rather than words
+    pub fn indices(&self) -> Range<u32> {
+        match self.index_format {
+            wgpu::IndexFormat::Uint16 => self.index_range.start * 2..self.index_range.end * 2,
+            wgpu::IndexFormat::Uint32 => self.index_range.clone(),
+        }
+    }
+}
+
+/// How far a world extends vertically, in blocks. Modded dimensions and data packs can change this, the overworld
+/// spans -64 to 320
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WorldBounds {
+    pub min_y: i32,
+    /// Exclusive
+    pub max_y: i32,
+}
+
+impl WorldBounds {
+    pub const OVERWORLD: WorldBounds = WorldBounds {
+        min_y: -64,
+        max_y: 320,
+    };
+
+    pub fn height(&self) -> u32 {
+        (self.max_y - self.min_y).max(0) as u32
+    }
+
+    /// The Y coordinates of the sections in this world, in sections
+    pub fn section_range(&self) -> Range<i32> {
+        let section_height = CHUNK_SECTION_HEIGHT as i32;
+
+        self.min_y.div_euclid(section_height)
+            ..(self.max_y + section_height - 1).div_euclid(section_height)
+    }
+
+    pub fn contains_section(&self, section_y: i32) -> bool {
+        self.section_range().contains(&section_y)
+    }
+}
+
+impl Default for WorldBounds {
+    fn default() -> Self {
+        Self::OVERWORLD
+    }
 }
 
 ///The struct representing a Chunk section, with various render layers, split into sections
@@ -76,6 +218,13 @@ pub struct SectionStorage {
     storage: HashMap<IVec3, Section>,
     allocator: RangeAllocator<u32>,
     width: i32,
+    tint_slots: HashMap<IVec3, u32>,
+    /// The colors last uploaded to each tint slot, kept to upload them again with other
+    /// [crate::render::atlas::TintRamp]s
+    tint_colors: HashMap<u32, [u32; SECTION_TINT_SLOTS]>,
+    free_tint_slots: Vec<u32>,
+    next_tint_slot: u32,
+    world_bounds: WorldBounds,
 }
 impl SectionStorage {
     pub fn new(range: u32) -> Self {
@@ -83,21 +232,39 @@ impl SectionStorage {
             storage: HashMap::new(),
             width: 0,
             allocator: RangeAllocator::new(0..range),
+            tint_slots: HashMap::new(),
+            tint_colors: HashMap::new(),
+            free_tint_slots: Vec::new(),
+            //Slot 0 is reserved for sections which have no tint colors uploaded
+            next_tint_slot: 1,
+            world_bounds: WorldBounds::default(),
         }
     }
     pub fn clear(&mut self) {
         self.allocator.reset();
         self.storage.clear();
+        self.tint_slots.clear();
+        self.tint_colors.clear();
+        self.free_tint_slots.clear();
+        self.next_tint_slot = 1;
     }
     pub fn set_width(&mut self, w: i32) {
         self.width = w;
     }
+    pub fn world_bounds(&self) -> WorldBounds {
+        self.world_bounds
+    }
+    /// Set the height of the world when entering a dimension. Sections outside of it are removed on the next
+    /// [SectionStorage::trim]
+    pub fn set_world_bounds(&mut self, bounds: WorldBounds) {
+        self.world_bounds = bounds;
+    }
     pub fn trim(&mut self, pos: IVec2) {
         let mut to_remove = vec![];
         for (k, section) in &self.storage {
             let dist = (k.xz() - pos).abs();
             let radius = self.width + 2; //temp fix until proper sync
-            if dist.x > radius || dist.y > radius {
+            if dist.x > radius || dist.y > radius || !self.world_bounds.contains_section(k.y) {
                 to_remove.push(*k);
                 for layer in &section.layers {
                     if let Some(l) = layer.as_ref() {
@@ -109,9 +276,45 @@ impl SectionStorage {
         }
         to_remove.iter().for_each(|pos| {
             self.storage.remove(pos);
+            if let Some(slot) = self.tint_slots.remove(pos) {
+                self.tint_colors.remove(&slot);
+                self.free_tint_slots.push(slot);
+            }
         });
     }
-    pub fn replace(&mut self, pos: IVec3, baked_layers: &Vec<BakedLayer>) -> Section {
+    /// The slot in the section tint buffer this section's colors are stored at, 0 if none were uploaded
+    pub fn get_tint_slot(&self, pos: IVec3) -> u32 {
+        self.tint_slots.get(&pos).copied().unwrap_or(0)
+    }
+    /// Returns the existing tint slot for the section or allocates a new one, if there is space left
+    pub fn allocate_tint_slot(&mut self, pos: IVec3) -> Option<u32> {
+        if let Some(slot) = self.tint_slots.get(&pos) {
+            return Some(*slot);
+        }
+
+        let slot = match self.free_tint_slots.pop() {
+            Some(slot) => slot,
+            None if self.next_tint_slot < MAX_TINTED_SECTIONS => {
+                self.next_tint_slot += 1;
+                self.next_tint_slot - 1
+            }
+            None => return None,
+        };
+
+        self.tint_slots.insert(pos, slot);
+        Some(slot)
+    }
+    /// Remember the colors uploaded to a tint slot, see [SectionStorage::tint_colors]
+    pub fn set_tint_colors(&mut self, slot: u32, colors: [u32; SECTION_TINT_SLOTS]) {
+        self.tint_colors.insert(slot, colors);
+    }
+    /// Every allocated tint slot with the sRGB colors last uploaded to it
+    pub fn tint_colors(&self) -> impl Iterator<Item = (u32, [u32; SECTION_TINT_SLOTS])> + '_ {
+        self.tint_colors
+            .iter()
+            .map(|(slot, colors)| (*slot, *colors))
+    }
+    pub fn replace(&mut self, pos: IVec3, baked_layers: &[BakedLayer]) -> Section {
         if let Some(previous_section) = self.storage.get(&pos) {
             for layer in &previous_section.layers {
                 if let Some(l) = layer.as_ref() {
@@ -121,6 +324,11 @@ impl SectionStorage {
             }
         }
         let section = Section {
+            //Keep the time the section first appeared so that rebaking doesn't fade it in again
+            loaded_at: self
+                .storage
+                .get(&pos)
+                .map_or_else(Instant::now, |previous_section| previous_section.loaded_at),
             layers: baked_layers
                 .iter()
                 .map(|layer| {
@@ -134,6 +342,7 @@ impl SectionStorage {
                                 .allocator
                                 .allocate_range(layer.indices.len() as u32 / 4)
                                 .unwrap(),
+                            index_format: layer.index_format,
                         })
                     } else {
                         None
@@ -152,6 +361,8 @@ impl SectionStorage {
 #[derive(Clone)]
 pub struct Section {
     pub layers: Vec<Option<SectionRanges>>,
+    /// When this section was first baked, used to fade newly loaded sections in
+    pub loaded_at: Instant,
 }
 
 impl Default for Section {
@@ -162,7 +373,29 @@ impl Default for Section {
 
 impl Section {
     pub fn new() -> Self {
-        Self { layers: Vec::new() }
+        Self {
+            layers: Vec::new(),
+            loaded_at: Instant::now(),
+        }
+    }
+
+    /// How many triangles this section draws in `layers`
+    pub fn triangle_count(&self, layers: &HashSet<RenderLayer>) -> u32 {
+        RenderLayer::ALL
+            .into_iter()
+            .filter(|layer| layers.contains(layer))
+            .filter_map(|layer| self.layers.get(layer as usize)?.as_ref())
+            .map(|ranges| ranges.indices().len() as u32 / 3)
+            .sum()
+    }
+
+    /// How far along fading in this section is, from 0 to 1. A duration of 0 disables fading
+    pub fn fade(&self, duration: f32) -> f32 {
+        if duration <= 0.0 {
+            return 1.0;
+        }
+
+        (self.loaded_at.elapsed().as_secs_f32() / duration).min(1.0)
     }
 }
 
@@ -170,35 +403,215 @@ impl Section {
 fn get_block(block_manager: &BlockManager, state: ChunkBlockState) -> Option<Arc<ModelMesh>> {
     let key = match state {
         ChunkBlockState::Air => return None,
+        ChunkBlockState::State(key) if block_manager.renders_nothing.contains(&key.block) => {
+            return None
+        }
         ChunkBlockState::State(key) => key,
     };
 
-    block_manager
-        .blocks
-        .get_index(key.block as usize)?
-        .1
-        .get_model(key.augment, 0)
+    block_manager.get_model(key)
+}
+
+/// A hash of a world position like vanilla's `Mth.getSeed`, for per-block randomness which is the same on every bake
+/// and machine
+pub fn position_seed(pos: IVec3) -> i64 {
+    let seed = (pos.x as i64).wrapping_mul(3129871)
+        ^ (pos.z as i64).wrapping_mul(116129781)
+        ^ pos.y as i64;
+    let seed = seed
+        .wrapping_mul(seed)
+        .wrapping_mul(42317861)
+        .wrapping_add(seed.wrapping_mul(11));
+
+    seed >> 16
+}
+
+/// How many quarter turns the top and bottom textures of a randomly rotated block at this world position get, see
+/// [BlockManager::rotates_randomly]. Uses [position_seed], so it doesn't change between bakes
+pub fn random_uv_rotation(pos: IVec3) -> u8 {
+    (position_seed(pos) & 3) as u8
+}
+
+/// The vertex [bake_section_with] hands to mappers
+pub type Vertex = TerrainVertex;
+
+/// The default vertex mapper, which packs vertices into the format the built-in terrain shader reads. `atlas_origin`
+/// is added to every UV, for block atlases which are placed inside a larger texture; it's `[0, 0]` for the block atlas
+/// wgpu-mc creates itself
+pub fn terrain_mapper(atlas_origin: [u16; 2]) -> impl Fn(Vertex) -> [u8; Vertex::VERTEX_LENGTH] {
+    move |vertex| {
+        Vertex {
+            uv: [
+                vertex.uv[0] + atlas_origin[0],
+                vertex.uv[1] + atlas_origin[1],
+            ],
+            ..vertex
+        }
+        .compressed()
+    }
 }
 
 pub fn bake_section<Provider: BlockStateProvider>(pos: IVec3, wm: &WmRenderer, bsp: &Provider) {
+    bake_section_with(pos, wm, bsp, &terrain_mapper([0, 0]));
+}
+
+/// Bake a section using a custom vertex format. `mapper` is called for every [TerrainVertex] and returns the bytes
+/// which get uploaded to the chunk buffer in its place, for example to pack an instance id next to the output of
+/// [terrain_mapper]. `N` must be a multiple of 4, and the shader pack's terrain shader has to read the same layout.
+pub fn bake_section_with<Provider, Mapper, const N: usize>(
+    pos: IVec3,
+    wm: &WmRenderer,
+    bsp: &Provider,
+    mapper: &Mapper,
+) where
+    Provider: BlockStateProvider,
+    Mapper: Fn(TerrainVertex) -> [u8; N],
+{
     let bm = wm.mc.block_manager.read();
+    let tint_mode = *wm.mc.tint_mode.read();
 
-    let baked_section = bake_layers(pos, &bm, bsp);
+    let started = Instant::now();
+    let baked_section = bake_layers(pos, &bm, bsp, tint_mode, mapper);
+    wm.mc.bake_times.lock().record(pos, started.elapsed());
+
+    if *wm.mc.bake_voxels.read() {
+        let voxels = SectionVoxelData::bake(&bm, bsp);
+        wm.voxel_update_queue.0.send((pos, voxels)).unwrap();
+    }
 
     wm.chunk_update_queue.0.send((pos, baked_section)).unwrap();
 }
 
-#[derive(Clone, Default)]
+/// What a call to [WmRenderer::submit_chunk_updates] or [WmRenderer::flush_dirty] uploaded
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChunkUpdateStats {
+    /// Sections which were re-uploaded
+    pub sections: usize,
+    /// Bakes which were skipped because the same section was baked again before they were uploaded
+    pub superseded: usize,
+    /// Dirty sections which [WmRenderer::flush_dirty] baked again, each once however often it was marked
+    pub rebaked: usize,
+}
+
+/// The sections, in sections, whose meshes depend on the block at `block`: its own, and its neighbours' when it's on
+/// their border, since their culled faces and ambient occlusion look at it too. Vanilla re-renders the same ones
+pub fn sections_around_block(block: IVec3) -> HashSet<IVec3> {
+    let mut sections = HashSet::new();
+
+    for x in -1..=1 {
+        for y in -1..=1 {
+            for z in -1..=1 {
+                let neighbour = block + ivec3(x, y, z);
+                sections.insert(ivec3(neighbour.x >> 4, neighbour.y >> 4, neighbour.z >> 4));
+            }
+        }
+    }
+
+    sections
+}
+
+/// Keeps only the latest bake of each section, since older ones would be overwritten in the same frame anyway
+pub fn coalesce_chunk_updates(
+    updates: impl Iterator<Item = (IVec3, Vec<BakedLayer>)>,
+) -> (HashMap<IVec3, Vec<BakedLayer>>, ChunkUpdateStats) {
+    let mut dirty = HashMap::new();
+    let mut superseded = 0;
+
+    for (pos, layers) in updates {
+        if dirty.insert(pos, layers).is_some() {
+            superseded += 1;
+        }
+    }
+
+    let stats = ChunkUpdateStats {
+        sections: dirty.len(),
+        superseded,
+    };
+
+    (dirty, stats)
+}
+
+/// How long meshing one section took, see [crate::mc::MinecraftState::recent_bake_times]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SectionBakeTime {
+    pub pos: IVec3,
+    pub duration: Duration,
+}
+
+/// The last [BAKE_TIME_HISTORY] section bakes, for finding meshing spikes while terrain streams in
+#[derive(Debug, Default)]
+pub struct BakeTimes(VecDeque<SectionBakeTime>);
+
+impl BakeTimes {
+    /// Forgets the oldest bake once the history is full
+    pub fn record(&mut self, pos: IVec3, duration: Duration) {
+        if self.0.len() == BAKE_TIME_HISTORY {
+            self.0.pop_front();
+        }
+
+        self.0.push_back(SectionBakeTime { pos, duration });
+    }
+
+    /// Oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &SectionBakeTime> {
+        self.0.iter()
+    }
+
+    pub fn slowest(&self) -> Option<SectionBakeTime> {
+        self.0.iter().copied().max_by_key(|bake| bake.duration)
+    }
+}
+
+#[derive(Clone)]
 pub struct BakedLayer {
     pub vertices: Vec<u8>,
     pub indices: Vec<u8>,
+    pub index_format: wgpu::IndexFormat,
+}
+
+impl Default for BakedLayer {
+    fn default() -> Self {
+        Self {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            index_format: wgpu::IndexFormat::Uint32,
+        }
+    }
 }
 
-fn bake_layers<Provider: BlockStateProvider>(
+impl BakedLayer {
+    /// Switch to 16 bit indices if every vertex can be addressed with them, which halves the size of the indices.
+    /// Quads always have 6 indices, so the 16 bit indices still fill whole 4 byte words of the chunk buffer
+    fn compact_indices(&mut self, vertex_count: usize) {
+        if self.index_format == wgpu::IndexFormat::Uint16 || vertex_count > u16::MAX as usize + 1 {
+            return;
+        }
+
+        self.indices = self
+            .indices
+            .chunks_exact(4)
+            .flat_map(|index| (u32::from_ne_bytes(index.try_into().unwrap()) as u16).to_ne_bytes())
+            .collect();
+        self.index_format = wgpu::IndexFormat::Uint16;
+    }
+}
+
+pub(crate) fn bake_layers<Provider, Mapper, const N: usize>(
     section_pos: IVec3,
     block_manager: &BlockManager,
     state_provider: &Provider,
-) -> Vec<BakedLayer> {
+    tint_mode: TintMode,
+    mapper: &Mapper,
+) -> Vec<BakedLayer>
+where
+    Provider: BlockStateProvider,
+    Mapper: Fn(TerrainVertex) -> [u8; N],
+{
+    assert!(
+        N % 4 == 0,
+        "Vertex mappers have to return a multiple of 4 bytes, not {N}"
+    );
+
     let mut layers = vec![BakedLayer::default(); 3];
 
     let section_offset = 16 * section_pos;
@@ -215,11 +628,47 @@ fn bake_layers<Provider: BlockStateProvider>(
         let block_state: ChunkBlockState = state_provider.get_state(pos);
 
         if let Some(model_mesh) = get_block(block_manager, block_state) {
+            let render_layer = match block_state {
+                ChunkBlockState::State(key) => block_manager.get_render_layer(key, &model_mesh),
+                ChunkBlockState::Air => unreachable!(),
+            };
+
             const INDICES: [u32; 6] = [1, 3, 0, 2, 3, 1];
+            let tint_slot = |face: &BlockModelFace| match tint_mode {
+                TintMode::PerSection => section_tint_slot(face.tint_index).unwrap_or(0),
+                TintMode::Baked => 0,
+            };
+            //Faces tinted per section get their color in the shader
+            let tint_color = |face: &BlockModelFace| match face.tint_index {
+                tint_index if tint_index != -1 && tint_slot(face) == 0 => {
+                    block_manager.vertex_color_space.convert_srgb_color(
+                        state_provider.get_block_color(pos + section_offset, tint_index),
+                    )
+                }
+                _ => 0xffffffff,
+            };
+
+            let uv_scroll = |face: &BlockModelFace| match block_state {
+                ChunkBlockState::State(key) => block_manager.uv_scroll_channel(key, face),
+                ChunkBlockState::Air => 0,
+            };
+
+            let sway = match block_state {
+                ChunkBlockState::State(key) => block_manager.sways.get(&key).copied(),
+                ChunkBlockState::Air => None,
+            };
+
+            let emission = match block_state {
+                ChunkBlockState::State(key) => block_manager.light_emission(key),
+                ChunkBlockState::Air => 0,
+            };
+
             let mut add_quad =
                 |face: &BlockModelFace, _light_level: LightLevel, dir: Direction, color: u32| {
-                    let baked_layer = &mut layers[RenderLayer::Solid as usize];
-                    let vec_index = baked_layer.vertices.len() / Vertex::VERTEX_LENGTH;
+                    let tint_slot = tint_slot(face);
+                    let uv_scroll = uv_scroll(face);
+                    let baked_layer = &mut layers[render_layer as usize];
+                    let vec_index = baked_layer.vertices.len() / N;
 
                     let dir_vec = dir.to_vec();
 
@@ -267,9 +716,15 @@ fn bake_layers<Provider: BlockStateProvider>(
                                     let p2 = p1 + axes[0];
                                     let p3 = p1 + axes[1];
 
-                                    let b1 = state_provider.get_state(p1).is_air().not() as u8;
-                                    let b2 = state_provider.get_state(p2).is_air().not() as u8;
-                                    let b3 = state_provider.get_state(p3).is_air().not() as u8;
+                                    let b1 = block_manager
+                                        .is_air_like(state_provider.get_state(p1))
+                                        .not() as u8;
+                                    let b2 = block_manager
+                                        .is_air_like(state_provider.get_state(p2))
+                                        .not() as u8;
+                                    let b3 = block_manager
+                                        .is_air_like(state_provider.get_state(p3))
+                                        .not() as u8;
 
                                     let l1 = state_provider.get_light_level(p1);
                                     let l2 = state_provider.get_light_level(p2);
@@ -299,7 +754,7 @@ fn bake_layers<Provider: BlockStateProvider>(
                                     (0, 0, 0, state_provider.get_light_level(pos))
                                 };
 
-                                Vertex {
+                                TerrainVertex {
                                     position: [
                                         fpos.x + model_vertex.position[0],
                                         fpos.y + model_vertex.position[1],
@@ -308,12 +763,19 @@ fn bake_layers<Provider: BlockStateProvider>(
                                     uv: model_vertex.tex_coords,
                                     normal: face.normal.to_array(),
                                     color,
+                                    tint_slot,
+                                    uv_scroll,
                                     uv_offset: 0,
-                                    lightmap_coords: light_level.byte,
-                                    ao: 3 - (b1 + b2 + b3),
+                                    lightmap_coords: light_level.with_emission(emission).byte,
+                                    ao: if model_mesh.ambient_occlusion {
+                                        3 - (b1 + b2 + b3)
+                                    } else {
+                                        3
+                                    },
+                                    wave: sway.map_or(0, |sway| sway.wave(model_vertex.position.y)),
                                 }
                             })
-                            .flat_map(Vertex::compressed),
+                            .flat_map(mapper),
                     );
                     baked_layer.indices.extend(
                         INDICES
@@ -323,12 +785,6 @@ fn bake_layers<Provider: BlockStateProvider>(
                 };
 
             let mut add_face = |face: &BlockModelFace, dir: Direction| {
-                let color = if face.tint_index != -1 {
-                    state_provider.get_block_color(pos + section_offset, face.tint_index)
-                } else {
-                    0xffffffff
-                };
-
                 let cull = if let Some(mesh) =
                     get_block(block_manager, state_provider.get_state(pos + dir.to_vec()))
                 {
@@ -338,42 +794,509 @@ fn bake_layers<Provider: BlockStateProvider>(
                 };
 
                 if !cull {
+                    let rotated;
+                    let face = match block_state {
+                        ChunkBlockState::State(key)
+                            if block_manager.rotates_randomly(key, &model_mesh, dir) =>
+                        {
+                            rotated = face.rotate_uvs(random_uv_rotation(pos + section_offset));
+                            &rotated
+                        }
+                        _ => face,
+                    };
+
                     let light_level: LightLevel =
                         state_provider.get_light_level(pos + dir.to_vec());
-                    add_quad(face, light_level, dir, color);
+                    add_quad(face, light_level, dir, tint_color(face));
                 }
             };
 
-            model_mesh.west.iter().for_each(|face| {
-                add_face(face, Direction::West);
-            });
-            model_mesh.east.iter().for_each(|face| {
-                add_face(face, Direction::East);
-            });
-            model_mesh.down.iter().for_each(|face| {
-                add_face(face, Direction::Down);
-            });
-            model_mesh.up.iter().for_each(|face| {
-                add_face(face, Direction::Up);
-            });
-            model_mesh.north.iter().for_each(|face| {
-                add_face(face, Direction::North);
-            });
-            model_mesh.south.iter().for_each(|face| {
-                add_face(face, Direction::South);
-            });
-            model_mesh.any.iter().for_each(|face| {
-                let light_level: LightLevel = state_provider.get_light_level(pos);
+            let coplanar_order = block_manager.coplanar_order;
 
-                let color = if face.tint_index != -1 {
-                    state_provider.get_block_color(pos + section_offset, face.tint_index)
-                } else {
-                    0xffffffff
-                };
+            for (faces, dir) in [
+                (&model_mesh.west, Direction::West),
+                (&model_mesh.east, Direction::East),
+                (&model_mesh.down, Direction::Down),
+                (&model_mesh.up, Direction::Up),
+                (&model_mesh.north, Direction::North),
+                (&model_mesh.south, Direction::South),
+            ] {
+                for face in in_coplanar_order(faces, coplanar_order) {
+                    add_face(face, dir);
+                }
+            }
 
-                add_quad(face, light_level, Direction::Up, color);
-            });
+            for face in in_coplanar_order(&model_mesh.any, coplanar_order) {
+                let light_level: LightLevel = state_provider.get_light_level(pos);
+
+                add_quad(face, light_level, Direction::Up, tint_color(face));
+            }
         }
     }
+
+    for layer in &mut layers {
+        let vertex_count = layer.vertices.len() / N;
+        layer.compact_indices(vertex_count);
+    }
+
     layers
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use glam::{ivec3, vec3, IVec2, IVec3, Vec3};
+    use indexmap::IndexMap;
+
+    use super::{
+        bake_layers, coalesce_chunk_updates, in_coplanar_order, random_uv_rotation,
+        section_tint_slot, sections_around_block, terrain_mapper, BakeTimes, BakedLayer,
+        BlockStateProvider, ChunkUpdateStats, CoplanarOrder, LightLevel, RenderLayer,
+        SectionStorage, TintMode, WorldBounds, BAKE_TIME_HISTORY,
+    };
+    use crate::mc::block::{
+        BlockMeshVertex, BlockModelFace, BlockstateKey, ChunkBlockState, GuiLight, ModelMesh,
+    };
+    use crate::mc::{Block, BlockManager};
+    use crate::render::pipeline::TerrainVertex;
+    use crate::render::wind::{Sway, MAX_WAVE};
+    use crate::texture::ColorSpace;
+
+    const BLOCK: BlockstateKey = BlockstateKey {
+        block: 0,
+        augment: 0,
+    };
+    const OCCLUDER: BlockstateKey = BlockstateKey {
+        block: 1,
+        augment: 0,
+    };
+
+    /// A single block at the origin with solid blocks diagonally above it, which would darken its top face
+    struct OccludedBlock;
+
+    impl BlockStateProvider for OccludedBlock {
+        fn get_state(&self, pos: IVec3) -> ChunkBlockState {
+            if pos == IVec3::ZERO {
+                ChunkBlockState::State(BLOCK)
+            } else if [ivec3(1, 1, 0), ivec3(0, 1, 1), ivec3(1, 1, 1)].contains(&pos) {
+                ChunkBlockState::State(OCCLUDER)
+            } else {
+                ChunkBlockState::Air
+            }
+        }
+
+        fn get_light_level(&self, _pos: IVec3) -> LightLevel {
+            LightLevel::from_sky_and_block(15, 0)
+        }
+
+        fn is_section_empty(&self, _rel_pos: IVec3) -> bool {
+            false
+        }
+
+        fn get_block_color(&self, _pos: IVec3, _tint_index: i32) -> u32 {
+            0xffffffff
+        }
+    }
+
+    pub(crate) fn mesh(up: Vec<BlockModelFace>, ambient_occlusion: bool) -> ModelMesh {
+        ModelMesh {
+            north: vec![],
+            south: vec![],
+            west: vec![],
+            east: vec![],
+            up,
+            down: vec![],
+            any: vec![],
+            cull: 0,
+            layer: RenderLayer::Solid,
+            ambient_occlusion,
+            gui_light: GuiLight::Side,
+            bounds: vec![],
+        }
+    }
+
+    fn bake_ao(ambient_occlusion: bool) -> Vec<u8> {
+        let mut blocks = IndexMap::new();
+        blocks.insert(
+            "block".into(),
+            Block::Variants(IndexMap::from([(
+                vec![],
+                vec![Arc::new(mesh(vec![up_face()], ambient_occlusion))],
+            )])),
+        );
+        blocks.insert(
+            "occluder".into(),
+            Block::Variants(IndexMap::from([(
+                vec![],
+                vec![Arc::new(mesh(vec![], true))],
+            )])),
+        );
+
+        bake_solid_ao(&block_manager(blocks))
+    }
+
+    pub(crate) fn up_face() -> BlockModelFace {
+        let corners: [Vec3; 4] = [
+            vec3(0.0, 1.0, 0.0),
+            vec3(0.0, 1.0, 1.0),
+            vec3(1.0, 1.0, 1.0),
+            vec3(1.0, 1.0, 0.0),
+        ];
+
+        BlockModelFace {
+            vertices: corners.map(|position| BlockMeshVertex {
+                position,
+                tex_coords: [0, 0],
+            }),
+            normal: vec3(0.0, 1.0, 0.0),
+            tint_index: -1,
+            animation_uv_offset: 0,
+        }
+    }
+
+    pub(crate) fn block_manager(blocks: IndexMap<String, Block>) -> BlockManager {
+        BlockManager {
+            blocks,
+            render_layers: HashMap::new(),
+            interned_meshes: HashMap::new(),
+            renders_nothing: HashSet::new(),
+            uv_scrolls: Vec::new(),
+            uv_scroll_channels: HashMap::new(),
+            random_rotation: HashSet::new(),
+            random_rotation_full_cubes: false,
+            sways: HashMap::new(),
+            light_emission: HashMap::new(),
+            vertex_color_space: ColorSpace::Srgb,
+            coplanar_order: CoplanarOrder::default(),
+        }
+    }
+
+    /// The AO byte of each vertex baked into the solid layer for [OccludedBlock], which has the wave strength in its
+    /// upper bits
+    fn bake_solid_ao(block_manager: &BlockManager) -> Vec<u8> {
+        let layers = bake_layers(
+            IVec3::ZERO,
+            block_manager,
+            &OccludedBlock,
+            TintMode::Baked,
+            &terrain_mapper([0, 0]),
+        );
+
+        layers[RenderLayer::Solid as usize]
+            .vertices
+            .chunks(TerrainVertex::VERTEX_LENGTH)
+            .map(|vertex| vertex[13])
+            .collect()
+    }
+
+    #[test]
+    fn emissive_blocks_are_lit_by_their_own_light() {
+        let mut block_manager = block_manager(IndexMap::from([(
+            "glowstone".to_string(),
+            Block::Variants(IndexMap::from([(
+                vec![],
+                vec![Arc::new(mesh(vec![up_face()], true))],
+            )])),
+        )]));
+
+        let lightmap_coords = |block_manager: &BlockManager| {
+            bake_layers(
+                IVec3::ZERO,
+                block_manager,
+                &OccludedBlock,
+                TintMode::Baked,
+                &terrain_mapper([0, 0]),
+            )[RenderLayer::Solid as usize]
+                .vertices
+                .chunks(TerrainVertex::VERTEX_LENGTH)
+                .map(|vertex| vertex[12])
+                .collect::<Vec<_>>()
+        };
+
+        //Sky light only, the surroundings have no block light
+        assert_eq!(lightmap_coords(&block_manager), vec![0xf0; 4]);
+
+        block_manager.set_light_emission(BLOCK, 15);
+        assert_eq!(block_manager.light_emission(BLOCK), 15);
+        assert_eq!(block_manager.light_emission(OCCLUDER), 0);
+        assert_eq!(lightmap_coords(&block_manager), vec![0xff; 4]);
+
+        block_manager.set_light_emission(BLOCK, 0);
+        assert!(block_manager.light_emission.is_empty());
+    }
+
+    #[test]
+    fn grass_sways_at_the_top_only() {
+        //A single quad standing upright, like one half of a cross model
+        let corners: [Vec3; 4] = [
+            vec3(0.0, 0.0, 0.5),
+            vec3(0.0, 1.0, 0.5),
+            vec3(1.0, 1.0, 0.5),
+            vec3(1.0, 0.0, 0.5),
+        ];
+        let face = BlockModelFace {
+            vertices: corners.map(|position| BlockMeshVertex {
+                position,
+                tex_coords: [0, 0],
+            }),
+            normal: vec3(0.0, 0.0, 1.0),
+            tint_index: -1,
+            animation_uv_offset: 0,
+        };
+
+        let mut block_manager = block_manager(IndexMap::from([(
+            "grass".to_string(),
+            Block::Variants(IndexMap::from([(
+                vec![],
+                vec![Arc::new(ModelMesh {
+                    any: vec![face],
+                    ..mesh(vec![], false)
+                })],
+            )])),
+        )]));
+
+        assert_eq!(bake_solid_ao(&block_manager), vec![3; 4]);
+
+        block_manager.set_sway(BLOCK, Some(Sway::ROOTED));
+        let packed = bake_solid_ao(&block_manager);
+
+        //AO stays in the low bits, the wave strength follows the height of each corner
+        assert!(packed.iter().all(|byte| byte & 3 == 3));
+        assert_eq!(
+            packed.iter().map(|byte| byte >> 2).collect::<Vec<_>>(),
+            [0, MAX_WAVE, MAX_WAVE, 0]
+        );
+    }
+
+    #[test]
+    fn unbaked_blocks_are_skipped() {
+        //Only the block itself is baked, the occluders around it aren't yet
+        let block_manager = block_manager(IndexMap::from([(
+            "block".to_string(),
+            Block::Variants(IndexMap::from([(
+                vec![],
+                vec![Arc::new(mesh(vec![up_face()], true))],
+            )])),
+        )]));
+
+        assert!(block_manager.is_baked(BLOCK));
+        assert!(!block_manager.is_baked(OCCLUDER));
+        assert!(!block_manager.is_baked(BlockstateKey {
+            block: 0,
+            augment: 1,
+        }));
+
+        //Unbaked occluders are air, so nothing darkens the top face
+        assert_eq!(bake_solid_ao(&block_manager), vec![3; 4]);
+    }
+
+    #[test]
+    fn ambient_occlusion_enabled() {
+        let ao = bake_ao(true);
+
+        assert_eq!(ao.len(), 4);
+        assert!(ao.iter().any(|&ao| ao < 3));
+    }
+
+    #[test]
+    fn ambient_occlusion_disabled() {
+        assert_eq!(bake_ao(false), vec![3; 4]);
+    }
+
+    #[test]
+    fn random_rotation_varies_across_a_floor() {
+        let rotations = (0..16)
+            .flat_map(|x| (0..16).map(move |z| random_uv_rotation(ivec3(x, 64, z))))
+            .collect::<Vec<_>>();
+
+        for quarter_turns in 0..4 {
+            assert!(rotations.contains(&quarter_turns));
+        }
+        assert_eq!(random_uv_rotation(ivec3(3, 64, 7)), rotations[3 * 16 + 7]);
+    }
+
+    #[test]
+    fn rotated_uvs_move_around_the_quad() {
+        let face = BlockModelFace {
+            vertices: [[0, 0], [0, 16], [16, 16], [16, 0]].map(|tex_coords| BlockMeshVertex {
+                position: Vec3::ZERO,
+                tex_coords,
+            }),
+            normal: vec3(0.0, 1.0, 0.0),
+            tint_index: -1,
+            animation_uv_offset: 0,
+        };
+
+        let rotated = face.rotate_uvs(1);
+
+        assert_eq!(rotated.vertices[0].tex_coords, [0, 16]);
+        assert_eq!(rotated.vertices[3].tex_coords, [0, 0]);
+        assert_eq!(face.rotate_uvs(4), face);
+    }
+
+    fn layer_with_quads(quads: u32) -> BakedLayer {
+        BakedLayer {
+            indices: (0..quads)
+                .flat_map(|quad| [1, 3, 0, 2, 3, 1].map(|index| index + quad * 4))
+                .flat_map(u32::to_ne_bytes)
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn small_layer_uses_16_bit_indices() {
+        let mut layer = layer_with_quads(2);
+        layer.compact_indices(8);
+
+        assert_eq!(layer.index_format, wgpu::IndexFormat::Uint16);
+        assert_eq!(
+            layer
+                .indices
+                .chunks_exact(2)
+                .map(|index| u16::from_ne_bytes([index[0], index[1]]))
+                .collect::<Vec<_>>(),
+            vec![1, 3, 0, 2, 3, 1, 5, 7, 4, 6, 7, 5]
+        );
+    }
+
+    #[test]
+    fn large_layer_keeps_32_bit_indices() {
+        let quads = 20000;
+        let mut layer = layer_with_quads(quads);
+        layer.compact_indices(quads as usize * 4);
+
+        assert_eq!(layer.index_format, wgpu::IndexFormat::Uint32);
+        assert_eq!(layer.indices.len(), quads as usize * 6 * 4);
+        assert_eq!(
+            layer.indices[layer.indices.len() - 4..],
+            (quads * 4 - 3).to_ne_bytes()
+        );
+    }
+
+    #[test]
+    fn world_bounds_cover_whole_sections() {
+        assert_eq!(WorldBounds::OVERWORLD.section_range(), -4..20);
+        assert_eq!(WorldBounds::OVERWORLD.height(), 384);
+
+        let tall = WorldBounds {
+            min_y: -200,
+            max_y: 500,
+        };
+        assert_eq!(tall.section_range(), -13..32);
+    }
+
+    #[test]
+    fn sections_outside_the_world_are_trimmed() {
+        let mut storage = SectionStorage::new(1 << 20);
+        storage.set_width(8);
+
+        for y in [-5, -4, 19, 20] {
+            let layer = BakedLayer {
+                vertices: vec![0; 4 * TerrainVertex::VERTEX_LENGTH],
+                ..layer_with_quads(1)
+            };
+            storage.replace(ivec3(0, y, 0), &[layer]);
+        }
+
+        storage.trim(IVec2::ZERO);
+
+        let mut kept = storage.iter().map(|(pos, _)| pos.y).collect::<Vec<_>>();
+        kept.sort();
+        assert_eq!(kept, [-4, 19]);
+
+        storage.set_world_bounds(WorldBounds {
+            min_y: 0,
+            max_y: 256,
+        });
+        storage.trim(IVec2::ZERO);
+
+        assert_eq!(storage.iter().count(), 0);
+    }
+
+    #[test]
+    fn rebaked_sections_are_uploaded_once() {
+        let (dirty, stats) = coalesce_chunk_updates(
+            [
+                (ivec3(0, 0, 0), vec![layer_with_quads(1)]),
+                (ivec3(1, 0, 0), vec![layer_with_quads(1)]),
+                (ivec3(0, 0, 0), vec![layer_with_quads(2)]),
+            ]
+            .into_iter(),
+        );
+
+        assert_eq!(
+            stats,
+            ChunkUpdateStats {
+                sections: 2,
+                superseded: 1,
+                rebaked: 0,
+            }
+        );
+        assert_eq!(dirty[&ivec3(0, 0, 0)][0].indices.len(), 2 * 6 * 4);
+    }
+
+    #[test]
+    fn block_changes_dirty_the_sections_bordering_them() {
+        //Inside a section only that section depends on the block
+        assert_eq!(
+            sections_around_block(ivec3(5, 70, 5)),
+            HashSet::from([ivec3(0, 4, 0)])
+        );
+
+        //On a corner, so do the seven other sections meeting there
+        let corner = sections_around_block(ivec3(0, 64, -1));
+        assert_eq!(corner.len(), 8);
+        assert!(corner.contains(&ivec3(-1, 3, 0)));
+        assert!(corner.contains(&ivec3(0, 4, -1)));
+        assert!(corner.contains(&ivec3(-1, 3, -1)));
+    }
+
+    #[test]
+    fn bake_times_keep_the_most_recent() {
+        let mut times = BakeTimes::default();
+
+        for x in 0..BAKE_TIME_HISTORY as i32 + 10 {
+            times.record(ivec3(x, 0, 0), Duration::from_micros(x as u64 % 100));
+        }
+
+        assert_eq!(times.iter().count(), BAKE_TIME_HISTORY);
+        assert_eq!(times.iter().next().unwrap().pos, ivec3(10, 0, 0));
+        assert_eq!(
+            times.iter().last().unwrap().pos,
+            ivec3(BAKE_TIME_HISTORY as i32 + 9, 0, 0)
+        );
+        assert_eq!(times.slowest().unwrap().duration, Duration::from_micros(99));
+    }
+
+    #[test]
+    fn tint_indices_without_a_section_slot_are_baked() {
+        assert_eq!(section_tint_slot(-1), None);
+        assert_eq!(section_tint_slot(0), Some(1));
+        assert_eq!(section_tint_slot(3), Some(4));
+        assert_eq!(section_tint_slot(4), None);
+    }
+
+    #[test]
+    fn coplanar_overlays_win_for_the_depth_compare() {
+        let faces = [0, 1].map(|tint_index| BlockModelFace {
+            tint_index,
+            ..up_face()
+        });
+        let tint_indices = |order| {
+            in_coplanar_order(&faces, order)
+                .map(|face| face.tint_index)
+                .collect::<Vec<_>>()
+        };
+
+        //The overlay is the later element, and has to be drawn first when the first of two coplanar faces wins
+        let strict = CoplanarOrder::for_depth_compare(wgpu::CompareFunction::Less);
+        assert_eq!(tint_indices(strict), vec![1, 0]);
+
+        let inclusive = CoplanarOrder::for_depth_compare(wgpu::CompareFunction::LessEqual);
+        assert_eq!(tint_indices(inclusive), vec![0, 1]);
+    }
+}
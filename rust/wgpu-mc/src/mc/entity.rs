@@ -14,6 +14,11 @@ use crate::texture::UV;
 use crate::{Display, WmRenderer};
 
 pub type Position = (f32, f32, f32);
+
+const SINGLE_TRANSFORMS_SIZE: wgpu::BufferAddress =
+    MAX_SINGLE_ENTITY_PARTS as wgpu::BufferAddress * PART_TRANSFORM_SIZE;
+/// One mat4x4<f32> per part
+const PART_TRANSFORM_SIZE: wgpu::BufferAddress = 64;
 pub type EntityType = usize;
 
 pub struct EntityManager {
@@ -394,6 +399,9 @@ pub struct Entity {
     pub model_root: EntityPart,
     /// Names of each part referencing an index for applicable transforms
     pub parts: HashMap<String, usize>,
+    /// How many parts the model has, which is what `@pc_parts_per_entity` is set to. This can be more than
+    /// `parts.len()`, since parts with the same name only have one entry there
+    pub part_count: usize,
     pub mesh: Arc<wgpu::Buffer>,
     pub vertex_count: u32,
 }
@@ -424,9 +432,48 @@ fn recurse_get_names(part: &EntityPart, index: &mut usize, names: &mut HashMap<S
         .for_each(|part| recurse_get_names(part, index, names));
 }
 
+/// The order the vertices of a model's triangles are in when viewed from the front. Pipelines expect counter-clockwise
+/// triangles (`FrontFace::Ccw`), but some exporters (e.g. Bedrock geometry) produce clockwise ones
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Winding {
+    #[default]
+    CounterClockwise,
+    Clockwise,
+}
+
+/// Reorder the vertices of a triangle list with the given winding so that it's front faces are counter-clockwise
+pub fn match_winding(vertices: &mut [EntityVertex], winding: Winding) {
+    if winding == Winding::Clockwise {
+        vertices
+            .chunks_exact_mut(3)
+            .for_each(|triangle| triangle.swap(1, 2));
+    }
+}
+
 impl Entity {
     ///Create an entity from an [EntityPart] and upload it's mesh to the GPU
     pub fn new(name: String, root: EntityPart, wgpu_state: &Display) -> Self {
+        Self::new_with_winding(name, root, wgpu_state, Winding::CounterClockwise)
+    }
+
+    ///Same as [Entity::new], but for models whose triangles are wound in the given order
+    pub fn new_with_winding(
+        name: String,
+        root: EntityPart,
+        wgpu_state: &Display,
+        winding: Winding,
+    ) -> Self {
+        Self::new_with_options(name, root, wgpu_state, winding, wgpu::BufferUsages::empty())
+    }
+
+    ///Same as [Entity::new_with_winding], with extra usages for the mesh buffer, see [crate::ExtraBufferUsages]
+    pub fn new_with_options(
+        name: String,
+        root: EntityPart,
+        wgpu_state: &Display,
+        winding: Winding,
+        extra_usages: wgpu::BufferUsages,
+    ) -> Self {
         let mut parts = HashMap::new();
 
         recurse_get_names(&root, &mut 0, &mut parts);
@@ -435,11 +482,12 @@ impl Entity {
 
         let mut part_id = 0;
         recurse_get_mesh(&root, &mut mesh, &mut part_id);
+        match_winding(&mut mesh, winding);
         let buffer = wgpu_state.device.create_buffer(&BufferDescriptor {
             //create buffer init get stuck idk why
             label: None,
             size: (mesh.len() * std::mem::size_of::<EntityVertex>()) as u64,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST | extra_usages,
             mapped_at_creation: false,
         });
         wgpu_state
@@ -449,6 +497,7 @@ impl Entity {
             name,
             model_root: root,
             parts,
+            part_count: part_id as usize,
             mesh: Arc::new(buffer),
             vertex_count: mesh.len() as u32,
         }
@@ -458,6 +507,9 @@ impl Entity {
 #[derive(Clone)]
 pub struct UploadedEntityInstances {
     pub bind_group: Arc<wgpu::BindGroup>,
+    /// Binds the first instance's part transforms as a uniform, for `@geo_entities_single`. None if the entity has
+    /// more than [MAX_SINGLE_ENTITY_PARTS] parts
+    pub single_bind_group: Option<Arc<wgpu::BindGroup>>,
     pub transforms_buffer: Arc<wgpu::Buffer>,
     pub instance_vbo: Arc<wgpu::Buffer>,
     pub len: u32,
@@ -467,13 +519,67 @@ pub struct UploadedEntityInstances {
 #[repr(C)]
 pub struct InstanceVertex {
     pub uv_offset: [u16; 2],
+    /// RGBA overlay color, packed little endian. The RGB is mixed into the entity's texture using the alpha as the blend factor,
+    /// see [InstanceVertex::overlay]
     pub overlay: u32,
+    /// Uniform scale about the entity's origin (the translation of its root part), e.g. 0.5 to draw the baby variant
+    /// of a mob with the adult's mesh
+    pub scale: f32,
+    /// RGBA color the entity's texture is multiplied by, packed little endian like `overlay`. See [InstanceVertex::tint]
+    pub tint: u32,
 }
 
 impl InstanceVertex {
-    const VAA: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
-        4 => Float32x2,
-        5 => Uint32
+    pub const OVERLAY_NONE: u32 = 0;
+    /// Opaque white, leaves the texture untouched
+    pub const TINT_NONE: u32 = u32::MAX;
+    /// The red flash entities get when they are damaged
+    pub const OVERLAY_HURT: u32 = Self::overlay([255, 0, 0], 77);
+    /// The white tint used for frozen entities
+    pub const OVERLAY_FROZEN: u32 = Self::overlay([255, 255, 255], 96);
+
+    /// Pack an overlay color and how strongly it replaces the texture color, where 0 leaves the texture untouched
+    pub const fn overlay(color: [u8; 3], factor: u8) -> u32 {
+        (color[0] as u32)
+            | ((color[1] as u32) << 8)
+            | ((color[2] as u32) << 16)
+            | ((factor as u32) << 24)
+    }
+
+    /// Pack an RGBA tint. The RGB multiplies the texture color before the overlay is mixed in, the alpha is the
+    /// alpha of the output, which only matters for pipelines that blend
+    pub const fn tint(color: [u8; 4]) -> u32 {
+        u32::from_le_bytes(color)
+    }
+
+    /// An instance with the given overlay, drawn at full size and untinted
+    pub const fn with_overlay(overlay: u32) -> Self {
+        Self {
+            uv_offset: [0, 0],
+            overlay,
+            scale: 1.0,
+            tint: Self::TINT_NONE,
+        }
+    }
+
+    /// Convert Minecraft's packed `OverlayTexture` coordinates (u | v << 16) into an overlay color.
+    /// Rows below 8 are the hurt overlay, the rest are the white flash with `u` as it's progress
+    pub fn overlay_from_vanilla(packed: u32) -> u32 {
+        let u = (packed & 0xffff).min(15);
+        let v = packed >> 16;
+
+        if v < 8 {
+            Self::OVERLAY_HURT
+        } else {
+            Self::overlay([255, 255, 255], ((u as f32 / 15.0) * 0.75 * 255.0) as u8)
+        }
+    }
+
+    const VAA: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        4 => Uint16x2,
+        5 => Uint32,
+        6 => Float32,
+        7 => Uint32
     ];
 
     pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
@@ -486,33 +592,103 @@ impl InstanceVertex {
     }
 }
 
+/// How many part transforms the uniform of `@geo_entities_single` pipelines holds
+pub const MAX_SINGLE_ENTITY_PARTS: usize = 64;
+
+/// Whether a [BundledEntityInstances] is drawn instanced by `@geo_entities` pipelines, or on its own by
+/// `@geo_entities_single` pipelines. Without a `@geo_entities_single` pipeline everything is drawn instanced
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum EntityDrawMode {
+    /// Single when the bundle holds exactly one instance
+    #[default]
+    Auto,
+    /// Always instanced, for crowds of mobs
+    Instanced,
+    /// Always single, for entities which are unique per instance like players with their own skin. Only the first
+    /// instance is drawn, so these bundles should hold one instance each
+    Single,
+}
+
 #[derive(Clone)]
 pub struct BundledEntityInstances {
     pub entity: Arc<Entity>,
     pub uploaded: UploadedEntityInstances,
     pub capacity: u32,
+    pub draw_mode: EntityDrawMode,
+    /// Draw with `@geo_entities_ignore_depth` pipelines, which run after the other entity pipelines without testing
+    /// against depth so the entities show through walls, e.g. for spectators. Without such a pipeline this is ignored
+    pub ignore_depth: bool,
+}
+
+/// The size of the part transforms of `instances` instances of an entity with `part_count` parts
+pub fn part_transforms_size(part_count: usize, instances: u32) -> wgpu::BufferAddress {
+    instances as wgpu::BufferAddress * part_count as wgpu::BufferAddress * PART_TRANSFORM_SIZE
 }
 
 impl BundledEntityInstances {
+    /// How many instances of `entity` fit in one bundle, since every instance's part transforms are bound as a single
+    /// storage buffer
+    pub fn max_instances(limits: &wgpu::Limits, entity: &Entity) -> u32 {
+        let max_size = (limits.max_storage_buffer_binding_size as wgpu::BufferAddress)
+            .min(limits.max_buffer_size);
+
+        (max_size / part_transforms_size(entity.part_count.max(1), 1)).min(u32::MAX as u64) as u32
+    }
+
+    /// Panics if `capacity` is more than [BundledEntityInstances::max_instances]
     pub fn new(
         wm: &WmRenderer,
         entity: Arc<Entity>,
         texture_view: &wgpu::TextureView,
         capacity: u32,
     ) -> Self {
+        let max_instances = Self::max_instances(&wm.display.device.limits(), &entity);
+
+        if capacity > max_instances {
+            panic!(
+                "Entity {} has {} parts, so at most {max_instances} instances fit within the storage buffer limit, not {capacity}",
+                entity.name, entity.part_count
+            );
+        }
+
+        //Big enough to bind the uniform of the single path, which always has room for MAX_SINGLE_ENTITY_PARTS
         let transforms_buffer =
             Arc::new(wm.display.device.create_buffer(&wgpu::BufferDescriptor {
                 label: None,
-                size: capacity as wgpu::BufferAddress
-                    * (entity.parts.len() as wgpu::BufferAddress)
-                    * 64,
-                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+                size: part_transforms_size(entity.part_count, capacity).max(SINGLE_TRANSFORMS_SIZE),
+                usage: BufferUsages::STORAGE | BufferUsages::UNIFORM | BufferUsages::COPY_DST,
                 mapped_at_creation: false,
             }));
 
+        let single_bind_group = (entity.part_count <= MAX_SINGLE_ENTITY_PARTS).then(|| {
+            Arc::new(
+                wm.display
+                    .device
+                    .create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: None,
+                        layout: wm.bind_group_layouts.get("entity_single").unwrap(),
+                        entries: &[
+                            wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                                    buffer: &transforms_buffer,
+                                    offset: 0,
+                                    size: wgpu::BufferSize::new(SINGLE_TRANSFORMS_SIZE),
+                                }),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 1,
+                                resource: wgpu::BindingResource::TextureView(texture_view),
+                            },
+                        ],
+                    }),
+            )
+        });
+
         Self {
             entity,
             uploaded: UploadedEntityInstances {
+                single_bind_group,
                 bind_group: Arc::new(wm.display.device.create_bind_group(
                     &wgpu::BindGroupDescriptor {
                         label: None,
@@ -533,15 +709,56 @@ impl BundledEntityInstances {
                 instance_vbo: Arc::new(wm.display.device.create_buffer(&BufferDescriptor {
                     label: None,
                     usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-                    size: 100000,
+                    size: capacity.max(1) as wgpu::BufferAddress
+                        * std::mem::size_of::<InstanceVertex>() as wgpu::BufferAddress,
                     mapped_at_creation: false,
                 })),
                 len: capacity,
             },
             capacity,
+            draw_mode: EntityDrawMode::default(),
+            ignore_depth: false,
         }
     }
 
+    /// Whether the buffers were created with room for `instances` instances. `capacity` is how many are drawn, which
+    /// can be lowered below what the buffers hold to reuse them for fewer instances
+    pub fn has_room_for(&self, instances: u32) -> bool {
+        self.uploaded.transforms_buffer.size()
+            >= part_transforms_size(self.entity.part_count, instances)
+            && self.uploaded.instance_vbo.size()
+                >= instances as wgpu::BufferAddress
+                    * std::mem::size_of::<InstanceVertex>() as wgpu::BufferAddress
+    }
+
+    /// Which entity geometry's pipelines draw this bundle, out of the ones the graph has pipelines for. Bundles which
+    /// [BundledEntityInstances::ignore_depth] are always drawn instanced
+    pub fn geometry(
+        &self,
+        has_single_pipeline: bool,
+        has_ignore_depth_pipeline: bool,
+    ) -> &'static str {
+        if self.ignore_depth && has_ignore_depth_pipeline {
+            "@geo_entities_ignore_depth"
+        } else if self.draws_single() && has_single_pipeline {
+            "@geo_entities_single"
+        } else {
+            "@geo_entities"
+        }
+    }
+
+    /// Whether `@geo_entities_single` pipelines draw this bundle instead of `@geo_entities` pipelines, see
+    /// [EntityDrawMode]
+    pub fn draws_single(&self) -> bool {
+        let single = match self.draw_mode {
+            EntityDrawMode::Auto => self.capacity == 1,
+            EntityDrawMode::Instanced => false,
+            EntityDrawMode::Single => true,
+        };
+
+        single && self.uploaded.single_bind_group.is_some()
+    }
+
     // pub fn upload(&mut self, wm: &WmRenderer, instances: &[EntityInstance]) {
     //     self.count = instances.len() as u32;
     //
@@ -617,12 +834,14 @@ impl EntityInstance {
     }
 }
 
+/// Returns how many of `instance_transforms` the part and its descendants used. They're in the same depth first order
+/// as the part ids of the mesh, so a child's transforms come after every transform of the siblings before it
 fn recurse_transforms(
     mat: Mat4,
     part: &EntityPart,
     vec: &mut Vec<Mat4>,
     instance_transforms: &[Mat4],
-) {
+) -> usize {
     let instance_part_transform = instance_transforms[0];
 
     //mat is a transformation matrix that has been composed recursively from it's parent's and ancestors' transforms
@@ -633,14 +852,139 @@ fn recurse_transforms(
 
     vec.push(new_mat);
 
-    let mut slice = &instance_transforms[1..];
+    let mut used = 1;
+
+    for child in &part.children {
+        if used >= instance_transforms.len() {
+            break;
+        }
 
-    if slice.is_empty() {
-        return;
+        used += recurse_transforms(new_mat, child, vec, &instance_transforms[used..]);
     }
 
-    part.children.iter().for_each(|child| {
-        recurse_transforms(new_mat, child, vec, slice);
-        slice = &slice[1..];
-    });
+    used
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Mat4, Vec3};
+
+    use super::{
+        match_winding, part_transforms_size, recurse_transforms, EntityPart, InstanceVertex,
+        PartTransform, Winding,
+    };
+    use crate::render::entity::EntityVertex;
+
+    fn vertex(position: [f32; 3]) -> EntityVertex {
+        EntityVertex {
+            position,
+            tex_coords: [0, 0],
+            normal: [0.0, 0.0, 1.0],
+            part_id: 0,
+        }
+    }
+
+    #[test]
+    fn clockwise_triangles_face_forward() {
+        //Clockwise when viewed from +Z
+        let mut triangle = [
+            vertex([0.0, 0.0, 0.0]),
+            vertex([0.0, 1.0, 0.0]),
+            vertex([1.0, 0.0, 0.0]),
+        ];
+
+        match_winding(&mut triangle, Winding::Clockwise);
+
+        let [a, b, c] = triangle.map(|vertex| Vec3::from_array(vertex.position));
+        let facing = (b - a).cross(c - a);
+
+        assert!(facing.dot(Vec3::Z) > 0.0);
+    }
+
+    #[test]
+    fn counter_clockwise_triangles_are_unchanged() {
+        let mut triangle = [
+            vertex([0.0, 0.0, 0.0]),
+            vertex([1.0, 0.0, 0.0]),
+            vertex([0.0, 1.0, 0.0]),
+        ];
+
+        match_winding(&mut triangle, Winding::CounterClockwise);
+
+        assert_eq!(triangle[1].position, [1.0, 0.0, 0.0]);
+        assert_eq!(triangle[2].position, [0.0, 1.0, 0.0]);
+    }
+
+    fn part(index: usize, children: Vec<EntityPart>) -> EntityPart {
+        EntityPart {
+            name: format!("bone{index}"),
+            transform: PartTransform::identity(),
+            cuboids: vec![],
+            children,
+        }
+    }
+
+    #[test]
+    fn deep_models_get_a_transform_per_part() {
+        //A root with 9 limbs of 7 bones each, 64 parts in total
+        let mut next = 1;
+        let limbs = (0..9)
+            .map(|_| {
+                let bones: Vec<EntityPart> = (0..7)
+                    .map(|_| {
+                        next += 1;
+                        part(next - 1, vec![])
+                    })
+                    .collect();
+                //Chain the bones of a limb, each one a child of the one before
+                bones
+                    .into_iter()
+                    .rev()
+                    .reduce(|child, mut parent| {
+                        parent.children.push(child);
+                        parent
+                    })
+                    .unwrap()
+            })
+            .collect();
+        let root = part(0, limbs);
+
+        //Each part moves up by its own index, so its matrix is offset by the sum along its chain
+        let transforms: Vec<Mat4> = (0..64)
+            .map(|index| Mat4::from_translation(vec3(0.0, index as f32, 0.0)))
+            .collect();
+
+        let mut matrices = Vec::new();
+        let used = recurse_transforms(Mat4::IDENTITY, &root, &mut matrices, &transforms);
+
+        assert_eq!(used, 64);
+        assert_eq!(matrices.len(), 64);
+
+        //The last bone of the last limb, after the root and 8 limbs of 7 bones
+        let limb_start = 1 + 8 * 7;
+        let expected: usize = (limb_start..limb_start + 7).sum();
+        assert_eq!(matrices[63].w_axis.y, expected as f32);
+
+        assert_eq!(part_transforms_size(64, 4096), 64 * 64 * 4096);
+    }
+
+    #[test]
+    fn instance_attributes_fit_the_stride() {
+        let desc = InstanceVertex::desc();
+
+        assert_eq!(desc.array_stride, 16);
+        for attribute in desc.attributes {
+            assert!(attribute.offset + attribute.format.size() <= desc.array_stride);
+        }
+
+        let baby = InstanceVertex {
+            scale: 0.5,
+            tint: InstanceVertex::tint([255, 128, 0, 255]),
+            ..InstanceVertex::with_overlay(InstanceVertex::OVERLAY_NONE)
+        };
+        assert_eq!(
+            bytemuck::bytes_of(&baby)[8..],
+            [0.5f32.to_le_bytes(), [255, 128, 0, 255]].concat()
+        );
+    }
 }
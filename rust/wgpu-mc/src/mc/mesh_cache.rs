@@ -0,0 +1,213 @@
+//! Baked block meshes which can be saved by the host and loaded on the next start, so that blocks only have to be
+//! baked again when the resource packs change. See [crate::mc::MinecraftState::bake_blocks_cached]
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::mc::block::{ModelLoaders, ModelMesh};
+use crate::mc::resource::ResourcePath;
+use crate::mc::MissingModelPolicy;
+use crate::render::atlas::AtlasSnapshot;
+
+/// Bumped whenever baking or the cache's format changes in a way that makes older caches wrong
+pub const BLOCK_MESH_CACHE_VERSION: u32 = 2;
+
+/// The meshes of every baked [crate::mc::Block::Variants] block, along with the block atlas they were baked against,
+/// since their texture coordinates point into it. Multipart blocks are baked lazily and aren't cached
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BlockMeshCache {
+    /// From [block_mesh_cache_key], the cache is only used if this matches the key passed when baking
+    pub key: u64,
+    /// Every unique mesh, referred to by index from `blocks`
+    pub meshes: Vec<ModelMesh>,
+    /// Block name -> variant id (e.g. `facing=north,lit=true`) -> indices into `meshes`
+    pub blocks: HashMap<String, HashMap<String, Vec<usize>>>,
+    pub atlas: Option<AtlasSnapshot>,
+}
+
+impl BlockMeshCache {
+    pub fn new(key: u64) -> Self {
+        Self {
+            key,
+            ..Default::default()
+        }
+    }
+
+    /// The cached meshes of one variant, None if it isn't cached or refers to a mesh which doesn't exist
+    pub fn variant(&self, block: &str, variant: &str) -> Option<Vec<&ModelMesh>> {
+        self.blocks
+            .get(block)?
+            .get(variant)?
+            .iter()
+            .map(|&index| self.meshes.get(index))
+            .collect()
+    }
+
+    /// Encoded with bincode rather than JSON, the atlas image alone would be several times its size as a JSON array
+    pub fn to_bytes(&self) -> Result<Vec<u8>, anyhow::Error> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, anyhow::Error> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+/// The key a [BlockMeshCache] is valid for. `pack_hashes` are content hashes of the enabled resource packs in load
+/// order, computed by the host since only it knows where they come from. The blocks being baked, the names of the
+/// registered `model_loaders`, the `policy` they're baked with and [BLOCK_MESH_CACHE_VERSION] are included too.
+/// Loaders can't be compared beyond their names, so a host whose loaders bake differently after an update should
+/// include its own version in `pack_hashes`.
+///
+/// Keys are only comparable between builds using the same Rust toolchain, a different one just causes a rebake
+pub fn block_mesh_cache_key<'a>(
+    pack_hashes: impl IntoIterator<Item = impl Hash>,
+    block_states: impl IntoIterator<Item = (impl AsRef<str>, &'a ResourcePath)>,
+    model_loaders: &ModelLoaders,
+    policy: MissingModelPolicy,
+) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    BLOCK_MESH_CACHE_VERSION.hash(&mut hasher);
+    policy.hash(&mut hasher);
+
+    let mut loaders = model_loaders.keys().collect::<Vec<_>>();
+    loaders.sort();
+    loaders.hash(&mut hasher);
+
+    for pack_hash in pack_hashes {
+        pack_hash.hash(&mut hasher);
+    }
+
+    for (block_name, block_state) in block_states {
+        block_name.as_ref().hash(&mut hasher);
+        block_state.hash(&mut hasher);
+    }
+
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use glam::vec3;
+    use guillotiere::{size2, AtlasAllocator};
+
+    use crate::mc::block::{
+        BlockMeshVertex, BlockModelFace, ElementBounds, GuiLight, MeshBakeError, ModelLoaders,
+        ModelMesh,
+    };
+    use crate::mc::chunk::RenderLayer;
+    use crate::mc::resource::{ResourcePath, ResourceProvider};
+    use crate::mc::MissingModelPolicy;
+    use crate::render::atlas::{Atlas, AtlasSnapshot};
+
+    use super::{block_mesh_cache_key, BlockMeshCache};
+
+    fn mesh() -> ModelMesh {
+        let face = BlockModelFace {
+            vertices: [0.0, 1.0, 2.0, 3.0].map(|x| BlockMeshVertex {
+                position: vec3(x, 0.5, 1.0),
+                tex_coords: [x as u16 * 16, 32],
+            }),
+            normal: vec3(0.0, 1.0, 0.0),
+            tint_index: 0,
+            animation_uv_offset: 7,
+        };
+
+        ModelMesh {
+            north: vec![face],
+            south: vec![],
+            west: vec![],
+            east: vec![],
+            up: vec![face, face],
+            down: vec![],
+            any: vec![],
+            cull: 0b101,
+            layer: RenderLayer::Cutout,
+            ambient_occlusion: false,
+            gui_light: GuiLight::Front,
+            bounds: vec![ElementBounds {
+                min: vec3(0.0, 0.0, 0.0),
+                max: vec3(1.0, 0.5, 1.0),
+            }],
+        }
+    }
+
+    #[test]
+    fn round_trip_gives_identical_meshes() {
+        let mut cache = BlockMeshCache::new(42);
+        cache.meshes.push(mesh());
+        cache.blocks.insert(
+            "minecraft:oak_slab".into(),
+            [("type=bottom".to_string(), vec![0])].into(),
+        );
+
+        cache.atlas = Some(AtlasSnapshot {
+            size: 4,
+            allocator: AtlasAllocator::new(size2(4, 4)),
+            sprites: vec![("minecraft:block/stone".into(), ((0, 0), (2, 2)))],
+            image: (0..64).collect(),
+        });
+
+        let loaded = BlockMeshCache::from_bytes(&cache.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(loaded.key, 42);
+        assert_eq!(loaded.meshes, cache.meshes);
+        assert_eq!(loaded.blocks, cache.blocks);
+
+        let (atlas, loaded_atlas) = (cache.atlas.unwrap(), loaded.atlas.unwrap());
+        assert_eq!(loaded_atlas.sprites, atlas.sprites);
+        assert_eq!(loaded_atlas.image, atlas.image);
+        assert_eq!(
+            loaded.variant("minecraft:oak_slab", "type=bottom"),
+            Some(vec![&mesh()])
+        );
+        assert_eq!(loaded.variant("minecraft:oak_slab", "type=top"), None);
+    }
+
+    #[test]
+    fn key_changes_with_the_packs() {
+        let path = ResourcePath("minecraft:blockstates/stone.json".into());
+        let blocks = [("minecraft:stone", &path)];
+
+        let loaders = ModelLoaders::new();
+        let key = |packs: [u64; 2]| {
+            block_mesh_cache_key(packs, blocks, &loaders, MissingModelPolicy::Placeholder)
+        };
+
+        assert_eq!(key([1, 2]), key([1, 2]));
+        assert_ne!(key([1, 2]), key([1, 3]));
+        assert_ne!(key([1, 2]), key([2, 1]));
+    }
+
+    #[test]
+    fn key_changes_with_the_loaders_and_policy() {
+        let path = ResourcePath("minecraft:blockstates/stone.json".into());
+        let blocks = [("minecraft:stone", &path)];
+
+        let mut loaders = ModelLoaders::new();
+        let key =
+            |loaders: &ModelLoaders, policy| block_mesh_cache_key([1u64], blocks, loaders, policy);
+        let placeholder = key(&loaders, MissingModelPolicy::Placeholder);
+
+        assert_ne!(placeholder, key(&loaders, MissingModelPolicy::Skip));
+
+        loaders.insert(
+            "neoforge:obj".into(),
+            Arc::new(
+                |_: &serde_json::Value,
+                 _: &dyn ResourceProvider,
+                 _: &Atlas|
+                 -> Result<ModelMesh, MeshBakeError> {
+                    Err(MeshBakeError::UnresolvedTextureReference("#all".into()))
+                },
+            ),
+        );
+        assert_ne!(placeholder, key(&loaders, MissingModelPolicy::Placeholder));
+    }
+}
@@ -1,31 +1,57 @@
 //! Rust implementations of minecraft concepts that are important to us.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 
 use arc_swap::ArcSwap;
-use chunk::SectionStorage;
-use glam::{ivec2, IVec2};
+use chunk::{
+    sections_around_block, BakeTimes, CoplanarOrder, RenderLayer, SectionBakeTime, SectionStorage,
+    TintMode, MAX_TINTED_SECTIONS, SECTION_TINT_SLOTS,
+};
+use glam::{ivec2, ivec3, DVec3, IVec2, IVec3, Mat4, UVec3, Vec3};
 use indexmap::map::IndexMap;
 use minecraft_assets::schemas;
 use minecraft_assets::schemas::blockstates::multipart::StateValue;
+use minecraft_assets::schemas::blockstates::ModelProperties;
 use parking_lot::{Mutex, RwLock};
+use wgpu::util::DeviceExt;
 
 use crate::mc::entity::{BundledEntityInstances, Entity};
 use crate::mc::resource::ResourceProvider;
-use crate::render::atlas::{Atlas, TextureManager};
+use crate::render::atlas::{Atlas, TextureManager, TintRamp};
+use crate::render::blob_shadow::BlobShadow;
+use crate::render::decal::{Decal, DecalVertex};
+use crate::render::entity_lod::{EntityLod, EntityLodStats};
 use crate::render::pipeline::BLOCK_ATLAS;
+use crate::render::sky::SkyUniform;
+use crate::render::triangle_budget::TriangleBudget;
+use crate::render::underground_culling::UndergroundCulling;
+use crate::render::uv_scroll::{UvScroll, UvScrollUniform, MAX_UV_SCROLLS};
+use crate::render::void_plane::VoidPlane;
+use crate::render::wind::{Sway, Wind};
+use crate::texture::{ColorSpace, ColorSpaceUniform};
 use crate::util::BindableBuffer;
 use crate::{Display, WmRenderer};
 
-use self::block::ModelMesh;
+use self::block::{
+    BlockModelFace, BlockstateKey, ChunkBlockState, MeshBakeError, ModelLoaders, ModelMesh,
+};
+use self::break_particles::{block_break_particles, queue_particles, BlockBreakParticle};
+use self::direction::Direction;
+use self::mesh_cache::BlockMeshCache;
 use self::resource::ResourcePath;
+use self::voxels::SectionVoxels;
 
 pub mod block;
+pub mod break_particles;
 pub mod chunk;
 pub mod direction;
 pub mod entity;
+pub mod mesh_cache;
 pub mod resource;
+pub mod structure;
+pub mod voxels;
 /// Take in a block name (not a [ResourcePath]!) and optionally a variant state key, e.g. "facing=north" and format it some way
 /// for example, `minecraft:anvil[facing=north]` or `Block{minecraft:anvil}[facing=north]`
 pub type BlockVariantFormatter = dyn Fn(&str, Option<&str>) -> String;
@@ -34,6 +60,197 @@ pub struct BlockManager {
     /// This maps block state keys to either a [VariantMesh] or a [Multipart] struct. How the keys are formatted
     /// is defined by the user of wgpu-mc. For example `Block{minecraft:anvil}[facing=west]` or `minecraft:anvil#facing=west`
     pub blocks: IndexMap<String, Block>,
+    /// Render layers explicitly provided by the user of wgpu-mc, which take priority over [ModelMesh::layer]
+    pub render_layers: HashMap<BlockstateKey, RenderLayer>,
+    /// Every unique [ModelMesh] of the last bake, bucketed by [ModelMesh::structural_hash]. Cleared when baking starts,
+    /// so meshes of blocks which were baked again aren't kept alive
+    pub interned_meshes: HashMap<u64, Vec<Arc<ModelMesh>>>,
+    /// Indices into [BlockManager::blocks] of blocks which have no geometry at all, like `minecraft:cave_air`.
+    /// These are treated like [ChunkBlockState::Air] when baking terrain
+    pub renders_nothing: HashSet<u16>,
+    /// Registered with [BlockManager::add_uv_scroll], the channel of each is its index + 1
+    pub uv_scrolls: Vec<UvScroll>,
+    /// The channels of the scrolls each block state's faces are matched against when baking terrain
+    pub uv_scroll_channels: HashMap<BlockstateKey, Vec<u8>>,
+    /// Indices into [BlockManager::blocks] of blocks whose top and bottom faces are randomly rotated by position,
+    /// see [BlockManager::rotates_randomly]
+    pub random_rotation: HashSet<u16>,
+    /// Randomly rotate the top and bottom faces of every full cube, not just the blocks in
+    /// [BlockManager::random_rotation]
+    pub random_rotation_full_cubes: bool,
+    /// Block states whose vertices sway in the wind, see [BlockManager::set_sway]
+    pub sways: HashMap<BlockstateKey, Sway>,
+    /// The light level block states emit, see [BlockManager::set_light_emission]
+    pub light_emission: HashMap<BlockstateKey, u8>,
+    /// The color space biome colors are baked into vertices in, and uploaded with [Scene::set_section_tints] in.
+    /// [ColorSpace::Srgb], the default, keeps vanilla's colors as they are. Terrain shaders get it from
+    /// `@buffer_color_spaces` and combine the colors with the block atlas the way vanilla does either way, so
+    /// [ColorSpace::Linear] only matters to shaders lighting in linear space. Sections have to be rebaked after
+    /// changing it
+    pub vertex_color_space: ColorSpace,
+    /// The order coplanar faces of a block are emitted in. Sections have to be rebaked after changing it
+    pub coplanar_order: CoplanarOrder,
+}
+
+impl BlockManager {
+    /// The mesh of a block state, None if its block or variant hasn't been baked yet, e.g. while blocks are still
+    /// loading. Multipart variants are baked lazily, so they're None until first requested by name
+    pub fn get_model(&self, key: BlockstateKey) -> Option<Arc<ModelMesh>> {
+        self.blocks
+            .get_index(key.block as usize)?
+            .1
+            .get_model(key.augment, 0)
+    }
+
+    /// Whether [BlockManager::get_model] has a mesh for this block state. Terrain baking treats block states which
+    /// aren't baked like air
+    pub fn is_baked(&self, key: BlockstateKey) -> bool {
+        self.get_model(key).is_some()
+    }
+
+    /// Override which [RenderLayer] faces of this block state get baked into.
+    /// Block states without an explicit layer use the layer of their [ModelMesh], which is [RenderLayer::Solid]
+    pub fn set_render_layer(&mut self, key: BlockstateKey, layer: RenderLayer) {
+        self.render_layers.insert(key, layer);
+    }
+
+    /// Make the vertices of this block state sway in [Scene::wind], or stop them with None. Register both halves of
+    /// two block tall plants, e.g. with [Sway::LOWER_HALF] and [Sway::UPPER_HALF], so they stay joined. Sections have
+    /// to be rebaked for this to apply
+    pub fn set_sway(&mut self, key: BlockstateKey, sway: Option<Sway>) {
+        match sway {
+            Some(sway) => self.sways.insert(key, sway),
+            None => self.sways.remove(&key),
+        };
+    }
+
+    /// Register the block light level this block state emits, from 0 to 15 like vanilla's `luminance`, e.g. 14 for
+    /// torches and 15 for glowstone. Faces of emitting blocks are baked with at least that much block light, so they
+    /// stay bright however dark their surroundings are. The host still propagates light to other blocks. Sections have
+    /// to be rebaked for this to apply
+    pub fn set_light_emission(&mut self, key: BlockstateKey, level: u8) {
+        match level.min(15) {
+            0 => self.light_emission.remove(&key),
+            level => self.light_emission.insert(key, level),
+        };
+    }
+
+    /// The block light level this block state emits, 0 unless registered with [BlockManager::set_light_emission]
+    pub fn light_emission(&self, key: BlockstateKey) -> u8 {
+        self.light_emission.get(&key).copied().unwrap_or(0)
+    }
+
+    /// Blocks are flagged automatically when every variant bakes to an empty mesh, this allows overriding that
+    pub fn set_renders_nothing(&mut self, block: u16, renders_nothing: bool) {
+        if renders_nothing {
+            self.renders_nothing.insert(block);
+        } else {
+            self.renders_nothing.remove(&block);
+        }
+    }
+
+    /// Opt a block in or out of random top and bottom texture rotation, like vanilla does for e.g. sand to break up
+    /// tiling. The rotation is applied to the face's UVs, so faces which don't use a square part of their texture
+    /// get stretched
+    pub fn set_random_rotation(&mut self, block: u16, random_rotation: bool) {
+        if random_rotation {
+            self.random_rotation.insert(block);
+        } else {
+            self.random_rotation.remove(&block);
+        }
+    }
+
+    /// Whether the faces of this block state's `mesh` facing `dir` are rotated by [chunk::random_uv_rotation]
+    pub fn rotates_randomly(&self, key: BlockstateKey, mesh: &ModelMesh, dir: Direction) -> bool {
+        matches!(dir, Direction::Up | Direction::Down)
+            && (self.random_rotation.contains(&key.block)
+                || (self.random_rotation_full_cubes && mesh.is_full_cube()))
+    }
+
+    /// Whether this state is air, or a block which doesn't render anything and so shouldn't occlude its neighbours
+    pub fn is_air_like(&self, state: ChunkBlockState) -> bool {
+        match state {
+            ChunkBlockState::Air => true,
+            ChunkBlockState::State(key) => self.renders_nothing.contains(&key.block),
+        }
+    }
+
+    pub fn get_render_layer(&self, key: BlockstateKey, mesh: &ModelMesh) -> RenderLayer {
+        self.render_layers.get(&key).copied().unwrap_or(mesh.layer)
+    }
+
+    /// Scroll the faces of this block state which are textured with `scroll.sprite`, see [WmRenderer::update_uv_scrolls].
+    /// Identical scrolls share a channel. Sections have to be rebaked for this to apply.
+    /// Returns the channel, or None if [MAX_UV_SCROLLS] different scrolls are already registered
+    pub fn add_uv_scroll(&mut self, key: BlockstateKey, scroll: UvScroll) -> Option<u8> {
+        let index = match self
+            .uv_scrolls
+            .iter()
+            .position(|existing| *existing == scroll)
+        {
+            Some(index) => index,
+            None if self.uv_scrolls.len() < MAX_UV_SCROLLS => {
+                self.uv_scrolls.push(scroll);
+                self.uv_scrolls.len() - 1
+            }
+            None => return None,
+        };
+        let channel = index as u8 + 1;
+
+        let channels = self.uv_scroll_channels.entry(key).or_default();
+        if !channels.contains(&channel) {
+            channels.push(channel);
+        }
+
+        Some(channel)
+    }
+
+    /// The scroll channel a face of this block state is baked with, 0 if it doesn't scroll
+    pub fn uv_scroll_channel(&self, key: BlockstateKey, face: &BlockModelFace) -> u8 {
+        let Some(channels) = self.uv_scroll_channels.get(&key) else {
+            return 0;
+        };
+
+        channels
+            .iter()
+            .copied()
+            .find(|channel| {
+                let scroll = &self.uv_scrolls[*channel as usize - 1];
+                face.vertices
+                    .iter()
+                    .all(|vertex| scroll.contains(vertex.tex_coords))
+            })
+            .unwrap_or(0)
+    }
+
+    /// Returns a previously baked mesh identical to this one if there is one, so that block states with the same geometry
+    /// and textures (e.g. rotations of symmetric blocks) share a single [ModelMesh]
+    pub fn intern_mesh(&mut self, mesh: ModelMesh) -> Arc<ModelMesh> {
+        let bucket = self
+            .interned_meshes
+            .entry(mesh.structural_hash())
+            .or_default();
+
+        match bucket.iter().find(|interned| ***interned == mesh) {
+            Some(interned) => interned.clone(),
+            None => {
+                let mesh = Arc::new(mesh);
+                bucket.push(mesh.clone());
+                mesh
+            }
+        }
+    }
+
+    /// How many bytes [BlockManager::intern_mesh] saves over each user of an interned mesh having its own copy, see
+    /// [ModelMesh::size_in_bytes]. Block states referring to the same mesh count as separate users
+    pub fn interned_bytes_saved(&self) -> usize {
+        self.interned_meshes
+            .values()
+            .flatten()
+            //The intern table holds one of the references, and the first user would need a copy anyway
+            .map(|mesh| Arc::strong_count(mesh).saturating_sub(2) * mesh.size_in_bytes())
+            .sum()
+    }
 }
 
 #[derive(Debug)]
@@ -56,6 +273,7 @@ impl Block {
         key: impl IntoIterator<Item = (&'a str, &'a StateValue)> + Clone,
         resource_provider: &dyn ResourceProvider,
         block_atlas: &Atlas,
+        model_loaders: &ModelLoaders,
         //TODO use this
         _seed: u8,
     ) -> Option<(Arc<ModelMesh>, u16)> {
@@ -90,7 +308,8 @@ impl Block {
                     }
                 }
 
-                let mesh = multipart.generate_mesh(key, resource_provider, block_atlas);
+                let mesh =
+                    multipart.generate_mesh(key, resource_provider, block_atlas, model_loaders);
 
                 let mut multipart_write = multipart.keys.write();
                 multipart_write.insert(key_string, mesh.clone());
@@ -118,6 +337,316 @@ impl Block {
     }
 }
 
+/// How much geometry a block's models produce, for finding blocks with expensive meshes
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MeshStats {
+    /// How many models were baked for the block. Always 0 for multipart blocks, which are baked lazily as
+    /// combinations of their parts are first needed
+    pub models: usize,
+    /// Totals across all of the block's models
+    pub vertices: usize,
+    pub triangles: usize,
+    /// The triangles of the block's most expensive model, which is the most a single block in the world can cost
+    pub max_triangles: usize,
+}
+
+impl MeshStats {
+    pub fn of(block: &Block) -> Self {
+        let Block::Variants(variants) = block else {
+            return Self::default();
+        };
+
+        variants
+            .values()
+            .flatten()
+            .fold(Self::default(), |stats, mesh| {
+                let faces = mesh.face_count();
+
+                Self {
+                    models: stats.models + 1,
+                    vertices: stats.vertices + faces * 4,
+                    triangles: stats.triangles + faces * 2,
+                    max_triangles: stats.max_triangles.max(faces * 2),
+                }
+            })
+    }
+}
+
+/// What [MinecraftState::bake_blocks] does with a block whose blockstate or models are missing or malformed
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MissingModelPolicy {
+    /// Leave the block out of the [BlockManager]
+    Skip,
+    /// Bake the block, or just its broken models, as [block::MISSING_MODEL]
+    #[default]
+    Placeholder,
+    /// Stop baking and return the error
+    Error,
+}
+
+impl MissingModelPolicy {
+    /// Returns whether the block `error` happened for should be baked as the missing model, or the error if baking
+    /// should stop
+    fn recover(self, error: BlockBakeError) -> Result<bool, BlockBakeError> {
+        match self {
+            MissingModelPolicy::Skip => {
+                log::warn!("Skipping block: {error:?}");
+                Ok(false)
+            }
+            MissingModelPolicy::Placeholder => {
+                log::warn!("Using the missing model: {error:?}");
+                Ok(true)
+            }
+            MissingModelPolicy::Error => Err(error),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BlockBakeError {
+    MissingBlockstate {
+        block: String,
+        path: ResourcePath,
+    },
+    MalformedBlockstate {
+        block: String,
+        error: serde_json::Error,
+    },
+    Mesh {
+        block: String,
+        error: MeshBakeError,
+    },
+}
+
+fn read_blockstates(
+    resource_provider: &dyn ResourceProvider,
+    block_name: &str,
+    path: &ResourcePath,
+) -> Result<schemas::BlockStates, BlockBakeError> {
+    let json =
+        resource_provider
+            .get_string(path)
+            .ok_or_else(|| BlockBakeError::MissingBlockstate {
+                block: block_name.into(),
+                path: path.clone(),
+            })?;
+
+    serde_json::from_str(&json).map_err(|error| BlockBakeError::MalformedBlockstate {
+        block: block_name.into(),
+        error,
+    })
+}
+
+/// The part of [MinecraftState::bake_blocks] which doesn't need the GPU. Reads every block's blockstates from
+/// `resource_provider` and bakes their models with `bake_model`, using `bake_missing` for the placeholder of
+/// [MissingModelPolicy::Placeholder]. Returns whether baking finished and how many meshes were baked rather than taken
+/// from `cache`
+#[allow(clippy::too_many_arguments)]
+fn bake_block_states<'a>(
+    block_manager: &mut BlockManager,
+    resource_provider: &dyn ResourceProvider,
+    block_states: impl IntoIterator<Item = (impl AsRef<str>, &'a ResourcePath)>,
+    policy: MissingModelPolicy,
+    bake_model: &dyn Fn(&ModelProperties) -> Result<ModelMesh, MeshBakeError>,
+    bake_missing: &dyn Fn() -> ModelMesh,
+    progress: Option<&dyn Fn(usize, usize)>,
+    cancel: Option<&AtomicBool>,
+    mut stats: Option<&mut HashMap<String, MeshStats>>,
+    cache: Option<&mut BlockMeshCache>,
+) -> Result<(bool, usize), BlockBakeError> {
+    let block_states = block_states.into_iter().collect::<Vec<_>>();
+    let total = block_states.len();
+    let mut completed = true;
+    let mut baked_meshes = 0;
+    let mut error = None;
+
+    //Meshes are only shared within a bake, so meshes of blocks which were baked again can be freed
+    block_manager.interned_meshes.clear();
+
+    let missing_mesh = (policy == MissingModelPolicy::Placeholder)
+        .then(|| block_manager.intern_mesh(bake_missing()));
+
+    //What goes into the cache once baking is done, meshes are deduplicated by their interned Arc
+    let mut cached_meshes = Vec::new();
+    let mut cached_blocks = HashMap::new();
+    let mut cached_indices = HashMap::new();
+
+    //Figure out which block models there are
+    for (index, (block_name, block_state)) in block_states.into_iter().enumerate() {
+        if cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+            completed = false;
+            break;
+        }
+
+        let blockstates = read_blockstates(resource_provider, block_name.as_ref(), block_state);
+
+        let block = match blockstates {
+            Ok(schemas::BlockStates::Variants { variants }) => {
+                let mut cached_variants = HashMap::new();
+
+                let meshes: Result<
+                    IndexMap<Vec<(String, StateValue)>, Vec<Arc<ModelMesh>>>,
+                    BlockBakeError,
+                > = variants
+                    .iter()
+                    .map(|(variant_id, variant)| {
+                        let key_iter = if !variant_id.is_empty() {
+                            variant_id
+                                .split(',')
+                                .filter_map(|kv_pair| {
+                                    let mut split = kv_pair.split('=');
+                                    if kv_pair.is_empty() {
+                                        return None;
+                                    }
+
+                                    Some((
+                                        split.next().unwrap().to_string(),
+                                        match split.next().unwrap() {
+                                            "true" => StateValue::Bool(true),
+                                            "false" => StateValue::Bool(false),
+                                            other => StateValue::String(other.into()),
+                                        },
+                                    ))
+                                })
+                                .collect::<Vec<_>>()
+                        } else {
+                            vec![]
+                        };
+
+                        let cached = cache
+                            .as_deref()
+                            .and_then(|cache| cache.variant(block_name.as_ref(), variant_id));
+
+                        let models = match cached {
+                            Some(cached) => cached
+                                .into_iter()
+                                .map(|mesh| block_manager.intern_mesh(mesh.clone()))
+                                .collect::<Vec<Arc<ModelMesh>>>(),
+                            None => variant
+                                .models()
+                                .iter()
+                                .map(|variation| {
+                                    baked_meshes += 1;
+
+                                    match bake_model(variation) {
+                                        Ok(mesh) => Ok(block_manager.intern_mesh(mesh)),
+                                        //Only the broken model is replaced, the block's other states still render
+                                        Err(error) => match &missing_mesh {
+                                            Some(missing_mesh) => {
+                                                log::warn!(
+                                                    "Using the missing model for {} {variant_id}: {error:?}",
+                                                    block_name.as_ref()
+                                                );
+                                                Ok(missing_mesh.clone())
+                                            }
+                                            None => Err(BlockBakeError::Mesh {
+                                                block: block_name.as_ref().into(),
+                                                error,
+                                            }),
+                                        },
+                                    }
+                                })
+                                .collect::<Result<Vec<Arc<ModelMesh>>, BlockBakeError>>()?,
+                        };
+
+                        if cache.is_some() {
+                            let indices = models
+                                .iter()
+                                .map(|mesh| {
+                                    *cached_indices.entry(Arc::as_ptr(mesh)).or_insert_with(
+                                        || {
+                                            cached_meshes.push(ModelMesh::clone(mesh));
+                                            cached_meshes.len() - 1
+                                        },
+                                    )
+                                })
+                                .collect::<Vec<_>>();
+
+                            cached_variants.insert(variant_id.clone(), indices);
+                        }
+
+                        Ok((key_iter, models))
+                    })
+                    .collect();
+
+                meshes.map(|meshes| {
+                    if cache.is_some() {
+                        cached_blocks.insert(String::from(block_name.as_ref()), cached_variants);
+                    }
+
+                    Block::Variants(meshes)
+                })
+            }
+            Ok(schemas::BlockStates::Multipart { cases }) => Ok(Block::Multipart(Multipart {
+                cases,
+                keys: RwLock::new(IndexMap::new()),
+            })),
+            Err(error) => Err(error),
+        };
+
+        let block = match block.map_err(|error| policy.recover(error)) {
+            Ok(block) => block,
+            //Every state of the block uses the missing model, since an empty key matches any state
+            Err(Ok(true)) => Block::Variants(IndexMap::from([(
+                vec![],
+                vec![missing_mesh.clone().unwrap()],
+            )])),
+            Err(Ok(false)) => {
+                if let Some(progress) = progress {
+                    progress(index + 1, total);
+                }
+                continue;
+            }
+            Err(Err(stop)) => {
+                error = Some(stop);
+                break;
+            }
+        };
+
+        //Multipart meshes are generated lazily, so they're assumed to render something
+        let renders_nothing = match &block {
+            Block::Variants(variants) => variants.values().flatten().all(|mesh| mesh.is_empty()),
+            Block::Multipart(_) => false,
+        };
+
+        if let Some(stats) = &mut stats {
+            stats.insert(String::from(block_name.as_ref()), MeshStats::of(&block));
+        }
+
+        let (block_index, _) = block_manager
+            .blocks
+            .insert_full(String::from(block_name.as_ref()), block);
+        block_manager.set_renders_nothing(block_index as u16, renders_nothing);
+
+        if let Some(progress) = progress {
+            progress(index + 1, total);
+        }
+    }
+
+    drop(missing_mesh);
+
+    log::debug!(
+        "Baked {} block meshes, {} unique, sharing them saved {} bytes",
+        baked_meshes,
+        block_manager
+            .interned_meshes
+            .values()
+            .map(Vec::len)
+            .sum::<usize>(),
+        block_manager.interned_bytes_saved()
+    );
+
+    if let Some(cache) = cache {
+        cache.meshes = cached_meshes;
+        cache.blocks = cached_blocks;
+    }
+
+    match error {
+        Some(error) => Err(error),
+        None => Ok((completed, baked_meshes)),
+    }
+}
+
 #[derive(Debug)]
 pub struct Multipart {
     pub cases: Vec<schemas::blockstates::multipart::Case>,
@@ -131,6 +660,7 @@ impl Multipart {
             + Clone,
         resource_provider: &dyn ResourceProvider,
         block_atlas: &Atlas,
+        model_loaders: &ModelLoaders,
     ) -> Arc<ModelMesh> {
         let apply_variants = self.cases.iter().filter_map(|case| {
             if case.applies(key.clone()) {
@@ -144,6 +674,7 @@ impl Multipart {
             apply_variants.into_iter().flatten(),
             resource_provider,
             block_atlas,
+            model_loaders,
         )
         .unwrap();
 
@@ -171,63 +702,226 @@ pub struct SkyState {
     pub moon_phase: i32,
 }
 
-#[derive(Default, Clone)]
+impl SkyState {
+    /// The direction towards the sun, from [SkyState::angle] which is the fraction of the day (0 is noon)
+    pub fn sun_direction(&self) -> Vec3 {
+        let angle = self.angle * std::f32::consts::TAU;
+
+        Vec3::new(-angle.sin(), angle.cos(), 0.0)
+    }
+}
+
+#[derive(Default, Clone, Debug, PartialEq)]
 pub struct RenderEffectsData {
     pub fog_start: f32,
     pub fog_end: f32,
+    /// [FOG_SHAPE_SPHERE] or [FOG_SHAPE_CYLINDER]
     pub fog_shape: f32,
     pub fog_color: [f32; 4],
     pub color_modulator: [f32; 4],
     pub dimension_fog_color: [f32; 4],
 }
 
+/// Fog by distance from the camera, vanilla's `FogShape.SPHERE`
+pub const FOG_SHAPE_SPHERE: f32 = 0.0;
+/// Fog by horizontal distance from the camera, or vertical distance if that's further, vanilla's `FogShape.CYLINDER`
+pub const FOG_SHAPE_CYLINDER: f32 = 1.0;
+
+/// Which of vanilla's fogs [RenderEffectsData::from_render_distance] computes
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum FogMode {
+    /// The fog hiding the edge of the loaded terrain
+    #[default]
+    Terrain,
+    /// The much closer fog of dimensions like the nether
+    Thick,
+}
+
+impl RenderEffectsData {
+    /// Fog distances matching vanilla's at a render distance of `chunks`, with every color left at its default
+    pub fn from_render_distance(chunks: u32, mode: FogMode) -> Self {
+        let distance = chunks as f32 * 16.0;
+
+        let (fog_start, fog_end, fog_shape) = match mode {
+            FogMode::Terrain => (
+                distance - (distance / 10.0).clamp(4.0, 64.0),
+                distance,
+                FOG_SHAPE_CYLINDER,
+            ),
+            FogMode::Thick => (distance * 0.05, distance.min(192.0) * 0.5, FOG_SHAPE_SPHERE),
+        };
+
+        Self {
+            fog_start,
+            fog_end,
+            fog_shape,
+            ..Default::default()
+        }
+    }
+
+    /// Bound as `@pc_fog`. Shaders should skip fog when the end isn't past the start, which is the case by default
+    pub fn fog_params(&self) -> [f32; 4] {
+        [self.fog_start, self.fog_end, self.fog_shape, 0.0]
+    }
+
+    /// Bound as `@pc_environment_data` for the sky pipelines: the fog start, end and shape, the fog color, the color
+    /// modulator without its alpha and the dimension fog color
+    pub fn environment_data(&self) -> [f32; 14] {
+        let [r, g, b, _] = self.color_modulator;
+
+        let mut data = [0.0; 14];
+        data[..3].copy_from_slice(&[self.fog_start, self.fog_end, self.fog_shape]);
+        data[3..7].copy_from_slice(&self.fog_color);
+        data[7..10].copy_from_slice(&[r, g, b]);
+        data[10..].copy_from_slice(&self.dimension_fog_color);
+        data
+    }
+}
+
+/// See [Scene::section_visibility]
+pub type SectionVisibility = dyn Fn(IVec3) -> bool + Send + Sync;
+
 pub struct Scene {
     pub section_storage: RwLock<SectionStorage>,
+    /// Sections, in sections, whose blocks changed since they were last baked, see [Scene::mark_block_dirty] and
+    /// [WmRenderer::flush_dirty]
+    pub dirty_sections: Mutex<HashSet<IVec3>>,
     pub camera_section_pos: RwLock<IVec2>,
+    /// The camera's world position, used to sort translucent terrain back to front
+    pub camera_position: RwLock<DVec3>,
+    /// The camera's pitch in degrees, positive when looking down, used by [Scene::underground_culling]
+    pub camera_pitch: RwLock<f32>,
+    /// How far, in blocks, each section's bounding box is expanded before frustum culling. This keeps geometry
+    /// which extends past its section (offset plants, fluids) from popping at the edges of the screen
+    pub frustum_culling_margin: RwLock<f32>,
+    /// How many seconds newly loaded sections take to fade in. 0, the default, disables fading
+    pub section_fade_duration: RwLock<f32>,
+    /// The terrain layers which are drawn, e.g. only [RenderLayer::Solid] for a minimap or everything but
+    /// [RenderLayer::Transparent] for a reflection pass. All layers are enabled by default
+    pub enabled_render_layers: RwLock<HashSet<RenderLayer>>,
+    /// Skips the farthest terrain sections when drawing all of them would exceed the budget. None draws every visible
+    /// section, which is the default
+    pub triangle_budget: Mutex<Option<TriangleBudget>>,
+    /// Skips deep underground sections before frustum culling while the camera is on the surface. None draws every
+    /// section within the frustum, which is the default
+    pub underground_culling: Mutex<Option<UndergroundCulling>>,
+    /// Culls sections on top of frustum culling, e.g. with occlusion data from the server. It's called once per frame
+    /// for every section within the frustum, with the section's position in sections, and should be cheap. None
+    /// draws every section within the frustum
+    pub section_visibility: RwLock<Option<Box<SectionVisibility>>>,
     pub chunk_buffer: Arc<BindableBuffer>,
+    /// The blocks of every baked section for ray tracing passes, None unless [Scene::enable_voxels] was called
+    pub voxels: RwLock<Option<SectionVoxels>>,
+    /// Biome colors for sections baked with [TintMode::PerSection], [SECTION_TINT_SLOTS] colors per section
+    pub section_tint_buffer: Arc<BindableBuffer>,
 
     pub indirect_buffer: Arc<wgpu::Buffer>,
 
     pub entity_instances: Mutex<HashMap<String, BundledEntityInstances>>,
-    pub sky_state: SkyState,
+    /// Distances beyond which entities are culled or drawn with a reduced model. Hosts apply it when uploading
+    /// entity instances, see [EntityLod::partition]
+    pub entity_lod: RwLock<EntityLod>,
+    /// What [Scene::entity_lod] did to each entity type in its last upload, keyed by entity name
+    pub entity_lod_stats: Mutex<HashMap<String, EntityLodStats>>,
+    pub decals: RwLock<HashMap<u64, Decal>>,
+    /// Queued by [Scene::spawn_block_break_particles] until the host's particle renderer takes them, at most
+    /// [break_particles::MAX_QUEUED_PARTICLES]
+    pub block_break_particles: Mutex<Vec<BlockBreakParticle>>,
+    next_decal_id: AtomicU64,
+    /// Entity shadows drawn by pipelines using `@geo_blob_shadows`, keyed by an id chosen by the caller such as the
+    /// entity's network id
+    pub blob_shadows: RwLock<HashMap<u64, BlobShadow>>,
+    /// Multiplies the opacity of every blob shadow. Vanilla shadows are at most half opaque
+    pub blob_shadow_strength: RwLock<f32>,
+    /// Drawn by pipelines using `@geo_void_plane`, which should come after terrain and use `@texture_depth`
+    pub void_plane: RwLock<VoidPlane>,
+    /// Written to `@buffer_sky` at the start of every render, see [crate::render::sky::SkyUniform]
+    pub sky_state: RwLock<SkyState>,
 
     pub stars_index_buffer: Option<wgpu::Buffer>,
     pub stars_vertex_buffer: Option<wgpu::Buffer>,
     pub stars_length: u32,
-    pub render_effects: RenderEffectsData,
+    /// Fog and color effects, bound as `@pc_fog` and `@pc_fog_color`
+    pub render_effects: RwLock<RenderEffectsData>,
+    /// When set, [Scene::set_render_distance] recomputes the fog distances in [Scene::render_effects]. None leaves them
+    /// to the host, which is the default
+    pub fog_mode: RwLock<Option<FogMode>>,
+    /// Bound as `@pc_wind`, moves the block states registered with [BlockManager::set_sway]
+    pub wind: RwLock<Wind>,
 
     pub depth_texture: RwLock<wgpu::Texture>,
 }
 
 impl Scene {
     pub fn new(wm: &WmRenderer, framebuffer_size: wgpu::Extent3d) -> Self {
+        Self::with_chunk_buffer_size(wm, framebuffer_size, 100000000)
+    }
+
+    /// A scene whose chunk buffer holds `buffer_size` bytes of terrain, for scenes much smaller than a loaded world
+    /// such as [structure::StructureScene]
+    pub fn with_chunk_buffer_size(
+        wm: &WmRenderer,
+        framebuffer_size: wgpu::Extent3d,
+        buffer_size: u64,
+    ) -> Self {
         let indirect_buffer = wm.display.device.create_buffer(&wgpu::BufferDescriptor {
             label: None,
             size: 4 * 5 * 10000,
             usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::INDIRECT,
             mapped_at_creation: false,
         });
-        let buffer_size = 100000000u64;
+
+        //Slot 0 is used by sections without uploaded tints and stays white
+        let mut section_tints = vec![0u8; MAX_TINTED_SECTIONS as usize * SECTION_TINT_SLOTS * 4];
+        section_tints[..SECTION_TINT_SLOTS * 4].fill(0xff);
+
         Self {
             section_storage: RwLock::new(SectionStorage::new((buffer_size / 4) as u32)),
+            dirty_sections: Mutex::new(HashSet::new()),
             camera_section_pos: RwLock::new(ivec2(0, 0)),
+            camera_position: RwLock::new(DVec3::ZERO),
+            camera_pitch: RwLock::new(0.0),
+            frustum_culling_margin: RwLock::new(1.0),
+            section_fade_duration: RwLock::new(0.0),
+            enabled_render_layers: RwLock::new(HashSet::from(RenderLayer::ALL)),
+            triangle_budget: Mutex::new(None),
+            underground_culling: Mutex::new(None),
+            section_visibility: RwLock::new(None),
+            voxels: RwLock::new(None),
             chunk_buffer: Arc::new(BindableBuffer::new_deferred(
                 wm,
                 buffer_size,
                 wgpu::BufferUsages::COPY_DST
                     | wgpu::BufferUsages::VERTEX
                     | wgpu::BufferUsages::STORAGE
-                    | wgpu::BufferUsages::INDEX,
+                    | wgpu::BufferUsages::INDEX
+                    | wm.extra_buffer_usages.read().chunks,
+                "ssbo",
+            )),
+            section_tint_buffer: Arc::new(BindableBuffer::new(
+                wm,
+                &section_tints,
+                wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::STORAGE,
                 "ssbo",
             )),
             indirect_buffer: Arc::new(indirect_buffer),
 
             entity_instances: Default::default(),
-            sky_state: Default::default(),
+            entity_lod: Default::default(),
+            entity_lod_stats: Default::default(),
+            decals: Default::default(),
+            block_break_particles: Mutex::new(Vec::new()),
+            next_decal_id: AtomicU64::new(0),
+            blob_shadows: Default::default(),
+            blob_shadow_strength: RwLock::new(0.5),
+            void_plane: Default::default(),
+            sky_state: RwLock::new(SkyState::default()),
             stars_index_buffer: None,
             stars_vertex_buffer: None,
             stars_length: 0,
             render_effects: Default::default(),
+            fog_mode: RwLock::new(None),
+            wind: RwLock::new(Wind::default()),
             depth_texture: wm
                 .display
                 .device
@@ -238,13 +932,211 @@ impl Scene {
                     sample_count: 1,
                     dimension: wgpu::TextureDimension::D2,
                     format: wgpu::TextureFormat::Depth32Float,
-                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
                     view_formats: &[],
                 })
                 .into(),
         }
     }
 
+    /// The world position, in blocks, that terrain and the world geometry wgpu-mc draws itself (decals, block entities,
+    /// lines, the void plane) are rendered relative to. This is the corner of the section column the camera is in, so
+    /// rendered positions stay small and keep their precision far from the world origin.
+    ///
+    /// Entities and the GL path are drawn with transforms the host computes, which Minecraft already makes relative to
+    /// the camera, and the sky is only rotated, so none of them use this origin. Geometry the host supplies in world
+    /// space, like [Scene::block_break_particles], should go through [Scene::to_render_space]
+    pub fn render_origin(&self) -> IVec3 {
+        let pos = *self.camera_section_pos.read();
+        ivec3(pos.x << 4, 0, pos.y << 4)
+    }
+
+    /// Converts a world position to render space. The subtraction is done in f64 so that geometry supplied by the host
+    /// lines up with terrain even at very large coordinates
+    pub fn to_render_space(&self, world_pos: DVec3) -> Vec3 {
+        (world_pos - self.render_origin().as_dvec3()).as_vec3()
+    }
+
+    /// Upload the biome colors a section uses when baked with [TintMode::PerSection], indexed by tint index. They're
+    /// recolored by the active texture variant's [crate::render::atlas::TintRamp]s.
+    /// This doesn't require the section to be rebaked. Returns false if there is no space left in the tint buffer.
+    pub fn set_section_tints(
+        &self,
+        wm: &WmRenderer,
+        pos: IVec3,
+        colors: [u32; SECTION_TINT_SLOTS],
+    ) -> bool {
+        let mut section_storage = self.section_storage.write();
+
+        let Some(slot) = section_storage.allocate_tint_slot(pos) else {
+            return false;
+        };
+
+        section_storage.set_tint_colors(slot, colors);
+        self.write_tint_slot(wm, slot, colors, &wm.mc.texture_manager.tint_ramps());
+
+        true
+    }
+
+    /// Upload the colors of every section's tint slot again, e.g. after [TextureManager::set_variant] changed the
+    /// [crate::render::atlas::TintRamp]s
+    pub fn upload_section_tints(&self, wm: &WmRenderer) {
+        let ramps = wm.mc.texture_manager.tint_ramps();
+
+        for (slot, colors) in self.section_storage.read().tint_colors() {
+            self.write_tint_slot(wm, slot, colors, &ramps);
+        }
+    }
+
+    fn write_tint_slot(
+        &self,
+        wm: &WmRenderer,
+        slot: u32,
+        colors: [u32; SECTION_TINT_SLOTS],
+        ramps: &[Option<TintRamp>; SECTION_TINT_SLOTS],
+    ) {
+        let color_space = wm.mc.block_manager.read().vertex_color_space;
+        let mut colors = colors;
+
+        for (color, ramp) in colors.iter_mut().zip(ramps) {
+            if let Some(ramp) = ramp {
+                *color = ramp.apply(*color);
+            }
+
+            *color = color_space.convert_srgb_color(*color);
+        }
+
+        wm.display.queue.write_buffer(
+            &self.section_tint_buffer.buffer,
+            (slot as usize * SECTION_TINT_SLOTS * 4) as wgpu::BufferAddress,
+            bytemuck::cast_slice(&colors),
+        );
+    }
+
+    /// Start keeping the blocks of baked sections in storage buffers, see [voxels] for their layout. `grid` is how many
+    /// sections the table covers around the camera and `capacity` how many non-empty sections it can hold blocks
+    /// for. Only sections baked from now on are included, so this should be called before the world is loaded
+    pub fn enable_voxels(&self, wm: &WmRenderer, grid: UVec3, capacity: u32) {
+        *self.voxels.write() = Some(SectionVoxels::new(wm, grid, capacity));
+        *wm.mc.bake_voxels.write() = true;
+    }
+
+    /// Add textured geometry which is drawn coplanar to a surface without z-fighting, like paintings and signs.
+    /// `transform` places `mesh` in render space, and `depth_bias` is how far, in blocks, it's pulled towards the camera
+    /// (see [crate::render::decal::DEFAULT_DECAL_DEPTH_BIAS]). Returns an id for [Scene::remove_decal]
+    pub fn add_decal(
+        &self,
+        wm: &WmRenderer,
+        mesh: &[DecalVertex],
+        transform: Mat4,
+        depth_bias: f32,
+    ) -> u64 {
+        let vertex_buffer =
+            wm.display
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("decal"),
+                    contents: bytemuck::cast_slice(mesh),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+
+        let id = self.next_decal_id.fetch_add(1, Ordering::Relaxed);
+
+        self.decals.write().insert(
+            id,
+            Decal {
+                vertex_buffer: Arc::new(vertex_buffer),
+                vertex_count: mesh.len() as u32,
+                transform,
+                depth_bias,
+            },
+        );
+
+        id
+    }
+
+    pub fn remove_decal(&self, id: u64) -> bool {
+        self.decals.write().remove(&id).is_some()
+    }
+
+    /// Queue the particles the block state `key` breaks into at the world position `pos` on
+    /// [Scene::block_break_particles], textured with pieces of its faces' sprites. Returns how many were spawned,
+    /// none if the block isn't baked or has no faces
+    pub fn spawn_block_break_particles(
+        &self,
+        wm: &WmRenderer,
+        pos: IVec3,
+        key: BlockstateKey,
+    ) -> usize {
+        let block_manager = wm.mc.block_manager.read();
+
+        let Some(mesh) = block_manager.get_model(key) else {
+            return 0;
+        };
+
+        let atlas_generation = wm
+            .mc
+            .texture_manager
+            .atlas_generation(BLOCK_ATLAS)
+            .unwrap_or_default();
+        let particles = block_break_particles(&mesh, pos, atlas_generation);
+        let count = particles.len();
+        queue_particles(&mut self.block_break_particles.lock(), particles);
+
+        count
+    }
+
+    /// Have the next [WmRenderer::flush_dirty] bake the section at `pos`, in sections, again
+    pub fn mark_section_dirty(&self, pos: IVec3) {
+        self.dirty_sections.lock().insert(pos);
+    }
+
+    /// Have the next [WmRenderer::flush_dirty] bake every section whose mesh depends on the block at `block`, in
+    /// blocks, again, see [chunk::sections_around_block]. Call it whenever a block changes
+    pub fn mark_block_dirty(&self, block: IVec3) {
+        self.dirty_sections
+            .lock()
+            .extend(sections_around_block(block));
+    }
+
+    /// How many sections the next [WmRenderer::flush_dirty] will bake, at most
+    pub fn dirty_section_count(&self) -> usize {
+        self.dirty_sections.lock().len()
+    }
+
+    /// Call whenever the render distance changes. With a [Scene::fog_mode] set, the fog distances in
+    /// [Scene::render_effects] are updated to match it, otherwise this does nothing. Also call it after changing the
+    /// fog mode
+    pub fn set_render_distance(&self, chunks: u32) {
+        let Some(mode) = *self.fog_mode.read() else {
+            return;
+        };
+
+        let fog = RenderEffectsData::from_render_distance(chunks, mode);
+        let mut render_effects = self.render_effects.write();
+
+        render_effects.fog_start = fog.fog_start;
+        render_effects.fog_end = fog.fog_end;
+        render_effects.fog_shape = fog.fog_shape;
+    }
+
+    /// Replace [Scene::render_effects]. With a [Scene::fog_mode] set, the fog distances from
+    /// [Scene::set_render_distance] are kept
+    pub fn set_render_effects(&self, effects: RenderEffectsData) {
+        let mut render_effects = self.render_effects.write();
+
+        *render_effects = if self.fog_mode.read().is_some() {
+            RenderEffectsData {
+                fog_start: render_effects.fog_start,
+                fog_end: render_effects.fog_end,
+                fog_shape: render_effects.fog_shape,
+                ..effects
+            }
+        } else {
+            effects
+        };
+    }
+
     pub fn resize_depth_texture(&self, wm: &WmRenderer, width: u32, height: u32) {
         self.depth_texture.read().destroy();
         *self.depth_texture.write() = wm.display.device.create_texture(&wgpu::TextureDescriptor {
@@ -258,7 +1150,8 @@ impl Scene {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            //Copied by the first depth peeling layer, see crate::render::depth_peel
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
             view_formats: &[],
         })
     }
@@ -272,9 +1165,24 @@ pub struct MinecraftState {
 
     pub resource_provider: Arc<dyn ResourceProvider>,
     pub texture_manager: TextureManager,
+    /// How terrain baking handles biome tints, see [TintMode]
+    pub tint_mode: RwLock<TintMode>,
+    /// Whether terrain baking also sends each section's blocks for [Scene::voxels], set by [Scene::enable_voxels]
+    pub bake_voxels: RwLock<bool>,
+    /// How long recent terrain bakes took, see [MinecraftState::recent_bake_times]
+    pub bake_times: Mutex<BakeTimes>,
+    /// Custom model loaders used when baking blocks, see [MinecraftState::register_model_loader]
+    pub model_loaders: RwLock<ModelLoaders>,
 
     pub animated_block_buffer: ArcSwap<Option<wgpu::Buffer>>,
     pub animated_block_bind_group: ArcSwap<Option<wgpu::BindGroup>>,
+    /// Bound as `@buffer_uv_scrolls`, written by [WmRenderer::update_uv_scrolls]
+    pub uv_scroll_buffer: Arc<wgpu::Buffer>,
+    /// Bound as `@buffer_sky`, written from [Scene::sky_state] whenever a scene is rendered
+    pub sky_buffer: Arc<wgpu::Buffer>,
+    /// Bound as `@buffer_color_spaces`, written from [BlockManager::vertex_color_space] and the block atlas' format
+    /// whenever a scene is rendered
+    pub color_space_buffer: Arc<wgpu::Buffer>,
 }
 
 impl MinecraftState {
@@ -287,20 +1195,108 @@ impl MinecraftState {
 
             block_manager: RwLock::new(BlockManager {
                 blocks: IndexMap::new(),
+                render_layers: HashMap::new(),
+                interned_meshes: HashMap::new(),
+                renders_nothing: HashSet::new(),
+                uv_scrolls: Vec::new(),
+                uv_scroll_channels: HashMap::new(),
+                random_rotation: HashSet::new(),
+                random_rotation_full_cubes: false,
+                sways: HashMap::new(),
+                light_emission: HashMap::new(),
+                vertex_color_space: ColorSpace::Srgb,
+                coplanar_order: CoplanarOrder::default(),
             }),
             resource_provider,
+            tint_mode: RwLock::new(TintMode::default()),
+            bake_voxels: RwLock::new(false),
+            bake_times: Mutex::new(BakeTimes::default()),
+            model_loaders: RwLock::new(HashMap::new()),
 
             animated_block_buffer: ArcSwap::new(Arc::new(None)),
             animated_block_bind_group: ArcSwap::new(Arc::new(None)),
+            uv_scroll_buffer: Arc::new(wgpu_state.device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("uv scrolls"),
+                    contents: bytemuck::cast_slice(
+                        &[UvScrollUniform::IDENTITY; MAX_UV_SCROLLS + 1],
+                    ),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                },
+            )),
+            sky_buffer: Arc::new(wgpu_state.device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("sky"),
+                    contents: bytemuck::bytes_of(&SkyUniform::new(&SkyState::default())),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                },
+            )),
+            color_space_buffer: Arc::new(wgpu_state.device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("color spaces"),
+                    contents: bytemuck::bytes_of(&ColorSpaceUniform::new(
+                        ColorSpace::Srgb,
+                        ColorSpace::Linear,
+                    )),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                },
+            )),
         }
     }
 
-    /// Bake blocks from their blockstates
+    /// How long the most recent section bakes took, oldest first. Only the meshing in
+    /// [chunk::bake_section_with] is timed, not the upload in [WmRenderer::submit_chunk_updates]
+    pub fn recent_bake_times(&self) -> Vec<SectionBakeTime> {
+        self.bake_times.lock().iter().copied().collect()
+    }
+
+    /// Take over the settings of `old`, which was created against a device that has been replaced, see
+    /// [WmRenderer::recreate_device]. Its blocks are kept so their indices stay the same, but their meshes point into
+    /// the old block atlas until they're baked again
+    pub(crate) fn carry_settings_from(&mut self, old: MinecraftState, display: &Display) {
+        let texture_quality = old.texture_manager.texture_quality();
+
+        *self.block_manager.get_mut() = old.block_manager.into_inner();
+        *self.tint_mode.get_mut() = old.tint_mode.into_inner();
+        *self.bake_voxels.get_mut() = old.bake_voxels.into_inner();
+        *self.model_loaders.get_mut() = old.model_loaders.into_inner();
+        *self.texture_manager.atlas_color_spaces.get_mut() =
+            old.texture_manager.atlas_color_spaces.into_inner();
+
+        if let Err(error) = self
+            .texture_manager
+            .set_texture_quality(display, texture_quality)
+        {
+            log::warn!("Couldn't keep the texture quality on the new device: {error:?}");
+        }
+    }
+
+    /// Bake models whose `loader` field is `loader` with `bake` instead of as vanilla JSON models, e.g. to support
+    /// OBJ models from mods. Only blocks baked afterwards use it
+    pub fn register_model_loader(
+        &self,
+        loader: impl Into<String>,
+        bake: impl Fn(
+                &serde_json::Value,
+                &dyn ResourceProvider,
+                &Atlas,
+            ) -> Result<ModelMesh, MeshBakeError>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.model_loaders
+            .write()
+            .insert(loader.into(), Arc::new(bake));
+    }
+
+    /// Bake blocks from their blockstates. Blocks whose blockstate or models are missing or malformed are handled
+    /// according to `policy`, with [MissingModelPolicy::Error] the blocks baked before the error are kept
     ///
     /// # Example
     ///
     ///```ignore
-    /// # use wgpu_mc::mc::MinecraftState;
+    /// # use wgpu_mc::mc::{MinecraftState, MissingModelPolicy};
     /// # use wgpu_mc::mc::resource::ResourcePath;
     /// # use wgpu_mc::WmRenderer;
     ///
@@ -309,88 +1305,266 @@ impl MinecraftState {
     ///
     /// minecraft_state.bake_blocks(
     ///     &wm,
-    ///     [("minecraft:anvil", &ResourcePath("minecraft:blockstates/anvil.json".into()))]
-    /// );
+    ///     [("minecraft:anvil", &ResourcePath("minecraft:blockstates/anvil.json".into()))],
+    ///     MissingModelPolicy::Placeholder,
+    /// ).unwrap();
     /// ```
     pub fn bake_blocks<'a>(
         &self,
         wm: &WmRenderer,
         block_states: impl IntoIterator<Item = (impl AsRef<str>, &'a ResourcePath)>,
-    ) {
+        policy: MissingModelPolicy,
+    ) -> Result<(), BlockBakeError> {
+        self.bake_blocks_with_progress(wm, block_states, policy, None, None, None)
+            .map(|_| ())
+    }
+
+    /// Same as [MinecraftState::bake_blocks], but meshes are taken from `cache` instead of being baked again if its
+    /// key matches `key`, see [mesh_cache::block_mesh_cache_key], in which case the block atlas is replaced with the
+    /// one stored in the cache. A cache with a different key is cleared instead. Either way `cache` holds every baked
+    /// block and the block atlas afterwards, ready to be saved by the host.
+    /// Returns false if any block had to be baked
+    pub fn bake_blocks_cached<'a>(
+        &self,
+        wm: &WmRenderer,
+        block_states: impl IntoIterator<Item = (impl AsRef<str>, &'a ResourcePath)>,
+        policy: MissingModelPolicy,
+        key: u64,
+        cache: &mut BlockMeshCache,
+    ) -> Result<bool, BlockBakeError> {
+        if cache.key != key {
+            *cache = BlockMeshCache::new(key);
+        } else if let Some(snapshot) = &cache.atlas {
+            let atlases = self.texture_manager.atlases.read();
+            let block_atlas = atlases.get(BLOCK_ATLAS).unwrap();
+
+            if !block_atlas.restore(snapshot, &*self.resource_provider) {
+                *cache = BlockMeshCache::new(key);
+            }
+        }
+
+        let (_, baked_meshes) =
+            self.bake_blocks_inner(wm, block_states, policy, None, None, None, Some(cache))?;
+
+        Ok(baked_meshes == 0)
+    }
+
+    /// Same as [MinecraftState::bake_blocks], but `progress` is called after each block is baked with the amount of
+    /// blocks baked so far and the total, and baking stops early once `cancel` is set. If `stats` is given, the
+    /// [MeshStats] of every baked block are inserted into it by block name.
+    ///
+    /// The [BlockManager] write lock is held for the whole bake, so terrain can't be baked or rendered until this returns.
+    /// Blocks which were baked before cancelling are kept and the atlas is still uploaded.
+    /// Returns false if baking was cancelled
+    pub fn bake_blocks_with_progress<'a>(
+        &self,
+        wm: &WmRenderer,
+        block_states: impl IntoIterator<Item = (impl AsRef<str>, &'a ResourcePath)>,
+        policy: MissingModelPolicy,
+        progress: Option<&dyn Fn(usize, usize)>,
+        cancel: Option<&AtomicBool>,
+        stats: Option<&mut HashMap<String, MeshStats>>,
+    ) -> Result<bool, BlockBakeError> {
+        self.bake_blocks_inner(wm, block_states, policy, progress, cancel, stats, None)
+            .map(|(completed, _)| completed)
+    }
+
+    /// Returns whether baking finished and how many meshes were baked rather than taken from `cache`
+    #[allow(clippy::too_many_arguments)]
+    fn bake_blocks_inner<'a>(
+        &self,
+        wm: &WmRenderer,
+        block_states: impl IntoIterator<Item = (impl AsRef<str>, &'a ResourcePath)>,
+        policy: MissingModelPolicy,
+        progress: Option<&dyn Fn(usize, usize)>,
+        cancel: Option<&AtomicBool>,
+        stats: Option<&mut HashMap<String, MeshStats>>,
+        mut cache: Option<&mut BlockMeshCache>,
+    ) -> Result<(bool, usize), BlockBakeError> {
         let mut block_manager = self.block_manager.write();
         let atlases = self.texture_manager.atlases.read();
         let block_atlas = atlases.get(BLOCK_ATLAS).unwrap();
+        let model_loaders = self.model_loaders.read();
 
-        //Figure out which block models there are
-        block_states
-            .into_iter()
-            .for_each(|(block_name, block_state)| {
-                let blockstates: schemas::BlockStates =
-                    serde_json::from_str(&self.resource_provider.get_string(block_state).unwrap())
-                        .unwrap();
-
-                let block = match &blockstates {
-                    schemas::BlockStates::Variants { variants } => {
-                        let meshes: IndexMap<Vec<(String, StateValue)>, Vec<Arc<ModelMesh>>> =
-                            variants
-                                .iter()
-                                .map(|(variant_id, variant)| {
-                                    let key_iter = if !variant_id.is_empty() {
-                                        variant_id
-                                            .split(',')
-                                            .filter_map(|kv_pair| {
-                                                let mut split = kv_pair.split('=');
-                                                if kv_pair.is_empty() {
-                                                    return None;
-                                                }
-
-                                                Some((
-                                                    split.next().unwrap().to_string(),
-                                                    match split.next().unwrap() {
-                                                        "true" => StateValue::Bool(true),
-                                                        "false" => StateValue::Bool(false),
-                                                        other => StateValue::String(other.into()),
-                                                    },
-                                                ))
-                                            })
-                                            .collect::<Vec<_>>()
-                                    } else {
-                                        vec![]
-                                    };
-
-                                    (
-                                        key_iter,
-                                        variant
-                                            .models()
-                                            .iter()
-                                            .map(|variation| {
-                                                Arc::new(
-                                                    ModelMesh::bake(
-                                                        std::slice::from_ref(variation),
-                                                        &*self.resource_provider,
-                                                        block_atlas,
-                                                    )
-                                                    .unwrap(),
-                                                )
-                                            })
-                                            .collect::<Vec<Arc<ModelMesh>>>(),
-                                    )
-                                })
-                                .collect();
-
-                        Block::Variants(meshes)
-                    }
-                    schemas::BlockStates::Multipart { cases } => Block::Multipart(Multipart {
-                        cases: cases.clone(),
-                        keys: RwLock::new(IndexMap::new()),
-                    }),
-                };
+        let result = bake_block_states(
+            &mut block_manager,
+            &*self.resource_provider,
+            block_states,
+            policy,
+            &|variation| {
+                ModelMesh::bake(
+                    std::slice::from_ref(variation),
+                    &*self.resource_provider,
+                    block_atlas,
+                    &model_loaders,
+                )
+            },
+            &|| ModelMesh::bake_missing(&*self.resource_provider, block_atlas),
+            progress,
+            cancel,
+            stats,
+            cache.as_deref_mut(),
+        );
 
-                block_manager
-                    .blocks
-                    .insert(String::from(block_name.as_ref()), block);
-            });
+        if let Some(cache) = cache {
+            cache.atlas = Some(block_atlas.snapshot());
+        }
 
         block_atlas.upload(wm);
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use super::{
+        bake_block_states, Block, BlockBakeError, BlockManager, FogMode, MissingModelPolicy,
+        RenderEffectsData, FOG_SHAPE_CYLINDER, FOG_SHAPE_SPHERE,
+    };
+    use crate::mc::chunk::tests::{block_manager, mesh, up_face};
+    use crate::mc::resource::tests::InMemoryResources;
+    use crate::mc::resource::ResourcePath;
+
+    #[test]
+    fn terrain_fog_ends_at_the_render_distance() {
+        let fog = RenderEffectsData::from_render_distance(12, FogMode::Terrain);
+
+        assert_eq!((fog.fog_start, fog.fog_end), (172.8, 192.0));
+        assert_eq!(fog.fog_shape, FOG_SHAPE_CYLINDER);
+
+        //The fade is at least 4 and at most 64 blocks long
+        let near = RenderEffectsData::from_render_distance(2, FogMode::Terrain);
+        assert_eq!(near.fog_end - near.fog_start, 4.0);
+
+        let far = RenderEffectsData::from_render_distance(48, FogMode::Terrain);
+        assert_eq!(far.fog_end - far.fog_start, 64.0);
+    }
+
+    #[test]
+    fn thick_fog_is_capped() {
+        let fog = RenderEffectsData::from_render_distance(32, FogMode::Thick);
+
+        assert_eq!((fog.fog_start, fog.fog_end), (25.6, 96.0));
+        assert_eq!(fog.fog_shape, FOG_SHAPE_SPHERE);
+    }
+
+    #[test]
+    fn environment_data_matches_the_sky_shaders() {
+        let effects = RenderEffectsData {
+            fog_start: 1.0,
+            fog_end: 2.0,
+            fog_shape: FOG_SHAPE_CYLINDER,
+            fog_color: [0.1, 0.2, 0.3, 0.4],
+            color_modulator: [0.5, 0.6, 0.7, 0.8],
+            dimension_fog_color: [0.9, 1.0, 1.1, 1.2],
+        };
+
+        //The color modulator's alpha isn't part of the sky shaders' PushConstants
+        assert_eq!(
+            effects.environment_data(),
+            [1.0, 2.0, 1.0, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.9, 1.0, 1.1, 1.2]
+        );
+    }
+
+    #[test]
+    fn interning_reports_the_bytes_it_saves() {
+        let mut block_manager = block_manager(IndexMap::new());
+
+        let first = block_manager.intern_mesh(mesh(vec![up_face()], true));
+        assert_eq!(block_manager.interned_bytes_saved(), 0);
+
+        let second = block_manager.intern_mesh(mesh(vec![up_face()], true));
+        let third = block_manager.intern_mesh(mesh(vec![up_face()], true));
+        assert_eq!(
+            block_manager.interned_bytes_saved(),
+            2 * first.size_in_bytes()
+        );
+
+        drop((second, third));
+        assert_eq!(block_manager.interned_bytes_saved(), 0);
+    }
+
+    #[test]
+    fn one_malformed_blockstate_doesnt_stop_the_rest() {
+        let resources = InMemoryResources::new(&[
+            (
+                "test:blockstates/stone.json",
+                r#"{ "variants": { "": { "model": "test:block/stone" } } }"#,
+            ),
+            (
+                "test:blockstates/broken.json",
+                r#"{ "variants": { "": { "model": "#,
+            ),
+            (
+                "test:blockstates/dirt.json",
+                r#"{ "variants": { "": { "model": "test:block/dirt" } } }"#,
+            ),
+        ]);
+
+        let paths = ["test:stone", "test:broken", "test:dirt", "test:missing"].map(|block| {
+            (
+                block,
+                ResourcePath::from(block)
+                    .prepend("blockstates/")
+                    .append(".json"),
+            )
+        });
+
+        //Every model bakes to an up face with ambient occlusion, and the placeholder to one without
+        let bake = |policy| {
+            let mut block_manager = block_manager(IndexMap::new());
+            let result = bake_block_states(
+                &mut block_manager,
+                &resources,
+                paths.iter().map(|(block, path)| (*block, path)),
+                policy,
+                &|_| Ok(mesh(vec![up_face()], true)),
+                &|| mesh(vec![up_face()], false),
+                None,
+                None,
+                None,
+                None,
+            );
+
+            (block_manager, result)
+        };
+
+        let baked = |block_manager: &BlockManager, block: &str, ambient_occlusion: bool| {
+            matches!(
+                block_manager.blocks.get(block),
+                Some(Block::Variants(variants)) if !variants.is_empty() && variants
+                    .values()
+                    .flatten()
+                    .all(|mesh| !mesh.is_empty() && mesh.ambient_occlusion == ambient_occlusion)
+            )
+        };
+
+        //The broken and missing blocks are left out, the rest is baked
+        let (skipped, result) = bake(MissingModelPolicy::Skip);
+        assert!(matches!(result, Ok((true, 2))));
+        assert!(baked(&skipped, "test:stone", true));
+        assert!(baked(&skipped, "test:dirt", true));
+        assert!(!skipped.blocks.contains_key("test:broken"));
+        assert!(!skipped.blocks.contains_key("test:missing"));
+
+        //The placeholder stands in for them
+        let (placeholder, result) = bake(MissingModelPolicy::Placeholder);
+        assert!(matches!(result, Ok((true, 2))));
+        assert!(baked(&placeholder, "test:stone", true));
+        assert!(baked(&placeholder, "test:dirt", true));
+        assert!(baked(&placeholder, "test:broken", false));
+        assert!(baked(&placeholder, "test:missing", false));
+
+        //Baking stops at the broken block, keeping the blocks baked before it
+        let (stopped, result) = bake(MissingModelPolicy::Error);
+        assert!(matches!(
+            result,
+            Err(BlockBakeError::MalformedBlockstate { ref block, .. }) if block == "test:broken"
+        ));
+        assert!(baked(&stopped, "test:stone", true));
+        assert!(!stopped.blocks.contains_key("test:dirt"));
     }
 }
@@ -1,4 +1,7 @@
 use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+use parking_lot::RwLock;
 
 /// Describes a minecraft resource, like "minecraft:stone". Useful in combination with
 /// [ResourceProvider], which gets you the actual resource.
@@ -81,3 +84,168 @@ pub trait ResourceProvider: Send + Sync {
         String::from_utf8(self.get_bytes(id)?).ok()
     }
 }
+
+/// One resource pack in a [LayeredResourceProvider]
+pub struct ResourceLayer {
+    pub name: String,
+    pub provider: Arc<dyn ResourceProvider>,
+    pub enabled: bool,
+}
+
+/// A stack of resource packs, like Minecraft's resource pack list. Resources are looked up from the last enabled layer
+/// down, so a later pack overrides an earlier one path by path and falls back to it for everything else. Pass it as
+/// the renderer's resource provider and keep a handle to reorder or toggle layers, then call
+/// [crate::WmRenderer::reload_atlas_sprites] (or bake the blocks again) to rebuild the atlases from the new stack
+#[derive(Default)]
+pub struct LayeredResourceProvider {
+    layers: RwLock<Vec<ResourceLayer>>,
+}
+
+impl LayeredResourceProvider {
+    /// Add an enabled layer on top of the others.
+    /// Returns false without adding anything if there's already a layer with this name
+    pub fn push(&self, name: impl Into<String>, provider: Arc<dyn ResourceProvider>) -> bool {
+        let mut layers = self.layers.write();
+        let name = name.into();
+
+        if layers.iter().any(|layer| layer.name == name) {
+            return false;
+        }
+
+        layers.push(ResourceLayer {
+            name,
+            provider,
+            enabled: true,
+        });
+
+        true
+    }
+
+    /// Returns false if there's no layer with this name
+    pub fn remove(&self, name: &str) -> bool {
+        let mut layers = self.layers.write();
+        let count = layers.len();
+
+        layers.retain(|layer| layer.name != name);
+        layers.len() != count
+    }
+
+    /// Returns false if there's no layer with this name
+    pub fn set_enabled(&self, name: &str, enabled: bool) -> bool {
+        match self
+            .layers
+            .write()
+            .iter_mut()
+            .find(|layer| layer.name == name)
+        {
+            Some(layer) => {
+                layer.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move a layer to `index`, where 0 is the bottom of the stack. Indices past the top move it to the top.
+    /// Returns false if there's no layer with this name
+    pub fn move_layer(&self, name: &str, index: usize) -> bool {
+        let mut layers = self.layers.write();
+
+        let Some(from) = layers.iter().position(|layer| layer.name == name) else {
+            return false;
+        };
+
+        let layer = layers.remove(from);
+        let index = index.min(layers.len());
+        layers.insert(index, layer);
+
+        true
+    }
+
+    /// The name of every layer from the bottom of the stack to the top, and whether it's enabled
+    pub fn layers(&self) -> Vec<(String, bool)> {
+        self.layers
+            .read()
+            .iter()
+            .map(|layer| (layer.name.clone(), layer.enabled))
+            .collect()
+    }
+}
+
+impl ResourceProvider for LayeredResourceProvider {
+    fn get_bytes(&self, id: &ResourcePath) -> Option<Vec<u8>> {
+        self.layers
+            .read()
+            .iter()
+            .rev()
+            .filter(|layer| layer.enabled)
+            .find_map(|layer| layer.provider.get_bytes(id))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use super::{LayeredResourceProvider, ResourcePath, ResourceProvider};
+
+    /// Resources held in memory for tests, keyed by their full path such as `test:models/block/stone.json`
+    pub(crate) struct InMemoryResources(HashMap<ResourcePath, &'static str>);
+
+    impl InMemoryResources {
+        pub(crate) fn new(resources: &[(&str, &'static str)]) -> Self {
+            Self(
+                resources
+                    .iter()
+                    .map(|(path, contents)| (ResourcePath::from(*path), *contents))
+                    .collect(),
+            )
+        }
+    }
+
+    impl ResourceProvider for InMemoryResources {
+        fn get_bytes(&self, id: &ResourcePath) -> Option<Vec<u8>> {
+            self.0.get(id).map(|contents| contents.as_bytes().to_vec())
+        }
+    }
+
+    fn pack(resources: &[(&str, &'static str)]) -> Arc<InMemoryResources> {
+        Arc::new(InMemoryResources::new(resources))
+    }
+
+    #[test]
+    fn later_layers_override_earlier_ones() {
+        let stone = ResourcePath::from("minecraft:textures/block/stone.png");
+        let dirt = ResourcePath::from("minecraft:textures/block/dirt.png");
+
+        let layers = LayeredResourceProvider::default();
+        assert!(layers.push(
+            "vanilla",
+            pack(&[
+                (stone.0.as_str(), "base stone"),
+                (dirt.0.as_str(), "base dirt")
+            ])
+        ));
+        assert!(layers.push("overlay", pack(&[(stone.0.as_str(), "overlay stone")])));
+        assert!(!layers.push("overlay", pack(&[])));
+
+        assert_eq!(layers.get_string(&stone).unwrap(), "overlay stone");
+        assert_eq!(layers.get_string(&dirt).unwrap(), "base dirt");
+
+        layers.set_enabled("overlay", false);
+        assert_eq!(layers.get_string(&stone).unwrap(), "base stone");
+
+        layers.set_enabled("overlay", true);
+        assert!(layers.move_layer("overlay", 0));
+        assert_eq!(layers.get_string(&stone).unwrap(), "base stone");
+        assert_eq!(
+            layers.layers(),
+            vec![("overlay".to_string(), true), ("vanilla".to_string(), true)]
+        );
+
+        assert!(layers.remove("vanilla"));
+        assert_eq!(layers.get_string(&stone).unwrap(), "overlay stone");
+        assert_eq!(layers.get_bytes(&dirt), None);
+    }
+}
@@ -0,0 +1,231 @@
+//! Block structures rendered on their own, without a loaded world, e.g. schematic previews in mod tools.
+//!
+//! The structure is baked with the same mesher as terrain, one 16x16x16 section at a time, into a [Scene] whose chunk
+//! buffer is only as large as the baked mesh. Draw it with any shader pack using `@geo_terrain`.
+
+use glam::{ivec3, IVec3};
+
+use crate::mc::block::ChunkBlockState;
+use crate::mc::chunk::{bake_layers, terrain_mapper, BlockStateProvider, LightLevel};
+use crate::mc::Scene;
+use crate::WmRenderer;
+
+pub struct StructureScene {
+    pub scene: Scene,
+    /// The structure's size in blocks
+    pub size: IVec3,
+}
+
+impl StructureScene {
+    /// Bakes the blocks of `provider` from 0, 0, 0 up to (but not including) `size`. Everything outside of that is
+    /// air lit by the sky, so the faces on the outside of the structure are drawn. Sections don't fade in
+    pub fn new<Provider: BlockStateProvider>(
+        wm: &WmRenderer,
+        provider: &Provider,
+        size: IVec3,
+        framebuffer_size: wgpu::Extent3d,
+    ) -> Self {
+        let block_manager = wm.mc.block_manager.read();
+        let tint_mode = *wm.mc.tint_mode.read();
+        let sections = (size + 15) / 16;
+
+        let baked = (0..sections.x)
+            .flat_map(|x| {
+                (0..sections.y).flat_map(move |y| (0..sections.z).map(move |z| ivec3(x, y, z)))
+            })
+            .map(|section_pos| {
+                let section = StructureSection {
+                    provider,
+                    size,
+                    offset: section_pos * 16,
+                };
+
+                (
+                    section_pos,
+                    bake_layers(
+                        section_pos,
+                        &block_manager,
+                        &section,
+                        tint_mode,
+                        &terrain_mapper([0, 0]),
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        drop(block_manager);
+
+        //In 4 byte words, the same unit the section allocator uses
+        let words = baked
+            .iter()
+            .flat_map(|(_, layers)| layers)
+            .map(|layer| (layer.vertices.len() + layer.indices.len()) as u64 / 4)
+            .sum::<u64>();
+
+        let scene = Scene::with_chunk_buffer_size(wm, framebuffer_size, words.max(1) * 4);
+
+        {
+            let mut storage = scene.section_storage.write();
+
+            for (pos, layers) in &baked {
+                wm.upload_section(&scene, &mut storage, *pos, layers);
+            }
+        }
+
+        Self { scene, size }
+    }
+}
+
+/// One section of a structure, as the mesher sees it. Positions are relative to the section
+struct StructureSection<'a, Provider> {
+    provider: &'a Provider,
+    size: IVec3,
+    offset: IVec3,
+}
+
+impl<Provider> StructureSection<'_, Provider> {
+    fn contains(&self, pos: IVec3) -> bool {
+        pos.cmpge(IVec3::ZERO).all() && pos.cmplt(self.size).all()
+    }
+}
+
+impl<Provider: BlockStateProvider> BlockStateProvider for StructureSection<'_, Provider> {
+    fn get_state(&self, pos: IVec3) -> ChunkBlockState {
+        let pos = pos + self.offset;
+
+        if self.contains(pos) {
+            self.provider.get_state(pos)
+        } else {
+            ChunkBlockState::Air
+        }
+    }
+
+    fn get_light_level(&self, pos: IVec3) -> LightLevel {
+        let pos = pos + self.offset;
+
+        if self.contains(pos) {
+            self.provider.get_light_level(pos)
+        } else {
+            LightLevel::from_sky_and_block(15, 0)
+        }
+    }
+
+    fn is_section_empty(&self, _rel_pos: IVec3) -> bool {
+        false
+    }
+
+    //The mesher already offsets the position by the section's
+    fn get_block_color(&self, pos: IVec3, tint_index: i32) -> u32 {
+        self.provider.get_block_color(pos, tint_index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use glam::{ivec3, vec3, IVec3, Vec3};
+    use indexmap::IndexMap;
+
+    use super::StructureSection;
+    use crate::mc::block::{
+        BlockMeshVertex, BlockModelFace, BlockstateKey, ChunkBlockState, ModelMesh,
+    };
+    use crate::mc::chunk::tests::{block_manager, mesh};
+    use crate::mc::chunk::{
+        bake_layers, terrain_mapper, BlockStateProvider, LightLevel, RenderLayer, TintMode,
+    };
+    use crate::mc::Block;
+    use crate::render::pipeline::TerrainVertex;
+
+    const STONE: BlockstateKey = BlockstateKey {
+        block: 0,
+        augment: 0,
+    };
+
+    /// Solid everywhere, so only the structure's bounds decide what's air
+    struct Solid;
+
+    impl BlockStateProvider for Solid {
+        fn get_state(&self, _pos: IVec3) -> ChunkBlockState {
+            ChunkBlockState::State(STONE)
+        }
+
+        fn get_light_level(&self, _pos: IVec3) -> LightLevel {
+            LightLevel::from_sky_and_block(15, 0)
+        }
+
+        fn is_section_empty(&self, _rel_pos: IVec3) -> bool {
+            false
+        }
+
+        fn get_block_color(&self, _pos: IVec3, _tint_index: i32) -> u32 {
+            0xffffffff
+        }
+    }
+
+    /// The face of a unit cube pointing along `normal`
+    fn face(normal: Vec3) -> Vec<BlockModelFace> {
+        let corners = [(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0)].map(|(a, b)| {
+            let side = if normal.max_element() > 0.0 { 1.0 } else { 0.0 };
+
+            let position = if normal.x != 0.0 {
+                vec3(side, a, b)
+            } else if normal.y != 0.0 {
+                vec3(a, side, b)
+            } else {
+                vec3(a, b, side)
+            };
+
+            BlockMeshVertex {
+                position,
+                tex_coords: [0, 0],
+            }
+        });
+
+        vec![BlockModelFace {
+            vertices: corners,
+            normal,
+            tint_index: -1,
+            animation_uv_offset: 0,
+        }]
+    }
+
+    #[test]
+    fn only_outer_faces_of_a_filled_structure_are_baked() {
+        let cube = ModelMesh {
+            north: face(vec3(0.0, 0.0, -1.0)),
+            south: face(vec3(0.0, 0.0, 1.0)),
+            west: face(vec3(-1.0, 0.0, 0.0)),
+            east: face(vec3(1.0, 0.0, 0.0)),
+            down: face(vec3(0.0, -1.0, 0.0)),
+            cull: 0b111111,
+            ..mesh(face(vec3(0.0, 1.0, 0.0)), true)
+        };
+
+        let block_manager = block_manager(IndexMap::from([(
+            "stone".into(),
+            Block::Variants(IndexMap::from([(vec![], vec![Arc::new(cube)])])),
+        )]));
+
+        let section = StructureSection {
+            provider: &Solid,
+            size: ivec3(5, 5, 5),
+            offset: IVec3::ZERO,
+        };
+
+        let layers = bake_layers(
+            IVec3::ZERO,
+            &block_manager,
+            &section,
+            TintMode::Baked,
+            &terrain_mapper([0, 0]),
+        );
+
+        //5x5 faces on each of the 6 sides
+        assert_eq!(
+            layers[RenderLayer::Solid as usize].vertices.len(),
+            6 * 25 * 4 * TerrainVertex::VERTEX_LENGTH
+        );
+    }
+}
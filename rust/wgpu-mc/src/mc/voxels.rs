@@ -0,0 +1,328 @@
+//! The blocks of loaded sections in storage buffers, for compute or fragment passes which trace rays through the world
+//! instead of drawing its mesh. This is opt-in with [crate::mc::Scene::enable_voxels], since it costs 16KiB of GPU
+//! memory for every section which isn't empty, on top of the terrain mesh.
+//!
+//! # Layout
+//!
+//! [SectionVoxels::bind_group] uses the `voxels` layout, which binds two read-only storage buffers:
+//!
+//! ```wgsl
+//! struct VoxelSection {
+//!     position: vec3<i32>,
+//!     slot: u32,
+//!     bricks: vec2<u32>,
+//!     _padding: vec2<u32>,
+//! }
+//!
+//! @group(0) @binding(0) var<storage> voxel_table: array<VoxelSection>;
+//! @group(0) @binding(1) var<storage> voxel_blocks: array<u32>;
+//! ```
+//!
+//! `voxel_table` is a grid of [SectionVoxels::grid] sections which wraps around, so it doesn't move with the camera.
+//! The section at `p` (in sections) is looked up at `c = p mod grid` (always positive), index
+//! `(c.y * grid.z + c.z) * grid.x + c.x`. Other sections map to the same entry, so `position` has to be checked
+//! against `p`, a mismatch means the section isn't loaded.
+//!
+//! `bricks` is the occupancy of the section's 4x4x4 bricks of 4x4x4 blocks. The brick containing block `b` is
+//! bit `i % 32` of `bricks[i / 32]`, with `i = ((b.y / 4) * 4 + b.z / 4) * 4 + b.x / 4`. Empty sections have no bricks
+//! set, and rays can skip over them entirely.
+//!
+//! `slot` is where the section's blocks start in `voxel_blocks`, in sections of 4096 blocks. Block `b` is at
+//! `slot * 4096 + (b.y * 16 + b.z) * 16 + b.x`. It's [NO_VOXEL_SLOT] for empty sections, and for occupied sections
+//! which didn't fit within [SectionVoxels::capacity], which can be traced using `bricks` alone.
+//!
+//! Each block is 0 for air and blocks which render nothing. Other blocks are `(block + 1) | (augment << 16)`, from the
+//! [crate::mc::block::BlockstateKey], so `block` is an index into [crate::mc::BlockManager::blocks]. Materials are up
+//! to the shader pack to upload.
+
+use std::sync::Arc;
+
+use glam::{ivec3, IVec3, UVec3};
+use wgpu::util::DeviceExt;
+
+use crate::mc::block::ChunkBlockState;
+use crate::mc::chunk::{BlockStateProvider, SECTION_VOLUME};
+use crate::mc::BlockManager;
+use crate::WmRenderer;
+
+/// The slot of sections with no block data, see the module docs
+pub const NO_VOXEL_SLOT: u32 = u32::MAX;
+
+/// Matches the `VoxelSection` WGSL struct in the module docs
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VoxelTableEntry {
+    pub position: [i32; 3],
+    pub slot: u32,
+    pub bricks: [u32; 2],
+    _padding: [u32; 2],
+}
+
+impl VoxelTableEntry {
+    /// Doesn't match the position of any section
+    pub const UNLOADED: Self = Self {
+        position: [i32::MIN; 3],
+        slot: NO_VOXEL_SLOT,
+        bricks: [0; 2],
+        _padding: [0; 2],
+    };
+}
+
+/// The blocks of one section, encoded as described in the module docs
+#[derive(Clone, Debug, PartialEq)]
+pub struct SectionVoxelData {
+    pub blocks: Box<[u32]>,
+    /// Bit `i` is brick `i` from the module docs
+    pub bricks: u64,
+}
+
+pub fn encode_voxel(block_manager: &BlockManager, state: ChunkBlockState) -> u32 {
+    match state {
+        _ if block_manager.is_air_like(state) => 0,
+        ChunkBlockState::State(key) => (key.block as u32 + 1) | ((key.augment as u32) << 16),
+        ChunkBlockState::Air => unreachable!(),
+    }
+}
+
+impl SectionVoxelData {
+    /// `blocks` has [SECTION_VOLUME] entries, indexed like `voxel_blocks`
+    pub fn from_blocks(blocks: Box<[u32]>) -> Self {
+        assert_eq!(blocks.len(), SECTION_VOLUME);
+
+        let bricks = blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, block)| **block != 0)
+            .fold(0u64, |bricks, (index, _)| {
+                let (x, z, y) = (index & 15, (index >> 4) & 15, index >> 8);
+                bricks | (1 << (((y / 4) * 4 + z / 4) * 4 + x / 4))
+            });
+
+        Self { blocks, bricks }
+    }
+
+    pub fn bake<Provider: BlockStateProvider>(
+        block_manager: &BlockManager,
+        state_provider: &Provider,
+    ) -> Self {
+        let mut blocks = vec![0; SECTION_VOLUME].into_boxed_slice();
+
+        if !state_provider.is_section_empty(ivec3(0, 0, 0)) {
+            for (index, block) in blocks.iter_mut().enumerate() {
+                let index = index as i32;
+                let pos = ivec3(index & 15, index >> 8, (index >> 4) & 15);
+
+                *block = encode_voxel(block_manager, state_provider.get_state(pos));
+            }
+        }
+
+        Self::from_blocks(blocks)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bricks == 0
+    }
+}
+
+/// The CPU side of `voxel_table`, which decides where sections and their blocks are stored
+#[derive(Clone, Debug)]
+pub struct VoxelTable {
+    pub grid: UVec3,
+    pub capacity: u32,
+    entries: Vec<VoxelTableEntry>,
+    free_slots: Vec<u32>,
+    next_slot: u32,
+}
+
+impl VoxelTable {
+    pub fn new(grid: UVec3, capacity: u32) -> Self {
+        assert!(grid.cmpgt(UVec3::ZERO).all(), "Voxel grids can't be empty");
+
+        Self {
+            grid,
+            capacity,
+            entries: vec![VoxelTableEntry::UNLOADED; grid.element_product() as usize],
+            free_slots: Vec::new(),
+            next_slot: 0,
+        }
+    }
+
+    pub fn entries(&self) -> &[VoxelTableEntry] {
+        &self.entries
+    }
+
+    pub fn index(&self, pos: IVec3) -> usize {
+        let cell = pos.rem_euclid(self.grid.as_ivec3()).as_uvec3();
+        ((cell.y * self.grid.z + cell.z) * self.grid.x + cell.x) as usize
+    }
+
+    pub fn get(&self, pos: IVec3) -> Option<&VoxelTableEntry> {
+        let entry = &self.entries[self.index(pos)];
+        (entry.position == pos.to_array()).then_some(entry)
+    }
+
+    /// Stores the section, replacing whichever section was at its entry, and returns the entry's index. The slot of
+    /// the entry is where `data.blocks` has to be written
+    pub fn insert(&mut self, pos: IVec3, data: &SectionVoxelData) -> usize {
+        let index = self.index(pos);
+        let mut entry = self.entries[index];
+
+        if entry.position != pos.to_array() || data.is_empty() {
+            if entry.slot != NO_VOXEL_SLOT {
+                self.free_slots.push(entry.slot);
+            }
+            entry.slot = NO_VOXEL_SLOT;
+        }
+
+        if !data.is_empty() && entry.slot == NO_VOXEL_SLOT {
+            entry.slot = match self.free_slots.pop() {
+                Some(slot) => slot,
+                None if self.next_slot < self.capacity => {
+                    self.next_slot += 1;
+                    self.next_slot - 1
+                }
+                None => NO_VOXEL_SLOT,
+            };
+        }
+
+        entry.position = pos.to_array();
+        entry.bricks = [data.bricks as u32, (data.bricks >> 32) as u32];
+        self.entries[index] = entry;
+
+        index
+    }
+}
+
+/// The GPU buffers described in the module docs, kept up to date by [WmRenderer::submit_chunk_updates]
+pub struct SectionVoxels {
+    pub table: VoxelTable,
+    pub table_buffer: Arc<wgpu::Buffer>,
+    pub blocks_buffer: Arc<wgpu::Buffer>,
+    pub bind_group: Arc<wgpu::BindGroup>,
+}
+
+impl SectionVoxels {
+    /// `capacity` is how many sections which aren't empty can have their blocks stored at once
+    pub fn new(wm: &WmRenderer, grid: UVec3, capacity: u32) -> Self {
+        let table = VoxelTable::new(grid, capacity);
+
+        let table_buffer =
+            wm.display
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("voxel table"),
+                    contents: bytemuck::cast_slice(table.entries()),
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                });
+
+        let blocks_buffer = wm.display.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("voxel blocks"),
+            size: (capacity.max(1) as usize * SECTION_VOLUME * 4) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = wm
+            .display
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("voxels"),
+                layout: wm.bind_group_layouts.get("voxels").unwrap(),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: table_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: blocks_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+
+        Self {
+            table,
+            table_buffer: Arc::new(table_buffer),
+            blocks_buffer: Arc::new(blocks_buffer),
+            bind_group: Arc::new(bind_group),
+        }
+    }
+
+    pub fn grid(&self) -> UVec3 {
+        self.table.grid
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.table.capacity
+    }
+
+    pub fn upload(&mut self, wm: &WmRenderer, pos: IVec3, data: &SectionVoxelData) {
+        let index = self.table.insert(pos, data);
+        let entry = self.table.entries()[index];
+
+        wm.display.queue.write_buffer(
+            &self.table_buffer,
+            (index * std::mem::size_of::<VoxelTableEntry>()) as wgpu::BufferAddress,
+            bytemuck::bytes_of(&entry),
+        );
+
+        if entry.slot != NO_VOXEL_SLOT {
+            wm.display.queue.write_buffer(
+                &self.blocks_buffer,
+                (entry.slot as usize * SECTION_VOLUME * 4) as wgpu::BufferAddress,
+                bytemuck::cast_slice(&data.blocks),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{ivec3, uvec3};
+
+    use super::{SectionVoxelData, VoxelTable, NO_VOXEL_SLOT};
+    use crate::mc::chunk::SECTION_VOLUME;
+
+    fn section_with_block(index: usize) -> SectionVoxelData {
+        let mut blocks = vec![0; SECTION_VOLUME].into_boxed_slice();
+        blocks[index] = 1;
+        SectionVoxelData::from_blocks(blocks)
+    }
+
+    #[test]
+    fn bricks_mark_occupied_blocks() {
+        //x = 5, z = 0, y = 15 is brick x = 1, z = 0, y = 3
+        let data = section_with_block((15 * 16) * 16 + 5);
+
+        assert_eq!(data.bricks, 1 << ((3 * 4) * 4 + 1));
+        assert!(SectionVoxelData::from_blocks(vec![0; SECTION_VOLUME].into()).is_empty());
+    }
+
+    #[test]
+    fn sections_sharing_an_entry_replace_each_other() {
+        let mut table = VoxelTable::new(uvec3(4, 2, 4), 1);
+        let occupied = section_with_block(0);
+
+        let first = table.insert(ivec3(1, 0, -1), &occupied);
+        assert_eq!(table.entries()[first].slot, 0);
+
+        //Same entry, so the slot is taken over
+        let second = table.insert(ivec3(5, 2, 3), &occupied);
+        assert_eq!(first, second);
+        assert!(table.get(ivec3(1, 0, -1)).is_none());
+        assert_eq!(table.get(ivec3(5, 2, 3)).unwrap().slot, 0);
+
+        //Out of slots, so only the bricks are stored
+        let third = table.insert(ivec3(0, 0, 0), &occupied);
+        assert_eq!(table.entries()[third].slot, NO_VOXEL_SLOT);
+        assert_eq!(table.entries()[third].bricks, [1, 0]);
+
+        //Emptied sections give their slot back
+        table.insert(
+            ivec3(5, 2, 3),
+            &SectionVoxelData::from_blocks(vec![0; SECTION_VOLUME].into()),
+        );
+        assert_eq!(table.insert(ivec3(0, 0, 0), &occupied), third);
+        assert_eq!(table.get(ivec3(0, 0, 0)).unwrap().slot, 0);
+    }
+}
@@ -1,23 +1,58 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 
 use bytemuck::{Pod, Zeroable};
 use guillotiere::euclid::Size2D;
 use guillotiere::AtlasAllocator;
-use image::imageops::overlay;
-use image::{ImageBuffer, Rgba};
+use image::imageops::{overlay, replace, resize, FilterType};
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba, RgbaImage};
 use minecraft_assets::schemas;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use serde_derive::{Deserialize, Serialize};
+use wgpu::util::DeviceExt;
 use wgpu::Extent3d;
 
+use crate::mc::chunk::SECTION_TINT_SLOTS;
 use crate::mc::resource::{ResourcePath, ResourceProvider};
-use crate::texture::{TextureAndView, UV};
+use crate::mc::Scene;
+use crate::render::pipeline::BLOCK_ATLAS;
+use crate::render::shaderpack::{BlockSamplerConfig, SamplerFilter};
+use crate::texture::{ColorSpace, TextureAndView, TextureStaging, UploadStats, UV};
 use crate::{Display, WmRenderer};
 
 /// The width and height of an [atlas](Atlas];
 pub const ATLAS_DIMENSIONS: u32 = 2048;
 
+/// The mip levels of the block atlas, the full size level and vanilla's default of 4 smaller ones. 16x sprites are
+/// down to a single pixel at the smallest level, any further and they'd bleed into their neighbours
+pub const BLOCK_ATLAS_MIP_LEVELS: u32 = 5;
+
+/// The mip level which is uploaded first while an [Atlas] is streamed in, see [Atlas::upload_pending]. A quarter of the
+/// width, so the preview of a 512x pack costs about as much as a 128x one
+pub const ATLAS_PREVIEW_LEVEL: u32 = 2;
+
+/// The [AtlasLayer] holding PBR normal maps, built when a shader pack enables `pbr`
+pub const NORMAL_LAYER: &str = "normal";
+/// A normal pointing straight out of the sprite, used for sprites without a normal map
+pub const FLAT_NORMAL: Rgba<u8> = Rgba([128, 128, 255, 255]);
+/// The [AtlasLayer] holding PBR specular maps in the LabPBR layout (red is smoothness, green is reflectance or metal)
+pub const SPECULAR_LAYER: &str = "specular";
+/// Fully rough and non-metallic with ~4% reflectance, used for sprites without a specular map
+pub const DEFAULT_SPECULAR: Rgba<u8> = Rgba([0, 10, 0, 255]);
+
+/// An extra image laid out exactly like its [Atlas], e.g. normal maps. Every sprite is placed in the same rectangle
+/// as in the atlas, so shaders sample a layer with the same UVs as the atlas itself
+pub struct AtlasLayer {
+    /// Appended to a sprite's file name to find its layer image, e.g. `_n` for `stone_n.png`
+    pub suffix: String,
+    /// The color of sprites which don't have a layer image
+    pub fill: Rgba<u8>,
+    pub image: RwLock<RgbaImage>,
+    pub texture: Arc<TextureAndView>,
+}
+
 /// A texture atlas. This is used in many places, most notably terrain and entity rendering.
 /// Combines multiple small textures into a single big one, which can help improve performance.
 ///
@@ -67,9 +102,93 @@ pub struct Atlas {
     pub animated_textures: RwLock<Vec<schemas::texture::TextureAnimation>>,
     ///
     pub animated_texture_offsets: RwLock<HashMap<ResourcePath, u32>>,
+    /// Extra images sharing this atlas' layout, keyed by name, see [Atlas::add_layer]
+    pub layers: RwLock<HashMap<String, AtlasLayer>>,
+    /// The mip levels from [ATLAS_PREVIEW_LEVEL] down, bound instead of [Atlas::texture] while sprites are streamed
+    /// in, see [Atlas::bindable_texture]. Sampled with the same UVs as the full texture
+    pub preview: Arc<TextureAndView>,
+    /// The image of every mip level after the first, each half the size of the one before. There are always enough to
+    /// fill [Atlas::preview], even if the texture itself has fewer. They're updated along with the parts of the texture
+    /// which are uploaded, see [downsample]
+    mips: RwLock<Vec<RgbaImage>>,
+    /// Sprites whose [Atlas::preview] hasn't been uploaded yet, in the order they'll be streamed in
+    pending_previews: Mutex<VecDeque<ResourcePath>>,
+    /// Sprites which haven't been uploaded to [Atlas::texture] yet, in the order they'll be streamed in
+    pending_uploads: Mutex<VecDeque<ResourcePath>>,
+    /// Whether [Atlas::preview] is bound, from [Atlas::allocate] or [Atlas::restore] until every sprite was uploaded at
+    /// full size
+    streaming: AtomicBool,
+    generation: AtomicU64,
     size: u32,
 }
 
+static NEXT_ATLAS_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+/// Unique across every [Atlas], so an atlas which was replaced by a new one doesn't look unchanged
+fn next_atlas_generation() -> u64 {
+    NEXT_ATLAS_GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// How far along streaming an [Atlas] to the GPU is, see [Atlas::upload_pending]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AtlasUploadProgress {
+    /// Sprites whose preview was uploaded by this call
+    pub previewed: usize,
+    /// Sprites still waiting for their preview
+    pub previews_remaining: usize,
+    /// Sprites uploaded at full size by this call
+    pub uploaded: usize,
+    /// Sprites still waiting to be uploaded at full size
+    pub remaining: usize,
+}
+
+/// How a texture variant recolors a biome tint, see [TextureManager::register_variant_tint]. The biome's color is
+/// blended towards `color` by `amount`, so 1.0 replaces it outright and smaller amounts keep some of the difference
+/// between biomes
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TintRamp {
+    /// sRGB encoded like vanilla's biome colors, the highest byte is ignored
+    pub color: u32,
+    pub amount: f32,
+}
+
+impl TintRamp {
+    /// Blend an sRGB biome color. The highest byte is kept as it is
+    pub fn apply(&self, color: u32) -> u32 {
+        let target = self.color.to_le_bytes();
+        let mut bytes = color.to_le_bytes();
+
+        for (channel, target) in bytes[..3].iter_mut().zip(target) {
+            let blended = *channel as f32 + (target as f32 - *channel as f32) * self.amount;
+            *channel = blended.round().clamp(0.0, 255.0) as u8;
+        }
+
+        u32::from_le_bytes(bytes)
+    }
+}
+
+/// Everything needed to put an [Atlas] back the way it was without loading its sprites again, see
+/// [Atlas::snapshot]. Layers aren't included, they're loaded from the resource provider on [Atlas::restore]
+#[derive(Serialize, Deserialize)]
+pub struct AtlasSnapshot {
+    pub size: u32,
+    pub allocator: AtlasAllocator,
+    /// Sprite paths and their UVs, sorted by path
+    pub sprites: Vec<(String, UV)>,
+    #[serde(with = "serde_bytes")]
+    pub image: Vec<u8>,
+}
+
+impl Debug for AtlasSnapshot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "AtlasSnapshot {{ size: {}, sprites: {:?} }}",
+            self.size, self.sprites
+        )
+    }
+}
+
 impl Debug for Atlas {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "Atlas {{ uv_map: {:?} }}", self.uv_map.read())
@@ -78,7 +197,23 @@ impl Debug for Atlas {
 
 impl Atlas {
     pub fn new(display: &Display, _resizes: bool) -> Self {
-        let tv = TextureAndView::from_rgb_bytes(
+        Self::with_color_space(display, ColorSpace::Linear)
+    }
+
+    /// An atlas whose texture is sampled in `color_space`. Its layers are always linear, since they hold data like
+    /// normals rather than colors
+    pub fn with_color_space(display: &Display, color_space: ColorSpace) -> Self {
+        Self::with_mip_levels(display, color_space, 1)
+    }
+
+    /// An atlas whose texture has `mip_level_count` mip levels, which are generated from the sprites as they're
+    /// uploaded. Layers only have the full size level and no [Atlas::preview]
+    pub fn with_mip_levels(
+        display: &Display,
+        color_space: ColorSpace,
+        mip_level_count: u32,
+    ) -> Self {
+        let tv = TextureAndView::with_mip_levels(
             display,
             &vec![0u8; (ATLAS_DIMENSIONS * ATLAS_DIMENSIONS) as usize * 4],
             Extent3d {
@@ -87,7 +222,23 @@ impl Atlas {
                 depth_or_array_layers: 1,
             },
             None,
-            wgpu::TextureFormat::Rgba8Unorm,
+            color_space.rgba8_format(),
+            mip_level_count,
+        )
+        .unwrap();
+
+        let preview_size = ATLAS_DIMENSIONS >> ATLAS_PREVIEW_LEVEL;
+        let preview = TextureAndView::with_mip_levels(
+            display,
+            &vec![0u8; (preview_size * preview_size) as usize * 4],
+            Extent3d {
+                width: preview_size,
+                height: preview_size,
+                depth_or_array_layers: 1,
+            },
+            None,
+            color_space.rgba8_format(),
+            mip_level_count.saturating_sub(ATLAS_PREVIEW_LEVEL).max(1),
         )
         .unwrap();
 
@@ -101,11 +252,56 @@ impl Atlas {
             texture: Arc::new(tv),
             animated_textures: RwLock::new(Vec::new()),
             animated_texture_offsets: Default::default(),
+            layers: Default::default(),
+            preview: Arc::new(preview),
+            mips: RwLock::new(empty_mips(
+                ATLAS_DIMENSIONS,
+                mip_level_count.max(ATLAS_PREVIEW_LEVEL + 1),
+            )),
+            pending_previews: Default::default(),
+            pending_uploads: Default::default(),
+            streaming: AtomicBool::new(false),
+            generation: AtomicU64::new(next_atlas_generation()),
             size: ATLAS_DIMENSIONS,
         }
     }
 
-    /// Add multiple textures to the atlas. This automatically handles .mcmeta files when dealing with block textures
+    /// Changes whenever sprites are added or reloaded, or the atlas is cleared or restored and the UVs of existing
+    /// sprites may have moved. Anything which keeps UVs around, like
+    /// [crate::mc::break_particles::BlockBreakParticle], should store this along with them and look them up again in
+    /// [Atlas::uv_map] once it doesn't match anymore. Overwriting a single sprite in place (e.g. with
+    /// [Atlas::write_sprite]) keeps the generation, since UVs stay valid
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    fn bump_generation(&self) {
+        self.generation
+            .store(next_atlas_generation(), Ordering::Release);
+    }
+
+    /// The texture to bind for this atlas. That's [Atlas::preview] from [Atlas::allocate] or [Atlas::restore] until
+    /// [Atlas::upload_pending] has uploaded every sprite at full size, and [Atlas::texture] after that. See
+    /// [crate::render::graph::RenderGraph::swap_atlas_textures]
+    pub fn bindable_texture(&self) -> &Arc<TextureAndView> {
+        if self.streaming.load(Ordering::Acquire) {
+            &self.preview
+        } else {
+            &self.texture
+        }
+    }
+
+    /// Queue a sprite for both passes of [Atlas::upload_pending]
+    fn queue_upload(&self, path: &ResourcePath) {
+        self.pending_previews.lock().push_back(path.clone());
+        self.pending_uploads.lock().push_back(path.clone());
+    }
+
+    /// Add multiple textures to the atlas. This automatically handles .mcmeta files when dealing with block textures.
+    ///
+    /// Sprites are packed in [packing_order] rather than the order they're given in, so allocating the same sprites in
+    /// the same batches gives the same UVs on every run and machine. Adding, removing or resizing any sprite can still
+    /// move the others
     pub fn allocate<'a, T>(
         &self,
         images: impl IntoIterator<Item = (&'a ResourcePath, &'a T)>,
@@ -113,6 +309,12 @@ impl Atlas {
     ) where
         T: AsRef<[u8]> + 'a,
     {
+        let mut images = images
+            .into_iter()
+            .map(|(name, slice)| (name, image::load_from_memory(slice.as_ref()).unwrap()))
+            .collect::<Vec<_>>();
+        packing_order(&mut images);
+
         let mut allocator = self.allocator.write();
         let mut image_buffer = self.image.write();
         let mut map = self.uv_map.write();
@@ -120,17 +322,20 @@ impl Atlas {
         let mut animated_textures = self.animated_textures.write();
         // let mut animated_texture_offsets = self.animated_texture_offsets.write();
 
-        images.into_iter().for_each(|(name, slice)| {
+        images.iter().for_each(|(name, image)| {
             self.allocate_one(
                 &mut image_buffer,
                 &mut map,
                 &mut allocator,
                 &mut animated_textures,
                 name,
-                slice.as_ref(),
+                image,
                 resource_provider,
             );
         });
+
+        self.streaming.store(true, Ordering::Release);
+        self.bump_generation();
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -141,76 +346,672 @@ impl Atlas {
         allocator: &mut AtlasAllocator,
         animated_textures: &mut Vec<schemas::texture::TextureAnimation>,
         path: &ResourcePath,
-        image_bytes: &[u8],
+        image: &DynamicImage,
         resource_provider: &dyn ResourceProvider,
     ) {
-        let image = image::load_from_memory(image_bytes).unwrap();
-
         let allocation = allocator
             .allocate(Size2D::new(image.width() as i32, image.height() as i32))
             .unwrap();
 
         overlay(
             image_buffer,
-            &image,
+            image,
             allocation.rectangle.min.x as i64,
             allocation.rectangle.min.y as i64,
         );
 
-        let mcmeta_path = path.append(".mcmeta");
-
-        let mcmeta = resource_provider
-            .get_string(&mcmeta_path)
-            .and_then(|string| serde_json::from_str::<schemas::texture::Texture>(&string).ok());
-
-        if let Some(texture) = mcmeta {
-            if let Some(animation) = texture.animation {
-                animated_textures.push(animation)
-            }
+        if let Some(animation) = load_animation(path, resource_provider) {
+            animated_textures.push(animation)
         }
 
-        map.insert(
-            path.clone(),
+        self.queue_upload(path);
+
+        let rect = (
+            (
+                allocation.rectangle.min.x as u16,
+                allocation.rectangle.min.y as u16,
+            ),
             (
-                (
-                    allocation.rectangle.min.x as u16,
-                    allocation.rectangle.min.y as u16,
-                ),
-                (
-                    allocation.rectangle.max.x as u16,
-                    allocation.rectangle.max.y as u16,
-                ),
+                allocation.rectangle.max.x as u16,
+                allocation.rectangle.max.y as u16,
             ),
         );
+
+        for layer in self.layers.read().values() {
+            place_layer_sprite(
+                &mut layer.image.write(),
+                rect,
+                load_layer_sprite(path, &layer.suffix, resource_provider).as_ref(),
+                layer.fill,
+            );
+        }
+
+        map.insert(path.clone(), rect);
+    }
+
+    /// Add a layer sharing this atlas' layout, e.g. [NORMAL_LAYER] with the suffix `_n`. Every sprite which is already
+    /// in the atlas gets its layer image loaded from the resource provider right away, sprites allocated later are
+    /// added to the layer as they're allocated. Sprites without a layer image are filled with `fill`.
+    /// Returns false without replacing anything if a layer with this name already exists
+    pub fn add_layer(
+        &self,
+        display: &Display,
+        name: impl Into<String>,
+        suffix: impl Into<String>,
+        fill: Rgba<u8>,
+        resource_provider: &dyn ResourceProvider,
+    ) -> bool {
+        let uv_map = self.uv_map.read();
+        let mut layers = self.layers.write();
+        let name = name.into();
+
+        if layers.contains_key(&name) {
+            return false;
+        }
+
+        let suffix = suffix.into();
+        let mut image = ImageBuffer::from_pixel(self.size, self.size, fill);
+
+        for (path, rect) in uv_map.iter() {
+            place_layer_sprite(
+                &mut image,
+                *rect,
+                load_layer_sprite(path, &suffix, resource_provider).as_ref(),
+                fill,
+            );
+        }
+
+        let texture = TextureAndView::from_rgb_bytes(
+            display,
+            image.as_raw(),
+            Extent3d {
+                width: self.size,
+                height: self.size,
+                depth_or_array_layers: 1,
+            },
+            None,
+            wgpu::TextureFormat::Rgba8Unorm,
+        )
+        .unwrap();
+
+        layers.insert(
+            name,
+            AtlasLayer {
+                suffix,
+                fill,
+                image: RwLock::new(image),
+                texture: Arc::new(texture),
+            },
+        );
+
+        true
     }
 
     /// Upload the atlas texture to the GPU. If the Atlas has to resize the texture on the GPU, then the bindable_texture that this struct provides may
     /// become obsolete if you .load() the BindableTexture before calling upload(), so you should get the BindableTexture after calling this function and not before-hand.
     /// Returns true if the atlas was resized.
     pub fn upload(&self, wm: &WmRenderer) -> bool {
-        wm.display.queue.write_texture(
-            self.texture.texture.as_image_copy(),
-            self.image.read().as_raw(),
+        let full = ((0, 0), (self.size as u16, self.size as u16));
+
+        let image = self.image.read();
+
+        self.write_preview(wm, &image, full);
+        self.write_full(wm, &image, full);
+
+        for layer in self.layers.read().values() {
+            self.write_rect(wm, &layer.texture.texture, &layer.image.read(), full, 0);
+        }
+
+        self.pending_previews.lock().clear();
+        self.pending_uploads.lock().clear();
+        self.streaming.store(false, Ordering::Release);
+
+        false
+    }
+
+    /// Upload sprites which were allocated or changed since the last upload, stopping once roughly `byte_budget` bytes
+    /// have been written. Calling this once per frame instead of [Atlas::upload] streams large (e.g. 512x) packs in
+    /// over several frames, so the first frame isn't blocked on uploading the whole atlas.
+    ///
+    /// Streaming happens in two passes. Every queued sprite first gets its mips generated and the levels from
+    /// [ATLAS_PREVIEW_LEVEL] down uploaded to [Atlas::preview], which is bound until streaming is done, so the world
+    /// paints quickly at low resolution. Only then are the full size levels and [AtlasLayer]s uploaded, and once the
+    /// last one is [Atlas::bindable_texture] switches to the full texture, see
+    /// [crate::render::graph::RenderGraph::swap_atlas_textures]. Use [Atlas::prioritize_uploads] to stream visible
+    /// sprites first
+    pub fn upload_pending(&self, wm: &WmRenderer, byte_budget: usize) -> AtlasUploadProgress {
+        let image = self.image.read();
+        let uv_map = self.uv_map.read();
+        let layers = self.layers.read();
+        let mut previews = self.pending_previews.lock();
+        let mut pending = self.pending_uploads.lock();
+
+        let mut previewed = 0;
+        let mut uploaded = 0;
+        let mut bytes_written = 0;
+
+        while bytes_written < byte_budget {
+            let Some(path) = previews.pop_front() else {
+                break;
+            };
+
+            let Some(&rect) = uv_map.get(&path) else {
+                continue;
+            };
+
+            bytes_written += self.write_preview(wm, &image, rect);
+            previewed += 1;
+        }
+
+        //The full size pass reuses the mips generated for the preview, so it waits until they're all up to date
+        while bytes_written < byte_budget && previews.is_empty() {
+            let Some(path) = pending.pop_front() else {
+                break;
+            };
+
+            let Some(&rect) = uv_map.get(&path) else {
+                continue;
+            };
+
+            bytes_written += self.write_full(wm, &image, rect);
+
+            for layer in layers.values() {
+                self.write_rect(wm, &layer.texture.texture, &layer.image.read(), rect, 0);
+            }
+
+            uploaded += 1;
+            bytes_written += rect_bytes(rect) * layers.len();
+        }
+
+        if pending.is_empty() {
+            self.streaming.store(false, Ordering::Release);
+        }
+
+        AtlasUploadProgress {
+            previewed,
+            previews_remaining: previews.len(),
+            uploaded,
+            remaining: pending.len(),
+        }
+    }
+
+    /// Copy a rectangle of an image the size of `mip_level` of this atlas into the same place in `texture`
+    fn write_rect(
+        &self,
+        wm: &WmRenderer,
+        texture: &wgpu::Texture,
+        image: &RgbaImage,
+        rect: UV,
+        mip_level: u32,
+    ) {
+        let ((min_x, min_y), (max_x, max_y)) = rect;
+
+        if min_x == max_x || min_y == max_y {
+            return;
+        }
+
+        wm.mc.texture_manager.write_texture(
+            &wm.display,
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level,
+                origin: wgpu::Origin3d {
+                    x: min_x as u32,
+                    y: min_y as u32,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            image.as_raw(),
             wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * self.size),
-                rows_per_image: Some(self.size),
+                offset: (min_y as u64 * image.width() as u64 + min_x as u64) * 4,
+                bytes_per_row: Some(4 * image.width()),
+                rows_per_image: Some(image.height()),
             },
             Extent3d {
-                width: self.size,
-                height: self.size,
+                width: (max_x - min_x) as u32,
+                height: (max_y - min_y) as u32,
                 depth_or_array_layers: 1,
             },
         );
+    }
 
-        false
+    /// Regenerate the part of every mip level covering `rect` of the full size image, and upload the levels
+    /// [Atlas::preview] has. Returns the number of bytes written
+    fn write_preview(&self, wm: &WmRenderer, image: &RgbaImage, rect: UV) -> usize {
+        let mut mips = self.mips.write();
+        let mut rect = rect;
+        let mut bytes = 0;
+
+        for level in 0..mips.len() {
+            let (larger, smaller) = mips.split_at_mut(level);
+            let source = larger.last().unwrap_or(image);
+            let target = &mut smaller[0];
+
+            rect = mip_rect(rect);
+            downsample(source, target, rect);
+
+            let preview_level = (level as u32 + 1).checked_sub(ATLAS_PREVIEW_LEVEL);
+
+            if let Some(preview_level) =
+                preview_level.filter(|level| *level < self.preview.texture.mip_level_count())
+            {
+                self.write_rect(wm, &self.preview.texture, target, rect, preview_level);
+                bytes += rect_bytes(rect);
+            }
+        }
+
+        bytes
+    }
+
+    /// Upload `rect` of the full size image and of every mip level of [Atlas::texture], which have to be regenerated
+    /// by [Atlas::write_preview] first. The copies of all levels are recorded into the same upload encoder (see
+    /// [TextureStaging]) and submitted together with the next frame, so a sprite doesn't cost a submit per level.
+    /// Returns the number of bytes written
+    fn write_full(&self, wm: &WmRenderer, image: &RgbaImage, rect: UV) -> usize {
+        self.write_rect(wm, &self.texture.texture, image, rect, 0);
+
+        let mips = self.mips.read();
+        let mut rect = rect;
+        let mut bytes = rect_bytes(rect);
+
+        for (level, mip) in (1..self.texture.texture.mip_level_count()).zip(mips.iter()) {
+            rect = mip_rect(rect);
+            self.write_rect(wm, &self.texture.texture, mip, rect, level);
+            bytes += rect_bytes(rect);
+        }
+
+        bytes
+    }
+
+    /// Move these sprites to the front of both upload queues, e.g. the textures of blocks which are currently
+    /// visible. Sprites which were already uploaded are ignored
+    pub fn prioritize_uploads<'a>(&self, sprites: impl IntoIterator<Item = &'a ResourcePath>) {
+        let mut previews = self.pending_previews.lock();
+        let mut pending = self.pending_uploads.lock();
+
+        for sprite in sprites {
+            for queue in [&mut *previews, &mut *pending] {
+                if let Some(index) = queue.iter().position(|path| path == sprite) {
+                    let path = queue.remove(index).unwrap();
+                    queue.push_front(path);
+                }
+            }
+        }
+    }
+
+    /// Copy a sprite's current pixels out of the atlas image
+    pub fn read_sprite(&self, path: &ResourcePath) -> Option<RgbaImage> {
+        let ((min_x, min_y), (max_x, max_y)) = *self.uv_map.read().get(path)?;
+
+        Some(
+            self.image
+                .read()
+                .view(
+                    min_x as u32,
+                    min_y as u32,
+                    (max_x - min_x) as u32,
+                    (max_y - min_y) as u32,
+                )
+                .to_image(),
+        )
+    }
+
+    /// Overwrite an already allocated sprite in place, keeping its UVs so nothing has to be rebaked. The image is
+    /// resized to fit the sprite if needed. The change is queued for [Atlas::upload_pending]. [AtlasLayer]s keep
+    /// their image of the sprite.
+    /// Returns false if the sprite isn't in this atlas
+    pub fn write_sprite(&self, path: &ResourcePath, image: &RgbaImage) -> bool {
+        let Some(&((min_x, min_y), (max_x, max_y))) = self.uv_map.read().get(path) else {
+            return false;
+        };
+
+        let (width, height) = ((max_x - min_x) as u32, (max_y - min_y) as u32);
+
+        let resized;
+        let image = if image.dimensions() == (width, height) {
+            image
+        } else {
+            log::warn!(
+                "Resizing {} from {}x{} to {width}x{height} to fit its place in the atlas",
+                path.0,
+                image.width(),
+                image.height()
+            );
+
+            resized = resize(image, width, height, FilterType::Nearest);
+            &resized
+        };
+
+        replace(&mut *self.image.write(), image, min_x as i64, min_y as i64);
+        self.queue_upload(path);
+
+        true
+    }
+
+    /// Load every sprite and its layer images again from `resource_provider`, e.g. after the packs of a
+    /// [crate::mc::resource::LayeredResourceProvider] were reordered, and queue them for [Atlas::upload_pending]. UVs
+    /// are kept, so a sprite which changed size is scaled to fit its old place, and animations aren't reloaded. Bake
+    /// the blocks again to get both from the new packs.
+    /// Returns how many sprites were found in `resource_provider`
+    pub fn reload_sprites(&self, resource_provider: &dyn ResourceProvider) -> usize {
+        let uv_map = self.uv_map.read().clone();
+        let layers = self.layers.read();
+        let mut reloaded = 0;
+
+        for (path, rect) in &uv_map {
+            match load_layer_sprite(path, "", resource_provider) {
+                Some(image) => {
+                    self.write_sprite(path, &image);
+                    reloaded += 1;
+                }
+                //Still upload its layer images
+                None => self.queue_upload(path),
+            }
+
+            for layer in layers.values() {
+                place_layer_sprite(
+                    &mut layer.image.write(),
+                    *rect,
+                    load_layer_sprite(path, &layer.suffix, resource_provider).as_ref(),
+                    layer.fill,
+                );
+            }
+        }
+
+        self.bump_generation();
+
+        reloaded
+    }
+
+    /// Copy the allocator, UVs and image of this atlas, e.g. to save them next to baked block meshes which refer to
+    /// its UVs
+    pub fn snapshot(&self) -> AtlasSnapshot {
+        let mut sprites = self
+            .uv_map
+            .read()
+            .iter()
+            .map(|(path, uv)| (path.0.clone(), *uv))
+            .collect::<Vec<_>>();
+        sprites.sort();
+
+        AtlasSnapshot {
+            size: self.size,
+            allocator: self.allocator.read().clone(),
+            sprites,
+            image: self.image.read().as_raw().clone(),
+        }
+    }
+
+    /// Replace the contents of this atlas with a [AtlasSnapshot]. Animations and layer images are loaded again from
+    /// `resource_provider`, and every sprite is queued for upload.
+    /// Returns false without changing anything if the snapshot is for an atlas of a different size
+    pub fn restore(
+        &self,
+        snapshot: &AtlasSnapshot,
+        resource_provider: &dyn ResourceProvider,
+    ) -> bool {
+        if snapshot.size != self.size {
+            return false;
+        }
+
+        let Some(image) = ImageBuffer::from_raw(self.size, self.size, snapshot.image.clone())
+        else {
+            return false;
+        };
+
+        self.clear();
+
+        let mut uv_map = self.uv_map.write();
+        let mut animated_textures = self.animated_textures.write();
+        let layers = self.layers.read();
+
+        uv_map.clear();
+        *self.allocator.write() = snapshot.allocator.clone();
+        *self.image.write() = image;
+
+        for (path, rect) in &snapshot.sprites {
+            let path = ResourcePath(path.clone());
+
+            if let Some(animation) = load_animation(&path, resource_provider) {
+                animated_textures.push(animation);
+            }
+
+            for layer in layers.values() {
+                place_layer_sprite(
+                    &mut layer.image.write(),
+                    *rect,
+                    load_layer_sprite(&path, &layer.suffix, resource_provider).as_ref(),
+                    layer.fill,
+                );
+            }
+
+            self.queue_upload(&path);
+            uv_map.insert(path, *rect);
+        }
+
+        self.streaming.store(true, Ordering::Release);
+        self.bump_generation();
+
+        true
     }
 
     pub fn clear(&self) {
         self.allocator.write().clear();
         self.animated_texture_offsets.write().clear();
         self.animated_textures.write().clear();
+        self.pending_previews.lock().clear();
+        self.pending_uploads.lock().clear();
         *self.image.write() = ImageBuffer::new(self.size, self.size);
+
+        let mut mips = self.mips.write();
+        *mips = empty_mips(self.size, mips.len() as u32 + 1);
+
+        for layer in self.layers.read().values() {
+            *layer.image.write() = ImageBuffer::from_pixel(self.size, self.size, layer.fill);
+        }
+
+        self.bump_generation();
+    }
+}
+
+/// The animation in a sprite's `.mcmeta`, if it has one
+fn load_animation(
+    path: &ResourcePath,
+    resource_provider: &dyn ResourceProvider,
+) -> Option<schemas::texture::TextureAnimation> {
+    resource_provider
+        .get_string(&path.append(".mcmeta"))
+        .and_then(|string| serde_json::from_str::<schemas::texture::Texture>(&string).ok())
+        .and_then(|texture| texture.animation)
+}
+
+/// Where the layer image of a sprite is, with `suffix` added to the file name. Sprites named by their full texture
+/// path (`minecraft:textures/entity/pig.png`) keep it, block sprites (`minecraft:block/stone`) are looked up the same
+/// way they are when baking models, under `textures/`
+fn layer_sprite_path(path: &ResourcePath, suffix: &str) -> ResourcePath {
+    match path.0.strip_suffix(".png") {
+        Some(stem) => ResourcePath(format!("{stem}{suffix}.png")),
+        None => path.prepend("textures/").append(suffix).append(".png"),
+    }
+}
+
+fn load_layer_sprite(
+    path: &ResourcePath,
+    suffix: &str,
+    resource_provider: &dyn ResourceProvider,
+) -> Option<RgbaImage> {
+    let bytes = resource_provider.get_bytes(&layer_sprite_path(path, suffix))?;
+
+    image::load_from_memory(&bytes)
+        .ok()
+        .map(|image| image.into_rgba8())
+}
+
+/// Put a sprite's layer image in the same rectangle the sprite has in the atlas. Layer images with a different
+/// resolution than the sprite are resized so that their UVs still line up, missing ones are filled with `fill`
+fn place_layer_sprite(layer: &mut RgbaImage, rect: UV, sprite: Option<&RgbaImage>, fill: Rgba<u8>) {
+    let ((min_x, min_y), (max_x, max_y)) = rect;
+    let (width, height) = ((max_x - min_x) as u32, (max_y - min_y) as u32);
+
+    let sprite = match sprite {
+        Some(sprite) if sprite.dimensions() == (width, height) => sprite.clone(),
+        Some(sprite) => resize(sprite, width, height, FilterType::Nearest),
+        None => ImageBuffer::from_pixel(width, height, fill),
+    };
+
+    replace(layer, &sprite, min_x as i64, min_y as i64);
+}
+
+/// Transparent images for every mip level after the first of a `size` by `size` texture
+fn empty_mips(size: u32, mip_level_count: u32) -> Vec<RgbaImage> {
+    (1..mip_level_count)
+        .map(|level| ImageBuffer::new((size >> level).max(1), (size >> level).max(1)))
+        .collect()
+}
+
+/// The size of `rect` in bytes
+fn rect_bytes(rect: UV) -> usize {
+    let ((min_x, min_y), (max_x, max_y)) = rect;
+
+    (max_x - min_x) as usize * (max_y - min_y) as usize * 4
+}
+
+/// The rectangle of the next smaller mip level which `rect` is downsampled into, rounded outwards
+pub fn mip_rect(rect: UV) -> UV {
+    let ((min_x, min_y), (max_x, max_y)) = rect;
+
+    (
+        (min_x / 2, min_y / 2),
+        (max_x.div_ceil(2), max_y.div_ceil(2)),
+    )
+}
+
+/// Fill `rect` of `target` with the average of each 2x2 block of `source`, which is twice its size. Colors are
+/// weighted by their alpha, so transparent pixels of cutout textures like leaves don't darken the edges around them
+pub fn downsample(source: &RgbaImage, target: &mut RgbaImage, rect: UV) {
+    let ((min_x, min_y), (max_x, max_y)) = rect;
+
+    for y in min_y as u32..(max_y as u32).min(target.height()) {
+        for x in min_x as u32..(max_x as u32).min(target.width()) {
+            let mut color = [0u32; 3];
+            let mut alpha = 0u32;
+
+            let texels = [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| {
+                source.get_pixel(
+                    (x * 2 + dx).min(source.width() - 1),
+                    (y * 2 + dy).min(source.height() - 1),
+                )
+            });
+
+            for texel in texels {
+                let [r, g, b, a] = texel.0.map(u32::from);
+
+                color[0] += r * a;
+                color[1] += g * a;
+                color[2] += b * a;
+                alpha += a;
+            }
+
+            let [r, g, b] = if alpha == 0 {
+                [0, 1, 2].map(|channel| {
+                    texels
+                        .iter()
+                        .map(|texel| texel.0[channel] as u32)
+                        .sum::<u32>()
+                        / 4
+                })
+            } else {
+                color.map(|channel| (channel + alpha / 2) / alpha)
+            };
+
+            target.put_pixel(
+                x,
+                y,
+                Rgba([r as u8, g as u8, b as u8, ((alpha + 2) / 4) as u8]),
+            );
+        }
+    }
+}
+
+/// A texture with its own sampler instead of a place in an atlas, see [TextureManager::register_standalone]
+#[derive(Debug)]
+pub struct StandaloneTexture {
+    pub texture: Arc<TextureAndView>,
+    pub sampler: Arc<wgpu::Sampler>,
+}
+
+/// Sorts sprites by path, then by size. guillotiere's layout depends on the order sprites are allocated in, so this is
+/// what keeps packing stable
+pub fn packing_order(images: &mut [(&ResourcePath, DynamicImage)]) {
+    images.sort_by(|(a_path, a), (b_path, b)| {
+        a_path
+            .0
+            .cmp(&b_path.0)
+            .then_with(|| a.dimensions().cmp(&b.dimensions()))
+    });
+}
+
+/// How sharp textures sampled with `@sampler` look, see [TextureManager::set_texture_quality]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextureQuality {
+    /// The maximum anisotropy, from 1 (off) to 16. wgpu only allows it for samplers which filter linearly, so it's
+    /// ignored unless every filter of the shader pack's `block_sampler` is `linear`
+    pub anisotropy: u16,
+    /// Added to the mip level which is sampled, negative values sharpen distant textures and positive ones blur them.
+    /// WebGPU samplers have no LOD bias, so shaders apply it themselves with `@buffer_texture_quality`
+    pub mip_bias: f32,
+    /// The smallest mip level which is sampled, as a level index
+    pub max_mip: f32,
+}
+
+impl Default for TextureQuality {
+    fn default() -> Self {
+        Self {
+            anisotropy: 1,
+            mip_bias: 0.0,
+            max_mip: 32.0,
+        }
+    }
+}
+
+/// Why [TextureManager::set_texture_quality] rejected a [TextureQuality]
+#[derive(Debug, PartialEq)]
+pub enum TextureQualityError {
+    /// Anisotropy has to be between 1 and 16
+    Anisotropy(u16),
+    /// The adapter doesn't support anisotropic filtering at all
+    AnisotropyUnsupported,
+    /// Shaders can only bias the mip level by -16 to 16
+    MipBias(f32),
+    MaxMip(f32),
+}
+
+impl TextureQuality {
+    /// Checks the settings against what an adapter with `downlevel` capabilities supports
+    pub fn validate(&self, downlevel: wgpu::DownlevelFlags) -> Result<(), TextureQualityError> {
+        if !(1..=16).contains(&self.anisotropy) {
+            return Err(TextureQualityError::Anisotropy(self.anisotropy));
+        }
+
+        if self.anisotropy > 1 && !downlevel.contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING) {
+            return Err(TextureQualityError::AnisotropyUnsupported);
+        }
+
+        if !(-16.0..=16.0).contains(&self.mip_bias) {
+            return Err(TextureQualityError::MipBias(self.mip_bias));
+        }
+
+        if self.max_mip.is_nan() || self.max_mip < 0.0 {
+            return Err(TextureQualityError::MaxMip(self.max_mip));
+        }
+
+        Ok(())
+    }
+
+    /// The contents of `@buffer_texture_quality`, the mip bias padded to 16 bytes
+    pub fn uniform(&self) -> [f32; 4] {
+        [self.mip_bias, 0.0, 0.0, 0.0]
     }
 }
 
@@ -218,8 +1019,24 @@ impl Atlas {
 #[derive(Debug)]
 pub struct TextureManager {
     pub default_sampler: Arc<wgpu::Sampler>,
+    /// Bound as `@buffer_texture_quality`, see [TextureQuality::mip_bias]
+    pub texture_quality_buffer: Arc<wgpu::Buffer>,
+    texture_quality: RwLock<TextureQuality>,
 
     pub atlases: RwLock<HashMap<String, Atlas>>,
+    pub standalone: RwLock<HashMap<ResourcePath, StandaloneTexture>>,
+    /// The color space each atlas is created in by [TextureManager::create_atlas], atlases without an entry are
+    /// linear. Set the block atlas' before [WmRenderer::init] to light block textures in linear space
+    pub atlas_color_spaces: RwLock<HashMap<String, ColorSpace>>,
+
+    /// Alternate sprites keyed by variant id, then by atlas name and sprite, see [TextureManager::set_variant]
+    variants: RwLock<HashMap<String, HashMap<(String, ResourcePath), RgbaImage>>>,
+    /// Biome tint recolors keyed by variant id, indexed by tint index, see [TextureManager::register_variant_tint]
+    variant_tints: RwLock<HashMap<String, [Option<TintRamp>; SECTION_TINT_SLOTS]>>,
+    /// The pixels of sprites which the active variant replaced, so they can be restored
+    variant_originals: RwLock<HashMap<(String, ResourcePath), RgbaImage>>,
+    active_variant: RwLock<Option<String>>,
+    staging: Mutex<TextureStaging>,
 }
 
 impl TextureManager {
@@ -235,10 +1052,325 @@ impl TextureManager {
             ..Default::default()
         });
 
+        let texture_quality = TextureQuality::default();
+        let texture_quality_buffer =
+            wgpu_state
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("@buffer_texture_quality"),
+                    contents: bytemuck::cast_slice(&texture_quality.uniform()),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+
         Self {
             default_sampler: Arc::new(sampler),
+            texture_quality_buffer: Arc::new(texture_quality_buffer),
+            texture_quality: RwLock::new(texture_quality),
             atlases: RwLock::new(HashMap::new()),
+            standalone: RwLock::new(HashMap::new()),
+            atlas_color_spaces: Default::default(),
+            variants: Default::default(),
+            variant_tints: Default::default(),
+            variant_originals: Default::default(),
+            active_variant: Default::default(),
+            staging: Default::default(),
+        }
+    }
+
+    pub fn texture_quality(&self) -> TextureQuality {
+        *self.texture_quality.read()
+    }
+
+    /// Change the anisotropy and mip bias of `@sampler` together. The mip bias takes effect right away, the sampler
+    /// itself is rebuilt by [crate::render::graph::RenderGraph::rebuild_block_sampler], or when the next render graph
+    /// is created
+    pub fn set_texture_quality(
+        &self,
+        display: &Display,
+        quality: TextureQuality,
+    ) -> Result<(), TextureQualityError> {
+        quality.validate(display.adapter.get_downlevel_capabilities().flags)?;
+
+        display.queue.write_buffer(
+            &self.texture_quality_buffer,
+            0,
+            bytemuck::cast_slice(&quality.uniform()),
+        );
+        *self.texture_quality.write() = quality;
+
+        Ok(())
+    }
+
+    /// Create the sampler bound as `@sampler` with a shader pack's filters and the current [TextureQuality]
+    pub fn create_block_sampler(
+        &self,
+        display: &Display,
+        config: &BlockSamplerConfig,
+    ) -> wgpu::Sampler {
+        let quality = self.texture_quality();
+        let filters = [config.mag_filter, config.min_filter, config.mipmap_filter];
+
+        display.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("@sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: config.mag_filter.into(),
+            min_filter: config.min_filter.into(),
+            mipmap_filter: config.mipmap_filter.into(),
+            lod_max_clamp: quality.max_mip,
+            anisotropy_clamp: if filters.contains(&SamplerFilter::Nearest) {
+                1
+            } else {
+                quality.anisotropy
+            },
+            ..Default::default()
+        })
+    }
+
+    /// Upload RGBA8 pixels into a region of a texture, like [wgpu::Queue::write_texture] but small uploads reuse
+    /// staging memory, see [TextureStaging]. Use this for textures which are updated often. The upload is submitted
+    /// with the next frame, or by [TextureManager::flush_uploads]
+    pub fn write_texture(
+        &self,
+        display: &Display,
+        texture: wgpu::ImageCopyTexture,
+        data: &[u8],
+        layout: wgpu::ImageDataLayout,
+        size: Extent3d,
+    ) {
+        self.staging
+            .lock()
+            .write_texture(display, texture, data, layout, size);
+    }
+
+    /// Submit the uploads made through [TextureManager::write_texture] since the last flush, which
+    /// [crate::render::graph::RenderGraph::render] does at the start of every frame
+    pub fn flush_uploads(&self, display: &Display) {
+        self.staging.lock().flush(display);
+    }
+
+    /// What went through [TextureManager::write_texture] last frame
+    pub fn upload_stats(&self) -> UploadStats {
+        self.staging.lock().last_frame()
+    }
+
+    /// Register an alternate image for a sprite, used while `variant` (e.g. a season) is active
+    pub fn register_variant_sprite(
+        &self,
+        variant: impl Into<String>,
+        atlas: impl Into<String>,
+        sprite: ResourcePath,
+        image_bytes: &[u8],
+    ) -> Result<(), image::ImageError> {
+        let image = image::load_from_memory(image_bytes)?.into_rgba8();
+
+        self.variants
+            .write()
+            .entry(variant.into())
+            .or_default()
+            .insert((atlas.into(), sprite), image);
+
+        Ok(())
+    }
+
+    /// Recolor the biome tint with `tint_index` while `variant` is active, e.g. browning grass in autumn. Only sections
+    /// baked with [crate::mc::chunk::TintMode::PerSection] can be recolored without rebaking, tints baked into the
+    /// vertices keep their colors until the section is baked again.
+    /// Returns false if the tint index has no slot in the section tint buffer
+    pub fn register_variant_tint(
+        &self,
+        variant: impl Into<String>,
+        tint_index: usize,
+        ramp: TintRamp,
+    ) -> bool {
+        if tint_index >= SECTION_TINT_SLOTS {
+            return false;
         }
+
+        self.variant_tints
+            .write()
+            .entry(variant.into())
+            .or_default()[tint_index] = Some(ramp);
+
+        true
+    }
+
+    pub fn active_variant(&self) -> Option<String> {
+        self.active_variant.read().clone()
+    }
+
+    /// The tint recolors of the active variant, indexed by tint index, see [Scene::set_section_tints]
+    pub fn tint_ramps(&self) -> [Option<TintRamp>; SECTION_TINT_SLOTS] {
+        self.active_variant
+            .read()
+            .as_ref()
+            .and_then(|variant| self.variant_tints.read().get(variant).copied())
+            .unwrap_or_default()
+    }
+
+    /// Switch which variant's sprites and tints are active, or back to the originals with `None`. Sprites are
+    /// overwritten in place, so UVs, baked geometry and bind groups all stay valid, and the section tint buffer of
+    /// `scene` is rewritten with the variant's [TintRamp]s. Both are uploaded immediately so the change is visible on
+    /// the next frame
+    pub fn set_variant(&self, wm: &WmRenderer, scene: &Scene, variant: Option<&str>) {
+        let variants = self.variants.read();
+        let mut originals = self.variant_originals.write();
+        let atlases = self.atlases.read();
+
+        let new_sprites = variant.and_then(|variant| variants.get(variant));
+
+        //Restore sprites which the new variant doesn't replace
+        originals.retain(|(atlas, sprite), original| {
+            if new_sprites
+                .is_some_and(|sprites| sprites.contains_key(&(atlas.clone(), sprite.clone())))
+            {
+                return true;
+            }
+
+            if let Some(atlas) = atlases.get(atlas) {
+                atlas.write_sprite(sprite, original);
+            }
+
+            false
+        });
+
+        for ((atlas_name, sprite), image) in new_sprites.into_iter().flatten() {
+            let Some(atlas) = atlases.get(atlas_name) else {
+                continue;
+            };
+
+            if !originals.contains_key(&(atlas_name.clone(), sprite.clone())) {
+                let Some(original) = atlas.read_sprite(sprite) else {
+                    continue;
+                };
+
+                originals.insert((atlas_name.clone(), sprite.clone()), original);
+            }
+
+            atlas.write_sprite(sprite, image);
+        }
+
+        for atlas in atlases.values() {
+            atlas.upload_pending(wm, usize::MAX);
+        }
+
+        *self.active_variant.write() = variant.map(String::from);
+
+        scene.upload_section_tints(wm);
+    }
+
+    /// Register a new, empty [Atlas] which is packed and uploaded independently of the others, in the color space set
+    /// in [TextureManager::atlas_color_spaces]. Shader packs can reference it as `@texture_atlas_<name>`, see
+    /// [TextureManager::atlas_resource_name]. Atlases a shader pack references which don't exist yet are created when
+    /// the [crate::render::graph::RenderGraph] is built, so sprites can be allocated into them afterwards. Returns
+    /// false without replacing anything if an atlas with this name already exists
+    pub fn create_atlas(&self, display: &Display, name: impl Into<String>) -> bool {
+        let mut atlases = self.atlases.write();
+        let name = name.into();
+
+        if atlases.contains_key(&name) {
+            return false;
+        }
+
+        let color_space = self
+            .atlas_color_spaces
+            .read()
+            .get(&name)
+            .copied()
+            .unwrap_or_default();
+
+        let mip_level_count = if name == BLOCK_ATLAS {
+            BLOCK_ATLAS_MIP_LEVELS
+        } else {
+            1
+        };
+
+        atlases.insert(
+            name,
+            Atlas::with_mip_levels(display, color_space, mip_level_count),
+        );
+        true
+    }
+
+    /// The [Atlas::generation] of an atlas, None if there's no atlas with this name
+    pub fn atlas_generation(&self, name: &str) -> Option<u64> {
+        self.atlases.read().get(name).map(Atlas::generation)
+    }
+
+    /// The name a shader pack uses to bind an atlas' texture. The whole atlas name is used so atlases in different
+    /// namespaces or folders can't collide, `wgpu_mc:atlases/block` becomes `@texture_atlas_wgpu_mc:atlases/block`
+    pub fn atlas_resource_name(name: &str) -> String {
+        format!("@texture_atlas_{name}")
+    }
+
+    /// The atlas a shader pack resource binds, the reverse of [TextureManager::atlas_resource_name]. Resources of
+    /// the atlas' [AtlasLayer]s give the atlas too. None for resources which aren't atlases
+    pub fn resource_atlas_name(resource_id: &str) -> Option<&str> {
+        let name = resource_id.strip_prefix("@texture_atlas_")?;
+
+        Some(
+            [NORMAL_LAYER, SPECULAR_LAYER]
+                .into_iter()
+                .find_map(|layer| name.strip_suffix(layer)?.strip_suffix('_'))
+                .unwrap_or(name),
+        )
+    }
+
+    /// Load a texture on its own rather than into an atlas, so it can be sampled with `address_mode`. Use this for
+    /// textures which tile across a surface, like the GUI dirt background or the end sky, since sprites sampled past
+    /// their edge in an atlas pick up their neighbours. Everything else should stay atlased, each standalone texture
+    /// is another bind group switch. Textures uploaded through the GL pipeline are never atlased, and already repeat.
+    ///
+    /// `path` is the full path of the image, e.g. `minecraft:textures/gui/options_background.png`. Shader packs created
+    /// afterwards can bind it as `@texture_standalone_<path>` and `@sampler_standalone_<path>`, see
+    /// [TextureManager::standalone_resource_names]. The GL path binds it by a GL texture id instead, sampled with its
+    /// own repeating sampler. Returns false if the image couldn't be loaded
+    pub fn register_standalone(
+        &self,
+        display: &Display,
+        resource_provider: &dyn ResourceProvider,
+        path: ResourcePath,
+        address_mode: wgpu::AddressMode,
+    ) -> bool {
+        let Some(texture) = resource_provider
+            .get_bytes(&path)
+            .and_then(|bytes| TextureAndView::from_image_file_bytes(display, &bytes, &path.0).ok())
+        else {
+            return false;
+        };
+
+        let sampler = display.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(&path.0),
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        self.standalone.write().insert(
+            path,
+            StandaloneTexture {
+                texture: Arc::new(texture),
+                sampler: Arc::new(sampler),
+            },
+        );
+
+        true
+    }
+
+    /// The names a shader pack uses to bind a standalone texture and its sampler. The whole path is used so textures
+    /// with the same file name in different folders can't collide, `minecraft:textures/gui/options_background.png`
+    /// becomes `@texture_standalone_minecraft:textures/gui/options_background.png` and
+    /// `@sampler_standalone_minecraft:textures/gui/options_background.png`
+    pub fn standalone_resource_names(path: &ResourcePath) -> (String, String) {
+        (
+            format!("@texture_standalone_{}", path.0),
+            format!("@sampler_standalone_{}", path.0),
+        )
     }
 }
 
@@ -296,3 +1428,247 @@ struct AnimatedUV {
 //         out
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageBuffer, Rgba, RgbaImage};
+
+    use crate::mc::resource::ResourcePath;
+
+    use super::{
+        downsample, layer_sprite_path, mip_rect, packing_order, place_layer_sprite, TextureManager,
+        TextureQuality, TextureQualityError, TintRamp, FLAT_NORMAL,
+    };
+
+    const RECT: ((u16, u16), (u16, u16)) = ((16, 32), (32, 48));
+
+    fn layer_pixels(layer: &RgbaImage) -> Vec<(u32, u32)> {
+        layer
+            .enumerate_pixels()
+            .filter(|(_, _, pixel)| **pixel != Rgba([0, 0, 0, 0]))
+            .map(|(x, y, _)| (x, y))
+            .collect()
+    }
+
+    #[test]
+    fn mips_keep_cutout_colors() {
+        //A 2x2 leaf texel next to a fully transparent one
+        let mut source = RgbaImage::new(4, 2);
+        source.put_pixel(0, 0, Rgba([40, 120, 20, 255]));
+        source.put_pixel(1, 0, Rgba([0, 0, 0, 0]));
+        source.put_pixel(0, 1, Rgba([40, 120, 20, 255]));
+        source.put_pixel(1, 1, Rgba([0, 0, 0, 0]));
+        source.put_pixel(2, 0, Rgba([255, 255, 255, 255]));
+
+        let mut target = RgbaImage::new(2, 1);
+        downsample(&source, &mut target, ((0, 0), (2, 1)));
+
+        assert_eq!(target.get_pixel(0, 0), &Rgba([40, 120, 20, 128]));
+        assert_eq!(target.get_pixel(1, 0), &Rgba([255, 255, 255, 64]));
+    }
+
+    #[test]
+    fn texture_quality_is_checked_against_the_adapter() {
+        let sharp = TextureQuality {
+            anisotropy: 16,
+            mip_bias: -0.5,
+            max_mip: 4.0,
+        };
+
+        assert_eq!(sharp.validate(wgpu::DownlevelFlags::all()), Ok(()));
+        assert_eq!(
+            sharp.validate(wgpu::DownlevelFlags::empty()),
+            Err(TextureQualityError::AnisotropyUnsupported)
+        );
+        assert_eq!(
+            TextureQuality {
+                anisotropy: 1,
+                ..sharp
+            }
+            .validate(wgpu::DownlevelFlags::empty()),
+            Ok(())
+        );
+
+        assert_eq!(
+            TextureQuality {
+                anisotropy: 32,
+                ..sharp
+            }
+            .validate(wgpu::DownlevelFlags::all()),
+            Err(TextureQualityError::Anisotropy(32))
+        );
+        assert!(TextureQuality {
+            mip_bias: f32::NAN,
+            ..sharp
+        }
+        .validate(wgpu::DownlevelFlags::all())
+        .is_err());
+        assert!(TextureQuality {
+            max_mip: -1.0,
+            ..sharp
+        }
+        .validate(wgpu::DownlevelFlags::all())
+        .is_err());
+    }
+
+    #[test]
+    fn mip_rects_cover_odd_sprites() {
+        assert_eq!(mip_rect(RECT), ((8, 16), (16, 24)));
+        assert_eq!(mip_rect(((3, 5), (9, 6))), ((1, 2), (5, 3)));
+        assert_eq!(mip_rect(((0, 0), (1, 1))), ((0, 0), (1, 1)));
+    }
+
+    #[test]
+    fn tint_ramps_blend_biome_colors() {
+        let autumn = TintRamp {
+            color: 0x00_c0_60_20,
+            amount: 0.5,
+        };
+
+        assert_eq!(autumn.apply(0xff_40_a0_40), 0xff_80_80_30);
+        assert_eq!(
+            TintRamp {
+                amount: 1.0,
+                ..autumn
+            }
+            .apply(0x00_40_a0_40),
+            0x00_c0_60_20
+        );
+        assert_eq!(
+            TintRamp {
+                amount: 0.0,
+                ..autumn
+            }
+            .apply(0x00_40_a0_40),
+            0x00_40_a0_40
+        );
+    }
+
+    #[test]
+    fn layer_sprite_matches_atlas_rect() {
+        let mut layer = RgbaImage::new(64, 64);
+        let normal = ImageBuffer::from_pixel(16, 16, Rgba([255, 128, 128, 255]));
+
+        place_layer_sprite(&mut layer, RECT, Some(&normal), FLAT_NORMAL);
+
+        let pixels = layer_pixels(&layer);
+        assert_eq!(pixels.len(), 16 * 16);
+        assert_eq!(pixels.first(), Some(&(16, 32)));
+        assert_eq!(pixels.last(), Some(&(31, 47)));
+    }
+
+    #[test]
+    fn layer_sprite_resized_to_atlas_rect() {
+        let mut layer = RgbaImage::new(64, 64);
+        let normal = ImageBuffer::from_pixel(64, 64, Rgba([255, 128, 128, 255]));
+
+        place_layer_sprite(&mut layer, RECT, Some(&normal), FLAT_NORMAL);
+
+        let pixels = layer_pixels(&layer);
+        assert_eq!(pixels.len(), 16 * 16);
+        assert_eq!(pixels.first(), Some(&(16, 32)));
+    }
+
+    #[test]
+    fn missing_layer_sprite_filled() {
+        let mut layer = RgbaImage::new(64, 64);
+
+        place_layer_sprite(&mut layer, RECT, None, FLAT_NORMAL);
+
+        assert_eq!(*layer.get_pixel(20, 40), FLAT_NORMAL);
+        assert_eq!(layer_pixels(&layer).len(), 16 * 16);
+    }
+
+    #[test]
+    fn layer_sprite_paths() {
+        assert_eq!(
+            layer_sprite_path(&ResourcePath("minecraft:block/stone".into()), "_n").0,
+            "minecraft:textures/block/stone_n.png"
+        );
+        assert_eq!(
+            layer_sprite_path(
+                &ResourcePath("minecraft:textures/entity/pig.png".into()),
+                "_n"
+            )
+            .0,
+            "minecraft:textures/entity/pig_n.png"
+        );
+    }
+
+    #[test]
+    fn standalone_resource_names() {
+        assert_eq!(
+            TextureManager::standalone_resource_names(&ResourcePath(
+                "minecraft:textures/gui/options_background.png".into()
+            )),
+            (
+                "@texture_standalone_minecraft:textures/gui/options_background.png".to_string(),
+                "@sampler_standalone_minecraft:textures/gui/options_background.png".to_string()
+            )
+        );
+
+        //Same file name in another folder
+        assert_ne!(
+            TextureManager::standalone_resource_names(&ResourcePath(
+                "minecraft:textures/environment/options_background.png".into()
+            )),
+            TextureManager::standalone_resource_names(&ResourcePath(
+                "minecraft:textures/gui/options_background.png".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn atlas_resource_names() {
+        assert_eq!(
+            TextureManager::atlas_resource_name("wgpu_mc:atlases/block"),
+            "@texture_atlas_wgpu_mc:atlases/block"
+        );
+        assert_ne!(
+            TextureManager::atlas_resource_name("a:atlases/block"),
+            TextureManager::atlas_resource_name("b:atlases/block")
+        );
+
+        for resource_id in [
+            "@texture_atlas_wgpu_mc:atlases/block",
+            "@texture_atlas_wgpu_mc:atlases/block_normal",
+            "@texture_atlas_wgpu_mc:atlases/block_specular",
+        ] {
+            assert_eq!(
+                TextureManager::resource_atlas_name(resource_id),
+                Some("wgpu_mc:atlases/block")
+            );
+        }
+
+        assert_eq!(
+            TextureManager::resource_atlas_name("@texture_block_atlas"),
+            None
+        );
+    }
+
+    #[test]
+    fn packing_order_ignores_insertion_order() {
+        let stone = ResourcePath("minecraft:block/stone".into());
+        let dirt = ResourcePath("minecraft:block/dirt".into());
+        let sprite = |size| image::DynamicImage::new_rgba8(size, size);
+
+        let mut images = vec![
+            (&stone, sprite(16)),
+            (&dirt, sprite(32)),
+            (&dirt, sprite(16)),
+        ];
+        packing_order(&mut images);
+
+        assert_eq!(
+            images
+                .iter()
+                .map(|(path, image)| (path.0.as_str(), image.width()))
+                .collect::<Vec<_>>(),
+            [
+                ("minecraft:block/dirt", 16),
+                ("minecraft:block/dirt", 32),
+                ("minecraft:block/stone", 16)
+            ]
+        );
+    }
+}
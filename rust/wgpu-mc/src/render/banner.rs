@@ -0,0 +1,153 @@
+//! Banners and shields are static block entities drawn with the entity pipeline, their designs are a stack of
+//! dyed pattern layers. Instead of drawing a quad per layer, the layers are composited into one texture here which
+//! the banner or shield model is then drawn with, see [bake_banner]
+
+use image::imageops::{resize, FilterType};
+use image::RgbaImage;
+
+use crate::mc::resource::ResourcePath;
+use crate::render::atlas::Atlas;
+
+/// The RGB of each dye color as used to tint banner patterns, in the order of vanilla's `DyeColor` ids
+pub const DYE_COLORS: [[u8; 3]; 16] = [
+    [0xf9, 0xff, 0xfe], //white
+    [0xf9, 0x80, 0x1d], //orange
+    [0xc7, 0x4e, 0xbd], //magenta
+    [0x3a, 0xb3, 0xda], //light_blue
+    [0xfe, 0xd8, 0x3d], //yellow
+    [0x80, 0xc7, 0x1f], //lime
+    [0xf3, 0x8b, 0xaa], //pink
+    [0x47, 0x4f, 0x52], //gray
+    [0x9d, 0x9d, 0x97], //light_gray
+    [0x16, 0x9c, 0x9c], //cyan
+    [0x89, 0x32, 0xb8], //purple
+    [0x3c, 0x44, 0xaa], //blue
+    [0x83, 0x54, 0x32], //brown
+    [0x5e, 0x7c, 0x16], //green
+    [0xb0, 0x2e, 0x26], //red
+    [0x1d, 0x1d, 0x21], //black
+];
+
+/// One dyed pattern of a banner, e.g. `minecraft:entity/banner/stripe_bottom` in red
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BannerLayer {
+    /// The pattern's sprite. Patterns are white masks, their alpha is where the pattern is
+    pub pattern: ResourcePath,
+    pub color: [u8; 3],
+}
+
+/// Stack tinted pattern masks over `base` in order. Each pattern is multiplied by its color and blended over what's
+/// below by its alpha, like vanilla draws the layers. Patterns of a different size are scaled to `base`
+pub fn composite_banner<'a>(
+    base: &RgbaImage,
+    layers: impl IntoIterator<Item = (&'a RgbaImage, [u8; 3])>,
+) -> RgbaImage {
+    let mut banner = base.clone();
+
+    for (pattern, color) in layers {
+        let resized;
+        let pattern = if pattern.dimensions() == banner.dimensions() {
+            pattern
+        } else {
+            resized = resize(
+                pattern,
+                banner.width(),
+                banner.height(),
+                FilterType::Nearest,
+            );
+            &resized
+        };
+
+        for (below, mask) in banner.pixels_mut().zip(pattern.pixels()) {
+            let alpha = mask[3] as u32;
+
+            for channel in 0..3 {
+                let tinted = mask[channel] as u32 * color[channel] as u32 / 255;
+                below[channel] =
+                    ((tinted * alpha + below[channel] as u32 * (255 - alpha)) / 255) as u8;
+            }
+            below[3] = below[3].max(mask[3]);
+        }
+    }
+
+    banner
+}
+
+/// Composite a banner or shield design from sprites in `atlas`, where `base` is the undyed texture of the model
+/// (e.g. `minecraft:entity/banner_base`) and the first layer is normally the `base` pattern in the banner's own
+/// color. The result can be written into the texture the model is drawn with, for example with
+/// [Atlas::write_sprite]. Returns `None` if any of the sprites aren't in the atlas
+pub fn bake_banner(
+    atlas: &Atlas,
+    base: &ResourcePath,
+    layers: &[BannerLayer],
+) -> Option<RgbaImage> {
+    let base = atlas.read_sprite(base)?;
+    let patterns = layers
+        .iter()
+        .map(|layer| atlas.read_sprite(&layer.pattern))
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(composite_banner(
+        &base,
+        patterns
+            .iter()
+            .zip(layers)
+            .map(|(pattern, layer)| (pattern, layer.color)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::{composite_banner, DYE_COLORS};
+
+    /// A white mask covering the rows from `from` up to `to`
+    fn stripe(from: u32, to: u32) -> RgbaImage {
+        RgbaImage::from_fn(4, 4, |_, y| {
+            if (from..to).contains(&y) {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        })
+    }
+
+    #[test]
+    fn layers_stack_in_order() {
+        let base = RgbaImage::from_pixel(4, 4, Rgba([128, 128, 128, 255]));
+        let (red, blue) = (DYE_COLORS[14], DYE_COLORS[11]);
+
+        let banner = composite_banner(
+            &base,
+            [
+                (&stripe(0, 4), red),
+                (&stripe(2, 4), blue),
+                (&stripe(0, 0), red),
+            ],
+        );
+
+        assert_eq!(banner.get_pixel(0, 0).0, [red[0], red[1], red[2], 255]);
+        assert_eq!(banner.get_pixel(3, 3).0, [blue[0], blue[1], blue[2], 255]);
+    }
+
+    #[test]
+    fn transparent_patterns_keep_the_base() {
+        let base = RgbaImage::from_pixel(4, 4, Rgba([10, 20, 30, 255]));
+
+        assert_eq!(
+            composite_banner(&base, [(&stripe(0, 0), DYE_COLORS[0])]),
+            base
+        );
+    }
+
+    #[test]
+    fn patterns_are_scaled_to_the_base() {
+        let base = RgbaImage::from_pixel(8, 8, Rgba([0, 0, 0, 255]));
+        let banner = composite_banner(&base, [(&stripe(0, 2), [255, 255, 255])]);
+
+        assert_eq!(banner.get_pixel(7, 3).0, [255, 255, 255, 255]);
+        assert_eq!(banner.get_pixel(7, 4).0, [0, 0, 0, 255]);
+    }
+}
@@ -0,0 +1,93 @@
+//! The round shadows vanilla draws under entities when shadow mapping isn't used. Each shadow is a single quad lying
+//! on the ground below its entity, so it follows the height of the surface but doesn't wrap over ledges or slopes.
+
+/// How far above the ground, in blocks, an entity's shadow has faded out completely
+pub const BLOB_SHADOW_FADE_HEIGHT: f32 = 2.0;
+
+/// The shadow of one entity, see [crate::mc::Scene::blob_shadows]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BlobShadow {
+    /// The point on the ground directly below the entity, in render space
+    pub ground_position: [f32; 3],
+    /// The width of the entity's bounding box, which is the diameter of the shadow
+    pub width: f32,
+    /// How far the entity's feet are above `ground_position`
+    pub height_above_ground: f32,
+    pub enabled: bool,
+}
+
+impl BlobShadow {
+    /// The shadow's instance data with `strength` applied, or `None` if it's disabled or has faded out
+    pub fn instance(&self, strength: f32) -> Option<BlobShadowInstance> {
+        let opacity = strength * (1.0 - self.height_above_ground / BLOB_SHADOW_FADE_HEIGHT);
+
+        (self.enabled && opacity > 0.0).then_some(BlobShadowInstance {
+            center: self.ground_position,
+            radius: self.width * 0.5,
+            opacity: opacity.min(1.0),
+        })
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BlobShadowInstance {
+    pub center: [f32; 3],
+    pub radius: f32,
+    pub opacity: f32,
+}
+
+impl BlobShadowInstance {
+    const VAA: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32,
+        2 => Float32
+    ];
+
+    /// Instances are the only vertex buffer, the shader builds each quad from the vertex index
+    #[must_use]
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<BlobShadowInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::VAA,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlobShadow, BLOB_SHADOW_FADE_HEIGHT};
+
+    fn shadow(height_above_ground: f32) -> BlobShadow {
+        BlobShadow {
+            ground_position: [0.0, 64.0, 0.0],
+            width: 0.6,
+            height_above_ground,
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn fades_with_height() {
+        let grounded = shadow(0.0).instance(0.5).unwrap();
+        assert_eq!(grounded.opacity, 0.5);
+        assert_eq!(grounded.radius, 0.3);
+
+        let jumping = shadow(BLOB_SHADOW_FADE_HEIGHT * 0.5).instance(0.5).unwrap();
+        assert_eq!(jumping.opacity, 0.25);
+
+        assert!(shadow(BLOB_SHADOW_FADE_HEIGHT).instance(0.5).is_none());
+    }
+
+    #[test]
+    fn disabled_shadows_are_skipped() {
+        let shadow = BlobShadow {
+            enabled: false,
+            ..shadow(0.0)
+        };
+
+        assert!(shadow.instance(1.0).is_none());
+    }
+}
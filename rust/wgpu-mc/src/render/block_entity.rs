@@ -0,0 +1,164 @@
+//! Animated block entities (end portals, conduits, enchanting table books) are drawn as instanced custom geometry,
+//! with a per-instance animation phase and a shared animation time. Static block entities don't need any of this
+//! and should keep being baked into terrain or drawn as regular entities.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use wgpu::util::DeviceExt;
+
+use crate::render::decal::DecalVertex;
+use crate::render::graph::{set_push_constants, BoundPipeline, Geometry, RenderGraph};
+use crate::util::WmArena;
+use crate::WmRenderer;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct AnimatedBlockEntityInstance {
+    /// The block position in render space, see [crate::mc::Scene::render_origin]
+    pub position: [f32; 3],
+    /// Seconds added to the animation time, so that identical block entities don't all animate in lockstep
+    pub phase: f32,
+}
+
+impl AnimatedBlockEntityInstance {
+    const VAA: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
+        2 => Float32x3,
+        3 => Float32
+    ];
+
+    #[must_use]
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<AnimatedBlockEntityInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::VAA,
+        }
+    }
+}
+
+/// One block entity mesh drawn once per [AnimatedBlockEntityInstance]. Register it as custom geometry, with
+/// [AnimatedBlockEntityGeometry::vertex_layouts] as its vertex layout. The pipeline can use `@pc_animation_time`,
+/// the seconds since the geometry was created, which is visible to both the vertex and fragment shader
+pub struct AnimatedBlockEntityGeometry {
+    pub mesh: Arc<wgpu::Buffer>,
+    pub vertex_count: u32,
+    pub instances: Arc<wgpu::Buffer>,
+    pub instance_count: u32,
+    start: Instant,
+}
+
+impl AnimatedBlockEntityGeometry {
+    pub fn new(wm: &WmRenderer, mesh: &[DecalVertex]) -> Self {
+        let mesh_buffer = wm
+            .display
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("block entity mesh"),
+                contents: bytemuck::cast_slice(mesh),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+
+        Self {
+            mesh: Arc::new(mesh_buffer),
+            vertex_count: mesh.len() as u32,
+            instances: Arc::new(Self::create_instance_buffer(wm, &[])),
+            instance_count: 0,
+            start: Instant::now(),
+        }
+    }
+
+    /// The surface of an end portal block, for use with the sample `end_portal` shader
+    pub fn end_portal(wm: &WmRenderer) -> Self {
+        let vertex = |x: f32, z: f32| DecalVertex {
+            position: [x, 0.75, z],
+            uv: [x, z],
+        };
+
+        Self::new(
+            wm,
+            &[
+                vertex(0.0, 0.0),
+                vertex(0.0, 1.0),
+                vertex(1.0, 1.0),
+                vertex(0.0, 0.0),
+                vertex(1.0, 1.0),
+                vertex(1.0, 0.0),
+            ],
+        )
+    }
+
+    pub fn vertex_layouts() -> Vec<wgpu::VertexBufferLayout<'static>> {
+        vec![DecalVertex::desc(), AnimatedBlockEntityInstance::desc()]
+    }
+
+    fn create_instance_buffer(
+        wm: &WmRenderer,
+        instances: &[AnimatedBlockEntityInstance],
+    ) -> wgpu::Buffer {
+        //Empty vertex buffers can't be bound
+        let placeholder = [AnimatedBlockEntityInstance {
+            position: [0.0; 3],
+            phase: 0.0,
+        }];
+
+        wm.display
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("block entity instances"),
+                contents: bytemuck::cast_slice(if instances.is_empty() {
+                    &placeholder
+                } else {
+                    instances
+                }),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            })
+    }
+
+    pub fn set_instances(&mut self, wm: &WmRenderer, instances: &[AnimatedBlockEntityInstance]) {
+        let size = std::mem::size_of_val(instances) as wgpu::BufferAddress;
+
+        if size > self.instances.size() {
+            self.instances = Arc::new(Self::create_instance_buffer(wm, instances));
+        } else {
+            wm.display
+                .queue
+                .write_buffer(&self.instances, 0, bytemuck::cast_slice(instances));
+        }
+
+        self.instance_count = instances.len() as u32;
+    }
+}
+
+impl Geometry for AnimatedBlockEntityGeometry {
+    fn render<'graph: 'pass + 'arena, 'pass, 'arena: 'pass>(
+        &mut self,
+        _wm: &WmRenderer,
+        _render_graph: &'graph RenderGraph,
+        bound_pipeline: &'graph BoundPipeline,
+        render_pass: &mut wgpu::RenderPass<'pass>,
+        arena: &WmArena<'arena>,
+    ) {
+        if self.instance_count == 0 {
+            return;
+        }
+
+        bound_pipeline.bind(render_pass);
+
+        let mut push_constants = HashMap::new();
+        push_constants.insert(
+            "@pc_animation_time".into(),
+            (
+                Vec::from(bytemuck::cast_slice(&[self.start.elapsed().as_secs_f32()])),
+                wgpu::ShaderStages::VERTEX_FRAGMENT,
+            ),
+        );
+        set_push_constants(bound_pipeline, render_pass, Some(push_constants));
+
+        render_pass.set_vertex_buffer(0, arena.alloc(self.mesh.clone()).slice(..));
+        render_pass.set_vertex_buffer(1, arena.alloc(self.instances.clone()).slice(..));
+        render_pass.draw(0..self.vertex_count, 0..self.instance_count);
+    }
+}
@@ -0,0 +1,175 @@
+//! Camera data for shaders which need more than the combined matrices, such as fog, specular highlights or
+//! reconstructing positions from depth in post processing
+
+use glam::{Mat4, Vec3};
+use treeculler::Frustum;
+
+/// How a camera's projection matrix is built
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CameraProjection {
+    /// `fovy` is vertical, in radians
+    Perspective {
+        fovy: f32,
+        aspect: f32,
+        znear: f32,
+        zfar: f32,
+    },
+    /// Used as it is, for hosts which control the projection exactly, e.g. to match a matrix set up by another mod
+    Custom(Mat4),
+}
+
+impl CameraProjection {
+    pub fn matrix(&self) -> Mat4 {
+        match *self {
+            CameraProjection::Perspective {
+                fovy,
+                aspect,
+                znear,
+                zfar,
+            } => Mat4::perspective_rh(fovy, aspect, znear, zfar),
+            CameraProjection::Custom(matrix) => matrix,
+        }
+    }
+
+    /// The frustum to cull against for a camera with this projection and `view`. The planes are extracted from the
+    /// combined matrix, so this works for custom projections as well
+    pub fn frustum(&self, view: Mat4) -> Frustum<f32> {
+        Frustum::from_modelview_projection((self.matrix() * view).to_cols_array_2d())
+    }
+}
+
+/// One eye of a stereo camera, see [crate::WmRenderer::render_stereo]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StereoEye {
+    /// Where the eye is relative to the point between the eyes, in view space. Usually half the interpupillary
+    /// distance along X, negative for the left eye
+    pub offset: Vec3,
+    /// VR runtimes usually give each eye an asymmetric projection, which can be passed as [CameraProjection::Custom]
+    pub projection: CameraProjection,
+}
+
+impl StereoEye {
+    /// A left and right eye `ipd` apart which share a projection
+    pub fn pair(ipd: f32, projection: CameraProjection) -> [StereoEye; 2] {
+        [-0.5, 0.5].map(|side| StereoEye {
+            offset: Vec3::new(side * ipd, 0.0, 0.0),
+            projection,
+        })
+    }
+
+    /// The eye's view matrix, from the view matrix of the point between the eyes
+    pub fn view(&self, head_view: Mat4) -> Mat4 {
+        Mat4::from_translation(-self.offset) * head_view
+    }
+
+    pub fn frustum(&self, head_view: Mat4) -> Frustum<f32> {
+        self.projection.frustum(self.view(head_view))
+    }
+}
+
+/// Matches this WGSL struct, which is 160 bytes:
+///
+/// ```wgsl
+/// struct Camera {
+///     view_matrix: mat4x4<f32>,
+///     inverse_view_proj: mat4x4<f32>,
+///     camera_pos: vec4<f32>,
+///     camera_dir: vec4<f32>,
+/// }
+/// ```
+///
+/// The inverse is computed once when the uniform is built, so shaders never have to invert a matrix per pixel
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    pub view_matrix: [[f32; 4]; 4],
+    /// Takes clip space positions (NDC with the depth buffer value as Z) back into the space `view_matrix` transforms
+    /// from
+    pub inverse_view_proj: [[f32; 4]; 4],
+    /// The camera position in render space (see [crate::mc::Scene::to_render_space]), W is unused
+    pub camera_pos: [f32; 4],
+    /// The normalized direction the camera looks in, W is unused
+    pub camera_dir: [f32; 4],
+}
+
+impl CameraUniform {
+    pub fn new(view: Mat4, projection: Mat4, camera_pos: Vec3) -> Self {
+        let camera_dir = view
+            .inverse()
+            .transform_vector3(Vec3::NEG_Z)
+            .normalize_or_zero();
+
+        Self {
+            view_matrix: view.to_cols_array_2d(),
+            inverse_view_proj: (projection * view).inverse().to_cols_array_2d(),
+            camera_pos: camera_pos.extend(0.0).to_array(),
+            camera_dir: camera_dir.extend(0.0).to_array(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Mat4, Vec3};
+    use treeculler::{BVol, AABB};
+
+    use super::{CameraProjection, CameraUniform, StereoEye};
+
+    #[test]
+    fn inverse_view_proj_reconstructs_positions() {
+        let view = Mat4::look_to_rh(Vec3::ZERO, vec3(1.0, -0.5, 0.0), Vec3::Y);
+        let projection = Mat4::perspective_rh(70f32.to_radians(), 16.0 / 9.0, 0.05, 256.0);
+        let camera = CameraUniform::new(view, projection, vec3(8.0, 70.0, 8.0));
+
+        let position = vec3(10.0, -3.0, 2.0);
+        let clip = (projection * view).project_point3(position);
+        let reconstructed =
+            Mat4::from_cols_array_2d(&camera.inverse_view_proj).project_point3(clip);
+
+        assert!(reconstructed.distance(position) < 1e-3);
+        assert!(
+            Vec3::from_slice(&camera.camera_dir).distance(vec3(1.0, -0.5, 0.0).normalize()) < 1e-5
+        );
+    }
+
+    #[test]
+    fn custom_projection_culls_with_its_own_frustum() {
+        let projection =
+            CameraProjection::Custom(Mat4::orthographic_rh(-8.0, 8.0, -8.0, 8.0, 0.0, 64.0));
+        let view = Mat4::look_to_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y);
+        let frustum = projection.frustum(view);
+
+        let in_view = AABB::new([-1.0, -1.0, -10.0], [1.0, 1.0, -8.0]);
+        let beside = AABB::new([20.0, -1.0, -10.0], [22.0, 1.0, -8.0]);
+
+        assert_eq!(
+            projection.matrix(),
+            Mat4::orthographic_rh(-8.0, 8.0, -8.0, 8.0, 0.0, 64.0)
+        );
+        assert!(in_view.coherent_test_against_frustum(&frustum, 0).0);
+        assert!(!beside.coherent_test_against_frustum(&frustum, 0).0);
+    }
+
+    #[test]
+    fn eyes_are_offset_sideways() {
+        let head_view = Mat4::look_to_rh(vec3(0.0, 64.0, 0.0), Vec3::NEG_Z, Vec3::Y);
+        let [left, right] = StereoEye::pair(
+            0.064,
+            CameraProjection::Perspective {
+                fovy: 1.5,
+                aspect: 1.0,
+                znear: 0.05,
+                zfar: 256.0,
+            },
+        );
+
+        //A point straight ahead of the head is to the right of the left eye and to the left of the right one
+        let ahead = vec3(0.0, 64.0, -10.0);
+        let in_left = left.view(head_view).transform_point3(ahead);
+        let in_right = right.view(head_view).transform_point3(ahead);
+
+        assert!((in_left.x - 0.032).abs() < 1e-6);
+        assert!((in_right.x + 0.032).abs() < 1e-6);
+        assert_eq!(in_left.z, in_right.z);
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use glam::Mat4;
+
+use crate::render::atlas::ATLAS_DIMENSIONS;
+use crate::texture::UV;
+
+/// How far, in blocks, decals are pulled towards the camera by default. Enough to keep paintings and signs from
+/// z-fighting with the wall behind them without visibly detaching from it
+pub const DEFAULT_DECAL_DEPTH_BIAS: f32 = 1.0 / 256.0;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DecalVertex {
+    pub position: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+impl DecalVertex {
+    const VAA: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x2
+    ];
+
+    #[must_use]
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<DecalVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::VAA,
+        }
+    }
+
+    /// A `width` by `height` block quad on the XY plane facing +Z, textured with an atlas sprite.
+    /// Decals spanning several blocks, like a 4x3 painting, are a single quad: `DecalVertex::quad(4.0, 3.0, uv)`
+    pub fn quad(width: f32, height: f32, uv: UV) -> [DecalVertex; 6] {
+        let ((u1, v1), (u2, v2)) = uv;
+        let u1 = u1 as f32 / ATLAS_DIMENSIONS as f32;
+        let v1 = v1 as f32 / ATLAS_DIMENSIONS as f32;
+        let u2 = u2 as f32 / ATLAS_DIMENSIONS as f32;
+        let v2 = v2 as f32 / ATLAS_DIMENSIONS as f32;
+
+        let bottom_left = DecalVertex {
+            position: [0.0, 0.0, 0.0],
+            uv: [u1, v2],
+        };
+        let bottom_right = DecalVertex {
+            position: [width, 0.0, 0.0],
+            uv: [u2, v2],
+        };
+        let top_right = DecalVertex {
+            position: [width, height, 0.0],
+            uv: [u2, v1],
+        };
+        let top_left = DecalVertex {
+            position: [0.0, height, 0.0],
+            uv: [u1, v1],
+        };
+
+        [
+            bottom_left,
+            bottom_right,
+            top_right,
+            bottom_left,
+            top_right,
+            top_left,
+        ]
+    }
+}
+
+/// Textured geometry drawn coplanar to a surface, see [crate::mc::Scene::add_decal]
+#[derive(Clone, Debug)]
+pub struct Decal {
+    pub vertex_buffer: Arc<wgpu::Buffer>,
+    pub vertex_count: u32,
+    /// Transforms the decal's mesh into render space, see [crate::mc::Scene::render_origin]
+    pub transform: Mat4,
+    /// How far, in blocks, the decal is moved towards the camera along the view ray. This only changes its depth,
+    /// not where it appears on screen
+    pub depth_bias: f32,
+}
@@ -0,0 +1,86 @@
+//! Depth peeling, an order independent alternative to sorting translucent terrain back to front, see
+//! [crate::render::shaderpack::PipelineConfig::depth_peel_layers].
+//!
+//! Layers are peeled back to front, so each one can be blended straight over the framebuffer with the pipeline's
+//! usual blending. Every layer clears its depth attachment to 0.0 and keeps the farthest fragment with
+//! [wgpu::CompareFunction::Greater], while the shader discards fragments at or behind the layer before it, which it
+//! reads from `@bg_peel_depth`. The first layer reads a copy of the opaque depth instead, so translucent faces behind
+//! terrain are discarded. Anything more than `depth_peel_layers` surfaces deep loses its nearest surfaces
+
+use crate::WmRenderer;
+
+/// The depth of the layer being peeled, written by the depth test, and the depth of the layer peeled before it, read
+/// by the shader. They swap roles every layer
+#[derive(Debug)]
+pub struct DepthPeelTargets {
+    pub size: wgpu::Extent3d,
+    pub textures: [wgpu::Texture; 2],
+    pub views: [wgpu::TextureView; 2],
+    /// Bind the texture of the same index as `@bg_peel_depth`
+    pub bind_groups: [wgpu::BindGroup; 2],
+}
+
+impl DepthPeelTargets {
+    /// `size` has to match the scene's depth texture, which the first layer copies
+    pub fn new(wm: &WmRenderer, size: wgpu::Extent3d) -> Self {
+        let textures = [0, 1].map(|index| {
+            wm.display.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(&format!("depth peel {index}")),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Depth32Float,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            })
+        });
+        let views = textures
+            .each_ref()
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        let bind_groups = views.each_ref().map(|view| {
+            wm.display
+                .device
+                .create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("depth peel"),
+                    layout: wm.bind_group_layouts.get("peel_depth").unwrap(),
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(view),
+                    }],
+                })
+        });
+
+        Self {
+            size,
+            textures,
+            views,
+            bind_groups,
+        }
+    }
+}
+
+/// Which of the [DepthPeelTargets] a layer writes its depth to, and which it reads the previous layer's depth from
+pub fn peel_targets(layer: u32) -> (usize, usize) {
+    let write = layer as usize % 2;
+    (write, 1 - write)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::peel_targets;
+
+    #[test]
+    fn layers_read_what_the_previous_one_wrote() {
+        for layer in 1..8 {
+            let (_, read) = peel_targets(layer);
+            let (previous_write, _) = peel_targets(layer - 1);
+            assert_eq!(read, previous_write);
+        }
+
+        //The first layer reads the copy of the opaque depth
+        assert_eq!(peel_targets(0), (0, 1));
+    }
+}
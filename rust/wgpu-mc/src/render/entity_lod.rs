@@ -0,0 +1,173 @@
+//! Skips or simplifies entities far from the camera, which keeps crowded areas like mob farms cheap to draw, see
+//! [crate::mc::Scene::entity_lod]
+
+use std::collections::HashMap;
+
+use glam::Vec3;
+
+/// A simpler model drawn in place of an entity type's own model beyond `distance`. The model must have the same parts
+/// as the one it replaces, since it's drawn with the same part transforms
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntityLodModel {
+    pub distance: f32,
+    /// The name of the model in [crate::mc::MinecraftState::entity_models]
+    pub model: String,
+}
+
+/// Overrides for one entity type
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EntityTypeLod {
+    /// Replaces [EntityLod::max_distance] for this type when set
+    pub max_distance: Option<f32>,
+    pub reduced: Option<EntityLodModel>,
+}
+
+/// How far from the camera entities are drawn, in blocks. Nothing is culled by default
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EntityLod {
+    /// Entities further away than this aren't drawn, None draws them at any distance
+    pub max_distance: Option<f32>,
+    /// Keyed by entity name
+    pub per_type: HashMap<String, EntityTypeLod>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EntityDetail {
+    Full,
+    Reduced,
+    Culled,
+}
+
+/// How many instances of an entity type were drawn at each level of detail in the last upload
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct EntityLodStats {
+    pub full: u32,
+    pub reduced: u32,
+    pub culled: u32,
+}
+
+/// Instances sorted by [EntityDetail], culled ones are only counted
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntityLodPartition<T> {
+    pub full: Vec<T>,
+    pub reduced: Vec<T>,
+    pub culled: u32,
+}
+
+impl<T> EntityLodPartition<T> {
+    pub fn stats(&self) -> EntityLodStats {
+        EntityLodStats {
+            full: self.full.len() as u32,
+            reduced: self.reduced.len() as u32,
+            culled: self.culled,
+        }
+    }
+}
+
+impl EntityLod {
+    pub fn max_distance(&self, entity: &str) -> Option<f32> {
+        self.per_type
+            .get(entity)
+            .and_then(|overrides| overrides.max_distance)
+            .or(self.max_distance)
+    }
+
+    /// The reduced model drawn for this entity type, if it has one
+    pub fn reduced_model(&self, entity: &str) -> Option<&EntityLodModel> {
+        self.per_type
+            .get(entity)
+            .and_then(|overrides| overrides.reduced.as_ref())
+    }
+
+    pub fn detail(&self, entity: &str, distance: f32) -> EntityDetail {
+        if let Some(max_distance) = self.max_distance(entity) {
+            if distance > max_distance {
+                return EntityDetail::Culled;
+            }
+        }
+
+        match self.reduced_model(entity) {
+            Some(reduced) if distance > reduced.distance => EntityDetail::Reduced,
+            _ => EntityDetail::Full,
+        }
+    }
+
+    /// Sorts the instances of one entity type by how far `position` puts them from `camera`, keeping their order
+    pub fn partition<T>(
+        &self,
+        entity: &str,
+        camera: Vec3,
+        instances: impl IntoIterator<Item = T>,
+        position: impl Fn(&T) -> Vec3,
+    ) -> EntityLodPartition<T> {
+        let mut partition = EntityLodPartition {
+            full: Vec::new(),
+            reduced: Vec::new(),
+            culled: 0,
+        };
+
+        for instance in instances {
+            match self.detail(entity, position(&instance).distance(camera)) {
+                EntityDetail::Full => partition.full.push(instance),
+                EntityDetail::Reduced => partition.reduced.push(instance),
+                EntityDetail::Culled => partition.culled += 1,
+            }
+        }
+
+        partition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use glam::{vec3, Vec3};
+
+    use super::{EntityLod, EntityLodModel, EntityLodStats, EntityTypeLod};
+
+    #[test]
+    fn per_type_distances_override_the_global_one() {
+        let lod = EntityLod {
+            max_distance: Some(32.0),
+            per_type: HashMap::from([(
+                "minecraft:chicken".into(),
+                EntityTypeLod {
+                    max_distance: Some(16.0),
+                    reduced: Some(EntityLodModel {
+                        distance: 8.0,
+                        model: "minecraft:chicken_lod".into(),
+                    }),
+                },
+            )]),
+        };
+
+        let positions = [4.0, 12.0, 24.0, 40.0].map(|x| vec3(x, 0.0, 0.0));
+
+        let chickens = lod.partition("minecraft:chicken", Vec3::ZERO, positions, |p| *p);
+        assert_eq!(chickens.full, [positions[0]]);
+        assert_eq!(chickens.reduced, [positions[1]]);
+        assert_eq!(
+            chickens.stats(),
+            EntityLodStats {
+                full: 1,
+                reduced: 1,
+                culled: 2,
+            }
+        );
+
+        let cows = lod.partition("minecraft:cow", Vec3::ZERO, positions, |p| *p);
+        assert_eq!(cows.full, positions[..3]);
+        assert_eq!(cows.culled, 1);
+    }
+
+    #[test]
+    fn nothing_is_culled_by_default() {
+        let positions = [vec3(0.0, 0.0, 10000.0)];
+        let partition =
+            EntityLod::default().partition("minecraft:cow", Vec3::ZERO, positions, |p| *p);
+
+        assert_eq!(partition.full, positions);
+        assert_eq!(partition.culled, 0);
+    }
+}
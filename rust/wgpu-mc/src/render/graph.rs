@@ -1,27 +1,41 @@
 use glam::ivec3;
+use itertools::Either;
 use linked_hash_map::LinkedHashMap;
+use parking_lot::Mutex;
 use std::collections::HashMap;
 use std::sync::Arc;
 use treeculler::{BVol, Frustum, Vec3, AABB};
 
+use wgpu::util::DeviceExt;
 use wgpu::{
     Color, LoadOp, Operations, RenderPassColorAttachment, RenderPassDepthStencilAttachment,
     RenderPassDescriptor, SamplerBindingType, ShaderStages, StoreOp,
 };
 
-use crate::mc::chunk::RenderLayer;
+use crate::mc::chunk::{CoplanarOrder, RenderLayer};
 use crate::mc::entity::InstanceVertex;
 use crate::mc::resource::ResourcePath;
 use crate::mc::Scene;
+use crate::render::atlas::{
+    TextureManager, DEFAULT_SPECULAR, FLAT_NORMAL, NORMAL_LAYER, SPECULAR_LAYER,
+};
+use crate::render::blob_shadow::BlobShadowInstance;
+use crate::render::decal::{Decal, DecalVertex, DEFAULT_DECAL_DEPTH_BIAS};
+use crate::render::depth_peel::{peel_targets, DepthPeelTargets};
 use crate::render::entity::EntityVertex;
-use crate::render::pipeline::{QuadVertex, BLOCK_ATLAS};
-use crate::render::shader::WgslShader;
+use crate::render::introspect::{
+    framebuffer_auto_clear, target_reads, PipelineDescription, TargetRead,
+};
+use crate::render::pipeline::{QuadVertex, TerrainVertex, BLOCK_ATLAS, ENTITY_TEXTURE_SAMPLE_TYPE};
+use crate::render::shader::{load_pipeline_shader, ShaderInterface};
 use crate::render::shaderpack::{
-    BindGroupDef, LonghandResourceConfig, PipelineConfig, ShaderPackConfig,
-    ShorthandResourceConfig, TypeResourceConfig,
+    BindGroupDef, LonghandResourceConfig, PipelineConfig, PipelinesConfig, SamplerFilter,
+    ShaderPackConfig, ShorthandResourceConfig, SkippedPipeline, StorageAccess, Topology,
+    TranslucentDepth, TranslucentFaces, TypeResourceConfig, VertexFetch, SKY_GEOMETRIES,
 };
-use crate::render::sky::{SkyVertex, SunMoonVertex};
-use crate::texture::TextureAndView;
+use crate::render::sky::{SkyUniform, SkyVertex, SunMoonVertex};
+use crate::render::void_plane::VoidPlaneInstance;
+use crate::texture::{ColorSpace, ColorSpaceUniform, TextureAndView};
 use crate::util::WmArena;
 use crate::WmRenderer;
 
@@ -39,9 +53,321 @@ pub trait Geometry: Send + Sync {
 #[derive(Debug)]
 pub enum ResourceBacking {
     Buffer(Arc<wgpu::Buffer>, wgpu::BufferBindingType),
-    BufferArray(Vec<Arc<wgpu::Buffer>>),
+    BufferArray(Vec<Arc<wgpu::Buffer>>, wgpu::BufferBindingType),
     Texture2D(Arc<TextureAndView>),
-    Sampler(Arc<wgpu::Sampler>),
+    Sampler(Arc<wgpu::Sampler>, SamplerBindingType),
+}
+
+/// Writable storage buffers aren't allowed in vertex shaders, so they're only visible to the other stages, see
+/// [validate_vertex_storage]
+fn buffer_visibility(buffer_ty: wgpu::BufferBindingType) -> ShaderStages {
+    match buffer_ty {
+        wgpu::BufferBindingType::Storage { read_only: false } => {
+            ShaderStages::FRAGMENT | ShaderStages::COMPUTE
+        }
+        _ => ShaderStages::all(),
+    }
+}
+
+fn texture_sample_type(texture: &TextureAndView) -> wgpu::TextureSampleType {
+    //Multisampled textures can only be loaded from, never filtered
+    if texture.texture.sample_count() > 1 {
+        return wgpu::TextureSampleType::Float { filterable: false };
+    }
+
+    texture
+        .format
+        .sample_type(None, None)
+        .unwrap_or(wgpu::TextureSampleType::Float { filterable: false })
+}
+
+/// The textures in the built-in layouts of [BindGroupDef::Resource] bind groups, by binding
+fn resource_bind_group_textures(resource: &str) -> &'static [(u32, wgpu::TextureSampleType)] {
+    match resource {
+        "@bg_entity" | "@bg_entity_single" => &[(1, ENTITY_TEXTURE_SAMPLE_TYPE)],
+        "@bg_peel_depth" => &[(0, wgpu::TextureSampleType::Depth)],
+        _ => &[],
+    }
+}
+
+fn sampler_can_sample(
+    sampler_ty: SamplerBindingType,
+    sample_type: wgpu::TextureSampleType,
+) -> bool {
+    match (sampler_ty, sample_type) {
+        (SamplerBindingType::Comparison, wgpu::TextureSampleType::Depth) => true,
+        (SamplerBindingType::Comparison, _) => false,
+        (SamplerBindingType::Filtering, wgpu::TextureSampleType::Float { filterable }) => {
+            filterable
+        }
+        (SamplerBindingType::Filtering, _) => false,
+        (SamplerBindingType::NonFiltering, _) => true,
+    }
+}
+
+/// The resource a pipeline binds at `(group, binding)`, with its backing unless it's in a [BindGroupDef::Resource]
+/// bind group
+fn bound_resource<'a>(
+    pipeline_config: &'a PipelineConfig,
+    resources: &'a HashMap<String, ResourceBacking>,
+    (group, binding): (u32, u32),
+) -> Option<(&'a String, Option<&'a ResourceBacking>)> {
+    match pipeline_config.bind_groups.get(&(group as u64))? {
+        BindGroupDef::Entries(entries) => {
+            let resource_id = entries.get(&(binding as u64))?;
+
+            Some((resource_id, resources.get(resource_id)))
+        }
+        BindGroupDef::Resource(resource) => Some((resource, None)),
+    }
+}
+
+/// Panics if a pipeline's vertex shader uses a writable storage buffer, which [buffer_visibility] leaves out of the
+/// vertex stage since WebGPU doesn't allow them there
+fn validate_vertex_storage(
+    pipeline_name: &str,
+    pipeline_config: &PipelineConfig,
+    resources: &HashMap<String, ResourceBacking>,
+    interface: &ShaderInterface,
+) {
+    for binding in &interface.vertex_bindings {
+        if let Some((
+            resource_id,
+            Some(
+                ResourceBacking::Buffer(_, buffer_ty) | ResourceBacking::BufferArray(_, buffer_ty),
+            ),
+        )) = bound_resource(pipeline_config, resources, *binding)
+        {
+            if !buffer_visibility(*buffer_ty).contains(ShaderStages::VERTEX) {
+                panic!(
+                    "Pipeline {pipeline_name} uses the writable storage buffer {resource_id} in its vertex shader, which is only allowed in fragment and compute shaders. Declare it with `access: read` or only use it in the fragment shader"
+                );
+            }
+        }
+    }
+}
+
+/// Panics if a pipeline's shader samples a texture with a sampler that can't be used to sample it, since wgpu would
+/// otherwise fail with a much less helpful validation error when the pipeline is created. Only the combinations naga
+/// reports the shader using are checked, covering both the pack's own textures and those in the layouts of
+/// [BindGroupDef::Resource] bind groups
+fn validate_sampler_filtering(
+    pipeline_name: &str,
+    pipeline_config: &PipelineConfig,
+    resources: &HashMap<String, ResourceBacking>,
+    interface: &ShaderInterface,
+) {
+    let bound = |binding| bound_resource(pipeline_config, resources, binding);
+
+    for pair in &interface.sampled {
+        let Some((sampler_id, Some(ResourceBacking::Sampler(_, sampler_ty)))) = bound(pair.sampler)
+        else {
+            continue;
+        };
+
+        let (texture_id, sample_type) = match bound(pair.texture) {
+            Some((texture_id, Some(ResourceBacking::Texture2D(texture)))) => {
+                (texture_id, texture_sample_type(texture))
+            }
+            Some((resource, None)) => match resource_bind_group_textures(resource)
+                .iter()
+                .find(|(binding, _)| *binding == pair.texture.1)
+            {
+                Some((_, sample_type)) => (resource, *sample_type),
+                None => continue,
+            },
+            _ => continue,
+        };
+
+        if !sampler_can_sample(*sampler_ty, sample_type) {
+            panic!(
+                "Pipeline {pipeline_name} samples texture {texture_id} ({sample_type:?}) with {sampler_ty:?} sampler {sampler_id}, which can't sample it"
+            );
+        }
+    }
+}
+
+/// Samplers which blend texels need a `Filtering` binding, which also requires the textures they sample to be
+/// filterable
+fn filter_binding_type(filters: [SamplerFilter; 3]) -> SamplerBindingType {
+    if filters.contains(&SamplerFilter::Linear) {
+        SamplerBindingType::Filtering
+    } else {
+        SamplerBindingType::NonFiltering
+    }
+}
+
+/// Panics if a sampler's anisotropy can't be used, since wgpu only reports it as a validation error when the sampler
+/// is created
+fn validate_sampler_anisotropy(sampler_id: &str, filters: [SamplerFilter; 3], anisotropy: u16) {
+    if !(1..=16).contains(&anisotropy) {
+        panic!(
+            "Sampler {sampler_id} has an anisotropy of {anisotropy}, it must be between 1 and 16"
+        );
+    }
+
+    if anisotropy > 1 && filters.contains(&SamplerFilter::Nearest) {
+        panic!("Sampler {sampler_id} uses anisotropic filtering, which requires every filter to be linear");
+    }
+}
+
+/// Panics if a multisampled render target can't be resolved into the render target it names, since wgpu would only
+/// catch this once a pass using it is recorded
+fn validate_resolve_targets(
+    resources: &HashMap<String, ResourceBacking>,
+    resolve_targets: &HashMap<String, String>,
+) {
+    for (msaa_id, resolve_id) in resolve_targets {
+        let (Some(ResourceBacking::Texture2D(msaa)), Some(ResourceBacking::Texture2D(resolve))) =
+            (resources.get(msaa_id), resources.get(resolve_id))
+        else {
+            panic!(
+                "Render target {msaa_id} resolves into {resolve_id}, which isn't a render target"
+            );
+        };
+
+        if msaa.texture.sample_count() == 1 {
+            panic!("Render target {msaa_id} has a resolve target but isn't multisampled");
+        }
+
+        if resolve.texture.sample_count() != 1 {
+            panic!("Render target {msaa_id} resolves into {resolve_id}, which is multisampled");
+        }
+
+        if msaa.format != resolve.format || msaa.texture.size() != resolve.texture.size() {
+            panic!(
+                "Render target {msaa_id} ({:?}, {:?}) can't be resolved into {resolve_id} ({:?}, {:?})",
+                msaa.format,
+                msaa.texture.size(),
+                resolve.format,
+                resolve.texture.size()
+            );
+        }
+    }
+}
+
+/// A `render_target` or `texture_depth` resource, sized like the scene (see [WmRenderer::render_size]) so pipelines
+/// can draw to it and later ones sample it
+fn create_render_target(
+    wm: &WmRenderer,
+    resource_id: &str,
+    format: wgpu::TextureFormat,
+    samples: u32,
+) -> TextureAndView {
+    if !wm
+        .display
+        .adapter
+        .get_texture_format_features(format)
+        .flags
+        .sample_count_supported(samples)
+    {
+        panic!("Render target {resource_id} uses {samples} samples, which {format:?} doesn't support on this adapter");
+    }
+
+    let (width, height) = wm.render_size();
+
+    let texture = wm.display.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(resource_id),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: samples,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    TextureAndView {
+        texture,
+        view,
+        format,
+    }
+}
+
+/// Panics if a pipeline draws to something which isn't a texture, since its render pass could never be recorded
+fn validate_attachments(
+    pipeline_name: &str,
+    pipeline_config: &PipelineConfig,
+    resources: &HashMap<String, ResourceBacking>,
+) {
+    let is_texture = |id: &String| matches!(resources.get(id), Some(ResourceBacking::Texture2D(_)));
+
+    for output in &pipeline_config.output {
+        if output != "@framebuffer_texture" && !is_texture(output) {
+            panic!("Pipeline {pipeline_name} outputs to {output}, which isn't a render target");
+        }
+    }
+
+    if let Some(depth) = &pipeline_config.depth {
+        if depth != "@texture_depth" && !is_texture(depth) {
+            panic!("Pipeline {pipeline_name} uses {depth} as its depth attachment, which isn't a texture_depth");
+        }
+    }
+}
+
+/// Panics if a pipeline is a fallback for a pipeline which isn't in the pack, which is most likely a typo
+fn validate_fallbacks(pipelines: &PipelinesConfig) {
+    for (pipeline_name, pipeline_config) in &pipelines.pipelines {
+        if let Some(fallback_for) = &pipeline_config.fallback_for {
+            if !pipelines.pipelines.contains_key(fallback_for) {
+                panic!("Pipeline {pipeline_name} is a fallback for {fallback_for}, which doesn't exist");
+            }
+        }
+    }
+}
+
+/// Panics if a pipeline skipped for lacking device features was the only one drawing `@framebuffer_texture` or a
+/// render target which a remaining pipeline samples, since the pack would be missing what the pipeline drew
+fn validate_skipped_targets(skipped: &[PipelineDescription], pipelines: &[PipelineDescription]) {
+    for skipped_pipeline in skipped {
+        for target in skipped_pipeline.writes() {
+            if pipelines
+                .iter()
+                .any(|pipeline| pipeline.writes().any(|write| write == target))
+            {
+                continue;
+            }
+
+            if target == "@framebuffer_texture" {
+                panic!("Pipeline {} was skipped, and no other pipeline draws to @framebuffer_texture. Add a fallback for it", skipped_pipeline.name);
+            }
+
+            if let Some(pipeline) = pipelines.iter().find(|pipeline| {
+                pipeline
+                    .bindings
+                    .iter()
+                    .any(|binding| binding.resource == target)
+            }) {
+                panic!(
+                    "Pipeline {} samples {target}, which only {} draws to, and that was skipped. Add a fallback for it",
+                    pipeline.name, skipped_pipeline.name
+                );
+            }
+        }
+    }
+}
+
+/// Panics if a pipeline samples a render target its own pass draws to, since a texture can't be both in one render
+/// pass. Targets drawn by one pipeline and sampled by a later one are fine, see [target_reads]
+fn validate_target_reads(pipelines: &[PipelineDescription]) {
+    for pipeline in pipelines {
+        if let Some(binding) = pipeline
+            .bindings
+            .iter()
+            .find(|binding| pipeline.writes().any(|write| write == binding.resource))
+        {
+            panic!(
+                "Pipeline {} samples {}, which it also draws to. Draw to a separate target and sample that in a later pipeline instead",
+                pipeline.name, binding.resource
+            );
+        }
+    }
 }
 
 impl ResourceBacking {
@@ -49,8 +375,7 @@ impl ResourceBacking {
         match self {
             ResourceBacking::Buffer(_, buffer_ty) => wgpu::BindGroupLayoutEntry {
                 binding,
-                //TODO
-                visibility: ShaderStages::all(),
+                visibility: buffer_visibility(*buffer_ty),
                 ty: wgpu::BindingType::Buffer {
                     ty: *buffer_ty,
                     has_dynamic_offset: false,
@@ -58,30 +383,30 @@ impl ResourceBacking {
                 },
                 count: None,
             },
-            ResourceBacking::BufferArray(_buffers) => wgpu::BindGroupLayoutEntry {
+            ResourceBacking::BufferArray(_buffers, buffer_ty) => wgpu::BindGroupLayoutEntry {
                 binding,
-                visibility: ShaderStages::all(),
+                visibility: buffer_visibility(*buffer_ty),
                 ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    ty: *buffer_ty,
                     has_dynamic_offset: false,
                     min_binding_size: None,
                 },
                 count: None,
             },
-            ResourceBacking::Texture2D(_) => wgpu::BindGroupLayoutEntry {
+            ResourceBacking::Texture2D(texture) => wgpu::BindGroupLayoutEntry {
                 binding,
                 visibility: wgpu::ShaderStages::FRAGMENT,
                 ty: wgpu::BindingType::Texture {
-                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    sample_type: texture_sample_type(texture),
                     view_dimension: wgpu::TextureViewDimension::D2,
-                    multisampled: false,
+                    multisampled: texture.texture.sample_count() > 1,
                 },
                 count: None,
             },
-            ResourceBacking::Sampler(_) => wgpu::BindGroupLayoutEntry {
+            ResourceBacking::Sampler(_, sampler_ty) => wgpu::BindGroupLayoutEntry {
                 binding,
                 visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Sampler(SamplerBindingType::NonFiltering),
+                ty: wgpu::BindingType::Sampler(*sampler_ty),
                 count: None,
             },
         }
@@ -97,7 +422,7 @@ impl ResourceBacking {
                 binding: index,
                 resource: wgpu::BindingResource::TextureView(&texture.view),
             }],
-            ResourceBacking::Sampler(sampler) => vec![wgpu::BindGroupEntry {
+            ResourceBacking::Sampler(sampler, _sampler_ty) => vec![wgpu::BindGroupEntry {
                 binding: index,
                 resource: wgpu::BindingResource::Sampler(sampler),
             }],
@@ -121,8 +446,32 @@ pub enum WmBindGroup {
 #[derive(Debug)]
 pub struct BoundPipeline {
     pub pipeline: wgpu::RenderPipeline,
+    /// Used for [RenderLayer::Transparent] terrain, see [TranslucentDepth] and [TranslucentFaces]
+    pub translucent_pipeline: Option<wgpu::RenderPipeline>,
     pub bind_groups: Vec<(u32, WmBindGroup)>,
     pub config: PipelineConfig,
+    /// The pipeline layout's push constant ranges, see [merge_push_constant_ranges]
+    pub push_constant_ranges: Vec<wgpu::PushConstantRange>,
+}
+
+impl BoundPipeline {
+    /// Set the pipeline and its bind groups on `render_pass`, for geometries which don't take any
+    /// [WmBindGroup::Resource] bind groups like `@bg_entity`. Pipelines declaring a `@bg_` one anyway are skipped when
+    /// the graph is created, see [crate::render::shaderpack::PipelinesConfig::skip_unbound]
+    pub fn bind<'pass>(&'pass self, render_pass: &mut wgpu::RenderPass<'pass>) {
+        render_pass.set_pipeline(&self.pipeline);
+
+        for (index, bind_group) in self.bind_groups.iter() {
+            match bind_group {
+                WmBindGroup::Resource(name) => {
+                    panic!("the geometry doesn't set the bind group {name}")
+                }
+                WmBindGroup::Custom(bind_group) => {
+                    render_pass.set_bind_group(*index, bind_group, &[]);
+                }
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -130,9 +479,237 @@ pub struct RenderGraph {
     pub config: ShaderPackConfig,
     pub pipelines: LinkedHashMap<String, BoundPipeline>,
     pub resources: HashMap<String, ResourceBacking>,
+    /// Multisampled render targets mapped to the render targets they're resolved into
+    pub resolve_targets: HashMap<String, String>,
+    /// The pipeline which clears `@framebuffer_texture` to the clear color, because no pipeline in the shader pack sets
+    /// `clear` while drawing to it. This keeps the previous contents of the surface from showing through
+    pub auto_clear: Option<String>,
+    /// Created on the first frame with a pipeline that sets `depth_peel_layers`, and whenever the depth texture resizes
+    pub depth_peel: Mutex<Option<DepthPeelTargets>>,
+    /// Pipelines left out because the device lacks a feature they require, and fallbacks which weren't needed
+    pub skipped_pipelines: Vec<SkippedPipeline>,
 }
 
 impl RenderGraph {
+    /// Every pipeline in the order they run, with the resources they bind and the targets they draw to. Serialize it
+    /// (e.g. with `serde_json`) to show the graph in external tools
+    pub fn describe(&self) -> Vec<PipelineDescription> {
+        self.pipelines
+            .iter()
+            .map(|(name, pipeline)| {
+                PipelineDescription::new(name, &pipeline.config, &self.resolve_targets)
+            })
+            .collect()
+    }
+
+    /// Every render target which a pipeline samples, and which earlier pipeline drew what it samples
+    pub fn target_reads(&self) -> Vec<TargetRead> {
+        target_reads(&self.describe())
+    }
+
+    /// The sample count shared by every attachment of a pipeline. Panics if they disagree, as a pipeline can only
+    /// render to attachments with the sample count it was created with
+    fn attachment_sample_count(
+        &self,
+        pipeline_name: &str,
+        pipeline_config: &PipelineConfig,
+    ) -> u32 {
+        let sample_count = |attachment: &String| match &attachment[..] {
+            "@framebuffer_texture" | "@texture_depth" => 1,
+            _ => match self.resources.get(attachment) {
+                Some(ResourceBacking::Texture2D(texture)) => texture.texture.sample_count(),
+                _ => 1,
+            },
+        };
+
+        let mut counts = pipeline_config
+            .output
+            .iter()
+            .chain(pipeline_config.depth.iter())
+            .map(|attachment| (attachment, sample_count(attachment)));
+
+        let Some((first, count)) = counts.next() else {
+            return 1;
+        };
+
+        for (attachment, other) in counts {
+            if attachment == "@texture_depth" && count > 1 {
+                panic!("Pipeline {pipeline_name} renders to {first} with {count} samples, but @texture_depth has a single sample. Declare a texture_depth resource with `samples: {count}` as its depth attachment instead");
+            }
+
+            if other != count {
+                panic!("Pipeline {pipeline_name} renders to {first} with {count} samples and {attachment} with {other} samples");
+            }
+        }
+
+        count
+    }
+
+    /// The format a pipeline writes to one of its outputs with. `@framebuffer_texture` and anything which isn't a
+    /// declared texture use [crate::Display::view_format]
+    fn output_format(&self, wm: &WmRenderer, output: &str) -> wgpu::TextureFormat {
+        match self.resources.get(output) {
+            Some(ResourceBacking::Texture2D(texture)) => texture.format,
+            _ => wm.display.view_format(),
+        }
+    }
+
+    /// `@sampler` with the `block_sampler` filters and the current [crate::render::atlas::TextureQuality]
+    fn block_sampler(&self, wm: &WmRenderer) -> ResourceBacking {
+        let config = &self.config.block_sampler;
+        let sampler = wm
+            .mc
+            .texture_manager
+            .create_block_sampler(&wm.display, config);
+
+        ResourceBacking::Sampler(
+            Arc::new(sampler),
+            filter_binding_type([config.mag_filter, config.min_filter, config.mipmap_filter]),
+        )
+    }
+
+    /// Recreate the `render_target` and `texture_depth` resources declared by the shader pack at
+    /// [WmRenderer::render_size] if the surface was resized or the render scale changed since they were created, and
+    /// rebind them. Call this before rendering a frame
+    pub fn resize_render_targets(&mut self, wm: &WmRenderer) {
+        let (width, height) = wm.render_size();
+
+        let resized = self
+            .config
+            .resources
+            .resources
+            .iter()
+            .filter_map(|(resource_id, resource)| {
+                let ShorthandResourceConfig::Longhand(LonghandResourceConfig { typed, .. }) =
+                    resource
+                else {
+                    return None;
+                };
+
+                let (format, samples) = match typed {
+                    TypeResourceConfig::RenderTarget { samples, .. } => {
+                        (wm.display.view_format(), *samples)
+                    }
+                    TypeResourceConfig::TextureDepth { samples } => {
+                        (wgpu::TextureFormat::Depth32Float, *samples)
+                    }
+                    _ => return None,
+                };
+
+                match self.resources.get(resource_id) {
+                    Some(ResourceBacking::Texture2D(texture))
+                        if (texture.texture.width(), texture.texture.height())
+                            != (width, height) =>
+                    {
+                        Some((
+                            resource_id.clone(),
+                            create_render_target(wm, resource_id, format, samples),
+                        ))
+                    }
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        for (resource_id, texture) in resized {
+            self.resources.insert(
+                resource_id.clone(),
+                ResourceBacking::Texture2D(Arc::new(texture)),
+            );
+            self.rebind_resource(wm, &resource_id);
+        }
+    }
+
+    /// Rebind the atlases whose [crate::render::atlas::Atlas::bindable_texture] changed, so the full texture replaces
+    /// the preview once [crate::render::atlas::Atlas::upload_pending] has streamed every sprite in. Call this before
+    /// rendering a frame
+    pub fn swap_atlas_textures(&mut self, wm: &WmRenderer) {
+        let mut swapped = vec![];
+
+        for (name, atlas) in wm.mc.texture_manager.atlases.read().iter() {
+            let mut resource_ids = vec![TextureManager::atlas_resource_name(name)];
+
+            if name == BLOCK_ATLAS {
+                resource_ids.push("@texture_block_atlas".into());
+            }
+
+            for resource_id in resource_ids {
+                if let Some(ResourceBacking::Texture2D(texture)) = self.resources.get(&resource_id)
+                {
+                    if !Arc::ptr_eq(texture, atlas.bindable_texture()) {
+                        swapped.push((resource_id, atlas.bindable_texture().clone()));
+                    }
+                }
+            }
+        }
+
+        for (resource_id, texture) in swapped {
+            self.resources
+                .insert(resource_id.clone(), ResourceBacking::Texture2D(texture));
+            self.rebind_resource(wm, &resource_id);
+        }
+    }
+
+    /// Recreate `@sampler` after [TextureManager::set_texture_quality] changed its anisotropy or max mip
+    pub fn rebuild_block_sampler(&mut self, wm: &WmRenderer) {
+        let sampler = self.block_sampler(wm);
+        self.resources.insert("@sampler".into(), sampler);
+
+        self.rebind_resource(wm, "@sampler");
+    }
+
+    /// Recreate the bind groups of every pipeline which binds `resource_id`, after its backing was replaced
+    fn rebind_resource(&mut self, wm: &WmRenderer, resource_id: &str) {
+        for bound_pipeline in self.pipelines.values_mut() {
+            for (slot, bind_group) in bound_pipeline.bind_groups.iter_mut() {
+                let Some(BindGroupDef::Entries(entries)) =
+                    bound_pipeline.config.bind_groups.get(&(*slot as u64))
+                else {
+                    continue;
+                };
+
+                if !entries.values().any(|id| id == resource_id) {
+                    continue;
+                }
+
+                let entries = entries
+                    .iter()
+                    .flat_map(|(index, id)| {
+                        self.resources[id].get_bind_group_entries(*index as u32)
+                    })
+                    .collect::<Vec<wgpu::BindGroupEntry>>();
+
+                //The translucent pipeline shares the layout, so the bind group works for both
+                *bind_group = WmBindGroup::Custom(wm.display.device.create_bind_group(
+                    &wgpu::BindGroupDescriptor {
+                        label: None,
+                        layout: &bound_pipeline.pipeline.get_bind_group_layout(*slot),
+                        entries: &entries,
+                    },
+                ));
+            }
+        }
+    }
+
+    /// The size of the first attachment of a pipeline, which its viewport is relative to
+    pub fn attachment_size(&self, wm: &WmRenderer, pipeline_config: &PipelineConfig) -> (u32, u32) {
+        let attachment = pipeline_config
+            .output
+            .first()
+            .or(pipeline_config.depth.as_ref());
+
+        match attachment.and_then(|attachment| self.resources.get(attachment)) {
+            Some(ResourceBacking::Texture2D(texture)) => {
+                (texture.texture.width(), texture.texture.height())
+            }
+            _ if pipeline_config.native_resolution => {
+                let config = wm.display.config.read();
+                (config.width, config.height)
+            }
+            _ => wm.render_size(),
+        }
+    }
+
     fn create_pipelines(
         &mut self,
         wm: &WmRenderer,
@@ -144,6 +721,42 @@ impl RenderGraph {
         let arena = WmArena::new(1024);
 
         for (pipeline_name, pipeline_config) in &self.config.pipelines.pipelines {
+            validate_attachments(pipeline_name, pipeline_config, &self.resources);
+            let vert_entry = match pipeline_config.vertex_fetch {
+                VertexFetch::Attributes if pipeline_config.geometry == "@geo_terrain" => {
+                    "vert_attributes"
+                }
+                _ => "vert",
+            };
+
+            let interface = ShaderInterface::of_pipeline(
+                pipeline_config.shader_name(pipeline_name),
+                &*wm.mc.resource_provider,
+                vert_entry,
+            );
+            validate_sampler_filtering(pipeline_name, pipeline_config, &self.resources, &interface);
+            validate_vertex_storage(pipeline_name, pipeline_config, &self.resources, &interface);
+
+            if pipeline_config.depth_peel_layers > 0
+                && (pipeline_config.geometry != "@geo_terrain"
+                    || pipeline_config.depth.as_deref() != Some("@texture_depth"))
+            {
+                panic!("Pipeline {pipeline_name} uses depth peeling, which is only supported for @geo_terrain drawn with @texture_depth");
+            }
+
+            if pipeline_config.shadow.is_some() {
+                if pipeline_config.depth.is_none() {
+                    panic!("Pipeline {pipeline_name} is a shadow pass, but has no depth attachment to render the shadow map into");
+                }
+
+                //WebGPU only allows depth bias for triangles
+                if pipeline_config.topology != Topology::TriangleList {
+                    panic!(
+                        "Pipeline {pipeline_name} is a shadow pass, which has to draw triangles"
+                    );
+                }
+            }
+
             let bind_group_layouts = pipeline_config
                 .bind_groups
                 .iter()
@@ -166,8 +779,16 @@ impl RenderGraph {
                     }
                     BindGroupDef::Resource(resource) => {
                         match (&resource[..], &custom_bind_groups) {
-                            ("@bg_ssbo_chunks", _) => wm.bind_group_layouts.get("ssbo").unwrap(),
+                            ("@bg_ssbo_chunks", _) | ("@bg_ssbo_section_tints", _) => {
+                                wm.bind_group_layouts.get("ssbo").unwrap()
+                            }
                             ("@bg_entity", _) => wm.bind_group_layouts.get("entity").unwrap(),
+                            ("@bg_peel_depth", _) => {
+                                wm.bind_group_layouts.get("peel_depth").unwrap()
+                            }
+                            ("@bg_entity_single", _) => {
+                                wm.bind_group_layouts.get("entity_single").unwrap()
+                            }
                             (_, Some(custom)) => {
                                 if let Some(entry) = custom.get(resource) {
                                     entry
@@ -227,6 +848,22 @@ impl RenderGraph {
                             stages: wgpu::ShaderStages::VERTEX,
                             range: index..index + 12,
                         },
+                        "@pc_section_tint_slot" => wgpu::PushConstantRange {
+                            stages: wgpu::ShaderStages::VERTEX,
+                            range: index..index + 4,
+                        },
+                        "@pc_section_fade" => wgpu::PushConstantRange {
+                            stages: wgpu::ShaderStages::VERTEX,
+                            range: index..index + 4,
+                        },
+                        "@pc_wind" => wgpu::PushConstantRange {
+                            stages: wgpu::ShaderStages::VERTEX,
+                            range: index..index + 12,
+                        },
+                        "@pc_sun_direction" => wgpu::PushConstantRange {
+                            stages: wgpu::ShaderStages::VERTEX,
+                            range: index..index + 12,
+                        },
                         "@pc_total_sections" => wgpu::PushConstantRange {
                             stages: wgpu::ShaderStages::VERTEX,
                             range: index..index + 4,
@@ -235,14 +872,31 @@ impl RenderGraph {
                             stages: wgpu::ShaderStages::VERTEX,
                             range: index..index + 4,
                         },
+                        "@pc_animation_time" => wgpu::PushConstantRange {
+                            stages: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                            range: index..index + 4,
+                        },
+                        "@pc_decal_depth_bias" => wgpu::PushConstantRange {
+                            stages: wgpu::ShaderStages::VERTEX,
+                            range: index..index + 4,
+                        },
+                        "@pc_fog" | "@pc_fog_color" => wgpu::PushConstantRange {
+                            stages: wgpu::ShaderStages::FRAGMENT,
+                            range: index..index + 16,
+                        },
                         "@pc_electrum_color" => wgpu::PushConstantRange {
                             stages: wgpu::ShaderStages::FRAGMENT,
                             range: index..index + 16,
                         },
+                        "@pc_environment_data" => wgpu::PushConstantRange {
+                            stages: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                            range: index..index + 56,
+                        },
                         _ => unimplemented!(),
                     }
                 })
-                .collect::<Vec<wgpu::PushConstantRange>>();
+                .collect::<Vec<_>>();
+            let push_constants = merge_push_constant_ranges(push_constants);
 
             let layout =
                 wm.display
@@ -253,19 +907,28 @@ impl RenderGraph {
                         push_constant_ranges: &push_constants,
                     });
 
-            let shader = WgslShader::init(
-                &ResourcePath(format!("wgpu_mc:shaders/{}.wgsl", pipeline_name)),
+            let shader = load_pipeline_shader(
+                pipeline_config.shader_name(pipeline_name),
                 &*wm.mc.resource_provider,
                 &wm.display.device,
-                "frag".into(),
-                "vert".into(),
+                vert_entry,
             )
-            .unwrap();
+            .unwrap_or_else(|| panic!("Pipeline {pipeline_name} has no .wgsl or .vsh/.fsh shader"));
+            let (vert_module, vert_entry) = shader.get_vert();
+            let (frag_module, frag_entry) = shader.get_frag();
 
             let vertex_buffer = match &pipeline_config.geometry[..] {
-                "@geo_terrain" => None,
-                "@geo_entities" => Some(vec![EntityVertex::desc(), InstanceVertex::desc()]),
+                "@geo_terrain" => match pipeline_config.vertex_fetch {
+                    VertexFetch::Pulling => None,
+                    VertexFetch::Attributes => Some(vec![TerrainVertex::packed_desc()]),
+                },
+                "@geo_entities" | "@geo_entities_single" | "@geo_entities_ignore_depth" => {
+                    Some(vec![EntityVertex::desc(), InstanceVertex::desc()])
+                }
                 "@geo_quad" => Some(vec![QuadVertex::desc()]),
+                "@geo_decals" => Some(vec![DecalVertex::desc()]),
+                "@geo_blob_shadows" => Some(vec![BlobShadowInstance::desc()]),
+                "@geo_void_plane" => Some(vec![VoidPlaneInstance::desc()]),
                 "@geo_sun_moon" => Some(vec![SunMoonVertex::desc()]),
                 "@geo_sky_scatter" | "@geo_sky_stars" | "@geo_sky_fog" => {
                     Some(vec![SkyVertex::desc()])
@@ -281,17 +944,26 @@ impl RenderGraph {
                 }
             };
 
+            if let Some(viewport) = &pipeline_config.viewport {
+                let size = self.attachment_size(wm, pipeline_config);
+
+                if !viewport.is_within(size) {
+                    panic!("Pipeline {pipeline_name} has viewport {viewport:?}, which doesn't fit within its {size:?} attachments");
+                }
+            }
+
             let label = pipeline_name.to_string();
+            let sample_count = self.attachment_sample_count(pipeline_name, pipeline_config);
 
-            let render_pipeline =
+            let create_pipeline = |depth_write_enabled, depth_compare, cull_mode, coverage| {
                 wm.display
                     .device
                     .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
                         label: Some(&label),
                         layout: Some(&layout),
                         vertex: wgpu::VertexState {
-                            module: &shader.module,
-                            entry_point: "vert",
+                            module: vert_module,
+                            entry_point: vert_entry,
                             compilation_options: Default::default(),
                             buffers: match &vertex_buffer {
                                 None => &[],
@@ -299,10 +971,10 @@ impl RenderGraph {
                             },
                         },
                         primitive: wgpu::PrimitiveState {
-                            topology: wgpu::PrimitiveTopology::TriangleList,
+                            topology: pipeline_config.topology.primitive_topology(),
                             strip_index_format: None,
                             front_face: wgpu::FrontFace::Ccw,
-                            cull_mode: Some(wgpu::Face::Back),
+                            cull_mode,
                             unclipped_depth: false,
                             polygon_mode: Default::default(),
                             conservative: false,
@@ -310,23 +982,27 @@ impl RenderGraph {
                         depth_stencil: pipeline_config.depth.as_ref().map(|_| {
                             wgpu::DepthStencilState {
                                 format: wgpu::TextureFormat::Depth32Float,
-                                depth_write_enabled: true,
-                                depth_compare: wgpu::CompareFunction::Less,
+                                depth_write_enabled,
+                                depth_compare,
                                 stencil: wgpu::StencilState::default(),
-                                bias: Default::default(),
+                                bias: pipeline_config.depth_bias(),
                             }
                         }),
-                        multisample: Default::default(),
+                        multisample: wgpu::MultisampleState {
+                            count: sample_count,
+                            alpha_to_coverage_enabled: coverage && sample_count > 1,
+                            ..Default::default()
+                        },
                         fragment: Some(wgpu::FragmentState {
-                            module: &shader.module,
-                            entry_point: "frag",
+                            module: frag_module,
+                            entry_point: frag_entry,
                             compilation_options: Default::default(),
                             targets: &pipeline_config
                                 .output
                                 .iter()
-                                .map(|_| {
+                                .map(|output| {
                                     Some(wgpu::ColorTargetState {
-                                        format: wgpu::TextureFormat::Bgra8Unorm,
+                                        format: self.output_format(wm, output),
                                         blend: Some(match &pipeline_config.blending[..] {
                                             "alpha_blending" => wgpu::BlendState::ALPHA_BLENDING,
                                             "premultiplied_alpha_blending" => {
@@ -354,14 +1030,71 @@ impl RenderGraph {
                         }),
                         multiview: None,
                         cache: None,
-                    });
+                    })
+            };
+
+            let depth_compare = pipeline_config.depth_compare_function();
+
+            let translucent_cull_mode = match pipeline_config.translucent_faces {
+                TranslucentFaces::Front => Some(wgpu::Face::Back),
+                TranslucentFaces::Both => None,
+            };
+
+            //Each layer keeps the farthest fragment in front of the previous one, see crate::render::depth_peel
+            if pipeline_config.depth_peel_layers > 0 {
+                self.pipelines.insert(
+                    pipeline_name.clone(),
+                    BoundPipeline {
+                        pipeline: create_pipeline(
+                            true,
+                            wgpu::CompareFunction::Greater,
+                            translucent_cull_mode,
+                            false,
+                        ),
+                        translucent_pipeline: None,
+                        bind_groups: wm_bind_groups,
+                        config: pipeline_config.clone(),
+                        push_constant_ranges: push_constants,
+                    },
+                );
+
+                continue;
+            }
+
+            let render_pipeline = create_pipeline(
+                pipeline_config.depth_write,
+                depth_compare,
+                pipeline_config.cull_face(),
+                pipeline_config.alpha_to_coverage,
+            );
+
+            let translucent_pipeline = (pipeline_config.geometry == "@geo_terrain"
+                && (pipeline_config.translucent_depth == TranslucentDepth::ReadOnly
+                    || pipeline_config.translucent_faces == TranslucentFaces::Both
+                    || pipeline_config.alpha_to_coverage))
+                .then(|| match pipeline_config.translucent_depth {
+                    TranslucentDepth::Write => create_pipeline(
+                        pipeline_config.depth_write,
+                        depth_compare,
+                        translucent_cull_mode,
+                        false,
+                    ),
+                    TranslucentDepth::ReadOnly => create_pipeline(
+                        false,
+                        wgpu::CompareFunction::LessEqual,
+                        translucent_cull_mode,
+                        false,
+                    ),
+                });
 
             self.pipelines.insert(
                 pipeline_name.clone(),
                 BoundPipeline {
                     pipeline: render_pipeline,
+                    translucent_pipeline,
                     bind_groups: wm_bind_groups,
                     config: pipeline_config.clone(),
+                    push_constant_ranges: push_constants,
                 },
             );
         }
@@ -369,11 +1102,43 @@ impl RenderGraph {
 
     pub fn new(
         wm: &WmRenderer,
-        config: ShaderPackConfig,
+        mut config: ShaderPackConfig,
         mut resources: HashMap<String, ResourceBacking>,
         custom_bind_groups: Option<HashMap<String, &wgpu::BindGroupLayout>>,
         custom_geometry: Option<HashMap<String, Vec<wgpu::VertexBufferLayout>>>,
     ) -> Self {
+        validate_fallbacks(&config.pipelines);
+
+        let features = wm.display.device.features();
+        let limits = wm.display.device.limits();
+        let downlevel = wm.display.adapter.get_downlevel_capabilities();
+
+        let mut skipped_pipelines = config.pipelines.skip_unbound();
+        skipped_pipelines.extend(
+            config
+                .pipelines
+                .skip_unsupported(|feature| feature.is_supported(features, &limits, &downlevel)),
+        );
+
+        for skipped in &skipped_pipelines {
+            if let Some(bind_group) = &skipped.unbound {
+                log::warn!(
+                    "Skipping pipeline {}, its geometry {} doesn't set the bind group {}",
+                    skipped.name,
+                    skipped.config.geometry,
+                    bind_group
+                );
+            } else if !skipped.missing.is_empty() {
+                log::warn!(
+                    "Skipping pipeline {}, the device doesn't support {:?}",
+                    skipped.name,
+                    skipped.missing
+                );
+            }
+        }
+
+        let mut resolve_targets = HashMap::new();
+
         for (resource_id, shorthand) in &config.resources.resources {
             match shorthand {
                 ShorthandResourceConfig::Int(_) => {}
@@ -403,44 +1168,269 @@ impl RenderGraph {
                                 ResourceBacking::Texture2D(Arc::new(tav)),
                             );
                         }
-                        TypeResourceConfig::TextureDepth => {}
-                        TypeResourceConfig::F32 { .. } => {}
-                        TypeResourceConfig::F64 { .. } => {}
-                        TypeResourceConfig::I64 { .. } => {}
-                        TypeResourceConfig::I32 { .. } => {}
-                        TypeResourceConfig::Mat3(_) => {}
-                        TypeResourceConfig::Mat4(_) => {}
-                    }
-                }
+                        TypeResourceConfig::TextureDepth { samples } => {
+                            resources.insert(
+                                resource_id.clone(),
+                                ResourceBacking::Texture2D(Arc::new(create_render_target(
+                                    wm,
+                                    resource_id,
+                                    wgpu::TextureFormat::Depth32Float,
+                                    *samples,
+                                ))),
+                            );
+                        }
+                        TypeResourceConfig::RenderTarget { samples, resolve } => {
+                            resources.insert(
+                                resource_id.clone(),
+                                ResourceBacking::Texture2D(Arc::new(create_render_target(
+                                    wm,
+                                    resource_id,
+                                    wm.display.view_format(),
+                                    *samples,
+                                ))),
+                            );
+
+                            if let Some(resolve) = resolve {
+                                resolve_targets.insert(resource_id.clone(), resolve.clone());
+                            }
+                        }
+                        TypeResourceConfig::StorageBuffer { size, access } => {
+                            let buffer = wm.display.device.create_buffer(&wgpu::BufferDescriptor {
+                                label: Some(resource_id),
+                                size: *size,
+                                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                                mapped_at_creation: false,
+                            });
+
+                            resources.insert(
+                                resource_id.clone(),
+                                ResourceBacking::Buffer(
+                                    Arc::new(buffer),
+                                    wgpu::BufferBindingType::Storage {
+                                        read_only: *access == StorageAccess::Read,
+                                    },
+                                ),
+                            );
+                        }
+                        TypeResourceConfig::Sampler {
+                            address_mode,
+                            mag_filter,
+                            min_filter,
+                            mipmap_filter,
+                            compare,
+                            anisotropy,
+                        } => {
+                            validate_sampler_anisotropy(
+                                resource_id,
+                                [*mag_filter, *min_filter, *mipmap_filter],
+                                *anisotropy,
+                            );
+
+                            let sampler =
+                                wm.display.device.create_sampler(&wgpu::SamplerDescriptor {
+                                    label: Some(resource_id),
+                                    address_mode_u: (*address_mode).into(),
+                                    address_mode_v: (*address_mode).into(),
+                                    address_mode_w: (*address_mode).into(),
+                                    mag_filter: (*mag_filter).into(),
+                                    min_filter: (*min_filter).into(),
+                                    mipmap_filter: (*mipmap_filter).into(),
+                                    compare: compare.map(Into::into),
+                                    anisotropy_clamp: *anisotropy,
+                                    ..Default::default()
+                                });
+
+                            let sampler_ty = if compare.is_some() {
+                                SamplerBindingType::Comparison
+                            } else {
+                                filter_binding_type([*mag_filter, *min_filter, *mipmap_filter])
+                            };
+
+                            resources.insert(
+                                resource_id.clone(),
+                                ResourceBacking::Sampler(Arc::new(sampler), sampler_ty),
+                            );
+                        }
+                        TypeResourceConfig::F32 { .. } => {}
+                        TypeResourceConfig::F64 { .. } => {}
+                        TypeResourceConfig::I64 { .. } => {}
+                        TypeResourceConfig::I32 { .. } => {}
+                        TypeResourceConfig::Mat3(_) => {}
+                        TypeResourceConfig::Mat4(_) => {}
+                    }
+                }
             }
         }
 
+        let descriptions = config
+            .pipelines
+            .pipelines
+            .iter()
+            .map(|(name, pipeline)| PipelineDescription::new(name, pipeline, &resolve_targets))
+            .collect::<Vec<_>>();
+
+        validate_resolve_targets(&resources, &resolve_targets);
+        validate_target_reads(&descriptions);
+        validate_skipped_targets(
+            &skipped_pipelines
+                .iter()
+                .filter(|skipped| !skipped.missing.is_empty())
+                .map(|skipped| {
+                    PipelineDescription::new(&skipped.name, &skipped.config, &resolve_targets)
+                })
+                .collect::<Vec<_>>(),
+            &descriptions,
+        );
+
+        let auto_clear = framebuffer_auto_clear(&descriptions).map(str::to_string);
+
         let mut graph = Self {
             config,
             pipelines: LinkedHashMap::new(),
             resources,
+            resolve_targets,
+            auto_clear,
+            depth_peel: Mutex::new(None),
+            skipped_pipelines,
         };
 
+        //Create the atlases the pack binds which the host hasn't yet, so they're bound and can be filled later
+        for pipeline in graph.config.pipelines.pipelines.values() {
+            for bind_group in pipeline.bind_groups.values() {
+                let BindGroupDef::Entries(entries) = bind_group else {
+                    continue;
+                };
+
+                for name in entries
+                    .values()
+                    .filter_map(|resource_id| TextureManager::resource_atlas_name(resource_id))
+                {
+                    wm.mc.texture_manager.create_atlas(&wm.display, name);
+                }
+            }
+        }
+
         let atlases = wm.mc.texture_manager.atlases.read();
 
         let block_atlas = atlases.get(BLOCK_ATLAS).unwrap();
 
+        if graph.config.pbr {
+            for atlas in atlases.values() {
+                atlas.add_layer(
+                    &wm.display,
+                    NORMAL_LAYER,
+                    "_n",
+                    FLAT_NORMAL,
+                    &*wm.mc.resource_provider,
+                );
+                atlas.add_layer(
+                    &wm.display,
+                    SPECULAR_LAYER,
+                    "_s",
+                    DEFAULT_SPECULAR,
+                    &*wm.mc.resource_provider,
+                );
+            }
+        }
+
+        graph.resources.extend(atlases.iter().map(|(name, atlas)| {
+            (
+                TextureManager::atlas_resource_name(name),
+                ResourceBacking::Texture2D(atlas.bindable_texture().clone()),
+            )
+        }));
+
+        for (name, atlas) in atlases.iter() {
+            graph
+                .resources
+                .extend(atlas.layers.read().iter().map(|(layer_name, layer)| {
+                    (
+                        format!("{}_{layer_name}", TextureManager::atlas_resource_name(name)),
+                        ResourceBacking::Texture2D(layer.texture.clone()),
+                    )
+                }));
+        }
+
+        for (path, standalone) in wm.mc.texture_manager.standalone.read().iter() {
+            let (texture_name, sampler_name) = TextureManager::standalone_resource_names(path);
+
+            graph.resources.extend([
+                (
+                    texture_name,
+                    ResourceBacking::Texture2D(standalone.texture.clone()),
+                ),
+                (
+                    sampler_name,
+                    ResourceBacking::Sampler(
+                        standalone.sampler.clone(),
+                        SamplerBindingType::NonFiltering,
+                    ),
+                ),
+            ]);
+        }
+
+        for (layer_name, layer) in block_atlas.layers.read().iter() {
+            graph.resources.insert(
+                format!("@texture_block_atlas_{layer_name}"),
+                ResourceBacking::Texture2D(layer.texture.clone()),
+            );
+        }
+
+        let block_sampler = graph.block_sampler(wm);
+
         graph.resources.extend([
             (
                 "@texture_block_atlas".into(),
-                ResourceBacking::Texture2D(block_atlas.texture.clone()),
+                ResourceBacking::Texture2D(block_atlas.bindable_texture().clone()),
+            ),
+            ("@sampler".into(), block_sampler),
+            (
+                "@buffer_texture_quality".into(),
+                ResourceBacking::Buffer(
+                    wm.mc.texture_manager.texture_quality_buffer.clone(),
+                    wgpu::BufferBindingType::Uniform,
+                ),
             ),
             (
-                "@sampler".into(),
-                ResourceBacking::Sampler(wm.mc.texture_manager.default_sampler.clone()),
+                "@buffer_uv_scrolls".into(),
+                ResourceBacking::Buffer(
+                    wm.mc.uv_scroll_buffer.clone(),
+                    wgpu::BufferBindingType::Uniform,
+                ),
+            ),
+            (
+                "@buffer_sky".into(),
+                ResourceBacking::Buffer(wm.mc.sky_buffer.clone(), wgpu::BufferBindingType::Uniform),
+            ),
+            (
+                "@buffer_color_spaces".into(),
+                ResourceBacking::Buffer(
+                    wm.mc.color_space_buffer.clone(),
+                    wgpu::BufferBindingType::Uniform,
+                ),
             ),
         ]);
 
         graph.create_pipelines(wm, custom_bind_groups, custom_geometry);
 
+        if let Some(terrain) = graph
+            .pipelines
+            .values()
+            .find(|pipeline| pipeline.config.geometry == "@geo_terrain")
+        {
+            wm.mc.block_manager.write().coplanar_order =
+                CoplanarOrder::for_depth_compare(terrain.config.depth_compare_function());
+        }
+
         graph
     }
 
+    /// Record every pipeline into `encoder`, each as its own render pass in the order they're declared. Nothing is
+    /// submitted here, so a pipeline sampling a target drawn by an earlier one always sees this frame's contents, as
+    /// long as the caller submits `encoder` as a whole. See [target_reads].
+    ///
+    /// Pipelines with `clear` clear their outputs to `clear_color`, as does [RenderGraph::auto_clear] for the
+    /// framebuffer
     pub fn render(
         &self,
         wm: &WmRenderer,
@@ -450,14 +1440,215 @@ impl RenderGraph {
         clear_color: [u8; 3],
         geometry: &mut HashMap<String, Box<dyn Geometry>>,
         frustum: &Frustum<f32>,
+    ) {
+        self.render_with_clear_depth(
+            wm,
+            encoder,
+            scene,
+            render_target,
+            clear_color,
+            geometry,
+            frustum,
+            1.0,
+        );
+    }
+
+    /// Like [RenderGraph::render], but the first pipeline with a depth attachment clears it to `clear_depth`
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_with_clear_depth(
+        &self,
+        wm: &WmRenderer,
+        encoder: &mut wgpu::CommandEncoder,
+        scene: &Scene,
+        render_target: &wgpu::TextureView,
+        clear_color: [u8; 3],
+        geometry: &mut HashMap<String, Box<dyn Geometry>>,
+        frustum: &Frustum<f32>,
+        clear_depth: f32,
+    ) {
+        self.render_frame(
+            wm,
+            encoder,
+            scene,
+            render_target,
+            clear_color,
+            geometry,
+            frustum,
+            clear_depth,
+            None,
+            false,
+        );
+    }
+
+    /// [RenderGraph::render_with_clear_depth] with the framebuffer cleared to `framebuffer_clear` before any pipeline
+    /// runs instead of by [RenderGraph::auto_clear]. That's the texture bound as `@framebuffer_texture`, the scaled
+    /// target with a render scale, and `render_target` itself. The color and depth are cleared independently: with
+    /// `clear_depth_first`, `@texture_depth` is cleared to `clear_depth` up front as well, after it's resized to the
+    /// scaled target, and the first pipeline using it loads it instead
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn render_frame(
+        &self,
+        wm: &WmRenderer,
+        encoder: &mut wgpu::CommandEncoder,
+        scene: &Scene,
+        render_target: &wgpu::TextureView,
+        clear_color: [u8; 3],
+        geometry: &mut HashMap<String, Box<dyn Geometry>>,
+        frustum: &Frustum<f32>,
+        clear_depth: f32,
+        framebuffer_clear: Option<Color>,
+        clear_depth_first: bool,
     ) {
         let arena = WmArena::new(4096);
 
         let mut should_clear_depth = true;
 
-        for (pipeline_name, bound_pipeline) in &self.pipelines {
+        wm.mc.texture_manager.flush_uploads(&wm.display);
+
+        wm.display.queue.write_buffer(
+            &wm.mc.sky_buffer,
+            0,
+            bytemuck::bytes_of(&SkyUniform::new(&scene.sky_state.read())),
+        );
+
+        let block_atlas_format = wm.mc.texture_manager.atlases.read()[BLOCK_ATLAS]
+            .texture
+            .format;
+        wm.display.queue.write_buffer(
+            &wm.mc.color_space_buffer,
+            0,
+            bytemuck::bytes_of(&ColorSpaceUniform::new(
+                wm.mc.block_manager.read().vertex_color_space,
+                ColorSpace::of_format(block_atlas_format),
+            )),
+        );
+
+        if let Some((width, height)) = wm.prepare_scaled_target() {
+            scene.resize_depth_texture(wm, width, height);
+        }
+
+        let scaled_target = wm.scaled_target.read();
+        let mut scaled_target_drawn = false;
+
+        //The host may have resized the depth texture to the window after a resize which didn't change the scaled size
+        if let Some(target) = &*scaled_target {
+            let (width, height) = target.size();
+            let depth_size = scene.depth_texture.read().size();
+
+            if (depth_size.width, depth_size.height) != (width, height) {
+                scene.resize_depth_texture(wm, width, height);
+            }
+        }
+
+        if let Some(color) = framebuffer_clear {
+            let scaled_view = scaled_target.as_ref().map(|target| &target.texture.view);
+
+            for view in [Some(render_target), scaled_view].into_iter().flatten() {
+                encoder.begin_render_pass(&RenderPassDescriptor {
+                    label: Some("framebuffer clear"),
+                    color_attachments: &[Some(RenderPassColorAttachment {
+                        view,
+                        resolve_target: None,
+                        ops: Operations {
+                            load: LoadOp::Clear(color),
+                            store: StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+            }
+        }
+
+        if clear_depth_first {
+            let depth_view = scene
+                .depth_texture
+                .read()
+                .create_view(&wgpu::TextureViewDescriptor::default());
+
+            encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("depth clear"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: &depth_view,
+                    depth_ops: Some(Operations {
+                        load: LoadOp::Clear(clear_depth),
+                        store: StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+        }
+
+        //Peeling reads and writes depth textures of its own, which follow the size of the scene's depth texture
+        let mut depth_peel = self.depth_peel.lock();
+        let peels_terrain = self
+            .pipelines
+            .values()
+            .any(|pipeline| pipeline.config.depth_peel_layers > 0);
+
+        if peels_terrain {
+            let size = scene.depth_texture.read().size();
+
+            if depth_peel.as_ref().map(|peel| peel.size) != Some(size) {
+                *depth_peel = Some(DepthPeelTargets::new(wm, size));
+            }
+        }
+
+        let depth_peel = depth_peel.as_ref();
+
+        //A depth peeling pipeline runs once per layer
+        let passes = self.pipelines.iter().flat_map(|(name, pipeline)| {
+            let peel_layers = (pipeline.config.depth_peel_layers > 0)
+                .then_some(0..pipeline.config.depth_peel_layers);
+
+            match peel_layers {
+                None => vec![(name, pipeline, None)],
+                Some(layers) => layers.map(|layer| (name, pipeline, Some(layer))).collect(),
+            }
+        });
+
+        for (pipeline_name, bound_pipeline, peel_layer) in passes {
             let pipeline_config = self.config.pipelines.pipelines.get(pipeline_name).unwrap();
 
+            //The first layer discards what's behind opaque terrain, so it reads a copy of the scene's depth
+            if peel_layer == Some(0) {
+                let peel = depth_peel.unwrap();
+                let (_, read) = peel_targets(0);
+
+                encoder.copy_texture_to_texture(
+                    scene.depth_texture.read().as_image_copy(),
+                    peel.textures[read].as_image_copy(),
+                    peel.size,
+                );
+            }
+
+            if let Some(target) = &*scaled_target {
+                if pipeline_config.native_resolution && !scaled_target_drawn {
+                    target.blit(encoder, render_target);
+                    scaled_target_drawn = true;
+                }
+            }
+
+            //With a render scale the scene is drawn offscreen, and later stretched over the actual framebuffer
+            let framebuffer = match &*scaled_target {
+                Some(target) if !pipeline_config.native_resolution => &target.texture.view,
+                _ => render_target,
+            };
+
+            let auto_clears =
+                framebuffer_clear.is_none() && self.auto_clear.as_ref() == Some(pipeline_name);
+            let clears = |texture_name: &str| {
+                let clears = pipeline_config.clear
+                    || (auto_clears && texture_name == "@framebuffer_texture");
+
+                //Later depth peeling layers blend over the earlier ones
+                clears && !peel_layer.is_some_and(|layer| layer > 0)
+            };
+
             let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
                 label: None,
                 occlusion_query_set: None,
@@ -468,12 +1659,20 @@ impl RenderGraph {
                     .map(|texture_name| {
                         Some(RenderPassColorAttachment {
                             view: match &texture_name[..] {
-                                "@framebuffer_texture" => render_target,
-                                _ => unimplemented!(),
+                                "@framebuffer_texture" => framebuffer,
+                                _ => match self.resources.get(texture_name) {
+                                    Some(ResourceBacking::Texture2D(texture)) => &texture.view,
+                                    _ => unreachable!("Checked by validate_attachments"),
+                                },
                             },
-                            resolve_target: None,
+                            resolve_target: self.resolve_targets.get(texture_name).map(|resolve| {
+                                match self.resources.get(resolve) {
+                                    Some(ResourceBacking::Texture2D(texture)) => &texture.view,
+                                    _ => unreachable!(),
+                                }
+                            }),
                             ops: Operations {
-                                load: if !pipeline_config.clear {
+                                load: if !clears(texture_name) {
                                     LoadOp::Load
                                 } else {
                                     LoadOp::Clear(Color {
@@ -489,7 +1688,22 @@ impl RenderGraph {
                     })
                     .collect::<Vec<_>>(),
                 depth_stencil_attachment: pipeline_config.depth.as_ref().map(|depth_texture| {
-                    let will_clear_depth = should_clear_depth;
+                    //Every layer starts at the near plane, since it keeps the farthest fragment
+                    if let Some(layer) = peel_layer {
+                        let (write, _) = peel_targets(layer);
+
+                        return RenderPassDepthStencilAttachment {
+                            view: &depth_peel.unwrap().views[write],
+                            depth_ops: Some(Operations {
+                                load: LoadOp::Clear(0.0),
+                                store: StoreOp::Store,
+                            }),
+                            stencil_ops: None,
+                        };
+                    }
+
+                    let will_clear_depth = should_clear_depth
+                        && !(clear_depth_first && depth_texture == "@texture_depth");
                     should_clear_depth = false;
 
                     let depth_view = if depth_texture == "@texture_depth" {
@@ -508,7 +1722,7 @@ impl RenderGraph {
                     } else {
                         match self.resources.get(depth_texture) {
                             Some(ResourceBacking::Texture2D(view)) => &view.view,
-                            _ => unimplemented!("Unknown depth target {}", depth_texture),
+                            _ => unreachable!("Checked by validate_attachments"),
                         }
                     };
 
@@ -516,7 +1730,7 @@ impl RenderGraph {
                         view: depth_view,
                         depth_ops: Some(Operations {
                             load: if will_clear_depth {
-                                LoadOp::Clear(1.0)
+                                LoadOp::Clear(clear_depth)
                             } else {
                                 LoadOp::Load
                             },
@@ -527,6 +1741,25 @@ impl RenderGraph {
                 }),
             });
 
+            if let Some(viewport) = &pipeline_config.viewport {
+                let [x, y, width, height] =
+                    viewport.clamped_pixel_rect(self.attachment_size(wm, pipeline_config));
+
+                //The window shrank until the viewport is entirely outside of it
+                if width <= 0.0 || height <= 0.0 {
+                    continue;
+                }
+
+                render_pass.set_viewport(
+                    x,
+                    y,
+                    width,
+                    height,
+                    viewport.min_depth.0,
+                    viewport.max_depth.0,
+                );
+            }
+
             match &pipeline_config.geometry[..] {
                 "@geo_terrain" => {
                     render_pass.set_pipeline(&bound_pipeline.pipeline);
@@ -541,6 +1774,22 @@ impl RenderGraph {
                                         &[],
                                     );
                                 }
+                                "@bg_ssbo_section_tints" => {
+                                    render_pass.set_bind_group(
+                                        *index,
+                                        &scene.section_tint_buffer.bind_group,
+                                        &[],
+                                    );
+                                }
+                                "@bg_peel_depth" => {
+                                    let (_, read) = peel_targets(peel_layer.unwrap());
+
+                                    render_pass.set_bind_group(
+                                        *index,
+                                        &depth_peel.unwrap().bind_groups[read],
+                                        &[],
+                                    );
+                                }
                                 _ => unimplemented!(),
                             },
                             WmBindGroup::Custom(bind_group) => {
@@ -549,50 +1798,217 @@ impl RenderGraph {
                         }
                     }
 
-                    render_pass.set_index_buffer(
-                        scene.chunk_buffer.buffer.slice(..),
-                        wgpu::IndexFormat::Uint32,
-                    );
+                    let mut index_format = wgpu::IndexFormat::Uint32;
+                    render_pass.set_index_buffer(scene.chunk_buffer.buffer.slice(..), index_format);
 
                     let sections = scene.section_storage.write();
                     let camera_pos = *scene.camera_section_pos.read();
-                    for (pos, section) in sections.iter() {
-                        let rel_pos = ivec3(pos.x - camera_pos.x, pos.y, pos.z - camera_pos.y);
-                        let a: Vec3<f32> =
-                            [rel_pos.x as f32, rel_pos.y as f32, rel_pos.z as f32].into();
-                        let b: Vec3<f32> = a + Vec3::new(1.0, 1.0, 1.0);
+                    let margin = *scene.frustum_culling_margin.read();
+                    let margin = Vec3::new(margin, margin, margin);
+                    let fade_duration = *scene.section_fade_duration.read();
+                    let sun_direction = scene.sky_state.read().sun_direction();
+                    let wind = scene.wind.read().push_constant();
+                    let (fog, fog_color) = {
+                        let render_effects = scene.render_effects.read();
+                        (render_effects.fog_params(), render_effects.fog_color)
+                    };
+                    let section_visibility = scene.section_visibility.read();
+
+                    let candidates = match &mut *scene.underground_culling.lock() {
+                        Some(culling) => Either::Left(
+                            culling
+                                .filter(
+                                    scene.camera_position.read().y,
+                                    *scene.camera_pitch.read(),
+                                    sections.iter().map(|entry| (entry.0.y, entry)),
+                                )
+                                .into_iter(),
+                        ),
+                        None => Either::Right(sections.iter()),
+                    };
+
+                    let mut visible_sections = candidates
+                        .filter_map(|(pos, section)| {
+                            let rel_pos = ivec3(pos.x - camera_pos.x, pos.y, pos.z - camera_pos.y);
+                            let a: Vec3<f32> =
+                                [rel_pos.x as f32, rel_pos.y as f32, rel_pos.z as f32].into();
+                            let b: Vec3<f32> = a + Vec3::new(1.0, 1.0, 1.0);
+
+                            let bounds: AABB<f32> = AABB::new(
+                                (a * 16.0 - margin).into_array(),
+                                (b * 16.0 + margin).into_array(),
+                            );
+
+                            if !bounds.coherent_test_against_frustum(frustum, 0).0 {
+                                return None;
+                            }
+
+                            if let Some(is_visible) = &*section_visibility {
+                                if !is_visible(*pos) {
+                                    return None;
+                                }
+                            }
+
+                            Some((pos, rel_pos, section))
+                        })
+                        .collect::<Vec<_>>();
 
-                        let bounds: AABB<f32> =
-                            AABB::new((a * 16.0).into_array(), (b * 16.0).into_array());
+                    let camera = scene.to_render_space(*scene.camera_position.read());
+                    let distance = |rel_pos: &glam::IVec3| {
+                        (rel_pos.as_vec3() * 16.0 + 8.0).distance_squared(camera)
+                    };
+                    let enabled_layers = scene.enabled_render_layers.read();
+
+                    if let Some(budget) = &mut *scene.triangle_budget.lock() {
+                        visible_sections.sort_by(|(pos_a, rel_a, _), (pos_b, rel_b, _)| {
+                            distance(rel_a)
+                                .total_cmp(&distance(rel_b))
+                                .then_with(|| pos_a.to_array().cmp(&pos_b.to_array()))
+                        });
+
+                        let costs = visible_sections
+                            .iter()
+                            .map(|(_, rel_pos, section)| {
+                                (distance(rel_pos), section.triangle_count(&enabled_layers))
+                            })
+                            .collect::<Vec<_>>();
 
-                        if !bounds.coherent_test_against_frustum(frustum, 0).0 {
+                        visible_sections.truncate(budget.select(&costs));
+                    }
+
+                    //Translucent sections are drawn back to front. Sections at the same distance are ordered by their
+                    //position, so that ties don't flip between frames with the HashMap's iteration order
+                    let mut translucent_sections = visible_sections.clone();
+                    translucent_sections.sort_by(|(pos_a, rel_a, _), (pos_b, rel_b, _)| {
+                        distance(rel_b)
+                            .total_cmp(&distance(rel_a))
+                            .then_with(|| pos_a.to_array().cmp(&pos_b.to_array()))
+                    });
+
+                    //Draw every solid layer before the cutout and transparent ones
+                    for render_layer in RenderLayer::ALL {
+                        if !enabled_layers.contains(&render_layer) {
                             continue;
                         }
-                        if let Some(layer) = &section.layers[RenderLayer::Solid as usize] {
-                            let mut pc: HashMap<String, (Vec<u8>, ShaderStages)> = HashMap::new();
-                            //println!("draw {pos}");
-                            pc.insert(
-                                "@pc_section_position".to_string(),
-                                (
-                                    bytemuck::cast_slice(&rel_pos.to_array()).to_vec(),
-                                    ShaderStages::VERTEX,
-                                ),
-                            );
-                            set_push_constants(pipeline_config, &mut render_pass, Some(pc));
-                            render_pass.draw_indexed(
-                                layer.index_range.clone(),
-                                0,
-                                layer.vertex_range.start..layer.vertex_range.start + 1,
-                            );
+
+                        //Depth peeling takes over the translucent layer from the other terrain pipelines
+                        let is_transparent = render_layer == RenderLayer::Transparent;
+                        if peels_terrain && is_transparent != peel_layer.is_some() {
+                            continue;
+                        }
+
+                        let layer_sections = if render_layer == RenderLayer::Transparent {
+                            if let Some(pipeline) = &bound_pipeline.translucent_pipeline {
+                                render_pass.set_pipeline(pipeline);
+                            }
+
+                            &translucent_sections
+                        } else {
+                            &visible_sections
+                        };
+
+                        for (pos, rel_pos, section) in layer_sections {
+                            if let Some(Some(layer)) = section.layers.get(render_layer as usize) {
+                                let mut pc: HashMap<String, (Vec<u8>, ShaderStages)> =
+                                    HashMap::new();
+                                //println!("draw {pos}");
+                                pc.insert(
+                                    "@pc_section_position".to_string(),
+                                    (
+                                        bytemuck::cast_slice(&rel_pos.to_array()).to_vec(),
+                                        ShaderStages::VERTEX,
+                                    ),
+                                );
+                                pc.insert(
+                                    "@pc_section_tint_slot".to_string(),
+                                    (
+                                        bytemuck::cast_slice(&[sections.get_tint_slot(**pos)])
+                                            .to_vec(),
+                                        ShaderStages::VERTEX,
+                                    ),
+                                );
+                                pc.insert(
+                                    "@pc_section_fade".to_string(),
+                                    (
+                                        bytemuck::cast_slice(&[section.fade(fade_duration)])
+                                            .to_vec(),
+                                        ShaderStages::VERTEX,
+                                    ),
+                                );
+                                pc.insert(
+                                    "@pc_wind".to_string(),
+                                    (bytemuck::cast_slice(&wind).to_vec(), ShaderStages::VERTEX),
+                                );
+                                pc.insert(
+                                    "@pc_sun_direction".to_string(),
+                                    (
+                                        bytemuck::cast_slice(&sun_direction.to_array()).to_vec(),
+                                        ShaderStages::VERTEX,
+                                    ),
+                                );
+                                pc.insert(
+                                    "@pc_fog".to_string(),
+                                    (bytemuck::cast_slice(&fog).to_vec(), ShaderStages::FRAGMENT),
+                                );
+                                pc.insert(
+                                    "@pc_fog_color".to_string(),
+                                    (
+                                        bytemuck::cast_slice(&fog_color).to_vec(),
+                                        ShaderStages::FRAGMENT,
+                                    ),
+                                );
+                                set_push_constants(bound_pipeline, &mut render_pass, Some(pc));
+                                if layer.index_format != index_format {
+                                    index_format = layer.index_format;
+                                    render_pass.set_index_buffer(
+                                        scene.chunk_buffer.buffer.slice(..),
+                                        index_format,
+                                    );
+                                }
+
+                                if pipeline_config.vertex_fetch == VertexFetch::Attributes {
+                                    //Indices are relative to the section, the shader still gets the offset for the
+                                    //rest of the quad through the instance index
+                                    render_pass.set_vertex_buffer(
+                                        0,
+                                        scene
+                                            .chunk_buffer
+                                            .buffer
+                                            .slice(layer.vertex_range.start as u64 * 4..),
+                                    );
+                                }
+
+                                render_pass.draw_indexed(
+                                    layer.indices(),
+                                    0,
+                                    layer.vertex_range.start..layer.vertex_range.start + 1,
+                                );
+                            }
                         }
                     }
                 }
-                "@geo_entities" => {
+                "@geo_entities" | "@geo_entities_single" | "@geo_entities_ignore_depth" => {
                     render_pass.set_pipeline(&bound_pipeline.pipeline);
 
+                    let single = pipeline_config.geometry == "@geo_entities_single";
+                    //Without a single or ignore depth pipeline those bundles are drawn by the others
+                    let has_pipeline = |geometry: &str| {
+                        self.pipelines
+                            .values()
+                            .any(|pipeline| pipeline.config.geometry == geometry)
+                    };
+                    let has_single_pipeline = has_pipeline("@geo_entities_single");
+                    let has_ignore_depth_pipeline = has_pipeline("@geo_entities_ignore_depth");
+
                     let instances = { scene.entity_instances.lock().clone() };
 
                     for (_, entity_instances) in &instances {
+                        if entity_instances.geometry(has_single_pipeline, has_ignore_depth_pipeline)
+                            != pipeline_config.geometry
+                        {
+                            continue;
+                        }
+
                         for (index, bind_group) in bound_pipeline.bind_groups.iter() {
                             match bind_group {
                                 WmBindGroup::Resource(name) => match &name[..] {
@@ -603,6 +2019,17 @@ impl RenderGraph {
                                             &[],
                                         );
                                     }
+                                    "@bg_entity_single" => {
+                                        render_pass.set_bind_group(
+                                            *index,
+                                            &**entity_instances
+                                                .uploaded
+                                                .single_bind_group
+                                                .as_ref()
+                                                .unwrap(),
+                                            &[],
+                                        );
+                                    }
                                     _ => unimplemented!(),
                                 },
                                 WmBindGroup::Custom(bind_group) => {
@@ -615,12 +2042,12 @@ impl RenderGraph {
                         pc.insert(
                             "@pc_parts_per_entity".to_string(),
                             (
-                                bytemuck::cast_slice(&[entity_instances.entity.parts.len() as u32])
+                                bytemuck::cast_slice(&[entity_instances.entity.part_count as u32])
                                     .to_vec(),
                                 ShaderStages::VERTEX,
                             ),
                         );
-                        set_push_constants(pipeline_config, &mut render_pass, Some(pc));
+                        set_push_constants(bound_pipeline, &mut render_pass, Some(pc));
 
                         render_pass.set_vertex_buffer(0, entity_instances.entity.mesh.slice(..));
                         render_pass
@@ -628,8 +2055,172 @@ impl RenderGraph {
 
                         render_pass.draw(
                             0..entity_instances.entity.vertex_count,
-                            0..entity_instances.capacity,
+                            0..if single { 1 } else { entity_instances.capacity },
+                        );
+                    }
+                }
+                "@geo_decals" => {
+                    bound_pipeline.bind(&mut render_pass);
+
+                    let decals = arena.alloc(
+                        scene
+                            .decals
+                            .read()
+                            .values()
+                            .cloned()
+                            .collect::<Vec<Decal>>(),
+                    );
+
+                    for decal in decals.iter() {
+                        let mut pc: HashMap<String, (Vec<u8>, ShaderStages)> = HashMap::new();
+                        pc.insert(
+                            "@pc_mat4_model".to_string(),
+                            (
+                                bytemuck::cast_slice(&decal.transform.to_cols_array()).to_vec(),
+                                ShaderStages::VERTEX,
+                            ),
                         );
+                        pc.insert(
+                            "@pc_decal_depth_bias".to_string(),
+                            (
+                                bytemuck::cast_slice(&[decal.depth_bias]).to_vec(),
+                                ShaderStages::VERTEX,
+                            ),
+                        );
+                        set_push_constants(bound_pipeline, &mut render_pass, Some(pc));
+
+                        render_pass.set_vertex_buffer(0, decal.vertex_buffer.slice(..));
+                        render_pass.draw(0..decal.vertex_count, 0..1);
+                    }
+                }
+                "@geo_blob_shadows" => {
+                    let strength = *scene.blob_shadow_strength.read();
+
+                    let instances = scene
+                        .blob_shadows
+                        .read()
+                        .values()
+                        .filter_map(|shadow| shadow.instance(strength))
+                        .collect::<Vec<_>>();
+
+                    if instances.is_empty() {
+                        continue;
+                    }
+
+                    bound_pipeline.bind(&mut render_pass);
+
+                    let mut pc: HashMap<String, (Vec<u8>, ShaderStages)> = HashMap::new();
+                    pc.insert(
+                        "@pc_decal_depth_bias".to_string(),
+                        (
+                            bytemuck::cast_slice(&[DEFAULT_DECAL_DEPTH_BIAS]).to_vec(),
+                            ShaderStages::VERTEX,
+                        ),
+                    );
+                    set_push_constants(bound_pipeline, &mut render_pass, Some(pc));
+
+                    let instance_buffer = arena.alloc(wm.display.device.create_buffer_init(
+                        &wgpu::util::BufferInitDescriptor {
+                            label: Some("blob shadows"),
+                            contents: bytemuck::cast_slice(&instances),
+                            usage: wgpu::BufferUsages::VERTEX,
+                        },
+                    ));
+
+                    render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
+                    render_pass.draw(0..6, 0..instances.len() as u32);
+                }
+                "@geo_void_plane" => {
+                    let [r, g, b, _] = scene.render_effects.read().fog_color;
+
+                    let Some(instance) = scene.void_plane.read().instance(
+                        *scene.camera_position.read(),
+                        scene.render_origin().as_dvec3(),
+                        [r, g, b],
+                    ) else {
+                        continue;
+                    };
+
+                    bound_pipeline.bind(&mut render_pass);
+
+                    let instance_buffer = arena.alloc(wm.display.device.create_buffer_init(
+                        &wgpu::util::BufferInitDescriptor {
+                            label: Some("void plane"),
+                            contents: bytemuck::bytes_of(&instance),
+                            usage: wgpu::BufferUsages::VERTEX,
+                        },
+                    ));
+
+                    render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
+                    render_pass.draw(0..6, 0..1);
+                }
+                sky if SKY_GEOMETRIES.contains(&sky) => {
+                    let (vertices, vertex_count, indices) = match sky {
+                        "@geo_sky_scatter" => {
+                            let (vertices, indices) = SkyVertex::load_sky_dome();
+                            (bytemuck::cast_slice(&vertices).to_vec(), 0, indices)
+                        }
+                        "@geo_sky_fog" => {
+                            let (vertices, indices) = SkyVertex::load_fog_sphere();
+                            (
+                                bytemuck::cast_slice(&vertices).to_vec(),
+                                0,
+                                indices.to_vec(),
+                            )
+                        }
+                        "@geo_sky_stars" => {
+                            let stars = SkyVertex::load_stars();
+                            (
+                                bytemuck::cast_slice(stars).to_vec(),
+                                stars.len() as u32,
+                                Vec::new(),
+                            )
+                        }
+                        _ => {
+                            let moon_phase = scene.sky_state.read().moon_phase;
+                            let vertices = [
+                                SunMoonVertex::load_vertex_sun(),
+                                SunMoonVertex::load_vertex_moon(moon_phase),
+                            ];
+                            (bytemuck::cast_slice(&vertices).to_vec(), 12, Vec::new())
+                        }
+                    };
+
+                    bound_pipeline.bind(&mut render_pass);
+
+                    let mut pc: HashMap<String, (Vec<u8>, ShaderStages)> = HashMap::new();
+                    pc.insert(
+                        "@pc_environment_data".to_string(),
+                        (
+                            bytemuck::cast_slice(&scene.render_effects.read().environment_data())
+                                .to_vec(),
+                            ShaderStages::VERTEX_FRAGMENT,
+                        ),
+                    );
+                    set_push_constants(bound_pipeline, &mut render_pass, Some(pc));
+
+                    let vertex_buffer = arena.alloc(wm.display.device.create_buffer_init(
+                        &wgpu::util::BufferInitDescriptor {
+                            label: Some("sky"),
+                            contents: &vertices,
+                            usage: wgpu::BufferUsages::VERTEX,
+                        },
+                    ));
+                    render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+
+                    if indices.is_empty() {
+                        render_pass.draw(0..vertex_count, 0..1);
+                    } else {
+                        let index_buffer = arena.alloc(wm.display.device.create_buffer_init(
+                            &wgpu::util::BufferInitDescriptor {
+                                label: Some("sky indices"),
+                                contents: bytemuck::cast_slice(&indices),
+                                usage: wgpu::BufferUsages::INDEX,
+                            },
+                        ));
+                        render_pass
+                            .set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                        render_pass.draw_indexed(0..indices.len() as u32, 0, 0..1);
                     }
                 }
                 _ => match geometry.get_mut(&pipeline_config.geometry) {
@@ -640,26 +2231,151 @@ impl RenderGraph {
                 },
             }
         }
+
+        if let Some(target) = &*scaled_target {
+            if !scaled_target_drawn {
+                target.blit(encoder, render_target);
+            }
+        }
     }
 }
 
+/// Combine a pipeline's push constants into the ranges of its layout. wgpu only allows each shader stage in one
+/// range, and bytes shared by two ranges have to be set for the stages of both, so push constants which share a
+/// stage or overlap become one range covering all of them, visible to the union of their stages
+pub fn merge_push_constant_ranges(
+    ranges: impl IntoIterator<Item = wgpu::PushConstantRange>,
+) -> Vec<wgpu::PushConstantRange> {
+    let mut merged: Vec<wgpu::PushConstantRange> = Vec::new();
+
+    for mut range in ranges {
+        //Growing the range can make it reach ranges which were already kept, so repeat until nothing is absorbed
+        loop {
+            let count = merged.len();
+
+            merged.retain(|other| {
+                let overlaps =
+                    other.range.start < range.range.end && range.range.start < other.range.end;
+
+                if !overlaps && !other.stages.intersects(range.stages) {
+                    return true;
+                }
+
+                range.stages |= other.stages;
+                range.range =
+                    range.range.start.min(other.range.start)..range.range.end.max(other.range.end);
+
+                false
+            });
+
+            if merged.len() == count {
+                break;
+            }
+        }
+
+        merged.push(range);
+    }
+
+    merged.sort_by_key(|range| range.range.start);
+    merged
+}
+
+/// Set the push constants `pipeline` declares from `push_constants`. Each one is set for the stages of the
+/// [merge_push_constant_ranges] range it falls in, which can be more than the stages it's listed with
 pub fn set_push_constants(
-    pipeline: &PipelineConfig,
+    pipeline: &BoundPipeline,
     render_pass: &mut wgpu::RenderPass,
     push_constants: Option<HashMap<String, (Vec<u8>, wgpu::ShaderStages)>>,
 ) {
     pipeline
+        .config
         .push_constants
         .iter()
         .for_each(|(offset, resource)| {
+            let offset = *offset as u32;
+
             match push_constants
                 .as_ref()
                 .and_then(|others| others.get(resource))
             {
                 None => unimplemented!("Unknown push constant resource value"),
                 Some((data, stages)) => {
-                    render_pass.set_push_constants(*stages, *offset as u32, data)
+                    let stages = pipeline
+                        .push_constant_ranges
+                        .iter()
+                        .find(|range| range.range.contains(&offset))
+                        .map_or(*stages, |range| range.stages);
+
+                    render_pass.set_push_constants(stages, offset, data)
                 }
             }
         });
 }
+
+#[cfg(test)]
+mod tests {
+    use wgpu::{PushConstantRange, ShaderStages};
+
+    use super::merge_push_constant_ranges;
+
+    #[test]
+    fn push_constant_ranges_merge_by_offset_and_stage() {
+        //@pc_mat4_model, @pc_animation_time and @pc_fog
+        let merged = merge_push_constant_ranges([
+            PushConstantRange {
+                stages: ShaderStages::VERTEX,
+                range: 0..64,
+            },
+            PushConstantRange {
+                stages: ShaderStages::VERTEX_FRAGMENT,
+                range: 64..68,
+            },
+            PushConstantRange {
+                stages: ShaderStages::FRAGMENT,
+                range: 80..96,
+            },
+        ]);
+
+        assert_eq!(
+            merged,
+            vec![PushConstantRange {
+                stages: ShaderStages::VERTEX_FRAGMENT,
+                range: 0..96,
+            }]
+        );
+
+        //Separate stages in separate bytes stay apart, overlapping bytes don't
+        let merged = merge_push_constant_ranges([
+            PushConstantRange {
+                stages: ShaderStages::FRAGMENT,
+                range: 64..80,
+            },
+            PushConstantRange {
+                stages: ShaderStages::VERTEX,
+                range: 0..64,
+            },
+        ]);
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].range, 0..64);
+
+        let merged = merge_push_constant_ranges([
+            PushConstantRange {
+                stages: ShaderStages::VERTEX,
+                range: 0..16,
+            },
+            PushConstantRange {
+                stages: ShaderStages::FRAGMENT,
+                range: 8..24,
+            },
+        ]);
+
+        assert_eq!(
+            merged,
+            vec![PushConstantRange {
+                stages: ShaderStages::VERTEX_FRAGMENT,
+                range: 0..24,
+            }]
+        );
+    }
+}
@@ -0,0 +1,334 @@
+//! A serializable view of a [crate::render::graph::RenderGraph], for tools which show how a shader pack was resolved,
+//! see [crate::render::graph::RenderGraph::describe]
+
+use std::collections::{HashMap, HashSet};
+
+use serde_derive::Serialize;
+
+use crate::render::shaderpack::{BindGroupDef, PipelineConfig};
+
+/// One resource bound to a pipeline
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BindingDescription {
+    pub group: u64,
+    /// None for groups which are bound as a whole, like `@bg_entity`
+    pub binding: Option<u64>,
+    pub resource: String,
+}
+
+/// A push constant, `offset` is in bytes
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PushConstantDescription {
+    pub offset: u64,
+    pub name: String,
+}
+
+/// A pipeline in the order it runs, with everything it reads and writes
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PipelineDescription {
+    pub name: String,
+    pub geometry: String,
+    pub bindings: Vec<BindingDescription>,
+    pub push_constants: Vec<PushConstantDescription>,
+    pub outputs: Vec<String>,
+    pub depth: Option<String>,
+    pub clear: bool,
+    /// Multisampled outputs, and the render targets they're resolved into at the end of the pipeline
+    pub resolves: Vec<(String, String)>,
+}
+
+impl PipelineDescription {
+    pub fn new(
+        name: &str,
+        config: &PipelineConfig,
+        resolve_targets: &HashMap<String, String>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            geometry: config.geometry.clone(),
+            bindings: config
+                .bind_groups
+                .iter()
+                .flat_map(|(group, def)| match def {
+                    BindGroupDef::Entries(entries) => entries
+                        .iter()
+                        .map(|(binding, resource)| BindingDescription {
+                            group: *group,
+                            binding: Some(*binding),
+                            resource: resource.clone(),
+                        })
+                        .collect(),
+                    BindGroupDef::Resource(resource) => vec![BindingDescription {
+                        group: *group,
+                        binding: None,
+                        resource: resource.clone(),
+                    }],
+                })
+                .collect(),
+            push_constants: config
+                .push_constants
+                .iter()
+                .map(|(offset, name)| PushConstantDescription {
+                    offset: *offset,
+                    name: name.clone(),
+                })
+                .collect(),
+            outputs: config.output.clone(),
+            depth: config.depth.clone(),
+            clear: config.clear,
+            resolves: config
+                .output
+                .iter()
+                .filter_map(|output| {
+                    resolve_targets
+                        .get(output)
+                        .map(|resolve| (output.clone(), resolve.clone()))
+                })
+                .collect(),
+        }
+    }
+
+    /// Every render target this pipeline's pass draws to, including depth and resolve targets
+    pub fn writes(&self) -> impl Iterator<Item = &str> {
+        self.outputs
+            .iter()
+            .chain(self.depth.iter())
+            .chain(self.resolves.iter().map(|(_, resolve)| resolve))
+            .map(String::as_str)
+    }
+}
+
+/// A render target which a pipeline samples, see [target_reads]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct TargetRead {
+    pub pipeline: String,
+    pub target: String,
+    /// The closest earlier pipeline which draws to the target, so what it drew is what gets sampled. None if only later
+    /// pipelines draw to it, in which case the target still holds what it had at the end of the previous frame
+    pub written_by: Option<String>,
+}
+
+/// Every render target sampled by a pipeline in `pipelines`, which are in the order they run. Each pipeline is its own
+/// render pass in the one encoder [crate::render::graph::RenderGraph::render] records into, and wgpu makes the writes
+/// of a pass visible to every pass recorded after it, so nothing else is needed for a pipeline to read an earlier one's
+/// output
+pub fn target_reads(pipelines: &[PipelineDescription]) -> Vec<TargetRead> {
+    let targets: HashSet<&str> = pipelines
+        .iter()
+        .flat_map(PipelineDescription::writes)
+        .collect();
+
+    pipelines
+        .iter()
+        .enumerate()
+        .flat_map(|(index, pipeline)| {
+            pipeline
+                .bindings
+                .iter()
+                .filter(|binding| targets.contains(&binding.resource[..]))
+                .map(move |binding| TargetRead {
+                    pipeline: pipeline.name.clone(),
+                    target: binding.resource.clone(),
+                    written_by: pipelines[..index]
+                        .iter()
+                        .rev()
+                        .find(|earlier| earlier.writes().any(|write| write == binding.resource))
+                        .map(|earlier| earlier.name.clone()),
+                })
+        })
+        .collect()
+}
+
+/// The pipeline which clears `@framebuffer_texture` when no pipeline in `pipelines` sets `clear` while drawing to it,
+/// which is the first one to draw to it. Otherwise every frame would start from whatever the surface texture held, see
+/// [crate::render::graph::RenderGraph::auto_clear]
+pub fn framebuffer_auto_clear(pipelines: &[PipelineDescription]) -> Option<&str> {
+    let mut framebuffer_writers = pipelines.iter().filter(|pipeline| {
+        pipeline
+            .outputs
+            .iter()
+            .any(|output| output == "@framebuffer_texture")
+    });
+
+    if framebuffer_writers.clone().any(|pipeline| pipeline.clear) {
+        return None;
+    }
+
+    framebuffer_writers
+        .next()
+        .map(|pipeline| pipeline.name.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::render::shaderpack::PipelineConfig;
+
+    use super::{
+        framebuffer_auto_clear, target_reads, BindingDescription, PipelineDescription,
+        PushConstantDescription, TargetRead,
+    };
+
+    fn describe(name: &str, yaml: &str) -> PipelineDescription {
+        PipelineDescription::new(name, &serde_yaml::from_str(yaml).unwrap(), &HashMap::new())
+    }
+
+    #[test]
+    fn describes_a_pipeline() {
+        let config: PipelineConfig = serde_yaml::from_str(
+            r#"
+geometry: "@geo_entities"
+output: [ scene_msaa ]
+clear: true
+push_constants:
+  0: "@pc_parts_per_entity"
+bind_groups:
+  0:
+    0: "@mat4_view"
+  1: "@bg_entity"
+"#,
+        )
+        .unwrap();
+
+        let description = PipelineDescription::new(
+            "entity",
+            &config,
+            &HashMap::from([("scene_msaa".to_string(), "scene".to_string())]),
+        );
+
+        assert_eq!(
+            description.bindings,
+            vec![
+                BindingDescription {
+                    group: 0,
+                    binding: Some(0),
+                    resource: "@mat4_view".into(),
+                },
+                BindingDescription {
+                    group: 1,
+                    binding: None,
+                    resource: "@bg_entity".into(),
+                },
+            ]
+        );
+        assert_eq!(
+            description.push_constants,
+            vec![PushConstantDescription {
+                offset: 0,
+                name: "@pc_parts_per_entity".into(),
+            }]
+        );
+        assert!(description.clear);
+        assert_eq!(
+            description.resolves,
+            vec![("scene_msaa".to_string(), "scene".to_string())]
+        );
+    }
+
+    #[test]
+    fn post_processing_reads_what_was_drawn_this_frame() {
+        let pipelines = [
+            describe(
+                "terrain",
+                r#"
+geometry: "@geo_terrain"
+output: [ scene ]
+depth: "@texture_depth"
+"#,
+            ),
+            describe(
+                "blur",
+                r#"
+geometry: "@geo_quad"
+output: [ "@framebuffer_texture" ]
+bind_groups:
+  0:
+    0: scene
+    1: "@sampler"
+"#,
+            ),
+            describe(
+                "history",
+                r#"
+geometry: "@geo_quad"
+output: [ scene_history ]
+bind_groups:
+  0:
+    0: scene_history_previous
+    1: scene
+"#,
+            ),
+            describe(
+                "copy_history",
+                r#"
+geometry: "@geo_quad"
+output: [ scene_history_previous ]
+bind_groups:
+  0:
+    0: scene_history
+"#,
+            ),
+        ];
+
+        assert_eq!(
+            target_reads(&pipelines),
+            vec![
+                TargetRead {
+                    pipeline: "blur".into(),
+                    target: "scene".into(),
+                    written_by: Some("terrain".into()),
+                },
+                //Only drawn to later in the frame, so this is last frame's copy
+                TargetRead {
+                    pipeline: "history".into(),
+                    target: "scene_history_previous".into(),
+                    written_by: None,
+                },
+                TargetRead {
+                    pipeline: "history".into(),
+                    target: "scene".into(),
+                    written_by: Some("terrain".into()),
+                },
+                TargetRead {
+                    pipeline: "copy_history".into(),
+                    target: "scene_history".into(),
+                    written_by: Some("history".into()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn first_framebuffer_write_clears_when_nothing_else_does() {
+        let shadows = describe(
+            "shadows",
+            r#"
+geometry: "@geo_terrain"
+depth: shadow_map
+"#,
+        );
+        let terrain = describe(
+            "terrain",
+            r#"
+geometry: "@geo_terrain"
+output: [ "@framebuffer_texture" ]
+"#,
+        );
+        let sky = describe(
+            "sky",
+            r#"
+geometry: "@geo_sky_scatter"
+output: [ "@framebuffer_texture" ]
+clear: true
+"#,
+        );
+
+        assert_eq!(
+            framebuffer_auto_clear(&[shadows.clone(), terrain.clone()]),
+            Some("terrain")
+        );
+        assert_eq!(framebuffer_auto_clear(&[sky, terrain]), None);
+        assert_eq!(framebuffer_auto_clear(&[shadows]), None);
+    }
+}
@@ -0,0 +1,158 @@
+//! Colored line segments pushed from Rust every frame, for debugging spatial data like paths, vectors or grids.
+//! Register a [LineGeometry] as custom geometry and draw it with a pipeline using `topology: line_list`, like the
+//! sample `debug_lines` shader. Whether lines are hidden behind terrain is up to the pipeline's depth settings, for
+//! lines which are always visible register a second [LineGeometry] drawn by a pipeline with `depth_compare: always`
+//! and `shader: debug_lines`
+
+use std::sync::Arc;
+
+use glam::Vec3;
+use wgpu::util::DeviceExt;
+
+use crate::render::graph::{BoundPipeline, Geometry, RenderGraph};
+use crate::util::WmArena;
+use crate::WmRenderer;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    /// In render space, see [crate::mc::Scene::render_origin]
+    pub position: [f32; 3],
+    /// RGBA
+    pub color: [u8; 4],
+}
+
+impl LineVertex {
+    const VAA: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Unorm8x4
+    ];
+
+    #[must_use]
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::VAA,
+        }
+    }
+}
+
+/// Line segments which are drawn every frame until [LineGeometry::clear] is called. The segments are uploaded when
+/// the geometry is rendered, so they can be pushed at any point before
+#[derive(Default)]
+pub struct LineGeometry {
+    vertices: Vec<LineVertex>,
+    buffer: Option<Arc<wgpu::Buffer>>,
+    /// Whether `vertices` changed since they were last uploaded
+    dirty: bool,
+}
+
+impl LineGeometry {
+    pub fn vertex_layouts() -> Vec<wgpu::VertexBufferLayout<'static>> {
+        vec![LineVertex::desc()]
+    }
+
+    pub fn push(&mut self, start: Vec3, end: Vec3, color: [u8; 4]) {
+        self.vertices.extend([
+            LineVertex {
+                position: start.to_array(),
+                color,
+            },
+            LineVertex {
+                position: end.to_array(),
+                color,
+            },
+        ]);
+        self.dirty = true;
+    }
+
+    /// Remove every segment, e.g. at the start of a frame before pushing that frame's segments
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.dirty = true;
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.vertices.len() / 2
+    }
+
+    pub fn vertices(&self) -> &[LineVertex] {
+        &self.vertices
+    }
+
+    fn upload(&mut self, wm: &WmRenderer) {
+        let size = std::mem::size_of_val(&self.vertices[..]) as wgpu::BufferAddress;
+
+        match &self.buffer {
+            Some(buffer) if buffer.size() >= size => {
+                wm.display
+                    .queue
+                    .write_buffer(buffer, 0, bytemuck::cast_slice(&self.vertices));
+            }
+            _ => {
+                self.buffer = Some(Arc::new(wm.display.device.create_buffer_init(
+                    &wgpu::util::BufferInitDescriptor {
+                        label: Some("debug lines"),
+                        contents: bytemuck::cast_slice(&self.vertices),
+                        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                    },
+                )));
+            }
+        }
+
+        self.dirty = false;
+    }
+}
+
+impl Geometry for LineGeometry {
+    fn render<'graph: 'pass + 'arena, 'pass, 'arena: 'pass>(
+        &mut self,
+        wm: &WmRenderer,
+        _render_graph: &'graph RenderGraph,
+        bound_pipeline: &'graph BoundPipeline,
+        render_pass: &mut wgpu::RenderPass<'pass>,
+        arena: &WmArena<'arena>,
+    ) {
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        if self.dirty {
+            self.upload(wm);
+        }
+
+        bound_pipeline.bind(render_pass);
+
+        let buffer = arena.alloc(self.buffer.clone().unwrap());
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+        render_pass.draw(0..self.vertices.len() as u32, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec3;
+
+    use super::{LineGeometry, LineVertex};
+
+    #[test]
+    fn segments_keep_their_colors_until_cleared() {
+        let mut lines = LineGeometry::default();
+        lines.push(vec3(0.0, 64.0, 0.0), vec3(1.0, 64.0, 0.0), [255, 0, 0, 255]);
+        lines.push(vec3(0.0, 64.0, 0.0), vec3(0.0, 65.0, 0.0), [0, 255, 0, 255]);
+
+        assert_eq!(lines.segment_count(), 2);
+        assert_eq!(
+            lines.vertices()[3],
+            LineVertex {
+                position: [0.0, 65.0, 0.0],
+                color: [0, 255, 0, 255],
+            }
+        );
+        assert_eq!(std::mem::size_of::<LineVertex>(), 16);
+
+        lines.clear();
+        assert_eq!(lines.segment_count(), 0);
+    }
+}
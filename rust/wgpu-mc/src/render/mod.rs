@@ -1,7 +1,25 @@
 pub mod atlas;
+pub mod banner;
+pub mod blob_shadow;
+pub mod block_entity;
+pub mod camera;
+pub mod decal;
+pub mod depth_peel;
 pub mod entity;
+pub mod entity_lod;
 pub mod graph;
+pub mod introspect;
+pub mod lines;
+pub mod overlay;
+pub mod panorama;
 pub mod pipeline;
+pub mod scale;
 pub mod shader;
 pub mod shaderpack;
 pub mod sky;
+pub mod thumbnail;
+pub mod triangle_budget;
+pub mod underground_culling;
+pub mod uv_scroll;
+pub mod void_plane;
+pub mod wind;
@@ -0,0 +1,244 @@
+//! Textured quads drawn in screen space on top of the world, such as the crosshair, vignette or pumpkin blur. Register
+//! a [ScreenOverlayGeometry] as custom geometry and draw it with a pipeline like the sample `screen_overlay` shader,
+//! binding the texture the overlays are cut from. Overlays using different textures, e.g. a GUI atlas sprite and a
+//! standalone vignette texture, need a [ScreenOverlayGeometry] and pipeline each
+
+use wgpu::util::DeviceExt;
+
+use crate::render::atlas::ATLAS_DIMENSIONS;
+use crate::render::graph::{BoundPipeline, Geometry, RenderGraph};
+use crate::texture::UV;
+use crate::util::WmArena;
+use crate::WmRenderer;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct OverlayVertex {
+    /// In normalized device coordinates
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    /// RGBA, multiplied with the texture
+    pub tint: [u8; 4],
+}
+
+impl OverlayVertex {
+    const VAA: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Unorm8x4
+    ];
+
+    #[must_use]
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<OverlayVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::VAA,
+        }
+    }
+}
+
+/// Where on screen an overlay is drawn, in pixels of the pipeline's output. Rects are resolved when the overlay is
+/// drawn, so they follow the window as it's resized
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OverlayRect {
+    /// The whole screen, e.g. the vignette or pumpkin blur
+    Fullscreen,
+    /// A `width` by `height` rect in the middle of the screen, e.g. the crosshair
+    Centered { width: f32, height: f32 },
+    /// A rect with its top left corner `x` and `y` pixels from the top left of the screen
+    Pixels {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+}
+
+impl OverlayRect {
+    /// The top left corner and size of the rect on a `width` by `height` screen
+    pub fn resolve(&self, (screen_width, screen_height): (u32, u32)) -> [f32; 4] {
+        let (screen_width, screen_height) = (screen_width as f32, screen_height as f32);
+
+        match *self {
+            OverlayRect::Fullscreen => [0.0, 0.0, screen_width, screen_height],
+            OverlayRect::Centered { width, height } => [
+                (screen_width - width) / 2.0,
+                (screen_height - height) / 2.0,
+                width,
+                height,
+            ],
+            OverlayRect::Pixels {
+                x,
+                y,
+                width,
+                height,
+            } => [x, y, width, height],
+        }
+    }
+}
+
+/// A textured quad on screen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScreenOverlay {
+    pub rect: OverlayRect,
+    /// The top left and bottom right corners of the texture to draw, see [ScreenOverlay::sprite_uv]
+    pub uv: [[f32; 2]; 2],
+    /// RGBA, multiplied with the texture. The alpha fades the whole overlay, e.g. for the portal or powder snow
+    /// overlays which fade in
+    pub tint: [u8; 4],
+}
+
+impl ScreenOverlay {
+    /// The whole texture, for overlays using a standalone texture
+    pub const FULL_TEXTURE: [[f32; 2]; 2] = [[0.0, 0.0], [1.0, 1.0]];
+
+    /// The UVs of a sprite in an [crate::render::atlas::Atlas], for overlays cut from an atlas
+    pub fn sprite_uv(uv: UV) -> [[f32; 2]; 2] {
+        let ((u1, v1), (u2, v2)) = uv;
+
+        [
+            [u1 as f32, v1 as f32].map(|coord| coord / ATLAS_DIMENSIONS as f32),
+            [u2 as f32, v2 as f32].map(|coord| coord / ATLAS_DIMENSIONS as f32),
+        ]
+    }
+
+    /// Two triangles covering the overlay's rect on a `width` by `height` screen
+    pub fn vertices(&self, screen_size: (u32, u32)) -> [OverlayVertex; 6] {
+        let [x, y, width, height] = self.rect.resolve(screen_size);
+        let (screen_width, screen_height) =
+            (screen_size.0.max(1) as f32, screen_size.1.max(1) as f32);
+
+        let left = x / screen_width * 2.0 - 1.0;
+        let right = (x + width) / screen_width * 2.0 - 1.0;
+        let top = 1.0 - y / screen_height * 2.0;
+        let bottom = 1.0 - (y + height) / screen_height * 2.0;
+
+        let [[u1, v1], [u2, v2]] = self.uv;
+
+        let vertex = |position, uv| OverlayVertex {
+            position,
+            uv,
+            tint: self.tint,
+        };
+
+        let top_left = vertex([left, top], [u1, v1]);
+        let top_right = vertex([right, top], [u2, v1]);
+        let bottom_left = vertex([left, bottom], [u1, v2]);
+        let bottom_right = vertex([right, bottom], [u2, v2]);
+
+        [
+            bottom_left,
+            bottom_right,
+            top_right,
+            bottom_left,
+            top_right,
+            top_left,
+        ]
+    }
+}
+
+/// Overlays which are drawn every frame until [ScreenOverlayGeometry::clear] is called, in the order they were pushed.
+/// Hosts usually clear and push the overlays of the current frame before rendering it
+#[derive(Default)]
+pub struct ScreenOverlayGeometry {
+    overlays: Vec<ScreenOverlay>,
+}
+
+impl ScreenOverlayGeometry {
+    pub fn vertex_layouts() -> Vec<wgpu::VertexBufferLayout<'static>> {
+        vec![OverlayVertex::desc()]
+    }
+
+    pub fn push(&mut self, overlay: ScreenOverlay) {
+        self.overlays.push(overlay);
+    }
+
+    pub fn clear(&mut self) {
+        self.overlays.clear();
+    }
+
+    pub fn overlays(&self) -> &[ScreenOverlay] {
+        &self.overlays
+    }
+}
+
+impl Geometry for ScreenOverlayGeometry {
+    fn render<'graph: 'pass + 'arena, 'pass, 'arena: 'pass>(
+        &mut self,
+        wm: &WmRenderer,
+        render_graph: &'graph RenderGraph,
+        bound_pipeline: &'graph BoundPipeline,
+        render_pass: &mut wgpu::RenderPass<'pass>,
+        arena: &WmArena<'arena>,
+    ) {
+        if self.overlays.is_empty() {
+            return;
+        }
+
+        let screen_size = render_graph.attachment_size(wm, &bound_pipeline.config);
+
+        let vertices = self
+            .overlays
+            .iter()
+            .flat_map(|overlay| overlay.vertices(screen_size))
+            .collect::<Vec<_>>();
+
+        bound_pipeline.bind(render_pass);
+
+        let buffer = arena.alloc(wm.display.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("screen overlays"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            },
+        ));
+
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+        render_pass.draw(0..vertices.len() as u32, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OverlayRect, ScreenOverlay};
+
+    #[test]
+    fn crosshair_is_centered_and_vignette_covers_the_screen() {
+        let crosshair = ScreenOverlay {
+            rect: OverlayRect::Centered {
+                width: 30.0,
+                height: 30.0,
+            },
+            uv: ScreenOverlay::sprite_uv(((0, 0), (15, 15))),
+            tint: [255; 4],
+        };
+
+        let vertices = crosshair.vertices((800, 600));
+        let (xs, ys): (Vec<f32>, Vec<f32>) = vertices
+            .iter()
+            .map(|vertex| (vertex.position[0], vertex.position[1]))
+            .unzip();
+
+        for (coords, half_extent) in [(xs, 30.0 / 800.0), (ys, 30.0 / 600.0)] {
+            let min = coords.iter().copied().fold(f32::MAX, f32::min);
+            let max = coords.iter().copied().fold(f32::MIN, f32::max);
+
+            assert!((min + half_extent).abs() < 1e-6 && (max - half_extent).abs() < 1e-6);
+        }
+
+        let vignette = ScreenOverlay {
+            rect: OverlayRect::Fullscreen,
+            uv: ScreenOverlay::FULL_TEXTURE,
+            tint: [255, 255, 255, 128],
+        };
+
+        let vertices = vignette.vertices((800, 600));
+        //The top left of the screen is the top left of the texture
+        assert_eq!(vertices[5].position, [-1.0, 1.0]);
+        assert_eq!(vertices[5].uv, [0.0, 0.0]);
+        assert_eq!(vertices[1].position, [1.0, -1.0]);
+        assert_eq!(vertices[1].uv, [1.0, 1.0]);
+        assert!(vertices.iter().all(|vertex| vertex.tint[3] == 128));
+    }
+}
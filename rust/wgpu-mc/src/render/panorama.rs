@@ -0,0 +1,194 @@
+//! Rendering the scene in every direction around a point, for title screen panoramas and 360° screenshots, see
+//! [crate::WmRenderer::render_panorama]
+
+use glam::{vec3, Mat4, Vec3};
+use image::{Rgba, RgbaImage};
+
+use crate::render::camera::CameraProjection;
+
+/// Where a panorama is captured from. The faces are in the order of vanilla's `panorama_0.png` to `panorama_5.png`:
+/// forward, right, back, left, up and down, where forward is `yaw` with the pitch reset to level like vanilla does
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PanoramaCamera {
+    /// In the space of the host's `@mat4_view`, so usually [Vec3::ZERO] if the camera translation is part of
+    /// `@mat4_model` instead
+    pub position: Vec3,
+    /// In degrees, with Minecraft's convention of 0 facing +Z and 90 facing -X
+    pub yaw: f32,
+    pub znear: f32,
+    pub zfar: f32,
+}
+
+impl PanoramaCamera {
+    /// The direction each face looks in and the direction which is up in it. The up and down faces are seen as if the
+    /// camera was pitched up or down from the forward face, so their tops point backwards and forwards respectively
+    pub fn face_directions(&self) -> [(Vec3, Vec3); 6] {
+        let forward = look_direction(self.yaw);
+
+        [
+            (forward, Vec3::Y),
+            (look_direction(self.yaw + 90.0), Vec3::Y),
+            (look_direction(self.yaw + 180.0), Vec3::Y),
+            (look_direction(self.yaw - 90.0), Vec3::Y),
+            (Vec3::Y, -forward),
+            (Vec3::NEG_Y, forward),
+        ]
+    }
+
+    pub fn views(&self) -> [Mat4; 6] {
+        self.face_directions()
+            .map(|(direction, up)| Mat4::look_to_rh(self.position, direction, up))
+    }
+
+    /// Every face is square with a 90° field of view, so neighbouring faces meet exactly at their edges
+    pub fn projection(&self) -> CameraProjection {
+        CameraProjection::Perspective {
+            fovy: 90f32.to_radians(),
+            aspect: 1.0,
+            znear: self.znear,
+            zfar: self.zfar,
+        }
+    }
+
+    /// Which face `direction` falls on, and where on it, from 0 to 1 with 0, 0 in the top left corner
+    pub fn face_uv(&self, direction: Vec3) -> (usize, [f32; 2]) {
+        let directions = self.face_directions();
+
+        let face = (0..6)
+            .max_by(|a, b| {
+                direction
+                    .dot(directions[*a].0)
+                    .total_cmp(&direction.dot(directions[*b].0))
+            })
+            .unwrap();
+
+        let (forward, up) = directions[face];
+        let view = Mat4::look_to_rh(Vec3::ZERO, forward, up);
+        let ndc = (self.projection().matrix() * view).project_point3(direction);
+
+        (face, [(ndc.x + 1.0) / 2.0, (1.0 - ndc.y) / 2.0])
+    }
+
+    /// Stitch the faces returned by [crate::WmRenderer::render_panorama] into a `width` by `width / 2`
+    /// equirectangular image, with the forward face in the middle. Faces are sampled without filtering
+    pub fn equirectangular(&self, faces: &[RgbaImage; 6], width: u32) -> RgbaImage {
+        let width = width.max(2);
+        let height = width / 2;
+
+        let forward = look_direction(self.yaw);
+        let right = forward.cross(Vec3::Y);
+
+        RgbaImage::from_fn(width, height, |x, y| {
+            let longitude = ((x as f32 + 0.5) / width as f32 - 0.5) * std::f32::consts::TAU;
+            let latitude = (0.5 - (y as f32 + 0.5) / height as f32) * std::f32::consts::PI;
+
+            let direction = latitude.cos() * (longitude.sin() * right + longitude.cos() * forward)
+                + latitude.sin() * Vec3::Y;
+
+            let (face, [u, v]) = self.face_uv(direction);
+            let image = &faces[face];
+
+            if image.width() == 0 || image.height() == 0 {
+                return Rgba([0; 4]);
+            }
+
+            let pixel_x = ((u * image.width() as f32) as u32).min(image.width() - 1);
+            let pixel_y = ((v * image.height() as f32) as u32).min(image.height() - 1);
+
+            *image.get_pixel(pixel_x, pixel_y)
+        })
+    }
+}
+
+/// Minecraft's look vector for a level camera with `yaw` in degrees
+fn look_direction(yaw: f32) -> Vec3 {
+    let yaw = yaw.to_radians();
+    vec3(-yaw.sin(), 0.0, yaw.cos())
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+    use image::{Rgba, RgbaImage};
+
+    use super::PanoramaCamera;
+
+    const CAMERA: PanoramaCamera = PanoramaCamera {
+        position: Vec3::ZERO,
+        yaw: 30.0,
+        znear: 0.05,
+        zfar: 256.0,
+    };
+
+    fn assert_uv(actual: (usize, [f32; 2]), face: usize, uv: [f32; 2]) {
+        assert_eq!(actual.0, face);
+        assert!(
+            (actual.1[0] - uv[0]).abs() < 1e-4 && (actual.1[1] - uv[1]).abs() < 1e-4,
+            "{actual:?} isn't {uv:?} on face {face}"
+        );
+    }
+
+    #[test]
+    fn faces_look_around_like_vanilla() {
+        let [forward, right, back, left, up, down] = CAMERA.face_directions();
+
+        //Turning right from facing south faces west
+        let south = PanoramaCamera { yaw: 0.0, ..CAMERA }.face_directions();
+        assert!(south[0].0.distance(Vec3::Z) < 1e-6);
+        assert!(south[1].0.distance(Vec3::NEG_X) < 1e-6);
+
+        assert!(forward.0.distance(-back.0) < 1e-6);
+        assert!(right.0.distance(-left.0) < 1e-6);
+        assert!(forward.0.cross(Vec3::Y).distance(right.0) < 1e-6);
+        assert_eq!((up.0, down.0), (Vec3::Y, Vec3::NEG_Y));
+    }
+
+    #[test]
+    fn neighbouring_faces_meet_at_their_edges() {
+        let directions = CAMERA.face_directions();
+
+        for face in 0..4 {
+            let next = (face + 1) % 4;
+            let edge = (directions[face].0 + directions[next].0).normalize();
+
+            //Tiny nudges decide which side of the seam the direction falls on
+            assert_uv(
+                CAMERA.face_uv(edge + directions[face].0 * 1e-5),
+                face,
+                [1.0, 0.5],
+            );
+            assert_uv(
+                CAMERA.face_uv(edge + directions[next].0 * 1e-5),
+                next,
+                [0.0, 0.5],
+            );
+        }
+
+        let forward = directions[0].0;
+        let top_edge = (forward + Vec3::Y).normalize();
+        assert_uv(CAMERA.face_uv(top_edge + forward * 1e-5), 0, [0.5, 0.0]);
+        assert_uv(CAMERA.face_uv(top_edge + Vec3::Y * 1e-5), 4, [0.5, 1.0]);
+
+        let bottom_edge = (forward + Vec3::NEG_Y).normalize();
+        assert_uv(CAMERA.face_uv(bottom_edge + forward * 1e-5), 0, [0.5, 1.0]);
+        assert_uv(
+            CAMERA.face_uv(bottom_edge + Vec3::NEG_Y * 1e-5),
+            5,
+            [0.5, 0.0],
+        );
+    }
+
+    #[test]
+    fn equirectangular_has_the_forward_face_in_the_middle() {
+        let faces = [0u8, 1, 2, 3, 4, 5].map(|face| RgbaImage::from_pixel(4, 4, Rgba([face; 4])));
+        let stitched = CAMERA.equirectangular(&faces, 64);
+
+        assert_eq!(stitched.dimensions(), (64, 32));
+        assert_eq!(stitched.get_pixel(32, 16), &Rgba([0; 4]));
+        assert_eq!(stitched.get_pixel(48, 16), &Rgba([1; 4]));
+        assert_eq!(stitched.get_pixel(0, 16), &Rgba([2; 4]));
+        assert_eq!(stitched.get_pixel(16, 16), &Rgba([3; 4]));
+        assert_eq!(stitched.get_pixel(32, 0), &Rgba([4; 4]));
+        assert_eq!(stitched.get_pixel(32, 31), &Rgba([5; 4]));
+    }
+}
@@ -5,18 +5,26 @@ use std::collections::HashMap;
 pub const BLOCK_ATLAS: &str = "wgpu_mc:atlases/block";
 pub const ENTITY_ATLAS: &str = "wgpu_mc:atlases/entity";
 
+/// A single terrain vertex before it's packed into the chunk buffer. See [crate::mc::chunk::terrain_mapper]
 #[derive(Copy, Clone, Debug)]
-pub struct Vertex {
+pub struct TerrainVertex {
     pub position: [f32; 3],
     pub uv: [u16; 2],
     pub normal: [f32; 3],
     pub color: u32,
+    /// Tint index + 1 into the section's tint colors when using [crate::mc::chunk::TintMode::PerSection], 0 if untinted
+    pub tint_slot: u8,
+    /// Channel of the [crate::render::uv_scroll::UvScroll] the face moves with, 0 if it doesn't scroll
+    pub uv_scroll: u8,
     pub uv_offset: u32,
     pub lightmap_coords: u8,
+    /// 0 to 3
     pub ao: u8,
+    /// How strongly the vertex sways in the wind, up to [crate::render::wind::MAX_WAVE]
+    pub wave: u8,
 }
 
-impl Vertex {
+impl TerrainVertex {
     pub const VERTEX_LENGTH: usize = 16;
 
     pub fn compressed(self) -> [u8; Self::VERTEX_LENGTH] {
@@ -27,8 +35,12 @@ impl Vertex {
         // Animated UV index: 10 bits
         // XYZ add one flag: 3 bits
         // Block light nibble: 1 byte (4 bits for block, 4 bits for sky)
+        // AO: 2 bits
+        // Wave strength: 6 bits
+        // Section tint slot: 1 byte
+        // UV scroll channel: 1 byte
 
-        // Total: 101 bits (13 bytes)
+        // Total: 109 bits (14 bytes)
         let mut array = [0; Self::VERTEX_LENGTH];
 
         let x = self.position[0] * 16.0;
@@ -76,10 +88,26 @@ impl Vertex {
         array[10] = self.uv_offset as u8;
         array[11] = (((self.uv_offset >> 8) as u8) & 0b11) | (normal_bits << 2) | (flag_byte << 5);
         array[12] = self.lightmap_coords;
-        array[13] = self.ao;
+        array[13] = self.ao | (self.wave << 2);
+        array[14] = self.tint_slot;
+        array[15] = self.uv_scroll;
 
         array
     }
+
+    const PACKED_VAA: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![
+        0 => Uint32x4,
+    ];
+
+    /// Layout of [TerrainVertex::compressed] vertices in the chunk buffer, for terrain pipelines which don't pull their vertices
+    #[must_use]
+    pub fn packed_desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: Self::VERTEX_LENGTH as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::PACKED_VAA,
+        }
+    }
 }
 
 #[repr(C)]
@@ -104,6 +132,10 @@ impl QuadVertex {
     }
 }
 
+/// Entity textures are atlases, which shader packs sample with `@sampler` like the block atlas
+pub const ENTITY_TEXTURE_SAMPLE_TYPE: wgpu::TextureSampleType =
+    wgpu::TextureSampleType::Float { filterable: true };
+
 pub fn create_bind_group_layouts(device: &wgpu::Device) -> HashMap<String, BindGroupLayout> {
     [
         (
@@ -202,6 +234,24 @@ pub fn create_bind_group_layouts(device: &wgpu::Device) -> HashMap<String, BindG
                 }],
             }),
         ),
+        (
+            "voxels".into(),
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Voxels Bind Group Layout"),
+                entries: &[0, 1].map(|binding| wgpu::BindGroupLayoutEntry {
+                    binding,
+                    visibility: wgpu::ShaderStages::VERTEX
+                        | wgpu::ShaderStages::FRAGMENT
+                        | wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }),
+            }),
+        ),
         (
             "ssbo_mut".into(),
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -253,7 +303,51 @@ pub fn create_bind_group_layouts(device: &wgpu::Device) -> HashMap<String, BindG
                         binding: 1,
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            sample_type: ENTITY_TEXTURE_SAMPLE_TYPE,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            }),
+        ),
+        (
+            "peel_depth".into(),
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Depth Peel Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                }],
+            }),
+        ),
+        (
+            "entity_single".into(),
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Single Entity Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: ENTITY_TEXTURE_SAMPLE_TYPE,
                             view_dimension: wgpu::TextureViewDimension::D2,
                             multisampled: false,
                         },
@@ -0,0 +1,203 @@
+//! Rendering the scene at a different resolution than the window, see [crate::WmRenderer::set_render_scale]
+
+use std::sync::Arc;
+
+use crate::mc::resource::ResourcePath;
+use crate::render::shader::WgslShader;
+use crate::texture::TextureAndView;
+use crate::WmRenderer;
+
+pub const MIN_RENDER_SCALE: f32 = 0.25;
+pub const MAX_RENDER_SCALE: f32 = 2.0;
+
+/// How the scaled scene is filtered when it's drawn to the window
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum UpscaleFilter {
+    /// Keeps the blocky look when scaling up by whole numbers
+    #[default]
+    Nearest,
+    Linear,
+}
+
+impl From<UpscaleFilter> for wgpu::FilterMode {
+    fn from(filter: UpscaleFilter) -> Self {
+        match filter {
+            UpscaleFilter::Nearest => wgpu::FilterMode::Nearest,
+            UpscaleFilter::Linear => wgpu::FilterMode::Linear,
+        }
+    }
+}
+
+/// The size of a `width` by `height` surface at `scale`, never smaller than one pixel
+pub fn scaled_size(width: u32, height: u32, scale: f32) -> (u32, u32) {
+    (
+        ((width as f32 * scale).round() as u32).max(1),
+        ((height as f32 * scale).round() as u32).max(1),
+    )
+}
+
+/// The offscreen texture the scene is drawn to while the render scale isn't 1, and the pipeline which draws it to the
+/// window
+pub struct ScaledTarget {
+    pub texture: Arc<TextureAndView>,
+    pub filter: UpscaleFilter,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+}
+
+impl ScaledTarget {
+    pub fn new(wm: &WmRenderer, width: u32, height: u32, filter: UpscaleFilter) -> Self {
+        let device = &wm.display.device;
+        let format = wm.display.view_format();
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("scaled scene"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("scaled scene"),
+            mag_filter: filter.into(),
+            min_filter: filter.into(),
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("scaled scene"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("scaled scene"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("scaled scene"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = WgslShader::init(
+            &ResourcePath("wgpu_mc:shaders/blit.wgsl".into()),
+            &*wm.mc.resource_provider,
+            device,
+            "frag".into(),
+            "vert".into(),
+        )
+        .unwrap();
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("scaled scene"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader.module,
+                entry_point: &shader.vert_entry,
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader.module,
+                entry_point: &shader.frag_entry,
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: Default::default(),
+                })],
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            texture: Arc::new(TextureAndView {
+                texture,
+                view,
+                format,
+            }),
+            filter,
+            pipeline,
+            bind_group,
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.texture.texture.width(), self.texture.texture.height())
+    }
+
+    /// Draw the scaled scene over all of `target`
+    pub fn blit(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("scaled scene"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::scaled_size;
+
+    #[test]
+    fn scaled_sizes() {
+        assert_eq!(scaled_size(1920, 1080, 0.5), (960, 540));
+        assert_eq!(scaled_size(1920, 1080, 2.0), (3840, 2160));
+        assert_eq!(scaled_size(1, 1, 0.25), (1, 1));
+    }
+}
@@ -1,5 +1,7 @@
 use std::borrow::Cow;
 
+use wgpu::naga;
+
 use crate::mc::resource::{ResourcePath, ResourceProvider};
 use crate::wgpu::{ShaderModule, ShaderModuleDescriptor};
 
@@ -51,24 +53,26 @@ impl WmShader for WgslShader {
     }
 }
 
+#[cfg(feature = "glsl")]
 #[derive(Debug)]
 pub struct GlslShader {
     pub frag: ShaderModule,
     pub vert: ShaderModule,
 }
 
+#[cfg(feature = "glsl")]
 impl GlslShader {
     pub fn init(
         frag: &ResourcePath,
         vert: &ResourcePath,
         rp: &dyn ResourceProvider,
         device: &wgpu::Device,
-    ) -> Self {
-        let frag_src = rp.get_bytes(frag).unwrap();
-        let vert_src = rp.get_bytes(vert).unwrap();
+    ) -> Option<Self> {
+        let frag_src = rp.get_bytes(frag)?;
+        let vert_src = rp.get_bytes(vert)?;
 
-        let frag_src = std::str::from_utf8(&frag_src).unwrap();
-        let vert_src = std::str::from_utf8(&vert_src).unwrap();
+        let frag_src = std::str::from_utf8(&frag_src).ok()?;
+        let vert_src = std::str::from_utf8(&vert_src).ok()?;
 
         let frag_module = device.create_shader_module(ShaderModuleDescriptor {
             label: None,
@@ -88,13 +92,14 @@ impl GlslShader {
             },
         });
 
-        Self {
+        Some(Self {
             frag: frag_module,
             vert: vert_module,
-        }
+        })
     }
 }
 
+#[cfg(feature = "glsl")]
 impl WmShader for GlslShader {
     fn get_frag(&self) -> (&ShaderModule, &str) {
         (&self.frag, "main")
@@ -104,3 +109,204 @@ impl WmShader for GlslShader {
         (&self.vert, "main")
     }
 }
+
+/// Loads the shader for a shader pack pipeline: `shaders/<name>.wgsl` with the `frag` and `vert_entry` entry points,
+/// or else GLSL from `shaders/<name>.vsh` and `shaders/<name>.fsh`, which both use `main`. GLSL shaders are compiled
+/// by naga, so they need explicit `layout(set = ..., binding = ...)` qualifiers on every resource
+pub fn load_pipeline_shader(
+    name: &str,
+    rp: &dyn ResourceProvider,
+    device: &wgpu::Device,
+    vert_entry: &str,
+) -> Option<Box<dyn WmShader>> {
+    if let Some(shader) = WgslShader::init(
+        &ResourcePath(format!("wgpu_mc:shaders/{name}.wgsl")),
+        rp,
+        device,
+        "frag".into(),
+        vert_entry.into(),
+    ) {
+        return Some(Box::new(shader));
+    }
+
+    let vert = ResourcePath(format!("wgpu_mc:shaders/{name}.vsh"));
+    let frag = ResourcePath(format!("wgpu_mc:shaders/{name}.fsh"));
+
+    #[cfg(feature = "glsl")]
+    {
+        GlslShader::init(&frag, &vert, rp, device).map(|shader| Box::new(shader) as _)
+    }
+
+    #[cfg(not(feature = "glsl"))]
+    {
+        if rp.get_bytes(&vert).is_some() || rp.get_bytes(&frag).is_some() {
+            panic!(
+                "Shader {name} is written in GLSL, but wgpu-mc was built without the glsl feature"
+            );
+        }
+
+        None
+    }
+}
+
+/// A texture and the sampler a shader samples it with, both as `(group, binding)`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SampledTexture {
+    pub texture: (u32, u32),
+    pub sampler: (u32, u32),
+}
+
+/// How a shader uses the resources it binds, as reported by naga, for checks wgpu would otherwise only fail with a
+/// much less helpful validation error once the pipeline is created
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShaderInterface {
+    /// Every texture and sampler combination the entry points sample with
+    pub sampled: Vec<SampledTexture>,
+    /// Every resource the vertex entry point uses, as `(group, binding)`
+    pub vertex_bindings: Vec<(u32, u32)>,
+}
+
+impl ShaderInterface {
+    /// The interface of a module, whose vertex entry point is `vert_entry`. Empty if the module doesn't validate, in
+    /// which case wgpu reports the error itself once the module is used
+    pub fn new(module: &naga::Module, vert_entry: &str) -> Self {
+        let Ok(info) = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(module) else {
+            return Self::default();
+        };
+
+        let binding = |handle: naga::Handle<naga::GlobalVariable>| {
+            module.global_variables[handle]
+                .binding
+                .as_ref()
+                .map(|binding| (binding.group, binding.binding))
+        };
+
+        let mut sampled = (0..module.entry_points.len())
+            .flat_map(|index| &info.get_entry_point(index).sampling_set)
+            .filter_map(|key| {
+                Some(SampledTexture {
+                    texture: binding(key.image)?,
+                    sampler: binding(key.sampler)?,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        sampled.sort_by_key(|pair| (pair.texture, pair.sampler));
+        sampled.dedup();
+
+        let vertex_bindings = module
+            .entry_points
+            .iter()
+            .position(|entry| entry.stage == naga::ShaderStage::Vertex && entry.name == vert_entry)
+            .map(|index| {
+                let function = info.get_entry_point(index);
+
+                module
+                    .global_variables
+                    .iter()
+                    .filter(|(handle, _)| !function[*handle].is_empty())
+                    .filter_map(|(handle, _)| binding(handle))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            sampled,
+            vertex_bindings,
+        }
+    }
+
+    /// The interface of the shader [load_pipeline_shader] would load, parsed without a device
+    pub fn of_pipeline(name: &str, rp: &dyn ResourceProvider, vert_entry: &str) -> Self {
+        let source = |path: String| {
+            rp.get_bytes(&ResourcePath(path))
+                .and_then(|bytes| String::from_utf8(bytes).ok())
+        };
+
+        if let Some(source) = source(format!("wgpu_mc:shaders/{name}.wgsl")) {
+            return naga::front::wgsl::parse_str(&source)
+                .map(|module| Self::new(&module, vert_entry))
+                .unwrap_or_default();
+        }
+
+        #[cfg(feature = "glsl")]
+        {
+            [
+                (naga::ShaderStage::Vertex, "vsh"),
+                (naga::ShaderStage::Fragment, "fsh"),
+            ]
+            .into_iter()
+            .filter_map(|(stage, extension)| {
+                let source = source(format!("wgpu_mc:shaders/{name}.{extension}"))?;
+
+                naga::front::glsl::Frontend::default()
+                    .parse(&naga::front::glsl::Options::from(stage), &source)
+                    .ok()
+            })
+            .map(|module| Self::new(&module, "main"))
+            .fold(Self::default(), |mut interface, stage| {
+                interface.sampled.extend(stage.sampled);
+                interface.vertex_bindings.extend(stage.vertex_bindings);
+                interface
+            })
+        }
+
+        #[cfg(not(feature = "glsl"))]
+        {
+            Self::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::naga;
+
+    use super::{SampledTexture, ShaderInterface};
+
+    #[test]
+    fn interface_pairs_textures_with_their_samplers() {
+        let module = naga::front::wgsl::parse_str(
+            "
+            @group(0) @binding(0) var t_atlas: texture_2d<f32>;
+            @group(0) @binding(1) var s_atlas: sampler;
+            @group(0) @binding(2) var t_shadow: texture_depth_2d;
+            @group(0) @binding(3) var s_shadow: sampler_comparison;
+            @group(1) @binding(0) var<uniform> offset: vec4<f32>;
+
+            @vertex
+            fn vert(@location(0) position: vec4<f32>) -> @builtin(position) vec4<f32> {
+                return position + offset;
+            }
+
+            @fragment
+            fn frag(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+                let lit = textureSampleCompare(t_shadow, s_shadow, position.xy, position.z);
+                return textureSample(t_atlas, s_atlas, position.xy) * lit;
+            }
+            ",
+        )
+        .unwrap();
+
+        let interface = ShaderInterface::new(&module, "vert");
+
+        assert_eq!(interface.vertex_bindings, vec![(1, 0)]);
+        assert_eq!(
+            interface.sampled,
+            vec![
+                SampledTexture {
+                    texture: (0, 0),
+                    sampler: (0, 1),
+                },
+                SampledTexture {
+                    texture: (0, 2),
+                    sampler: (0, 3),
+                },
+            ]
+        );
+    }
+}
@@ -1,5 +1,7 @@
 //! Serde implementation of the [shaderpack specification](https://github.com/wgpu-mc/shader-spec)
 
+use std::hash::{Hash, Hasher};
+
 use linked_hash_map::LinkedHashMap;
 use serde_derive::*;
 
@@ -15,6 +17,15 @@ pub type Mat4 = [[f32; 4]; 4];
 pub struct ShaderPackConfig {
     pub version: String,
     pub support: String,
+    /// Build normal and specular map atlas layers from `_n` and `_s` textures for every atlas, bound as
+    /// `@texture_atlas_<name>_normal` and `@texture_atlas_<name>_specular` with the atlas' full name, see
+    /// [crate::render::atlas::Atlas::add_layer].
+    /// Off by default, so packs keep the vanilla look unless they opt in
+    #[serde(default)]
+    pub pbr: bool,
+    /// How `@sampler` filters the block atlas and other atlases
+    #[serde(default)]
+    pub block_sampler: BlockSamplerConfig,
     pub resources: ResourcesConfig,
     pub pipelines: PipelinesConfig,
 }
@@ -39,6 +50,28 @@ impl ShaderPackConfig {
     }
 }
 
+/// The filters of `@sampler`. By default texels stay crisp within a mip level, but distant blocks blend linearly
+/// between the block atlas' mip levels so they don't shimmer
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockSamplerConfig {
+    #[serde(default)]
+    pub mag_filter: SamplerFilter,
+    #[serde(default)]
+    pub min_filter: SamplerFilter,
+    #[serde(default = "mipmap_filter_default")]
+    pub mipmap_filter: SamplerFilter,
+}
+
+impl Default for BlockSamplerConfig {
+    fn default() -> Self {
+        Self {
+            mag_filter: SamplerFilter::Nearest,
+            min_filter: SamplerFilter::Nearest,
+            mipmap_filter: mipmap_filter_default(),
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct ResourcesConfig {
     #[serde(flatten)]
@@ -93,8 +126,43 @@ pub enum TypeResourceConfig {
         #[serde(default)]
         src: String,
     },
+    /// A depth texture the size of the scene, for pipelines which can't share `@texture_depth`. Multisampled pipelines
+    /// need one with a matching `samples`, since `@texture_depth` only ever has a single sample
     #[serde(rename = "texture_depth")]
-    TextureDepth,
+    TextureDepth {
+        #[serde(default = "samples_default")]
+        samples: u32,
+    },
+    /// A color texture the size of the scene (see [crate::WmRenderer::render_size]) which pipelines can output to and
+    /// later pipelines can sample. With more than one sample it's multisampled, and `resolve` names the single sampled
+    /// render target it gets resolved into at the end of every pass which outputs to it
+    RenderTarget {
+        #[serde(default = "samples_default")]
+        samples: u32,
+        #[serde(default)]
+        resolve: Option<String>,
+    },
+    StorageBuffer {
+        size: u64,
+        #[serde(default)]
+        access: StorageAccess,
+    },
+    Sampler {
+        #[serde(default)]
+        address_mode: SamplerAddressMode,
+        #[serde(default)]
+        mag_filter: SamplerFilter,
+        #[serde(default)]
+        min_filter: SamplerFilter,
+        #[serde(default)]
+        mipmap_filter: SamplerFilter,
+        /// Makes this a comparison sampler, for sampling depth textures such as shadow maps
+        #[serde(default)]
+        compare: Option<SamplerCompare>,
+        /// The maximum anisotropy, from 1 (off) to 16. Anything above 1 requires every filter to be `linear`
+        #[serde(default = "anisotropy_default")]
+        anisotropy: u16,
+    },
     F32 {
         #[serde(default)]
         range: [f32; 2],
@@ -119,6 +187,79 @@ pub enum TypeResourceConfig {
     Mat4(Mat4ValueOrMult),
 }
 
+/// Whether shaders may write to a storage buffer. Read-write storage buffers can't be bound to vertex shaders
+#[derive(Deserialize, Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageAccess {
+    #[default]
+    Read,
+    ReadWrite,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplerAddressMode {
+    #[default]
+    Clamp,
+    Repeat,
+    Mirror,
+}
+
+impl From<SamplerAddressMode> for wgpu::AddressMode {
+    fn from(mode: SamplerAddressMode) -> Self {
+        match mode {
+            SamplerAddressMode::Clamp => wgpu::AddressMode::ClampToEdge,
+            SamplerAddressMode::Repeat => wgpu::AddressMode::Repeat,
+            SamplerAddressMode::Mirror => wgpu::AddressMode::MirrorRepeat,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplerFilter {
+    #[default]
+    Nearest,
+    Linear,
+}
+
+impl From<SamplerFilter> for wgpu::FilterMode {
+    fn from(filter: SamplerFilter) -> Self {
+        match filter {
+            SamplerFilter::Nearest => wgpu::FilterMode::Nearest,
+            SamplerFilter::Linear => wgpu::FilterMode::Linear,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplerCompare {
+    Never,
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl From<SamplerCompare> for wgpu::CompareFunction {
+    fn from(compare: SamplerCompare) -> Self {
+        match compare {
+            SamplerCompare::Never => wgpu::CompareFunction::Never,
+            SamplerCompare::Less => wgpu::CompareFunction::Less,
+            SamplerCompare::Equal => wgpu::CompareFunction::Equal,
+            SamplerCompare::LessEqual => wgpu::CompareFunction::LessEqual,
+            SamplerCompare::Greater => wgpu::CompareFunction::Greater,
+            SamplerCompare::NotEqual => wgpu::CompareFunction::NotEqual,
+            SamplerCompare::GreaterEqual => wgpu::CompareFunction::GreaterEqual,
+            SamplerCompare::Always => wgpu::CompareFunction::Always,
+        }
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum Mat3ValueOrMult {
@@ -139,10 +280,169 @@ pub struct PipelinesConfig {
     pub pipelines: LinkedHashMap<String, PipelineConfig>,
 }
 
+/// A pipeline which was left out of the render graph, see [PipelinesConfig::skip_unsupported] and
+/// [PipelinesConfig::skip_unbound]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedPipeline {
+    pub name: String,
+    pub config: PipelineConfig,
+    /// The features in `requires` which the device lacks. Empty for a fallback whose pipeline wasn't skipped
+    pub missing: Vec<PipelineFeature>,
+    /// The [BindGroupDef::Resource] bind group which the pipeline's geometry doesn't set
+    pub unbound: Option<String>,
+}
+
+/// The [BindGroupDef::Resource] bind groups which a built in geometry sets while drawing, or `None` for a custom
+/// geometry, which sets its own
+pub fn geometry_resource_bind_groups(geometry: &str) -> Option<&'static [&'static str]> {
+    match geometry {
+        "@geo_terrain" => Some(&[
+            "@bg_ssbo_chunks",
+            "@bg_ssbo_section_tints",
+            "@bg_peel_depth",
+        ]),
+        "@geo_entities" | "@geo_entities_single" | "@geo_entities_ignore_depth" => {
+            Some(&["@bg_entity", "@bg_entity_single"])
+        }
+        "@geo_quad" | "@geo_decals" | "@geo_blob_shadows" | "@geo_void_plane" => Some(&[]),
+        sky if SKY_GEOMETRIES.contains(&sky) => Some(&[]),
+        _ => None,
+    }
+}
+
+impl PipelineConfig {
+    /// The first [BindGroupDef::Resource] bind group which nothing sets when drawing the pipeline's geometry. Custom
+    /// geometries may set any of their own, but not the `@bg_` ones
+    pub fn unbound_resource_bind_group(&self) -> Option<&str> {
+        let supported = geometry_resource_bind_groups(&self.geometry);
+
+        self.bind_groups.values().find_map(|def| match def {
+            BindGroupDef::Resource(name) => {
+                let bound = match supported {
+                    Some(supported) => supported.contains(&&name[..]),
+                    None => !name.starts_with("@bg_"),
+                };
+
+                (!bound).then_some(&name[..])
+            }
+            BindGroupDef::Entries(_) => None,
+        })
+    }
+}
+
+impl PipelinesConfig {
+    /// Remove the pipelines requiring a feature which `is_supported` rejects, then the fallbacks of the pipelines which
+    /// are left, keeping the order of the rest. Returns what was removed
+    pub fn skip_unsupported(
+        &mut self,
+        is_supported: impl Fn(PipelineFeature) -> bool,
+    ) -> Vec<SkippedPipeline> {
+        let unsupported = self
+            .pipelines
+            .iter()
+            .filter_map(|(name, config)| {
+                let missing = config
+                    .requires
+                    .iter()
+                    .copied()
+                    .filter(|feature| !is_supported(*feature))
+                    .collect::<Vec<_>>();
+
+                (!missing.is_empty()).then(|| (name.clone(), missing))
+            })
+            .collect::<Vec<_>>();
+
+        let mut skipped = unsupported
+            .into_iter()
+            .map(|(name, missing)| SkippedPipeline {
+                config: self.pipelines.remove(&name).unwrap(),
+                name,
+                missing,
+                unbound: None,
+            })
+            .collect::<Vec<_>>();
+
+        let unneeded_fallbacks = self
+            .pipelines
+            .iter()
+            .filter(|(_, config)| {
+                config
+                    .fallback_for
+                    .as_ref()
+                    .is_some_and(|pipeline| self.pipelines.contains_key(pipeline))
+            })
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+
+        skipped.extend(unneeded_fallbacks.into_iter().map(|name| SkippedPipeline {
+            config: self.pipelines.remove(&name).unwrap(),
+            name,
+            missing: Vec::new(),
+            unbound: None,
+        }));
+
+        skipped
+    }
+
+    /// Remove the pipelines declaring a resource bind group which their geometry doesn't set, see
+    /// [PipelineConfig::unbound_resource_bind_group], since drawing them would fail. Run before
+    /// [PipelinesConfig::skip_unsupported] so their fallbacks are kept. Returns what was removed
+    pub fn skip_unbound(&mut self) -> Vec<SkippedPipeline> {
+        let unbound = self
+            .pipelines
+            .iter()
+            .filter_map(|(name, config)| {
+                config
+                    .unbound_resource_bind_group()
+                    .map(|bind_group| (name.clone(), bind_group.to_string()))
+            })
+            .collect::<Vec<_>>();
+
+        unbound
+            .into_iter()
+            .map(|(name, bind_group)| SkippedPipeline {
+                config: self.pipelines.remove(&name).unwrap(),
+                name,
+                missing: Vec::new(),
+                unbound: Some(bind_group),
+            })
+            .collect()
+    }
+}
+
 fn blend_default() -> String {
     "alpha_blending".into()
 }
 
+fn depth_write_default() -> bool {
+    true
+}
+
+fn max_depth_default() -> ConfigF32 {
+    ConfigF32(1.0)
+}
+
+fn samples_default() -> u32 {
+    1
+}
+
+fn anisotropy_default() -> u16 {
+    1
+}
+
+//The bias wgpu's shadow example uses, enough for a Depth32Float shadow map of a few chunks
+fn shadow_constant_bias_default() -> i32 {
+    2
+}
+
+fn shadow_slope_scale_bias_default() -> ConfigF32 {
+    ConfigF32(2.0)
+}
+
+fn mipmap_filter_default() -> SamplerFilter {
+    SamplerFilter::Linear
+}
+
 #[derive(Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
 #[serde(untagged)]
 pub enum BindGroupDef {
@@ -154,11 +454,26 @@ pub enum BindGroupDef {
 pub struct PipelineConfig {
     pub geometry: String,
 
+    /// The shader to load instead of the one named after the pipeline, so pipelines which only differ in their depth
+    /// or blending state can share one, see [PipelineConfig::shader_name]
+    #[serde(default)]
+    pub shader: Option<String>,
+
     #[serde(default)]
     pub output: Vec<String>,
 
     pub depth: Option<String>,
 
+    /// Whether the pipeline writes to `depth`. Translucent geometry and overlays such as decals usually test against
+    /// depth without writing it
+    #[serde(default = "depth_write_default")]
+    pub depth_write: bool,
+
+    /// How fragments are tested against `depth`, one of `never`, `less`, `equal`, `less_equal`, `greater`,
+    /// `not_equal`, `greater_equal` or `always`
+    #[serde(default)]
+    pub depth_compare: DepthCompare,
+
     #[serde(default)]
     pub clear: bool,
 
@@ -170,6 +485,380 @@ pub struct PipelineConfig {
 
     #[serde(default = "blend_default")]
     pub blending: String,
+
+    #[serde(default)]
+    pub translucent_depth: TranslucentDepth,
+
+    #[serde(default)]
+    pub translucent_faces: TranslucentFaces,
+
+    #[serde(default)]
+    pub vertex_fetch: VertexFetch,
+
+    /// Which faces aren't drawn, faces are front facing when wound counter clockwise. Defaults to back faces, except
+    /// for the sky geometries, which are seen from inside, and shadow passes, see [PipelineConfig::cull_face]. The translucent layer of
+    /// `@geo_terrain` follows `translucent_faces` instead
+    #[serde(default)]
+    pub cull: Option<Cull>,
+
+    /// How vertices are assembled, `line_list` is for geometry like [crate::render::lines::LineGeometry]
+    #[serde(default)]
+    pub topology: Topology,
+
+    /// Turn the fragment alpha into sample coverage, which smooths the edges of cutout geometry like leaves without
+    /// sorting. Only has an effect when the pipeline's attachments are multisampled, and the fragment shader should
+    /// output alpha instead of discarding. The translucent layer of `@geo_terrain` never uses it
+    #[serde(default)]
+    pub alpha_to_coverage: bool,
+
+    /// Draw to `@framebuffer_texture` at window resolution even when the scene is rendered at a different scale, for
+    /// the GUI. These pipelines should come after the scene pipelines and can't use `@texture_depth`
+    #[serde(default)]
+    pub native_resolution: bool,
+
+    /// Restricts the pipeline to part of its attachments, e.g. one half of a split screen. Defaults to all of them
+    #[serde(default)]
+    pub viewport: Option<Viewport>,
+
+    /// Draw the translucent layer of `@geo_terrain` with this many passes of depth peeling instead of sorting it back
+    /// to front, see [crate::render::depth_peel]. Each pass clears its own depth, so `depth_write`, `depth_compare`,
+    /// `clear` and `translucent_depth` don't apply to it. The pipeline only draws the translucent layer and takes the
+    /// previous pass' depth as `@bg_peel_depth`, other `@geo_terrain` pipelines skip the translucent layer. 0 turns
+    /// it off
+    #[serde(default)]
+    pub depth_peel_layers: u32,
+
+    /// Marks the pipeline as a shadow pass rendering casters into a shadow map. Shadow passes cull front faces unless
+    /// `cull` is set, and offset their depth by the configured bias to keep lit surfaces from shadowing themselves
+    #[serde(default)]
+    pub shadow: Option<ShadowConfig>,
+
+    /// Optional device features the pipeline needs. On devices lacking any of them the pipeline is skipped with a
+    /// warning instead of failing the whole pack, so whatever it draws has to be drawn by a fallback as well
+    #[serde(default)]
+    pub requires: Vec<PipelineFeature>,
+
+    /// The name of a pipeline this one stands in for. It only runs if that pipeline was skipped, see `requires`
+    #[serde(default)]
+    pub fallback_for: Option<String>,
+}
+
+/// A device capability which a pipeline can require, see [PipelineConfig::requires]
+#[derive(Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PipelineFeature {
+    PushConstants,
+    /// Storage buffers in vertex and fragment shaders
+    StorageBuffers,
+    MultiDrawIndirect,
+    /// Filtering samplers on `r32float` and other 32 bit float textures
+    Float32Filterable,
+    DepthClipControl,
+    AnisotropicFiltering,
+}
+
+impl PipelineFeature {
+    /// Whether a device with `features` and `limits`, from an adapter with `downlevel` capabilities, has the feature.
+    /// Features have to be enabled on the device, it isn't enough for the adapter to support them
+    pub fn is_supported(
+        self,
+        features: wgpu::Features,
+        limits: &wgpu::Limits,
+        downlevel: &wgpu::DownlevelCapabilities,
+    ) -> bool {
+        match self {
+            PipelineFeature::PushConstants => {
+                features.contains(wgpu::Features::PUSH_CONSTANTS)
+                    && limits.max_push_constant_size > 0
+            }
+            PipelineFeature::StorageBuffers => {
+                limits.max_storage_buffers_per_shader_stage > 0
+                    && downlevel
+                        .flags
+                        .contains(wgpu::DownlevelFlags::VERTEX_STORAGE)
+            }
+            PipelineFeature::MultiDrawIndirect => {
+                features.contains(wgpu::Features::MULTI_DRAW_INDIRECT)
+            }
+            PipelineFeature::Float32Filterable => {
+                features.contains(wgpu::Features::FLOAT32_FILTERABLE)
+            }
+            PipelineFeature::DepthClipControl => {
+                features.contains(wgpu::Features::DEPTH_CLIP_CONTROL)
+            }
+            PipelineFeature::AnisotropicFiltering => downlevel
+                .flags
+                .contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING),
+        }
+    }
+}
+
+/// A float in a [PipelineConfig]. It's compared and hashed by its bits, so the configs holding it can derive [Hash]
+/// and [Eq] like the rest of [PipelineConfig]
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(transparent)]
+pub struct ConfigF32(pub f32);
+
+impl PartialEq for ConfigF32 {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for ConfigF32 {}
+
+impl Hash for ConfigF32 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+/// The depth bias of a shadow pass, see [PipelineConfig::shadow]. Too little bias gives surfaces acne, too much
+/// detaches shadows from their casters
+#[derive(Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct ShadowConfig {
+    /// Added to every fragment's depth, in units of the smallest depth difference the shadow map can hold
+    #[serde(default = "shadow_constant_bias_default")]
+    pub constant_bias: i32,
+    /// Scaled by how steeply the surface slopes away from the light, which needs more bias than surfaces facing it
+    #[serde(default = "shadow_slope_scale_bias_default")]
+    pub slope_scale_bias: ConfigF32,
+    /// The largest bias any fragment gets, 0 leaves it unclamped
+    #[serde(default)]
+    pub bias_clamp: ConfigF32,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            constant_bias: shadow_constant_bias_default(),
+            slope_scale_bias: shadow_slope_scale_bias_default(),
+            bias_clamp: ConfigF32(0.0),
+        }
+    }
+}
+
+impl ShadowConfig {
+    pub fn depth_bias(&self) -> wgpu::DepthBiasState {
+        wgpu::DepthBiasState {
+            constant: self.constant_bias,
+            slope_scale: self.slope_scale_bias.0,
+            clamp: self.bias_clamp.0,
+        }
+    }
+}
+
+/// Geometries drawn around the camera by the sky pass of [crate::render::graph::RenderGraph::render_frame], which
+/// aren't culled unless the pipeline sets `cull` since they're seen from inside
+pub const SKY_GEOMETRIES: [&str; 4] = [
+    "@geo_sky_scatter",
+    "@geo_sky_fog",
+    "@geo_sky_stars",
+    "@geo_sun_moon",
+];
+
+impl PipelineConfig {
+    /// The name of the shader under `wgpu_mc:shaders/` which the pipeline `pipeline_name` is drawn with
+    pub fn shader_name<'a>(&'a self, pipeline_name: &'a str) -> &'a str {
+        self.shader.as_deref().unwrap_or(pipeline_name)
+    }
+
+    /// The faces culled by this pipeline, from `cull` or the default for its geometry. Shadow passes render back
+    /// faces, which keeps the surfaces facing the light out of the shadow map
+    pub fn cull_face(&self) -> Option<wgpu::Face> {
+        let cull = self
+            .cull
+            .unwrap_or(if SKY_GEOMETRIES.contains(&&self.geometry[..]) {
+                Cull::None
+            } else if self.shadow.is_some() {
+                Cull::Front
+            } else {
+                Cull::Back
+            });
+
+        cull.face()
+    }
+
+    /// The depth bias from [PipelineConfig::shadow], pipelines which aren't shadow passes don't have any
+    pub fn depth_bias(&self) -> wgpu::DepthBiasState {
+        self.shadow
+            .map(|shadow| shadow.depth_bias())
+            .unwrap_or_default()
+    }
+
+    pub fn depth_compare_function(&self) -> wgpu::CompareFunction {
+        self.depth_compare.compare_function()
+    }
+}
+
+/// A region of a pipeline's attachments, applied with [wgpu::RenderPass::set_viewport]
+#[derive(Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct Viewport {
+    /// x, y, width and height from the top left corner, as fractions of the attachment size unless `pixels` is set
+    pub rect: [ConfigF32; 4],
+
+    #[serde(default)]
+    pub pixels: bool,
+
+    #[serde(default)]
+    pub min_depth: ConfigF32,
+
+    #[serde(default = "max_depth_default")]
+    pub max_depth: ConfigF32,
+}
+
+impl Viewport {
+    /// The rect in pixels for an attachment of `size`, without any clamping
+    pub fn pixel_rect(&self, size: (u32, u32)) -> [f32; 4] {
+        let rect = self.rect.map(|value| value.0);
+        let [x, y, width, height] = rect;
+
+        if self.pixels {
+            rect
+        } else {
+            [
+                x * size.0 as f32,
+                y * size.1 as f32,
+                width * size.0 as f32,
+                height * size.1 as f32,
+            ]
+        }
+    }
+
+    /// Like [Viewport::pixel_rect], but cut down to fit within `size`, since attachments shrink with the window
+    pub fn clamped_pixel_rect(&self, size: (u32, u32)) -> [f32; 4] {
+        let [x, y, width, height] = self.pixel_rect(size);
+        let x = x.clamp(0.0, size.0 as f32);
+        let y = y.clamp(0.0, size.1 as f32);
+
+        [
+            x,
+            y,
+            width.min(size.0 as f32 - x),
+            height.min(size.1 as f32 - y),
+        ]
+    }
+
+    /// Whether the viewport fits within an attachment of `size` and has a valid depth range
+    pub fn is_within(&self, size: (u32, u32)) -> bool {
+        let [x, y, width, height] = self.pixel_rect(size);
+
+        x >= 0.0
+            && y >= 0.0
+            && width > 0.0
+            && height > 0.0
+            && x + width <= size.0 as f32
+            && y + height <= size.1 as f32
+            && 0.0 <= self.min_depth.0
+            && self.min_depth.0 <= self.max_depth.0
+            && self.max_depth.0 <= 1.0
+    }
+}
+
+/// How `@geo_terrain` vertices reach the vertex shader. Other geometry ignores this
+#[derive(Deserialize, Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VertexFetch {
+    /// The `vert` entry point reads vertices from `@bg_ssbo_chunks` by index
+    #[default]
+    Pulling,
+    /// The `vert_attributes` entry point gets each packed vertex as a `vec4<u32>` at location 0 from the chunk buffer
+    /// bound as a vertex buffer. The rest of the quad is still read from `@bg_ssbo_chunks` for smooth lighting
+    Attributes,
+}
+
+/// How the translucent layer of `@geo_terrain` is depth tested. Other geometry ignores this
+#[derive(Deserialize, Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TranslucentDepth {
+    /// The same as the other layers, following `depth_write` and `depth_compare`
+    #[default]
+    Write,
+    /// `LessEqual` without depth writes, so that the back to front draw order decides between coplanar translucent
+    /// faces (e.g. stacked stained glass) instead of whichever the depth test lets through
+    ReadOnly,
+}
+
+/// Which sides of faces in the translucent layer of `@geo_terrain` are drawn. Other geometry ignores this
+#[derive(Deserialize, Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TranslucentFaces {
+    /// Back faces are culled like in the other layers
+    #[default]
+    Front,
+    /// Back faces are drawn too, so that surfaces stay visible from inside, e.g. the water surface seen from
+    /// underwater or the panes of glass around the camera
+    Both,
+}
+
+/// Which faces a pipeline culls, see [PipelineConfig::cull]
+#[derive(Deserialize, Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Cull {
+    #[default]
+    Back,
+    Front,
+    /// Draw every face regardless of winding, for 2D geometry like the GUI, whose quads the JVM may wind either way
+    None,
+}
+
+impl Cull {
+    pub fn face(self) -> Option<wgpu::Face> {
+        match self {
+            Cull::Back => Some(wgpu::Face::Back),
+            Cull::Front => Some(wgpu::Face::Front),
+            Cull::None => None,
+        }
+    }
+}
+
+/// How fragments are tested against a pipeline's depth attachment, see [PipelineConfig::depth_compare]
+#[derive(Deserialize, Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DepthCompare {
+    Never,
+    #[default]
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl DepthCompare {
+    pub fn compare_function(self) -> wgpu::CompareFunction {
+        match self {
+            DepthCompare::Never => wgpu::CompareFunction::Never,
+            DepthCompare::Less => wgpu::CompareFunction::Less,
+            DepthCompare::Equal => wgpu::CompareFunction::Equal,
+            DepthCompare::LessEqual => wgpu::CompareFunction::LessEqual,
+            DepthCompare::Greater => wgpu::CompareFunction::Greater,
+            DepthCompare::NotEqual => wgpu::CompareFunction::NotEqual,
+            DepthCompare::GreaterEqual => wgpu::CompareFunction::GreaterEqual,
+            DepthCompare::Always => wgpu::CompareFunction::Always,
+        }
+    }
+}
+
+/// How a pipeline assembles its vertices, see [PipelineConfig::topology]
+#[derive(Deserialize, Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Topology {
+    #[default]
+    TriangleList,
+    /// Every two vertices are a line segment
+    LineList,
+}
+
+impl Topology {
+    pub fn primitive_topology(self) -> wgpu::PrimitiveTopology {
+        match self {
+            Topology::TriangleList => wgpu::PrimitiveTopology::TriangleList,
+            Topology::LineList => wgpu::PrimitiveTopology::LineList,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
@@ -191,7 +880,10 @@ mod tests {
 
     use serde::Deserialize;
 
-    use super::ShaderPackConfig;
+    use super::{
+        BlockSamplerConfig, ConfigF32, PipelineFeature, SamplerFilter, ShaderPackConfig,
+        ShadowConfig, Topology, Viewport,
+    };
 
     fn deserialize_and_print_error<'a, T: Debug + Deserialize<'a>>(input: &'a str) {
         let config: Result<T, _> = serde_yaml::from_str(input);
@@ -209,6 +901,10 @@ mod tests {
     const FULL_YAML: &str = r#"
 version: "0.0.1"
 support: glsl # could also be wgsl
+pbr: true
+block_sampler:
+  mag_filter: nearest
+  min_filter: linear
 resources:
   shadowmap_texture_depth:
     type: texture_depth
@@ -242,10 +938,38 @@ resources:
   mvp_mat4:
     type: mat4
     mult: [wm_model_mat4, wm_view_mat4, wm_projection_mat4]
+  particles_storage:
+    type: storage_buffer
+    size: 65536
+    access: read_write
+  shadow_sampler:
+    type: sampler
+    address_mode: clamp
+    mag_filter: linear
+    min_filter: linear
+    compare: less_equal
+  terrain_sampler:
+    type: sampler
+    mag_filter: linear
+    min_filter: linear
+    mipmap_filter: linear
+    anisotropy: 16
+  scene_msaa:
+    type: render_target
+    samples: 4
+    resolve: scene_resolved
+  scene_resolved:
+    type: render_target
 pipelines:
   terrain_shadows:
     geometry: wm_geo_terrain # one
     depth: shadowmap_texture_depth
+    translucent_depth: read_only
+    translucent_faces: both
+    vertex_fetch: attributes
+    shadow:
+      constant_bias: 4
+      slope_scale_bias: 1.5
     uniforms:
       0:
         resource: model_view_mat4
@@ -257,6 +981,7 @@ pipelines:
   entity_shadows:
     geometry: wm_geo_entities
     depth: shadowmap_texture_depth
+    shadow: {}
     uniforms:
       0:
         resource: model_view_mat4
@@ -272,16 +997,298 @@ pipelines:
     geometry: wm_geo_terrain
     depth: wm_framebuffer_depth
     output: [wm_framebuffer_texture]
+    alpha_to_coverage: true
+    viewport:
+      rect: [0.0, 0.0, 0.5, 1.0]
   entities:
     geometry: wm_geo_entities
     depth: wm_framebuffer_depth
     output: [wm_framebuffer_texture]
     bind_groups:
       
+  clouds:
+    geometry: wm_geo_clouds
+    depth: wm_framebuffer_depth
+    depth_write: false
+    depth_compare: less_equal
+    output: [wm_framebuffer_texture]
+    bind_groups:
+      
+  gui:
+    geometry: wm_geo_gui
+    output: [wm_framebuffer_texture]
+    cull: none
+  sky:
+    geometry: "@geo_sky_scatter"
+    output: [wm_framebuffer_texture]
+  sun:
+    geometry: "@geo_sun_moon"
+    output: [wm_framebuffer_texture]
+    cull: back
+  entities_xray:
+    geometry: wm_geo_entities
+    shader: entities
+    depth: wm_framebuffer_depth
+    depth_write: false
+    depth_compare: always
+    output: [wm_framebuffer_texture]
+  debug_lines:
+    geometry: "@geo_debug_lines_xray"
+    depth: wm_framebuffer_depth
+    depth_write: false
+    depth_compare: always
+    output: [wm_framebuffer_texture]
+    topology: line_list
+  water:
+    geometry: wm_geo_terrain
+    depth: wm_framebuffer_depth
+    output: [wm_framebuffer_texture]
+    depth_peel_layers: 4
+  bloom_storage:
+    geometry: "@geo_quad"
+    output: [wm_framebuffer_texture]
+    requires: [storage_buffers, float32_filterable]
+  bloom:
+    geometry: "@geo_quad"
+    output: [wm_framebuffer_texture]
+    fallback_for: bloom_storage
 "#;
 
     #[test]
     fn complete_file() {
         deserialize_and_print_error::<ShaderPackConfig>(FULL_YAML);
     }
+
+    #[test]
+    fn depth_state_defaults_to_writing_with_less() {
+        let config: ShaderPackConfig = serde_yaml::from_str(FULL_YAML).unwrap();
+
+        let terrain = &config.pipelines.pipelines["terrain"];
+        assert!(terrain.depth_write);
+        assert_eq!(
+            terrain.depth_compare_function(),
+            wgpu::CompareFunction::Less
+        );
+
+        let clouds = &config.pipelines.pipelines["clouds"];
+        assert!(!clouds.depth_write);
+        assert_eq!(
+            clouds.depth_compare_function(),
+            wgpu::CompareFunction::LessEqual
+        );
+    }
+
+    #[test]
+    fn unknown_depth_compares_fail_to_parse() {
+        let yaml = FULL_YAML.replace("depth_compare: less_equal", "depth_compare: less_eqaul");
+
+        assert!(serde_yaml::from_str::<ShaderPackConfig>(&yaml).is_err());
+    }
+
+    #[test]
+    fn shaders_default_to_the_pipeline_name() {
+        let config: ShaderPackConfig = serde_yaml::from_str(FULL_YAML).unwrap();
+
+        let pipelines = &config.pipelines.pipelines;
+
+        assert_eq!(pipelines["entities"].shader_name("entities"), "entities");
+        assert_eq!(
+            pipelines["entities_xray"].shader_name("entities_xray"),
+            "entities"
+        );
+    }
+
+    #[test]
+    fn cull_defaults_to_back_faces() {
+        let config: ShaderPackConfig = serde_yaml::from_str(FULL_YAML).unwrap();
+
+        let pipelines = &config.pipelines.pipelines;
+
+        assert_eq!(pipelines["terrain"].cull_face(), Some(wgpu::Face::Back));
+        assert_eq!(pipelines["gui"].cull_face(), None);
+        //The sky is seen from inside, unless the pipeline says otherwise
+        assert_eq!(pipelines["sky"].cull_face(), None);
+        assert_eq!(pipelines["sun"].cull_face(), Some(wgpu::Face::Back));
+    }
+
+    #[test]
+    fn shadow_passes_cull_front_faces_with_bias() {
+        let config: ShaderPackConfig = serde_yaml::from_str(FULL_YAML).unwrap();
+
+        let pipelines = &config.pipelines.pipelines;
+
+        let terrain_shadows = &pipelines["terrain_shadows"];
+        assert_eq!(terrain_shadows.cull_face(), Some(wgpu::Face::Front));
+        assert_eq!(
+            terrain_shadows.depth_bias(),
+            wgpu::DepthBiasState {
+                constant: 4,
+                slope_scale: 1.5,
+                clamp: 0.0,
+            }
+        );
+
+        assert_eq!(
+            pipelines["entity_shadows"].shadow,
+            Some(ShadowConfig::default())
+        );
+        assert_eq!(
+            pipelines["terrain"].depth_bias(),
+            wgpu::DepthBiasState::default()
+        );
+    }
+
+    #[test]
+    fn topology_defaults_to_triangles() {
+        let config: ShaderPackConfig = serde_yaml::from_str(FULL_YAML).unwrap();
+
+        let pipelines = &config.pipelines.pipelines;
+
+        assert_eq!(pipelines["terrain"].topology, Topology::TriangleList);
+        assert_eq!(pipelines["debug_lines"].topology, Topology::LineList);
+        assert_eq!(
+            pipelines["debug_lines"].depth_compare_function(),
+            wgpu::CompareFunction::Always
+        );
+    }
+
+    #[test]
+    fn alpha_to_coverage_is_opt_in() {
+        let config: ShaderPackConfig = serde_yaml::from_str(FULL_YAML).unwrap();
+
+        let pipelines = &config.pipelines.pipelines;
+
+        assert!(pipelines["terrain"].alpha_to_coverage);
+        assert!(!pipelines["entities"].alpha_to_coverage);
+    }
+
+    #[test]
+    fn block_sampler_blends_between_mips_by_default() {
+        let config: ShaderPackConfig = serde_yaml::from_str(FULL_YAML).unwrap();
+
+        assert_eq!(
+            config.block_sampler,
+            BlockSamplerConfig {
+                mag_filter: SamplerFilter::Nearest,
+                min_filter: SamplerFilter::Linear,
+                mipmap_filter: SamplerFilter::Linear,
+            }
+        );
+
+        let defaults: BlockSamplerConfig = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(defaults, BlockSamplerConfig::default());
+        assert_eq!(defaults.mag_filter, SamplerFilter::Nearest);
+        assert_eq!(defaults.mipmap_filter, SamplerFilter::Linear);
+    }
+
+    #[test]
+    fn depth_peeling_is_opt_in() {
+        let config: ShaderPackConfig = serde_yaml::from_str(FULL_YAML).unwrap();
+
+        let pipelines = &config.pipelines.pipelines;
+
+        assert_eq!(pipelines["terrain"].depth_peel_layers, 0);
+        assert_eq!(pipelines["water"].depth_peel_layers, 4);
+    }
+
+    #[test]
+    fn unsupported_pipelines_are_replaced_by_their_fallbacks() {
+        let config: ShaderPackConfig = serde_yaml::from_str(FULL_YAML).unwrap();
+        let mut pipelines = config.pipelines;
+        let count = pipelines.pipelines.len();
+
+        //A device without storage buffers skips the enhanced pipeline and keeps its fallback
+        let skipped =
+            pipelines.skip_unsupported(|feature| feature == PipelineFeature::Float32Filterable);
+
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].name, "bloom_storage");
+        assert_eq!(skipped[0].missing, vec![PipelineFeature::StorageBuffers]);
+        assert!(pipelines.pipelines.contains_key("bloom"));
+        assert_eq!(pipelines.pipelines.len(), count - 1);
+
+        //A device with everything skips the fallback instead
+        let config: ShaderPackConfig = serde_yaml::from_str(FULL_YAML).unwrap();
+        let mut pipelines = config.pipelines;
+        let skipped = pipelines.skip_unsupported(|_| true);
+
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].name, "bloom");
+        assert!(skipped[0].missing.is_empty());
+        assert_eq!(
+            pipelines.pipelines.keys().last().map(String::as_str),
+            Some("bloom_storage")
+        );
+    }
+
+    #[test]
+    fn pipelines_with_unbound_bind_groups_are_skipped() {
+        let mut pipelines: PipelinesConfig = serde_yaml::from_str(
+            r#"
+terrain:
+  geometry: "@geo_terrain"
+  bind_groups:
+    0: "@bg_ssbo_chunks"
+decals:
+  geometry: "@geo_decals"
+  bind_groups:
+    0: "@bg_entity"
+lines:
+  geometry: wm_geo_lines
+  bind_groups:
+    0: "@bg_ssbo_chunks"
+gui:
+  geometry: wm_geo_gui
+  bind_groups:
+    0: "@texture_electrum_gui"
+"#,
+        )
+        .unwrap();
+
+        let skipped = pipelines.skip_unbound();
+
+        assert_eq!(
+            skipped
+                .iter()
+                .map(|skipped| (&skipped.name[..], skipped.unbound.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("decals", Some("@bg_entity")),
+                ("lines", Some("@bg_ssbo_chunks"))
+            ]
+        );
+        assert_eq!(
+            pipelines.pipelines.keys().collect::<Vec<_>>(),
+            vec!["terrain", "gui"]
+        );
+    }
+
+    #[test]
+    fn viewport_rects() {
+        let left_half = Viewport {
+            rect: [0.0, 0.0, 0.5, 1.0].map(ConfigF32),
+            pixels: false,
+            min_depth: ConfigF32(0.0),
+            max_depth: ConfigF32(1.0),
+        };
+
+        assert_eq!(
+            left_half.pixel_rect((1920, 1080)),
+            [0.0, 0.0, 960.0, 1080.0]
+        );
+        assert!(left_half.is_within((1920, 1080)));
+
+        let minimap = Viewport {
+            rect: [1600.0, 0.0, 320.0, 320.0].map(ConfigF32),
+            pixels: true,
+            ..left_half
+        };
+
+        assert!(minimap.is_within((1920, 1080)));
+        assert!(!minimap.is_within((1280, 720)));
+        assert_eq!(
+            minimap.clamped_pixel_rect((1700, 720)),
+            [1600.0, 0.0, 100.0, 320.0]
+        );
+    }
 }
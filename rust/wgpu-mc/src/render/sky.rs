@@ -1,3 +1,34 @@
+use std::sync::OnceLock;
+
+use crate::mc::SkyState;
+
+/// The contents of the `@buffer_sky` uniform, written from [crate::mc::Scene::sky_state] at the start of every
+/// render so that every sky pipeline sees the same values in a frame
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkyUniform {
+    /// From 0 to 1
+    pub color: [f32; 3],
+    pub angle: f32,
+    pub brightness: f32,
+    pub star_shimmer: f32,
+    pub moon_phase: i32,
+    _padding: u32,
+}
+
+impl SkyUniform {
+    pub fn new(state: &SkyState) -> Self {
+        Self {
+            color: state.color.map(|channel| channel as f32 / 255.0),
+            angle: state.angle,
+            brightness: state.brightness,
+            star_shimmer: state.star_shimmer,
+            moon_phase: state.moon_phase,
+            _padding: 0,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct SkyVertex {
@@ -30,6 +61,74 @@ impl SkyVertex {
         Self::load_vertex_sky(-16.0f32)
     }
 
+    /// The light sky above the camera and the dark sky below it in one indexed triangle list, drawn by
+    /// `@geo_sky_scatter`
+    pub fn load_sky_dome() -> (Vec<[f32; 3]>, Vec<u32>) {
+        let (light_vertices, light_indices) = Self::load_vertex_light_sky();
+        let (dark_vertices, dark_indices) = Self::load_vertex_dark_sky();
+
+        let vertices = light_vertices
+            .into_iter()
+            .chain(dark_vertices)
+            .collect::<Vec<_>>();
+        let indices = light_indices
+            .into_iter()
+            .chain(dark_indices.map(|index| index + light_vertices.len() as u32))
+            .collect::<Vec<_>>();
+
+        (vertices, indices)
+    }
+
+    /// Vanilla's 1500 randomly placed stars as a triangle list, drawn by `@geo_sky_stars`. They're always the same,
+    /// so they're only built once
+    pub fn load_stars() -> &'static [[f32; 3]] {
+        static STARS: OnceLock<Vec<[f32; 3]>> = OnceLock::new();
+
+        STARS.get_or_init(|| {
+            let mut random = JavaRandom::new(10842);
+            let mut vertices = Vec::new();
+
+            for _ in 0..1500 {
+                let x = (random.next_float() * 2.0 - 1.0) as f64;
+                let y = (random.next_float() * 2.0 - 1.0) as f64;
+                let z = (random.next_float() * 2.0 - 1.0) as f64;
+                let size = (0.15 + random.next_float() * 0.1) as f64;
+                let length_squared = x * x + y * y + z * z;
+
+                if length_squared >= 1.0 || length_squared <= 0.01 {
+                    continue;
+                }
+
+                let length = length_squared.sqrt();
+                let (x, y, z) = (x / length, y / length, z / length);
+                let yaw = x.atan2(z);
+                let pitch = (x * x + z * z).sqrt().atan2(y);
+                let roll = random.next_double() * std::f64::consts::TAU;
+
+                let corners = (0..4)
+                    .map(|corner| {
+                        let u = ((corner & 2) - 1) as f64 * size;
+                        let v = (((corner + 1) & 2) - 1) as f64 * size;
+                        let rolled_u = u * roll.cos() - v * roll.sin();
+                        let rolled_v = v * roll.cos() + u * roll.sin();
+                        let up = rolled_u * pitch.sin();
+                        let out = -rolled_u * pitch.cos();
+
+                        [
+                            (x * 100.0 + out * yaw.sin() - rolled_v * yaw.cos()) as f32,
+                            (y * 100.0 + up) as f32,
+                            (z * 100.0 + rolled_v * yaw.sin() + out * yaw.cos()) as f32,
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+
+                vertices.extend([0, 1, 2, 2, 3, 0].map(|corner| corners[corner]));
+            }
+
+            vertices
+        })
+    }
+
     //build a flat circle
     fn load_vertex_sky(level: f32) -> ([[f32; 3]; 19], [u32; 24]) {
         let offset = 512.0;
@@ -117,7 +216,7 @@ impl SunMoonVertex {
                 wgpu::VertexAttribute {
                     offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
                     shader_location: 1,
-                    format: wgpu::VertexFormat::Float32x3,
+                    format: wgpu::VertexFormat::Float32x2,
                 },
             ],
         }
@@ -191,6 +290,35 @@ impl SunMoonVertex {
     }
 }
 
+/// Java's `java.util.Random`, so the stars end up where vanilla puts them
+struct JavaRandom {
+    seed: u64,
+}
+
+impl JavaRandom {
+    const MULTIPLIER: u64 = 0x5DEECE66D;
+    const MASK: u64 = (1 << 48) - 1;
+
+    fn new(seed: u64) -> Self {
+        Self {
+            seed: (seed ^ Self::MULTIPLIER) & Self::MASK,
+        }
+    }
+
+    fn next(&mut self, bits: u32) -> u64 {
+        self.seed = self.seed.wrapping_mul(Self::MULTIPLIER).wrapping_add(0xB) & Self::MASK;
+        self.seed >> (48 - bits)
+    }
+
+    fn next_float(&mut self) -> f32 {
+        self.next(24) as f32 / (1 << 24) as f32
+    }
+
+    fn next_double(&mut self) -> f64 {
+        ((self.next(26) << 27) + self.next(27)) as f64 / (1u64 << 53) as f64
+    }
+}
+
 // #[repr(C)]
 // #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 // pub struct SkyboxVertex {
@@ -214,3 +342,47 @@ impl SunMoonVertex {
 //     }
 //
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::{SkyUniform, SkyVertex};
+    use crate::mc::SkyState;
+
+    #[test]
+    fn stars_match_vanilla() {
+        let stars = SkyVertex::load_stars();
+
+        //Vanilla's seed places 780 of its 1500 candidate stars, each a quad of two triangles
+        assert_eq!(stars.len(), 780 * 6);
+        assert!(stars.iter().all(|vertex| {
+            let distance = glam::Vec3::from_array(*vertex).length();
+            (99.0..101.0).contains(&distance)
+        }));
+    }
+
+    #[test]
+    fn uniform_matches_the_wgsl_layout() {
+        //vec3 color and angle, then brightness, star_shimmer and moon_phase, padded to 16 bytes
+        assert_eq!(std::mem::size_of::<SkyUniform>(), 32);
+
+        let uniform = SkyUniform::new(&SkyState {
+            color: [255, 0, 51],
+            angle: 0.25,
+            brightness: 1.0,
+            star_shimmer: 0.5,
+            moon_phase: 3,
+        });
+
+        assert_eq!(uniform.color, [1.0, 0.0, 0.2]);
+        assert_eq!(
+            bytemuck::bytes_of(&uniform)[12..28],
+            [
+                0.25f32.to_le_bytes(),
+                1.0f32.to_le_bytes(),
+                0.5f32.to_le_bytes(),
+                3i32.to_le_bytes()
+            ]
+            .concat()
+        );
+    }
+}
@@ -0,0 +1,369 @@
+//! Rendering a single block model to a small image, e.g. for inventory icons, see
+//! [crate::WmRenderer::render_model_thumbnail]
+
+use glam::{vec3, EulerRot, Mat3, Mat4, Vec3};
+use image::RgbaImage;
+use wgpu::util::DeviceExt;
+
+use crate::mc::block::{GuiLight, ModelMesh};
+use crate::mc::resource::ResourcePath;
+use crate::render::pipeline::BLOCK_ATLAS;
+use crate::render::shader::WgslShader;
+use crate::texture::{read_texture_after, TextureAndView};
+use crate::WmRenderer;
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// The two directional lights vanilla uses for block items in the GUI, and how much light every face gets regardless
+/// of direction
+const LIGHTS: [Vec3; 2] = [vec3(0.2, 1.0, -0.7), vec3(-0.2, 1.0, 0.7)];
+/// Lights flat item models with `"gui_light": "front"` head on, so their front faces are fully lit
+const FRONT_LIGHT: Vec3 = Vec3::Z;
+const AMBIENT_LIGHT: f32 = 0.4;
+const DIFFUSE_LIGHT: f32 = 0.6;
+
+/// How a model is posed in a thumbnail, matching the `display` transforms of vanilla models. Defaults to the `gui`
+/// transform of `minecraft:block/block`, the isometric angle blocks have in the inventory
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ThumbnailCamera {
+    /// Rotation around the X, Y and Z axes in degrees, applied in that order like vanilla's display rotations
+    pub rotation: [f32; 3],
+    /// How much of the thumbnail a block spans, 1 fills it edge to edge when viewed head on
+    pub scale: f32,
+}
+
+impl Default for ThumbnailCamera {
+    fn default() -> Self {
+        Self {
+            rotation: [30.0, 225.0, 0.0],
+            scale: 0.625,
+        }
+    }
+}
+
+impl ThumbnailCamera {
+    fn rotation(&self) -> Mat3 {
+        let [x, y, z] = self.rotation.map(f32::to_radians);
+        Mat3::from_euler(EulerRot::XYZ, x, y, z)
+    }
+
+    /// Transforms model space, where a full block spans 0 to 1, into clip space. The block's center ends up in the
+    /// center of the thumbnail
+    pub fn transform(&self) -> Mat4 {
+        Mat4::orthographic_rh(-0.5, 0.5, -0.5, 0.5, -2.0, 2.0)
+            * Mat4::from_scale(Vec3::splat(self.scale))
+            * Mat4::from_mat3(self.rotation())
+            * Mat4::from_translation(Vec3::splat(-0.5))
+    }
+
+    /// How lit a face with this model space normal is, between [AMBIENT_LIGHT] and 1
+    pub fn shade(&self, normal: Vec3, gui_light: GuiLight) -> f32 {
+        let normal = (self.rotation() * normal).normalize_or_zero();
+
+        let diffuse: f32 = match gui_light {
+            GuiLight::Front => normal.dot(FRONT_LIGHT).max(0.0),
+            GuiLight::Side => LIGHTS
+                .iter()
+                .map(|light| normal.dot(light.normalize()).max(0.0))
+                .sum(),
+        };
+
+        (AMBIENT_LIGHT + DIFFUSE_LIGHT * diffuse).min(1.0)
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ThumbnailVertex {
+    position: [f32; 4],
+    uv: [f32; 2],
+    shade: f32,
+}
+
+impl ThumbnailVertex {
+    const VAA: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+        0 => Float32x4,
+        1 => Float32x2,
+        2 => Float32
+    ];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ThumbnailVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::VAA,
+        }
+    }
+}
+
+/// The pipeline thumbnails are drawn with. It's created the first time a thumbnail is rendered and kept in
+/// [WmRenderer::thumbnail_renderer]
+pub struct ThumbnailRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+}
+
+impl ThumbnailRenderer {
+    pub fn new(wm: &WmRenderer) -> Self {
+        let device = &wm.display.device;
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("thumbnail"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("thumbnail"),
+            ..Default::default()
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("thumbnail"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = WgslShader::init(
+            &ResourcePath("wgpu_mc:shaders/thumbnail.wgsl".into()),
+            &*wm.mc.resource_provider,
+            device,
+            "frag".into(),
+            "vert".into(),
+        )
+        .unwrap();
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("thumbnail"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader.module,
+                entry_point: &shader.vert_entry,
+                compilation_options: Default::default(),
+                buffers: &[ThumbnailVertex::desc()],
+            },
+            primitive: Default::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: TextureAndView::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader.module,
+                entry_point: &shader.frag_entry,
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: FORMAT,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: Default::default(),
+                })],
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+        }
+    }
+
+    /// Draw `mesh` with the block atlas to a new `size` by `size` image with a transparent background, and block until
+    /// it's been read back. Tint indices are ignored, so grass and leaves come out uncolored
+    pub fn render(
+        &self,
+        wm: &WmRenderer,
+        mesh: &ModelMesh,
+        size: u32,
+        camera: &ThumbnailCamera,
+    ) -> RgbaImage {
+        let device = &wm.display.device;
+        let size = size.max(1);
+        let extent = wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        };
+
+        let atlases = wm.mc.texture_manager.atlases.read();
+        let atlas = atlases.get(BLOCK_ATLAS).unwrap();
+        let atlas_size = atlas.texture.texture.width() as f32;
+
+        let transform = camera.transform();
+        let vertices: Vec<ThumbnailVertex> = [
+            &mesh.north,
+            &mesh.south,
+            &mesh.west,
+            &mesh.east,
+            &mesh.up,
+            &mesh.down,
+            &mesh.any,
+        ]
+        .into_iter()
+        .flatten()
+        .flat_map(|face| {
+            let shade = camera.shade(face.normal, mesh.gui_light);
+
+            [0, 1, 2, 0, 2, 3].map(|index| {
+                let vertex = face.vertices[index];
+
+                ThumbnailVertex {
+                    position: (transform * vertex.position.extend(1.0)).to_array(),
+                    uv: vertex.tex_coords.map(|coord| coord as f32 / atlas_size),
+                    shade,
+                }
+            })
+        })
+        .collect();
+
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("thumbnail"),
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let depth = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("thumbnail depth"),
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: TextureAndView::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let depth_view = depth.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("thumbnail"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&atlas.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("thumbnail"),
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("thumbnail"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            if !vertices.is_empty() {
+                let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("thumbnail vertices"),
+                    contents: bytemuck::cast_slice(&vertices),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+
+                render_pass.set_pipeline(&self.pipeline);
+                render_pass.set_bind_group(0, &bind_group, &[]);
+                render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                render_pass.draw(0..vertices.len() as u32, 0..1);
+            }
+        }
+
+        read_texture_after(wm, encoder, &target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Vec3};
+
+    use super::ThumbnailCamera;
+    use crate::mc::block::GuiLight;
+
+    #[test]
+    fn default_camera_fits_a_full_block() {
+        let transform = ThumbnailCamera::default().transform();
+
+        let center = transform.project_point3(Vec3::splat(0.5));
+        assert!(center.x.abs() < 1e-5 && center.y.abs() < 1e-5);
+
+        for corner in 0..8 {
+            let corner = vec3(
+                (corner & 1) as f32,
+                ((corner >> 1) & 1) as f32,
+                ((corner >> 2) & 1) as f32,
+            );
+            let projected = transform.project_point3(corner);
+
+            assert!(projected.x.abs() <= 1.0 && projected.y.abs() <= 1.0);
+            assert!((0.0..=1.0).contains(&projected.z));
+        }
+    }
+
+    #[test]
+    fn top_is_lit_more_than_bottom() {
+        let camera = ThumbnailCamera::default();
+
+        assert!(camera.shade(Vec3::Y, GuiLight::Side) > camera.shade(Vec3::NEG_Y, GuiLight::Side));
+        assert!(camera.shade(Vec3::NEG_Y, GuiLight::Side) >= 0.4);
+    }
+
+    #[test]
+    fn front_light_fully_lights_faces_towards_the_camera() {
+        let camera = ThumbnailCamera {
+            rotation: [0.0; 3],
+            scale: 1.0,
+        };
+
+        assert_eq!(camera.shade(Vec3::Z, GuiLight::Front), 1.0);
+        assert_eq!(camera.shade(Vec3::NEG_Z, GuiLight::Front), 0.4);
+    }
+}
@@ -0,0 +1,113 @@
+//! Limits how many terrain triangles are drawn per frame by skipping the farthest sections, a crude way to keep the
+//! frame rate up on slow hardware, see [crate::mc::Scene::triangle_budget]
+
+/// Sections are dropped from farthest to nearest until the rest fit within `max_triangles`. Once sections have been
+/// dropped, they only come back when the sections within the wider distance would fit with `hysteresis` to spare, so
+/// sections at the edge don't flicker in and out as the triangle count hovers around the budget
+#[derive(Clone, Debug, PartialEq)]
+pub struct TriangleBudget {
+    pub max_triangles: u32,
+    /// A fraction of `max_triangles`
+    pub hysteresis: f32,
+    /// Sections at or beyond this distance were dropped last frame, None if every section was drawn
+    cutoff: Option<f32>,
+    dropped: usize,
+}
+
+impl TriangleBudget {
+    pub fn new(max_triangles: u32) -> Self {
+        Self {
+            max_triangles,
+            hysteresis: 0.1,
+            cutoff: None,
+            dropped: 0,
+        }
+    }
+
+    /// Whether sections were dropped last frame
+    pub fn is_limiting(&self) -> bool {
+        self.cutoff.is_some()
+    }
+
+    /// How many sections were dropped last frame
+    pub fn dropped_sections(&self) -> usize {
+        self.dropped
+    }
+
+    /// Takes each visible section's distance from the camera and triangle count, ordered from nearest to farthest,
+    /// and returns how many of the nearest ones to draw
+    pub fn select(&mut self, sections: &[(f32, u32)]) -> usize {
+        //How many of the nearest sections fit within `limit` triangles
+        let fitting = |limit: f32| {
+            let mut triangles = 0u64;
+
+            sections
+                .iter()
+                .take_while(|(_, section_triangles)| {
+                    triangles += *section_triangles as u64;
+                    triangles as f32 <= limit
+                })
+                .count()
+        };
+
+        let previous = match self.cutoff {
+            None => sections.len(),
+            Some(cutoff) => sections
+                .iter()
+                .take_while(|(distance, _)| *distance < cutoff)
+                .count(),
+        };
+        let previous_triangles: u64 = sections[..previous]
+            .iter()
+            .map(|(_, triangles)| *triangles as u64)
+            .sum();
+
+        let count = if previous_triangles > self.max_triangles as u64 {
+            fitting(self.max_triangles as f32)
+        } else {
+            fitting(self.max_triangles as f32 * (1.0 - self.hysteresis)).max(previous)
+        };
+
+        self.cutoff = sections.get(count).map(|(distance, _)| *distance);
+        self.dropped = sections.len() - count;
+
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TriangleBudget;
+
+    fn sections(count: usize) -> Vec<(f32, u32)> {
+        (0..count).map(|index| (index as f32, 100)).collect()
+    }
+
+    #[test]
+    fn drops_the_farthest_sections() {
+        let mut budget = TriangleBudget::new(450);
+
+        assert_eq!(budget.select(&sections(3)), 3);
+        assert!(!budget.is_limiting());
+
+        assert_eq!(budget.select(&sections(6)), 4);
+        assert!(budget.is_limiting());
+        assert_eq!(budget.dropped_sections(), 2);
+    }
+
+    #[test]
+    fn dropped_sections_return_with_room_to_spare() {
+        let mut budget = TriangleBudget::new(450);
+        budget.select(&sections(6));
+
+        //The fifth section would fit within the budget again, but not with 10% to spare
+        let mut cheaper = sections(6);
+        cheaper[4].1 = 10;
+        assert_eq!(budget.select(&cheaper), 4);
+
+        cheaper[4].1 = 0;
+        cheaper[5].1 = 0;
+        assert_eq!(budget.select(&cheaper), 6);
+        assert!(!budget.is_limiting());
+    }
+}
@@ -0,0 +1,103 @@
+//! Skips deep underground terrain sections while the camera is on the surface, a nearly free pre-filter which runs
+//! before frustum culling, see [crate::mc::Scene::underground_culling]
+
+/// Sections are skipped while the camera is at or above `surface_y` and isn't looking down more than `max_pitch`, if
+/// they're entirely below `surface_y` and their top is at least `depth` blocks below the camera. Caves seen through
+/// a ravine or a hole in the ground can disappear, so the defaults leave a generous margin
+#[derive(Clone, Debug, PartialEq)]
+pub struct UndergroundCulling {
+    /// The height, in blocks, from which the camera counts as being above ground, e.g. the sea level
+    pub surface_y: f64,
+    /// How far below the camera, in blocks, the top of a section has to be
+    pub depth: f64,
+    /// In degrees, with Minecraft's convention of positive pitches looking down
+    pub max_pitch: f32,
+    culled: usize,
+}
+
+impl Default for UndergroundCulling {
+    fn default() -> Self {
+        Self {
+            surface_y: 63.0,
+            depth: 48.0,
+            max_pitch: 30.0,
+            culled: 0,
+        }
+    }
+}
+
+impl UndergroundCulling {
+    /// How many sections were skipped last frame
+    pub fn culled_sections(&self) -> usize {
+        self.culled
+    }
+
+    /// Whether sections can be skipped at all for a camera at `camera_y` with `pitch`. The culling is off while the
+    /// camera is underground or looking down steeply
+    pub fn is_active(&self, camera_y: f64, pitch: f32) -> bool {
+        camera_y >= self.surface_y && pitch <= self.max_pitch
+    }
+
+    /// Whether the section at `section_y`, in sections, is skipped for a camera at `camera_y`. Only meaningful when
+    /// [UndergroundCulling::is_active]
+    pub fn culls(&self, camera_y: f64, section_y: i32) -> bool {
+        let top = (section_y as f64 + 1.0) * 16.0;
+
+        top <= self.surface_y && top + self.depth <= camera_y
+    }
+
+    /// Takes sections with their heights, in sections, and returns the ones which are kept, counting the skipped ones
+    /// for [UndergroundCulling::culled_sections]
+    pub fn filter<T>(
+        &mut self,
+        camera_y: f64,
+        pitch: f32,
+        sections: impl IntoIterator<Item = (i32, T)>,
+    ) -> Vec<T> {
+        let active = self.is_active(camera_y, pitch);
+        let mut culled = 0;
+
+        let kept = sections
+            .into_iter()
+            .filter_map(|(section_y, section)| {
+                if active && self.culls(camera_y, section_y) {
+                    culled += 1;
+                    None
+                } else {
+                    Some(section)
+                }
+            })
+            .collect();
+
+        self.culled = culled;
+
+        kept
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UndergroundCulling;
+
+    #[test]
+    fn surface_exploration_skips_deep_sections() {
+        let mut culling = UndergroundCulling::default();
+
+        //A column from y -64 to 320, with the camera standing on the ground
+        let column = || (-4..20).map(|section_y| (section_y, section_y));
+
+        let kept = culling.filter(70.0, 0.0, column());
+        assert_eq!(culling.culled_sections(), 5);
+        assert_eq!(kept.first(), Some(&1));
+        assert_eq!(kept.len(), 19);
+
+        //Mountains above the surface are never skipped, even far below a flying camera
+        let kept = culling.filter(300.0, 0.0, column());
+        assert_eq!(kept.first(), Some(&3));
+
+        //Looking down into a ravine or standing in a cave draws everything
+        assert_eq!(culling.filter(70.0, 60.0, column()).len(), 24);
+        assert_eq!(culling.culled_sections(), 0);
+        assert_eq!(culling.filter(20.0, 0.0, column()).len(), 24);
+    }
+}
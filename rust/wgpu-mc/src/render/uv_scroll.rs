@@ -0,0 +1,108 @@
+//! Textures which scroll across their faces, like conveyor belts from mods. This is cheaper than frame based texture
+//! animation for simple movement, see [crate::mc::BlockManager::add_uv_scroll]
+
+use crate::texture::UV;
+
+/// How many scrolls can be registered. Channel 0 is reserved for faces which don't scroll
+pub const MAX_UV_SCROLLS: usize = 255;
+
+/// A sprite in the block atlas which scrolls at a constant speed, wrapping around within the sprite
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UvScroll {
+    /// The sprite's UVs in atlas pixels. Faces whose UVs lie within it are the ones that scroll
+    pub sprite: UV,
+    /// How far the texture moves per second, in sprite widths and heights
+    pub velocity: [f32; 2],
+}
+
+impl UvScroll {
+    pub fn contains(&self, uv: [u16; 2]) -> bool {
+        let ((min_u, min_v), (max_u, max_v)) = self.sprite;
+        (min_u..=max_u).contains(&uv[0]) && (min_v..=max_v).contains(&uv[1])
+    }
+}
+
+/// Matches the `UvScroll` struct in the terrain shader. Entry 0 doesn't move anything, so non-scrolling faces go
+/// through the same math without a branch
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct UvScrollUniform {
+    /// The sprite's minimum UV and size, normalized to the atlas
+    pub rect: [f32; 4],
+    /// How far the sprite has scrolled, between 0 and 1. ZW are unused
+    pub offset: [f32; 4],
+}
+
+impl UvScrollUniform {
+    /// Leaves every UV between 0 and 1 as it is, `fract(uv / 2) * 2 == uv`
+    pub const IDENTITY: Self = Self {
+        rect: [0.0, 0.0, 2.0, 2.0],
+        offset: [0.0; 4],
+    };
+
+    pub fn new(scroll: &UvScroll, atlas_size: f32, seconds: f32) -> Self {
+        let ((min_u, min_v), (max_u, max_v)) = scroll.sprite;
+
+        Self {
+            rect: [
+                min_u as f32 / atlas_size,
+                min_v as f32 / atlas_size,
+                (max_u - min_u) as f32 / atlas_size,
+                (max_v - min_v) as f32 / atlas_size,
+            ],
+            //Wrapped on the CPU so the offset doesn't lose precision as time goes on
+            offset: [
+                (scroll.velocity[0] * seconds).rem_euclid(1.0),
+                (scroll.velocity[1] * seconds).rem_euclid(1.0),
+                0.0,
+                0.0,
+            ],
+        }
+    }
+}
+
+/// The contents of the `@buffer_uv_scrolls` uniform for these scrolls at `seconds`, indexed by channel
+pub fn uv_scroll_uniforms(
+    scrolls: &[UvScroll],
+    atlas_size: f32,
+    seconds: f32,
+) -> [UvScrollUniform; MAX_UV_SCROLLS + 1] {
+    let mut uniforms = [UvScrollUniform::IDENTITY; MAX_UV_SCROLLS + 1];
+
+    for (uniform, scroll) in uniforms[1..].iter_mut().zip(scrolls) {
+        *uniform = UvScrollUniform::new(scroll, atlas_size, seconds);
+    }
+
+    uniforms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{uv_scroll_uniforms, UvScroll, UvScrollUniform};
+
+    #[test]
+    fn offsets_wrap_within_the_sprite() {
+        let scroll = UvScroll {
+            sprite: ((16, 32), (32, 48)),
+            velocity: [0.5, -0.25],
+        };
+
+        let uniforms = uv_scroll_uniforms(&[scroll], 64.0, 3.0);
+
+        assert_eq!(uniforms[0], UvScrollUniform::IDENTITY);
+        assert_eq!(uniforms[1].rect, [0.25, 0.5, 0.25, 0.25]);
+        assert_eq!(uniforms[1].offset, [0.5, 0.25, 0.0, 0.0]);
+        assert_eq!(uniforms[2], UvScrollUniform::IDENTITY);
+    }
+
+    #[test]
+    fn faces_within_the_sprite_scroll() {
+        let scroll = UvScroll {
+            sprite: ((16, 32), (32, 48)),
+            velocity: [1.0, 0.0],
+        };
+
+        assert!(scroll.contains([16, 48]));
+        assert!(!scroll.contains([0, 32]));
+    }
+}
@@ -0,0 +1,112 @@
+//! The plane vanilla draws below the bottom of the world, so that looking down past loaded terrain shows fog instead
+//! of the clear color. It's a single quad centered below the camera which fades out towards its edge, and terrain in
+//! front of it hides it through the depth test.
+
+use glam::DVec3;
+
+/// See [crate::mc::Scene::void_plane]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VoidPlane {
+    /// The world's bottom Y in blocks, -64 in the vanilla overworld
+    pub bottom_y: f64,
+    /// Defaults to the fog color, [crate::mc::RenderEffectsData::fog_color]
+    pub color: Option<[f32; 3]>,
+    /// How far from the camera the plane reaches, in blocks. It's opaque up to half of this
+    pub radius: f32,
+    pub enabled: bool,
+}
+
+impl Default for VoidPlane {
+    fn default() -> Self {
+        Self {
+            bottom_y: -64.0,
+            color: None,
+            radius: 512.0,
+            enabled: true,
+        }
+    }
+}
+
+impl VoidPlane {
+    /// The plane's instance data for a camera at `camera_position`, both in world space, or `None` if it's disabled or
+    /// the camera isn't above the bottom of the world
+    pub fn instance(
+        &self,
+        camera_position: DVec3,
+        render_origin: DVec3,
+        fog_color: [f32; 3],
+    ) -> Option<VoidPlaneInstance> {
+        if !self.enabled || camera_position.y <= self.bottom_y {
+            return None;
+        }
+
+        let center =
+            DVec3::new(camera_position.x, self.bottom_y, camera_position.z) - render_origin;
+
+        Some(VoidPlaneInstance {
+            center: center.as_vec3().to_array(),
+            radius: self.radius,
+            color: self.color.unwrap_or(fog_color),
+        })
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VoidPlaneInstance {
+    pub center: [f32; 3],
+    pub radius: f32,
+    pub color: [f32; 3],
+}
+
+impl VoidPlaneInstance {
+    const VAA: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32,
+        2 => Float32x3
+    ];
+
+    /// The instance is the only vertex buffer, the shader builds the quad from the vertex index
+    #[must_use]
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<VoidPlaneInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::VAA,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::dvec3;
+
+    use super::VoidPlane;
+
+    #[test]
+    fn follows_the_camera_at_the_bottom_of_the_world() {
+        let instance = VoidPlane::default()
+            .instance(
+                dvec3(40.5, 70.0, -20.0),
+                dvec3(32.0, 0.0, -32.0),
+                [0.5, 0.6, 0.7],
+            )
+            .unwrap();
+
+        assert_eq!(instance.center, [8.5, -64.0, 12.0]);
+        assert_eq!(instance.color, [0.5, 0.6, 0.7]);
+    }
+
+    #[test]
+    fn hidden_below_the_bottom_of_the_world() {
+        let plane = VoidPlane::default();
+
+        assert!(plane
+            .instance(dvec3(0.0, -64.0, 0.0), dvec3(0.0, 0.0, 0.0), [0.0; 3])
+            .is_none());
+        assert!(plane
+            .instance(dvec3(0.0, -100.0, 0.0), dvec3(0.0, 0.0, 0.0), [0.0; 3])
+            .is_none());
+    }
+}
@@ -0,0 +1,115 @@
+//! Foliage swaying in the wind. Block states opt in with [crate::mc::BlockManager::set_sway], which bakes a wave
+//! strength into each of their vertices, and terrain shaders displace those vertices by [Wind], bound as `@pc_wind`
+
+/// The largest wave strength a vertex can be baked with, it's packed into 6 bits next to the AO
+pub const MAX_WAVE: u8 = 63;
+
+/// How strongly the vertices of a block state sway, linearly from the bottom of its model to the top. Vertices with a
+/// strength of 0 stay where they are, which keeps plants anchored to the ground
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sway {
+    pub bottom: f32,
+    pub top: f32,
+}
+
+impl Sway {
+    /// Grass, flowers and crops, which are fixed at the bottom
+    pub const ROOTED: Sway = Sway {
+        bottom: 0.0,
+        top: 1.0,
+    };
+    /// Leaves, which sway as a whole
+    pub const FULL: Sway = Sway {
+        bottom: 1.0,
+        top: 1.0,
+    };
+    /// The lower half of two block tall plants, which meets [Sway::UPPER_HALF] at the same strength
+    pub const LOWER_HALF: Sway = Sway {
+        bottom: 0.0,
+        top: 0.5,
+    };
+    pub const UPPER_HALF: Sway = Sway {
+        bottom: 0.5,
+        top: 1.0,
+    };
+
+    /// The wave strength of a vertex `y` blocks above the bottom of its model, between 0 and [MAX_WAVE]
+    pub fn wave(&self, y: f32) -> u8 {
+        let strength = self.bottom + (self.top - self.bottom) * y.clamp(0.0, 1.0);
+
+        (strength.clamp(0.0, 1.0) * MAX_WAVE as f32).round() as u8
+    }
+}
+
+/// The wind foliage sways in. Set [Wind::time] every frame, e.g. with the seconds since the world was loaded
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Wind {
+    pub time: f32,
+    /// How far in blocks a vertex with the full wave strength moves, 0 stops all swaying
+    pub strength: f32,
+    /// The horizontal direction the wind blows in, along X and Z
+    pub direction: [f32; 2],
+}
+
+impl Default for Wind {
+    fn default() -> Self {
+        Self {
+            time: 0.0,
+            strength: 0.1,
+            direction: [0.8, 0.6],
+        }
+    }
+}
+
+impl Wind {
+    /// The time followed by the direction scaled to the strength, as `@pc_wind` gets them. It's 12 bytes so that it
+    /// fits between `@pc_section_fade` and `@pc_fog` in terrain pipelines
+    pub fn push_constant(&self) -> [f32; 3] {
+        let [x, z] = self.direction;
+        let length = (x * x + z * z).sqrt();
+
+        if length == 0.0 {
+            return [self.time, 0.0, 0.0];
+        }
+
+        [
+            self.time,
+            x / length * self.strength,
+            z / length * self.strength,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Sway, Wind, MAX_WAVE};
+
+    #[test]
+    fn rooted_plants_are_fixed_at_the_bottom() {
+        assert_eq!(Sway::ROOTED.wave(0.0), 0);
+        assert_eq!(Sway::ROOTED.wave(1.0), MAX_WAVE);
+        assert_eq!(Sway::LOWER_HALF.wave(1.0), Sway::UPPER_HALF.wave(0.0));
+
+        //Models can stick out of their block
+        assert_eq!(Sway::ROOTED.wave(-0.5), 0);
+        assert_eq!(Sway::ROOTED.wave(1.5), MAX_WAVE);
+    }
+
+    #[test]
+    fn windless_direction_doesnt_sway() {
+        let wind = Wind {
+            direction: [0.0, 0.0],
+            ..Default::default()
+        };
+
+        assert_eq!(wind.push_constant(), [0.0; 3]);
+
+        let [_, x, z] = Wind {
+            strength: 2.0,
+            direction: [3.0, 4.0],
+            ..Default::default()
+        }
+        .push_constant();
+        assert!((x - 1.2).abs() < 1e-6 && (z - 1.6).abs() < 1e-6);
+    }
+}
@@ -1,6 +1,8 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::Arc;
 
-use image::GenericImageView;
+use image::{GenericImageView, RgbaImage};
 use wgpu::Extent3d;
 
 use crate::{Display, WmRenderer};
@@ -8,6 +10,97 @@ use crate::{Display, WmRenderer};
 pub type TextureId = u32;
 pub type UV = ((u16, u16), (u16, u16));
 
+/// How the bytes of a color texture are interpreted when it's sampled
+#[derive(Debug, Copy, Clone, Default, Hash, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Sampled as they are stored. Data textures like lightmaps and normal maps must stay linear
+    #[default]
+    Linear,
+    /// Decoded from sRGB when sampled, so shaders get linear colors to light. Encoding the result back to sRGB is left
+    /// to the framebuffer, or to the shader if the framebuffer isn't sRGB
+    Srgb,
+}
+
+impl ColorSpace {
+    /// The 8 bit RGBA format for textures in this color space
+    pub fn rgba8_format(self) -> wgpu::TextureFormat {
+        match self {
+            ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm,
+            ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+        }
+    }
+
+    /// The color space textures with `format` are sampled in
+    pub fn of_format(format: wgpu::TextureFormat) -> Self {
+        if format.is_srgb() {
+            ColorSpace::Srgb
+        } else {
+            ColorSpace::Linear
+        }
+    }
+
+    /// Convert the lowest three bytes of `color`, sRGB encoded like vanilla's biome colors, so that they're stored in
+    /// this color space. The highest byte isn't a color channel and is kept as it is
+    pub fn convert_srgb_color(self, color: u32) -> u32 {
+        match self {
+            ColorSpace::Srgb => color,
+            ColorSpace::Linear => {
+                let mut bytes = color.to_le_bytes();
+
+                for channel in &mut bytes[..3] {
+                    *channel = (srgb_to_linear(*channel as f32 / 255.0) * 255.0).round() as u8;
+                }
+
+                u32::from_le_bytes(bytes)
+            }
+        }
+    }
+}
+
+/// Decode an sRGB encoded channel from 0 to 1
+pub fn srgb_to_linear(channel: f32) -> f32 {
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encode a linear channel from 0 to 1 as sRGB
+pub fn linear_to_srgb(channel: f32) -> f32 {
+    if channel <= 0.0031308 {
+        channel * 12.92
+    } else {
+        1.055 * channel.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Bound as `@buffer_color_spaces`, so terrain shaders know how to combine vertex colors with the block atlas. Each is
+/// 0 for [ColorSpace::Linear] and 1 for [ColorSpace::Srgb]. Vertex colors are sRGB when their bytes are sRGB encoded,
+/// while the block atlas is sRGB when it's decoded to linear as it's sampled
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ColorSpaceUniform {
+    pub vertex_colors: u32,
+    pub block_atlas: u32,
+    _padding: [u32; 2],
+}
+
+impl ColorSpaceUniform {
+    pub fn new(vertex_colors: ColorSpace, block_atlas: ColorSpace) -> Self {
+        let index = |color_space| match color_space {
+            ColorSpace::Linear => 0,
+            ColorSpace::Srgb => 1,
+        };
+
+        Self {
+            vertex_colors: index(vertex_colors),
+            block_atlas: index(block_atlas),
+            _padding: [0; 2],
+        }
+    }
+}
+
 /// Representation of a texture that has been uploaded to wgpu along with the corresponding view
 #[derive(Debug)]
 pub struct TextureAndView {
@@ -56,11 +149,24 @@ impl TextureAndView {
         size: Extent3d,
         label: Option<&str>,
         format: wgpu::TextureFormat,
+    ) -> Result<Self, anyhow::Error> {
+        Self::with_mip_levels(wgpu_state, bytes, size, label, format, 1)
+    }
+
+    /// Like [TextureAndView::from_rgb_bytes], but with room for `mip_level_count` mip levels. Only the first level is
+    /// filled in with `bytes`, the others have to be written separately
+    pub fn with_mip_levels(
+        wgpu_state: &Display,
+        bytes: &[u8],
+        size: Extent3d,
+        label: Option<&str>,
+        format: wgpu::TextureFormat,
+        mip_level_count: u32,
     ) -> Result<Self, anyhow::Error> {
         let texture = wgpu_state.device.create_texture(&wgpu::TextureDescriptor {
             label,
             size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format,
@@ -126,3 +232,323 @@ impl BindableTexture {
         Self { tv, bind_group }
     }
 }
+
+/// The size of each chunk in [TextureStaging]'s ring. Uploads bigger than this aren't frequent enough to be worth
+/// keeping memory for and go through [wgpu::Queue::write_texture] instead
+pub const STAGING_CHUNK_SIZE: wgpu::BufferAddress = 1 << 20;
+/// How many idle chunks are kept, any more are dropped once the GPU is done with them
+const MAX_FREE_STAGING_CHUNKS: usize = 4;
+
+/// The row pitch and total size of a `width` x `height` RGBA8 region once staged in a buffer, where rows have to be
+/// aligned to [wgpu::COPY_BYTES_PER_ROW_ALIGNMENT]
+pub fn staged_layout(width: u32, height: u32) -> (u32, wgpu::BufferAddress) {
+    let bytes_per_row = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+    (
+        bytes_per_row,
+        bytes_per_row as wgpu::BufferAddress * height as wgpu::BufferAddress,
+    )
+}
+
+/// Copy `texture`, which has to have `COPY_SRC` usage, into an image and block until it's been read back. BGRA
+/// textures such as most surface formats are swizzled to RGBA
+pub fn read_texture(wm: &WmRenderer, texture: &wgpu::Texture) -> RgbaImage {
+    let encoder = wm
+        .display
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("texture readback"),
+        });
+
+    read_texture_after(wm, encoder, texture)
+}
+
+/// [read_texture], with the copy recorded after the commands in `encoder`, e.g. the pass which rendered `texture`
+pub fn read_texture_after(
+    wm: &WmRenderer,
+    mut encoder: wgpu::CommandEncoder,
+    texture: &wgpu::Texture,
+) -> RgbaImage {
+    let device = &wm.display.device;
+    let (width, height) = (texture.width(), texture.height());
+
+    let (bytes_per_row, staged_size) = staged_layout(width, height);
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("texture readback"),
+        size: staged_size,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &readback,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+
+    let index = wm.display.queue.submit([encoder.finish()]);
+
+    let slice = readback.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+    device.poll(wgpu::Maintain::WaitForSubmissionIndex(index));
+
+    let mut pixels: Vec<u8> = slice
+        .get_mapped_range()
+        .chunks(bytes_per_row as usize)
+        .flat_map(|row| &row[..width as usize * 4])
+        .copied()
+        .collect();
+    readback.unmap();
+
+    if matches!(
+        texture.format(),
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+    ) {
+        pixels.chunks_mut(4).for_each(|pixel| pixel.swap(0, 2));
+    }
+
+    RgbaImage::from_raw(width, height, pixels).unwrap()
+}
+
+/// What went through [TextureStaging] during a frame, to compare it against [wgpu::Queue::write_texture] on e.g. a
+/// dynamic map
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UploadStats {
+    /// Uploads copied out of the ring by the frame's upload encoder
+    pub staged: usize,
+    pub staged_bytes: wgpu::BufferAddress,
+    /// Uploads too big for a chunk, which went through [wgpu::Queue::write_texture]
+    pub direct: usize,
+    /// Chunks of the ring filled, each is unmapped and remapped once
+    pub chunks: usize,
+    /// Command buffers submitted for the uploads, 1 unless direct uploads had to be ordered after staged ones
+    pub submits: usize,
+}
+
+/// A ring of mapped staging buffers for small texture uploads that happen often, like animated sprites and maps.
+/// [wgpu::Queue::write_texture] allocates staging memory for every call, here uploads are instead packed into the
+/// current chunk and their copies recorded into one encoder, which [TextureStaging::flush] submits once per frame.
+/// Chunks are remapped once the GPU has copied out of them and reused oldest first, like [wgpu::util::StagingBelt]
+#[derive(Debug)]
+pub struct TextureStaging {
+    /// Mapped and ready to be written to, in the order they were recalled
+    free: VecDeque<Arc<wgpu::Buffer>>,
+    /// Chunks written to since the last submit and how much of each is used, uploads go into the last one
+    active: Vec<(Arc<wgpu::Buffer>, wgpu::BufferAddress)>,
+    /// Records the copies out of `active`
+    encoder: Option<wgpu::CommandEncoder>,
+    /// Chunks come back through this once they're mapped again after their copies
+    recalled: (Sender<Arc<wgpu::Buffer>>, Receiver<Arc<wgpu::Buffer>>),
+    stats: UploadStats,
+    last_frame: UploadStats,
+}
+
+impl Default for TextureStaging {
+    fn default() -> Self {
+        Self {
+            free: VecDeque::new(),
+            active: Vec::new(),
+            encoder: None,
+            recalled: channel(),
+            stats: UploadStats::default(),
+            last_frame: UploadStats::default(),
+        }
+    }
+}
+
+impl TextureStaging {
+    /// Upload RGBA8 pixels into a region of `texture`, the arguments are the same as [wgpu::Queue::write_texture].
+    /// The copy is recorded, and submitted by the next [TextureStaging::flush]
+    pub fn write_texture(
+        &mut self,
+        display: &Display,
+        texture: wgpu::ImageCopyTexture,
+        data: &[u8],
+        layout: wgpu::ImageDataLayout,
+        size: Extent3d,
+    ) {
+        let (bytes_per_row, staged_size) = staged_layout(size.width, size.height);
+        let row_size = size.width as usize * 4;
+        let source_bytes_per_row = layout.bytes_per_row.unwrap_or(size.width * 4) as usize;
+
+        if staged_size > STAGING_CHUNK_SIZE || size.depth_or_array_layers != 1 || row_size == 0 {
+            //The queue writes before anything submitted after this, so recorded uploads to the same texture go first
+            self.submit(display);
+            display.queue.write_texture(texture, data, layout, size);
+            self.stats.direct += 1;
+            return;
+        }
+
+        let (buffer, offset) = self.allocate(display, staged_size);
+
+        {
+            let mut staged = buffer
+                .slice(offset..offset + staged_size)
+                .get_mapped_range_mut();
+
+            for row in 0..size.height as usize {
+                let source = layout.offset as usize + row * source_bytes_per_row;
+                let destination = row * bytes_per_row as usize;
+
+                staged[destination..destination + row_size]
+                    .copy_from_slice(&data[source..source + row_size]);
+            }
+        }
+
+        self.encoder
+            .get_or_insert_with(|| {
+                display
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("Texture staging"),
+                    })
+            })
+            .copy_buffer_to_texture(
+                wgpu::ImageCopyBuffer {
+                    buffer: &buffer,
+                    layout: wgpu::ImageDataLayout {
+                        offset,
+                        bytes_per_row: Some(bytes_per_row),
+                        rows_per_image: Some(size.height),
+                    },
+                },
+                texture,
+                size,
+            );
+
+        self.stats.staged += 1;
+        self.stats.staged_bytes += staged_size;
+    }
+
+    /// Room for `size` bytes in the current chunk, or the start of the next one. Staged sizes are whole rows, so
+    /// offsets stay aligned for copies
+    fn allocate(
+        &mut self,
+        display: &Display,
+        size: wgpu::BufferAddress,
+    ) -> (Arc<wgpu::Buffer>, wgpu::BufferAddress) {
+        if let Some((buffer, used)) = self.active.last_mut() {
+            if *used + size <= STAGING_CHUNK_SIZE {
+                let offset = *used;
+                *used += size;
+
+                return (buffer.clone(), offset);
+            }
+        }
+
+        self.free.extend(self.recalled.1.try_iter());
+
+        let buffer = self.free.pop_front().unwrap_or_else(|| {
+            Arc::new(display.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Texture staging"),
+                size: STAGING_CHUNK_SIZE,
+                usage: wgpu::BufferUsages::MAP_WRITE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: true,
+            }))
+        });
+
+        self.active.push((buffer.clone(), size));
+
+        (buffer, 0)
+    }
+
+    /// Submit the copies recorded so far and start remapping the chunks they read from
+    fn submit(&mut self, display: &Display) {
+        let Some(encoder) = self.encoder.take() else {
+            return;
+        };
+
+        for (buffer, _) in &self.active {
+            buffer.unmap();
+        }
+
+        display.queue.submit([encoder.finish()]);
+        self.stats.submits += 1;
+        self.stats.chunks += self.active.len();
+
+        for (buffer, _) in self.active.drain(..) {
+            //Mapping waits for the copies, the callback runs during a later submit or poll
+            let sender = self.recalled.0.clone();
+            let recalled = buffer.clone();
+            let free = self.free.len();
+
+            buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Write, move |result| {
+                    if result.is_ok() && free < MAX_FREE_STAGING_CHUNKS {
+                        let _ = sender.send(recalled);
+                    }
+                });
+        }
+    }
+
+    /// Submit this frame's uploads in a single command buffer. Call this once per frame, before the frame's own
+    /// commands are submitted so they see the uploaded texels
+    pub fn flush(&mut self, display: &Display) {
+        self.submit(display);
+        self.last_frame = std::mem::take(&mut self.stats);
+    }
+
+    /// What was uploaded between the last two calls to [TextureStaging::flush]
+    pub fn last_frame(&self) -> UploadStats {
+        self.last_frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{linear_to_srgb, srgb_to_linear, staged_layout, ColorSpace};
+
+    #[test]
+    fn staged_rows_are_aligned() {
+        assert_eq!(staged_layout(64, 64), (256, 256 * 64));
+        assert_eq!(staged_layout(65, 2), (512, 512 * 2));
+        assert_eq!(staged_layout(128, 128), (512, 512 * 128));
+    }
+
+    #[test]
+    fn linear_vertex_colors_shade_grass_like_vanilla() {
+        //Plains grass, a pixel of the grass block's top and a corner darkened by AO
+        let tint = 0x005ac079;
+        let texel = 150.0 / 255.0;
+        let ao = 0.6 + 0.4 * (2.0 / 3.0);
+
+        //Vanilla multiplies the sRGB encoded colors as they are
+        let vanilla = tint.to_le_bytes()[..3]
+            .iter()
+            .map(|channel| *channel as f32 / 255.0 * texel * ao)
+            .collect::<Vec<_>>();
+
+        assert_eq!(ColorSpace::Srgb.convert_srgb_color(tint), tint);
+
+        //Baked in linear with an sRGB atlas, the terrain shader encodes both before multiplying them
+        let baked = ColorSpace::Linear.convert_srgb_color(tint);
+        let sampled = srgb_to_linear(texel);
+
+        let shaded = baked.to_le_bytes()[..3]
+            .iter()
+            .map(|channel| linear_to_srgb(*channel as f32 / 255.0) * linear_to_srgb(sampled) * ao)
+            .collect::<Vec<_>>();
+
+        for (shaded, vanilla) in shaded.iter().zip(&vanilla) {
+            assert!(
+                (shaded - vanilla).abs() < 1.0 / 255.0,
+                "{shaded:?} isn't {vanilla:?}"
+            );
+        }
+
+        //Storing linear colors darkens them, which is what the shader has to undo
+        assert!(baked.to_le_bytes()[..3]
+            .iter()
+            .zip(tint.to_le_bytes())
+            .all(|(linear, srgb)| *linear < srgb));
+        assert_eq!(baked >> 24, 0);
+    }
+}